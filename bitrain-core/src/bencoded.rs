@@ -1,15 +1,47 @@
-#[cfg(feature = "custom-bencode")]
-mod custom;
-
 use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+
+pub mod encoding;
 
-#[cfg(feature = "custom-bencode")]
 pub use encoding::{BDecode, BEncode};
 
+mod builder;
+pub use builder::MetainfoBuilder;
+
+mod canonical;
+
+mod layout;
+pub use layout::{FileSegment, Layout, PieceSegment};
+
+mod validate;
+pub use validate::Violation;
+
+mod edit;
+
+mod extensions;
+
+#[cfg(any(feature = "use-serde", feature = "custom-bencode"))]
+mod file;
+#[cfg(any(feature = "use-serde", feature = "custom-bencode"))]
+pub use file::FileError;
+
+#[cfg(feature = "custom-bencode")]
+mod custom;
+#[cfg(feature = "custom-bencode")]
+pub use custom::{Custom, CustomParseError, FromEntry, ToEntry};
+
 #[cfg(feature = "use-serde")]
 mod serde;
+#[cfg(feature = "use-serde")]
 pub use self::serde::*;
 
+#[cfg(feature = "use-serde")]
+mod view;
+#[cfg(feature = "use-serde")]
+pub use view::{InfoRef, MetainfoRef};
+
 #[cfg(feature = "use-serde")]
 use serde_derive::{Deserialize, Serialize};
 
@@ -17,16 +49,98 @@ use serde_derive::{Deserialize, Serialize};
 pub type BInt = u64;
 
 ///Bencoded string type.
+///
+///Ordered bytewise, same as dictionary keys in bencode. Displayed as lossy UTF-8, or as hex with `{:x}`.
 #[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "use-serde", serde(into = "serde_bytes::ByteBuf"))]
 #[cfg_attr(feature = "use-serde", serde(from = "serde_bytes::ByteBuf"))]
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
 pub struct BString(pub Vec<u8>);
 
 impl BString {
     pub fn into_inner(self) -> Vec<u8> {
         self.0
     }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    ///Returns string as `str`, if it is valid UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.0).ok()
+    }
+
+    ///Returns string as UTF-8, replacing invalid sequences with `U+FFFD`.
+    pub fn to_string_lossy(&self) -> std::borrow::Cow<'_, str> {
+        String::from_utf8_lossy(&self.0)
+    }
+}
+
+impl std::fmt::Display for BString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_string_lossy())
+    }
+}
+
+impl std::fmt::LowerHex for BString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{:02x}", b))
+    }
+}
+
+impl std::fmt::UpperHex for BString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{:02X}", b))
+    }
+}
+
+impl From<Vec<u8>> for BString {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<&[u8]> for BString {
+    fn from(bytes: &[u8]) -> Self {
+        Self(bytes.to_vec())
+    }
+}
+
+impl<const N: usize> From<&[u8; N]> for BString {
+    fn from(bytes: &[u8; N]) -> Self {
+        Self(bytes.to_vec())
+    }
+}
+
+impl From<String> for BString {
+    fn from(string: String) -> Self {
+        Self(string.into_bytes())
+    }
+}
+
+impl From<&str> for BString {
+    fn from(string: &str) -> Self {
+        Self(string.as_bytes().to_vec())
+    }
+}
+
+impl From<BString> for Vec<u8> {
+    fn from(bstring: BString) -> Self {
+        bstring.0
+    }
+}
+
+impl AsRef<[u8]> for BString {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl std::borrow::Borrow<[u8]> for BString {
+    fn borrow(&self) -> &[u8] {
+        &self.0
+    }
 }
 
 pub trait Parser<T>: Sized {
@@ -43,7 +157,7 @@ pub trait Saver<T>: Sized {
 
 #[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
 ///Parsed `.torrent` metadata file
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Metainfo {
     ///Describes the file(s) of the torrent.
     pub info: Info,
@@ -69,6 +183,118 @@ pub struct Metainfo {
     ///The string encoding format used to generate the pieces part of the info dictionary in the metadata file.
     #[cfg_attr(feature = "use-serde", serde(skip_serializing_if = "Option::is_none"))]
     pub encoding: Option<String>,
+    ///Keys, not modeled by this struct (i.e. `url-list`, `nodes`), which are written back on saving.
+    #[cfg_attr(feature = "use-serde", serde(flatten))]
+    pub extra: encoding::BDictionary,
+    ///Exact bytes of `info` dictionary, as it was in parsed file.
+    #[cfg_attr(feature = "use-serde", serde(skip))]
+    raw_info: Option<RawInfo>,
+    ///Exact bytes of parsed file.
+    #[cfg_attr(feature = "use-serde", serde(skip))]
+    raw: Option<BString>,
+}
+
+///`info` dictionary exactly as it was in parsed file, together with [`Info`], it was parsed into.
+///
+///As [`Metainfo::info`] is public, it can be changed directly, so bytes are valid only while it equals `parsed`.
+#[derive(Debug, Clone)]
+struct RawInfo {
+    parsed: Info,
+    bytes: BString,
+}
+
+impl Metainfo {
+    ///Returns `info` dictionary exactly as it was in parsed file, or `None` if bytes of file weren't recorded
+    ///(i.e. metainfo was constructed or parsed with generic [`Parser::parse`] of `Serde`) or [`Metainfo::info`]
+    ///was changed since.
+    ///
+    ///Info-hash must be computed over these bytes: re-encoding [`Info`] may differ from original
+    ///(i.e. if file contains keys, not modeled by [`Info`]).
+    pub fn raw_info(&self) -> Option<&[u8]> {
+        self.raw_info
+            .as_ref()
+            .filter(|raw_info| raw_info.parsed == self.info)
+            .map(|raw_info| raw_info.bytes.as_ref())
+    }
+
+    ///Creates metainfo without trackers from `info`, parsed from `raw_info` dictionary (i.e. one, fetched from peers
+    ///by info-hash of magnet link), keeping its exact bytes, so info-hash is preserved.
+    pub fn from_raw_info(info: Info, raw_info: BString) -> Self {
+        Self {
+            raw_info: Some(RawInfo { parsed: info.clone(), bytes: raw_info }),
+            info,
+            announce: String::new(),
+            announce_list: None,
+            creation_date: None,
+            comment: None,
+            created_by: None,
+            encoding: None,
+            extra: encoding::BDictionary::new(),
+            raw: None,
+        }
+    }
+
+    ///Returns bytes of parsed file, or `None` if they weren't recorded (see [`Metainfo::raw_info`]).
+    pub fn raw(&self) -> Option<&[u8]> {
+        self.raw.as_ref().map(AsRef::as_ref)
+    }
+
+    ///Returns `true` if saving `self` reproduces parsed file byte for byte, which is the case, until metainfo
+    ///is changed. Otherwise only `info` dictionary is reproduced exactly, while the rest of file is saved in
+    ///canonical form.
+    pub fn is_byte_exact(&self) -> bool {
+        let mut encoded = self.to_entry().encode().into_vec();
+        self.restore_raw(&mut encoded);
+
+        self.raw() == Some(&encoded[..])
+    }
+
+    ///Records `source`, from which `self` was parsed, and span of `info` dictionary in it.
+    #[cfg(any(feature = "use-serde", feature = "custom-bencode"))]
+    pub(crate) fn record_raw(&mut self, source: &[u8]) {
+        self.raw_info = encoding::borrowed::dictionary_value_span(source, b"info")
+            .ok()
+            .flatten()
+            .map(|span| RawInfo { parsed: self.info.clone(), bytes: BString(source[span].to_vec()) });
+        self.raw = Some(BString(source.to_vec()));
+    }
+
+    ///Replaces `encoded` self with [`Metainfo::raw`], if it is known and `self` wasn't changed since parsing.
+    ///Otherwise replaces `info` dictionary of `encoded` with [`Metainfo::raw_info`], if it is known and `info`
+    ///wasn't changed, so saved file has the same info-hash as parsed one.
+    pub(crate) fn restore_raw(&self, encoded: &mut Vec<u8>) {
+        let Some(raw_info) = self.raw_info() else { return };
+
+        if let Ok(Some(span)) = encoding::borrowed::dictionary_value_span(encoded, b"info") {
+            encoded.splice(span, raw_info.iter().copied());
+        }
+
+        // Original file is restored, if it has the same canonical form (keys of dictionaries can be unsorted in it)
+        let Some(raw) = &self.raw else { return };
+        let Ok(original) = encoding::borrowed::Entry::from_bytes(&raw.0) else { return };
+        let mut canonical = original.to_owned_entry().encode().into_vec();
+
+        if let Ok(Some(span)) = encoding::borrowed::dictionary_value_span(&canonical, b"info") {
+            canonical.splice(span, raw_info.iter().copied());
+        }
+        if canonical == *encoded {
+            encoded.clone_from(&raw.0);
+        }
+    }
+}
+
+///Bytes of parsed file aren't compared, so parsed metainfo equals one, constructed with the same values.
+impl PartialEq for Metainfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.info == other.info
+            && self.announce == other.announce
+            && self.announce_list == other.announce_list
+            && self.creation_date == other.creation_date
+            && self.comment == other.comment
+            && self.created_by == other.created_by
+            && self.encoding == other.encoding
+            && self.extra == other.extra
+    }
 }
 
 ///Parsed `info` section of `.torrent` metadata file.
@@ -98,6 +324,57 @@ pub struct Info {
     ///A list of files in this torrent.
     #[cfg_attr(feature = "use-serde", serde(flatten))]
     pub files: Files,
+    ///Unknown keys of `info` dictionary (i.e. `source`), preserved for re-encoding.
+    #[cfg_attr(feature = "use-serde", serde(flatten, deserialize_with = "self::serde::utils::deserialize_info_extra"))]
+    pub extra: encoding::BDictionary,
+}
+
+impl Info {
+    ///Number of piece hashes in `pieces`. Incomplete trailing hash, if any, is not counted.
+    pub fn piece_count(&self) -> usize {
+        self.pieces.0.len() / 20
+    }
+
+    ///Returns SHA1 hash of piece at `index`, or `None` if it is out of range.
+    pub fn piece_hash(&self, index: usize) -> Option<&[u8; 20]> {
+        self.piece_hashes().nth(index)
+    }
+
+    ///Iterates over SHA1 hashes of all pieces in order.
+    pub fn piece_hashes(&self) -> impl ExactSizeIterator<Item = &[u8; 20]> + '_ {
+        self.pieces
+            .0
+            .chunks_exact(20)
+            .map(|hash| hash.try_into().expect("chunk is 20 bytes long"))
+    }
+
+    ///Returns `true` if torrent is private (BEP 27), so its peers must come only from its trackers,
+    ///but not from DHT, peer exchange or local service discovery.
+    pub fn is_private(&self) -> bool {
+        self.private == Some(true)
+    }
+
+    ///Iterates over path, length and MD5 sum of all files in order, regardless of torrent being single- or
+    ///multi-file one.
+    ///
+    ///Paths are relative to download directory: single file is `name`, files of multi-file torrent are
+    ///under `name` directory.
+    pub fn iter_files(&self) -> impl Iterator<Item = (PathBuf, BInt, Option<&BString>)> + '_ {
+        let (single, multiple) = match &self.files {
+            Files::Single { length, md5sum } => (Some((PathBuf::from(&self.name), *length, md5sum.as_ref())), &[][..]),
+            Files::Multiple { files } => (None, &files[..]),
+        };
+
+        single.into_iter().chain(multiple.iter().map(|file| {
+            let path = file.path.iter().fold(PathBuf::from(&self.name), |path, part| path.join(part));
+            (path, file.length, file.md5sum.as_ref())
+        }))
+    }
+
+    ///Total length of all files in bytes.
+    pub fn total_size(&self) -> BInt {
+        self.iter_files().map(|(_, length, _)| length).sum()
+    }
 }
 
 #[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
@@ -126,23 +403,92 @@ pub struct FileInfo {
     ///A list containing one or more string elements that together represent the path and filename.
     ///Each element in the list corresponds to either a directory name or (in the case of the final element) the filename.
     pub path: Vec<String>,
+    ///Optional string of file attributes (BEP 47), i.e. `"p"` for padding files.
+    #[cfg_attr(feature = "use-serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub attr: Option<String>,
+}
+
+impl FileInfo {
+    ///Returns `true` if file is padding file (BEP 47), which consists of zeros and isn't stored on disk.
+    pub fn is_padding(&self) -> bool {
+        self.attr.as_deref().is_some_and(|attr| attr.contains('p'))
+    }
 }
 
+///Responce of HTTP tracker to announce request.
+///
+///Can be converted into [`Result`] with [`TrackerResponce::into_result`].
 #[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
 #[cfg_attr(feature = "use-serde", serde(untagged))]
 #[derive(Debug, Clone, PartialEq)]
 pub enum TrackerResponce {
-    Success {
-        #[cfg_attr(feature = "use-serde", serde(flatten))]
-        info: TrackerInfo,
-        peers: PeerList
-    },
-    Error {
-        #[cfg_attr(feature = "use-serde", serde(rename = "failure reason"))]
-        failure_reason: BString,
-    },
+    Success(AnnounceResponce),
+    Error(TrackerFailure),
+}
+
+impl TrackerResponce {
+    pub fn into_result(self) -> Result<AnnounceResponce, TrackerFailure> {
+        self.into()
+    }
+
+    /// Iterates over both IPv4 and IPv6 peers of successful responce.
+    pub fn peers(&self) -> impl Iterator<Item = PeerAddr> + '_ {
+        match self {
+            Self::Success(responce) => Some(responce.peers()),
+            Self::Error(_) => None,
+        }
+        .into_iter()
+        .flatten()
+    }
 }
 
+impl From<TrackerResponce> for Result<AnnounceResponce, TrackerFailure> {
+    fn from(responce: TrackerResponce) -> Self {
+        match responce {
+            TrackerResponce::Success(responce) => Ok(responce),
+            TrackerResponce::Error(failure) => Err(failure),
+        }
+    }
+}
+
+///Successful responce of tracker to announce request.
+#[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnounceResponce {
+    #[cfg_attr(feature = "use-serde", serde(flatten))]
+    pub info: TrackerInfo,
+    pub peers: PeerList,
+    ///Compact list of IPv6 peers.
+    ///
+    ///See <http://bittorrent.org/beps/bep_0007.html> for more info.
+    #[cfg_attr(feature = "use-serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub peers6: Option<CompactPeers6>,
+}
+
+impl AnnounceResponce {
+    /// Iterates over both IPv4 and IPv6 peers.
+    pub fn peers(&self) -> impl Iterator<Item = PeerAddr> + '_ {
+        self.peers.iter().chain(self.peers6.iter().flatten())
+    }
+}
+
+///Responce of tracker, which rejected announce request.
+#[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackerFailure {
+    ///Human-readable reason of failure.
+    #[cfg_attr(feature = "use-serde", serde(rename = "failure reason"))]
+    pub failure_reason: BString,
+}
+
+impl std::fmt::Display for TrackerFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "tracker failure: {}", self.failure_reason)
+    }
+}
+
+impl std::error::Error for TrackerFailure {}
+
 #[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq)]
 pub struct TrackerInfo {
@@ -154,7 +500,42 @@ pub struct TrackerInfo {
     #[cfg_attr(feature = "use-serde", serde(skip_serializing_if = "Option::is_none"))]
     id: Option<BString>,
     complete: BInt,
-    incomplete: BInt,    
+    incomplete: BInt,
+    #[cfg_attr(feature = "use-serde", serde(rename = "warning message"))]
+    #[cfg_attr(feature = "use-serde", serde(skip_serializing_if = "Option::is_none"))]
+    warning_message: Option<BString>,
+}
+
+impl TrackerInfo {
+    /// Interval, client should wait between regular announces.
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval)
+    }
+
+    /// Minimum interval between announces. Client must not reannounce more frequently than this.
+    pub fn min_interval(&self) -> Option<Duration> {
+        self.min_interval.map(Duration::from_secs)
+    }
+
+    /// Id, which client should send back with next announces.
+    pub fn tracker_id(&self) -> Option<&BString> {
+        self.id.as_ref()
+    }
+
+    /// Number of peers with the complete file (seeders).
+    pub fn complete(&self) -> BInt {
+        self.complete
+    }
+
+    /// Number of peers, which are still downloading (leechers).
+    pub fn incomplete(&self) -> BInt {
+        self.incomplete
+    }
+
+    /// Human-readable warning. Unlike failure, announce is still processed by tracker.
+    pub fn warning_message(&self) -> Option<&BString> {
+        self.warning_message.as_ref()
+    }
 }
 
 #[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
@@ -165,6 +546,144 @@ pub enum PeerList {
     Compact(BString),    
 }
 
+impl PeerList {
+    /// Length of single IPv4 entry in compact peer list.
+    ///
+    /// See <http://bittorrent.org/beps/bep_0023.html> for more info.
+    pub const COMPACT_V4_LEN: usize = 6;
+
+    /// Encodes `addrs` into compact peer list.
+    pub fn from_compact(addrs: impl IntoIterator<Item = SocketAddrV4>) -> Self {
+        let bytes = addrs
+            .into_iter()
+            .flat_map(|addr| {
+                let mut entry = [0; Self::COMPACT_V4_LEN];
+                entry[..4].copy_from_slice(&addr.ip().octets());
+                entry[4..].copy_from_slice(&addr.port().to_be_bytes());
+                entry
+            })
+            .collect();
+
+        Self::Compact(BString(bytes))
+    }
+
+    /// Decodes compact peer list, ignoring trailing incomplete entry.
+    ///
+    /// Returns `None` if list is in canonical form.
+    pub fn decode_compact(&self) -> Option<Vec<SocketAddrV4>> {
+        match self {
+            Self::Compact(bytes) => Some(utils::decode_compact_v4(&bytes.0).collect()),
+            Self::Canonical(_) => None,
+        }
+    }
+
+    /// Iterates over peer addresses regardless of list form.
+    ///
+    /// Canonical entries with port out of range or non UTF-8 `ip` are skipped.
+    pub fn iter(&self) -> Peers<'_> {
+        let inner = match self {
+            Self::Canonical(peers) => PeersInner::Canonical(peers.iter()),
+            Self::Compact(bytes) => PeersInner::Compact(bytes.0.chunks_exact(Self::COMPACT_V4_LEN)),
+        };
+
+        Peers { inner }
+    }
+}
+
+impl<'a> IntoIterator for &'a PeerList {
+    type Item = PeerAddr;
+    type IntoIter = Peers<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Compact list of IPv6 peers, consisting of 18-byte entries (16 bytes of address and 2 bytes of port).
+///
+/// See <http://bittorrent.org/beps/bep_0007.html> for more info.
+#[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "use-serde", serde(transparent))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompactPeers6(pub BString);
+
+impl CompactPeers6 {
+    pub const ENTRY_LEN: usize = 18;
+
+    pub fn from_addrs(addrs: impl IntoIterator<Item = SocketAddrV6>) -> Self {
+        let bytes = addrs
+            .into_iter()
+            .flat_map(|addr| {
+                let mut entry = [0; Self::ENTRY_LEN];
+                entry[..16].copy_from_slice(&addr.ip().octets());
+                entry[16..].copy_from_slice(&addr.port().to_be_bytes());
+                entry
+            })
+            .collect();
+
+        Self(BString(bytes))
+    }
+
+    /// Decodes list, ignoring trailing incomplete entry.
+    pub fn decode(&self) -> Vec<SocketAddrV6> {
+        self.iter().collect()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = SocketAddrV6> + '_ {
+        self.0 .0.chunks_exact(Self::ENTRY_LEN).map(utils::compact_v6_entry)
+    }
+}
+
+impl<'a> IntoIterator for &'a CompactPeers6 {
+    type Item = PeerAddr;
+    type IntoIter = Box<dyn Iterator<Item = PeerAddr> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter().map(|addr| PeerAddr::Socket(addr.into())))
+    }
+}
+
+/// Address of peer from tracker responce.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PeerAddr {
+    Socket(SocketAddr),
+    /// Canonical peer lists are allowed to contain DNS names instead of IP addresses.
+    Host(String, u16),
+}
+
+impl PeerAddr {
+    /// Converts address into form accepted by [`Peer::new`](`crate::peer::Peer::new`).
+    pub fn into_host_port(self) -> (String, u16) {
+        match self {
+            Self::Socket(addr) => (addr.ip().to_string(), addr.port()),
+            Self::Host(host, port) => (host, port),
+        }
+    }
+}
+
+/// Iterator over addresses of [`PeerList`].
+pub struct Peers<'a> {
+    inner: PeersInner<'a>,
+}
+
+enum PeersInner<'a> {
+    Canonical(std::slice::Iter<'a, PeerCanonical>),
+    Compact(std::slice::ChunksExact<'a, u8>),
+}
+
+impl Iterator for Peers<'_> {
+    type Item = PeerAddr;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.inner {
+            PeersInner::Canonical(peers) => peers.find_map(PeerCanonical::addr),
+            PeersInner::Compact(chunks) => chunks
+                .next()
+                .map(|entry| PeerAddr::Socket(utils::compact_v4_entry(entry).into())),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "use-serde", derive(Serialize, Deserialize))]
 pub struct PeerCanonical {
@@ -172,4 +691,173 @@ pub struct PeerCanonical {
     id: BString,
     ip: BString,
     port: BInt,
-}
\ No newline at end of file
+}
+
+impl PeerCanonical {
+    pub fn id(&self) -> &BString {
+        &self.id
+    }
+
+    /// IP address (IPv4 dotted quad or IPv6 hexed) or DNS name of peer.
+    pub fn ip(&self) -> &BString {
+        &self.ip
+    }
+
+    pub fn port(&self) -> BInt {
+        self.port
+    }
+
+    fn addr(&self) -> Option<PeerAddr> {
+        let port = u16::try_from(self.port).ok()?;
+        let ip = std::str::from_utf8(&self.ip.0).ok()?;
+
+        match ip.parse::<IpAddr>() {
+            Ok(ip) => Some(PeerAddr::Socket(SocketAddr::new(ip, port))),
+            Err(_) => Some(PeerAddr::Host(ip.to_owned(), port)),
+        }
+    }
+}
+
+mod utils {
+    use super::*;
+
+    pub fn decode_compact_v4(bytes: &[u8]) -> impl Iterator<Item = SocketAddrV4> + '_ {
+        bytes
+            .chunks_exact(PeerList::COMPACT_V4_LEN)
+            .map(compact_v4_entry)
+    }
+
+    pub fn compact_v6_entry(entry: &[u8]) -> SocketAddrV6 {
+        let mut octets = [0; 16];
+        octets.copy_from_slice(&entry[..16]);
+        let port = u16::from_be_bytes([entry[16], entry[17]]);
+
+        SocketAddrV6::new(Ipv6Addr::from(octets), port, 0, 0)
+    }
+
+    pub fn compact_v4_entry(entry: &[u8]) -> SocketAddrV4 {
+        let ip = Ipv4Addr::new(entry[0], entry[1], entry[2], entry[3]);
+        let port = u16::from_be_bytes([entry[4], entry[5]]);
+
+        SocketAddrV4::new(ip, port)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact_peers_roundtrip() {
+        let addrs = vec![
+            SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 6881),
+            SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 254), 51413),
+        ];
+
+        let list = PeerList::from_compact(addrs.clone());
+
+        assert_eq!(
+            list,
+            PeerList::Compact(BString(vec![10, 0, 0, 1, 0x1A, 0xE1, 192, 168, 1, 254, 0xC8, 0xD5]))
+        );
+        assert_eq!(list.decode_compact(), Some(addrs));
+    }
+
+    #[test]
+    fn piece_hashes_are_sliced() {
+        let info = Info {
+            piece_length: 16,
+            pieces: BString([[1; 20], [2; 20]].concat()),
+            private: None,
+            name: "test".to_owned(),
+            files: Files::Single {
+                length: 20,
+                md5sum: None,
+            },
+            extra: encoding::BDictionary::new(),
+        };
+
+        assert_eq!(info.piece_count(), 2);
+        assert_eq!(info.piece_hash(1), Some(&[2; 20]));
+        assert_eq!(info.piece_hash(2), None);
+        assert_eq!(info.piece_hashes().collect::<Vec<_>>(), [&[1; 20], &[2; 20]]);
+    }
+
+    #[test]
+    fn files_are_iterated_alike() {
+        let mut info = Info {
+            piece_length: 16,
+            pieces: BString(vec![]),
+            private: None,
+            name: "root".to_owned(),
+            files: Files::Single {
+                length: 20,
+                md5sum: Some(BString::from("sum")),
+            },
+            extra: encoding::BDictionary::new(),
+        };
+        assert_eq!(info.iter_files().collect::<Vec<_>>(), [(PathBuf::from("root"), 20, Some(&BString::from("sum")))]);
+
+        let file = |length, path: &[&str]| FileInfo {
+            length,
+            md5sum: None,
+            path: path.iter().map(|part| part.to_string()).collect(),
+            attr: None,
+        };
+        info.files = Files::Multiple {
+            files: vec![file(3, &["a"]), file(4, &["dir", "b"])],
+        };
+
+        assert_eq!(
+            info.iter_files().map(|(path, length, _)| (path, length)).collect::<Vec<_>>(),
+            [(PathBuf::from("root/a"), 3), (PathBuf::from("root/dir/b"), 4)]
+        );
+        assert_eq!(info.total_size(), 7);
+    }
+
+    #[test]
+    fn bstring_is_displayed() {
+        let bstring = BString::from(b"ab\xff");
+
+        assert_eq!(bstring.to_string(), "ab\u{fffd}");
+        assert_eq!(format!("{:x}", bstring), "6162ff");
+        assert_eq!(format!("{:X}", bstring), "6162FF");
+        assert!(BString::from("a") < BString::from("ab"));
+        assert_eq!(BString::from("ab").as_str(), Some("ab"));
+    }
+
+    #[test]
+    fn compact_peers6_roundtrip() {
+        let addrs = vec![SocketAddrV6::new(Ipv6Addr::LOCALHOST, 6881, 0, 0)];
+        let list = CompactPeers6::from_addrs(addrs.clone());
+
+        assert_eq!(list.0 .0.len(), CompactPeers6::ENTRY_LEN);
+        assert_eq!(list.decode(), addrs);
+    }
+
+    #[test]
+    fn canonical_and_compact_iterate_alike() {
+        let canonical = PeerList::Canonical(vec![
+            PeerCanonical {
+                id: BString(vec![0; 20]),
+                ip: BString(b"10.0.0.1".to_vec()),
+                port: 6881,
+            },
+            PeerCanonical {
+                id: BString(vec![0; 20]),
+                ip: BString(b"peer.example".to_vec()),
+                port: 6881,
+            },
+        ]);
+        let compact = PeerList::from_compact(vec![SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 6881)]);
+
+        assert_eq!(
+            canonical.iter().collect::<Vec<_>>(),
+            vec![
+                PeerAddr::Socket("10.0.0.1:6881".parse().unwrap()),
+                PeerAddr::Host("peer.example".to_owned(), 6881)
+            ]
+        );
+        assert_eq!(compact.iter().next(), canonical.iter().next());
+    }
+}
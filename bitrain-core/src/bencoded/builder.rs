@@ -0,0 +1,521 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use sha1::Digest;
+use sha2::Sha256;
+
+use super::encoding::{BDictionary, Entry};
+use super::{BInt, BString, FileInfo, Files, Info, Metainfo};
+use crate::storage::PieceHasher;
+
+/// Builder of [`Metainfo`] for file or directory on disk.
+///
+/// Files of directory are listed in lexicographical order of their paths. Piece length is chosen
+/// automatically from total size of content, unless set with [`MetainfoBuilder::piece_length`].
+///
+/// With [`MetainfoBuilder::hybrid`] BitTorrent v2 (BEP 52) `file tree` and `piece layers` are added
+/// alongside v1 `pieces`, so torrent is accepted by both v1 and v2 clients.
+#[derive(Debug, Clone)]
+pub struct MetainfoBuilder {
+    path: PathBuf,
+    announce: String,
+    announce_list: Option<Vec<Vec<String>>>,
+    name: Option<String>,
+    piece_length: Option<BInt>,
+    private: Option<bool>,
+    comment: Option<String>,
+    created_by: Option<String>,
+    creation_date: Option<BInt>,
+    hybrid: bool,
+    hasher: PieceHasher,
+}
+
+impl Metainfo {
+    /// Starts building metainfo for file or directory at `path`, announced to tracker at `announce` URL.
+    pub fn builder(path: impl Into<PathBuf>, announce: impl Into<String>) -> MetainfoBuilder {
+        MetainfoBuilder {
+            path: path.into(),
+            announce: announce.into(),
+            announce_list: None,
+            name: None,
+            piece_length: None,
+            private: None,
+            comment: None,
+            created_by: None,
+            creation_date: None,
+            hybrid: false,
+            hasher: PieceHasher::default(),
+        }
+    }
+}
+
+impl MetainfoBuilder {
+    /// Smallest piece length, chosen automatically.
+    pub const MIN_PIECE_LENGTH: BInt = 1 << 14;
+    /// Largest piece length, chosen automatically.
+    pub const MAX_PIECE_LENGTH: BInt = 1 << 24;
+    /// Piece count, automatically chosen piece length aims for.
+    pub const TARGET_PIECE_COUNT: BInt = 1500;
+    /// Size of leaf blocks of v2 merkle trees, which is also smallest piece length of hybrid torrent.
+    pub const V2_BLOCK_SIZE: BInt = 1 << 14;
+
+    pub fn announce_list(mut self, tiers: Vec<Vec<String>>) -> Self {
+        self.announce_list = Some(tiers);
+        self
+    }
+
+    /// Name of torrent, defaults to file name of path.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets piece length, which must be power of two.
+    pub fn piece_length(mut self, piece_length: BInt) -> Self {
+        self.piece_length = Some(piece_length);
+        self
+    }
+
+    pub fn private(mut self, private: bool) -> Self {
+        self.private = Some(private);
+        self
+    }
+
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    pub fn created_by(mut self, created_by: impl Into<String>) -> Self {
+        self.created_by = Some(created_by.into());
+        self
+    }
+
+    /// Creation time in seconds since UNIX epoch. Not set by default, so output doesn't depend on time of building.
+    pub fn creation_date(mut self, creation_date: BInt) -> Self {
+        self.creation_date = Some(creation_date);
+        self
+    }
+
+    /// Builds hybrid v1+v2 torrent. Every file, except the last one, is followed by padding file,
+    /// so v1 pieces don't cross file boundaries, as v2 requires.
+    pub fn hybrid(mut self, hybrid: bool) -> Self {
+        self.hybrid = hybrid;
+        self
+    }
+
+    /// Sets hasher of pieces, i.e. to limit number of threads. All CPUs are used by default.
+    pub fn hasher(mut self, hasher: PieceHasher) -> Self {
+        self.hasher = hasher;
+        self
+    }
+
+    /// Reads all files and hashes their pieces.
+    ///
+    /// # Errors
+    ///
+    /// Besides I/O errors, fails with [`io::ErrorKind::InvalidInput`] if path contains no files,
+    /// file names are not valid UTF-8 or piece length is not power of two
+    /// (or is less than [`Self::V2_BLOCK_SIZE`] for hybrid torrent).
+    pub fn build(self) -> io::Result<Metainfo> {
+        let name = match self.name {
+            Some(name) => name,
+            None => utils::file_name(&self.path)?,
+        };
+
+        let is_dir = fs::metadata(&self.path)?.is_dir();
+        let entries = if is_dir {
+            utils::walk(&self.path)?
+        } else {
+            vec![(self.path.clone(), fs::metadata(&self.path)?.len())]
+        };
+
+        if entries.is_empty() {
+            return Err(utils::invalid_input("no files to build torrent from"));
+        }
+
+        let total_length = entries.iter().map(|(_, length)| length).sum::<BInt>();
+        let piece_length = self
+            .piece_length
+            .unwrap_or_else(|| Self::auto_piece_length(total_length));
+
+        if !piece_length.is_power_of_two() {
+            return Err(utils::invalid_input("piece length must be power of two"));
+        }
+
+        if self.hybrid && piece_length < Self::V2_BLOCK_SIZE {
+            return Err(utils::invalid_input("piece length of hybrid torrent must be at least 16 KiB"));
+        }
+
+        // Padding after each file, aligning next one to piece boundary
+        let paddings = entries
+            .iter()
+            .enumerate()
+            .map(|(i, (_, length))| {
+                if self.hybrid && i + 1 < entries.len() {
+                    (piece_length - length % piece_length) % piece_length
+                } else {
+                    0
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let pieces = utils::hash_pieces(
+            entries.iter().zip(&paddings).map(|((path, _), padding)| (path, *padding)),
+            piece_length,
+            self.hasher,
+        )?;
+
+        let files = if is_dir {
+            let mut files = vec![];
+
+            for ((path, length), padding) in entries.iter().zip(&paddings) {
+                files.push(FileInfo {
+                    length: *length,
+                    md5sum: None,
+                    path: utils::relative_path(&self.path, path)?,
+                    attr: None,
+                });
+
+                if *padding > 0 {
+                    files.push(FileInfo {
+                        length: *padding,
+                        md5sum: None,
+                        path: vec![".pad".to_owned(), padding.to_string()],
+                        attr: Some("p".to_owned()),
+                    });
+                }
+            }
+
+            Files::Multiple { files }
+        } else {
+            Files::Single {
+                length: total_length,
+                md5sum: None,
+            }
+        };
+
+        let mut info_extra = BDictionary::new();
+        let mut extra = BDictionary::new();
+
+        if self.hybrid {
+            let mut file_tree = BDictionary::new();
+            let mut piece_layers = BDictionary::new();
+
+            for (path, length) in &entries {
+                let tree_path = if is_dir {
+                    utils::relative_path(&self.path, path)?
+                } else {
+                    vec![name.clone()]
+                };
+
+                let mut file = BDictionary::new();
+                file.insert(utils::key("length"), Entry::Integer(*length));
+
+                if let Some(tree) = utils::merkle_tree(path, piece_length)? {
+                    file.insert(utils::key("pieces root"), Entry::String(BString(tree.root.to_vec())));
+
+                    if let Some(piece_layer) = tree.piece_layer {
+                        piece_layers.insert(BString(tree.root.to_vec()), Entry::String(BString(piece_layer)));
+                    }
+                }
+
+                utils::insert_into_tree(&mut file_tree, &tree_path, file);
+            }
+
+            info_extra.insert(utils::key("meta version"), Entry::Integer(2));
+            info_extra.insert(utils::key("file tree"), Entry::Dictionary(file_tree));
+            extra.insert(utils::key("piece layers"), Entry::Dictionary(piece_layers));
+        }
+
+        Ok(Metainfo {
+            info: Info {
+                piece_length,
+                pieces,
+                private: self.private,
+                name,
+                files,
+                extra: info_extra,
+            },
+            announce: self.announce,
+            announce_list: self.announce_list,
+            creation_date: self.creation_date,
+            comment: self.comment,
+            created_by: self.created_by,
+            encoding: None,
+            extra,
+            raw_info: None,
+            raw: None,
+        })
+    }
+
+    /// Smallest power of two, which splits `total_length` into at most [`Self::TARGET_PIECE_COUNT`] pieces,
+    /// clamped between [`Self::MIN_PIECE_LENGTH`] and [`Self::MAX_PIECE_LENGTH`].
+    pub fn auto_piece_length(total_length: BInt) -> BInt {
+        let piece_length = total_length.div_ceil(Self::TARGET_PIECE_COUNT).next_power_of_two();
+
+        piece_length.clamp(Self::MIN_PIECE_LENGTH, Self::MAX_PIECE_LENGTH)
+    }
+}
+
+mod utils {
+    use super::*;
+
+    pub fn invalid_input(msg: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, msg)
+    }
+
+    pub fn file_name(path: &Path) -> io::Result<String> {
+        let path = path.canonicalize()?;
+
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .map(ToOwned::to_owned)
+            .ok_or_else(|| invalid_input("file name is not valid UTF-8"))
+    }
+
+    /// Lists regular files in `dir` recursively with their lengths, sorted by path.
+    /// `.pad` directories with padding files, written by some clients, are skipped.
+    pub fn walk(dir: &Path) -> io::Result<Vec<(PathBuf, BInt)>> {
+        let mut files = vec![];
+        let mut dirs = vec![dir.to_owned()];
+
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                let metadata = fs::metadata(&path)?;
+
+                if metadata.is_dir() && path.file_name() == Some(".pad".as_ref()) {
+                    continue;
+                } else if metadata.is_dir() {
+                    dirs.push(path);
+                } else if metadata.is_file() {
+                    files.push((path, metadata.len()));
+                }
+            }
+        }
+
+        files.sort();
+        Ok(files)
+    }
+
+    pub fn relative_path(root: &Path, path: &Path) -> io::Result<Vec<String>> {
+        path.strip_prefix(root)
+            .map_err(|_| invalid_input("file is outside of torrent root"))?
+            .components()
+            .map(|component| {
+                component
+                    .as_os_str()
+                    .to_str()
+                    .map(ToOwned::to_owned)
+                    .ok_or_else(|| invalid_input("file name is not valid UTF-8"))
+            })
+            .collect()
+    }
+
+    pub fn key(key: &str) -> BString {
+        BString(key.as_bytes().to_vec())
+    }
+
+    /// Memory, batch of pieces, which are hashed in parallel, may take.
+    const BATCH_MEMORY: usize = 64 << 20;
+
+    /// Hashes concatenation of `files`, each followed by given number of zero padding bytes,
+    /// in pieces of `piece_length`. Last piece may be shorter.
+    pub fn hash_pieces<'a>(
+        files: impl IntoIterator<Item = (&'a PathBuf, BInt)>,
+        piece_length: BInt,
+        hasher: PieceHasher,
+    ) -> io::Result<BString> {
+        let batch_size = (hasher.threads() * 2).min(BATCH_MEMORY / piece_length as usize).max(1);
+        let mut pieces = vec![];
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut piece = Vec::with_capacity(piece_length as usize);
+
+        for (path, padding) in files {
+            let mut file = File::open(path)?.chain(io::repeat(0).take(padding));
+
+            loop {
+                let remaining = piece_length as usize - piece.len();
+                file.by_ref().take(remaining as u64).read_to_end(&mut piece)?;
+
+                // Piece is not complete only if file is exhausted
+                if piece.len() < piece_length as usize {
+                    break;
+                }
+
+                batch.push(std::mem::replace(&mut piece, Vec::with_capacity(piece_length as usize)));
+                if batch.len() == batch_size {
+                    pieces.extend(hasher.hash_all(&batch).iter().flatten());
+                    batch.clear();
+                }
+            }
+        }
+
+        if !piece.is_empty() {
+            batch.push(piece);
+        }
+        pieces.extend(hasher.hash_all(&batch).iter().flatten());
+
+        Ok(BString(pieces))
+    }
+
+    pub struct MerkleTree {
+        pub root: [u8; 32],
+        /// Concatenated hashes of piece layer, if file is longer than one piece.
+        pub piece_layer: Option<Vec<u8>>,
+    }
+
+    /// Builds v2 merkle tree of file at `path` from SHA-256 hashes of its 16 KiB blocks, padded with zero hashes
+    /// to power of two. Empty file has no tree.
+    pub fn merkle_tree(path: &Path, piece_length: BInt) -> io::Result<Option<MerkleTree>> {
+        let mut file = File::open(path)?;
+        let mut layer = vec![];
+        let mut block = Vec::with_capacity(MetainfoBuilder::V2_BLOCK_SIZE as usize);
+
+        loop {
+            block.clear();
+            file.by_ref().take(MetainfoBuilder::V2_BLOCK_SIZE).read_to_end(&mut block)?;
+
+            if block.is_empty() {
+                break;
+            }
+
+            layer.push(<[u8; 32]>::from(Sha256::digest(&block)));
+        }
+
+        if layer.is_empty() {
+            return Ok(None);
+        }
+
+        let blocks_per_piece = (piece_length / MetainfoBuilder::V2_BLOCK_SIZE) as usize;
+        let piece_count = layer.len().div_ceil(blocks_per_piece);
+        let has_piece_layer = layer.len() > blocks_per_piece;
+
+        layer.resize(layer.len().next_power_of_two(), [0; 32]);
+
+        let mut width = 1;
+        let mut piece_layer = None;
+
+        while layer.len() > 1 {
+            if width == blocks_per_piece && has_piece_layer {
+                piece_layer = Some(layer[..piece_count].concat());
+            }
+
+            layer = layer
+                .chunks(2)
+                .map(|pair| Sha256::new().chain_update(pair[0]).chain_update(pair[1]).finalize().into())
+                .collect();
+            width *= 2;
+        }
+
+        Ok(Some(MerkleTree { root: layer[0], piece_layer }))
+    }
+
+    /// Inserts `file` into nested directories of v2 `file tree` under `path`.
+    pub fn insert_into_tree(tree: &mut BDictionary, path: &[String], file: BDictionary) {
+        let node = path.iter().fold(tree, |tree, part| {
+            match tree.entry(key(part)).or_insert_with(|| Entry::Dictionary(BDictionary::new())) {
+                Entry::Dictionary(node) => node,
+                _ => unreachable!("file tree contains only dictionaries"),
+            }
+        });
+
+        node.insert(key(""), Entry::Dictionary(file));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+    use sha1::Sha1;
+
+    #[test]
+    fn single_file_is_hashed() {
+        let dir = temp_dir("builder-single");
+        let path = dir.join("hello.txt");
+        fs::write(&path, b"hello").unwrap();
+
+        let metainfo = Metainfo::builder(&path, "http://tracker.example/announce")
+            .private(true)
+            .comment("test")
+            .build()
+            .unwrap();
+
+        assert_eq!(metainfo.info.name, "hello.txt");
+        assert_eq!(metainfo.info.piece_length, MetainfoBuilder::MIN_PIECE_LENGTH);
+        assert_eq!(metainfo.info.files, Files::Single { length: 5, md5sum: None });
+        assert_eq!(&*metainfo.info.pieces.0, &*Sha1::digest(b"hello"));
+        assert_eq!(metainfo.info.private, Some(true));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn directory_is_hashed_as_one_stream() {
+        let dir = temp_dir("builder-multi");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("b.txt"), b"abc").unwrap();
+        fs::write(dir.join("sub").join("a.txt"), b"defgh").unwrap();
+
+        let metainfo = Metainfo::builder(&dir, "http://tracker.example/announce")
+            .name("content")
+            .piece_length(4)
+            .build()
+            .unwrap();
+
+        let Files::Multiple { files } = &metainfo.info.files else { panic!("single file torrent") };
+        let paths = files.iter().map(|file| file.path.join("/")).collect::<Vec<_>>();
+        let expected = [&b"abcd"[..], b"efgh"]
+            .iter()
+            .flat_map(Sha1::digest)
+            .collect::<Vec<_>>();
+
+        assert_eq!(paths, ["b.txt", "sub/a.txt"]);
+        assert_eq!(metainfo.info.pieces.0, expected);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn hybrid_torrent_is_padded() {
+        let dir = temp_dir("builder-hybrid");
+        let first = vec![1; 20000];
+        fs::write(dir.join("a.bin"), &first).unwrap();
+        fs::write(dir.join("b.txt"), b"hello").unwrap();
+
+        let metainfo = Metainfo::builder(&dir, "http://tracker.example/announce")
+            .piece_length(MetainfoBuilder::V2_BLOCK_SIZE)
+            .hybrid(true)
+            .build()
+            .unwrap();
+
+        let Files::Multiple { files } = &metainfo.info.files else { panic!("single file torrent") };
+        let padding = &files[1];
+
+        assert_eq!(files.len(), 3);
+        assert_eq!(padding.length, 2 * MetainfoBuilder::V2_BLOCK_SIZE - 20000);
+        assert!(padding.is_padding());
+        assert_eq!(metainfo.info.pieces.0.len(), 3 * 20);
+
+        let blocks = [Sha256::digest(&first[..1 << 14]), Sha256::digest(&first[1 << 14..])];
+        let root = Sha256::new().chain_update(blocks[0]).chain_update(blocks[1]).finalize();
+        let file_tree = metainfo.info.extra.get(&b"file tree"[..]).unwrap();
+
+        assert_eq!(metainfo.info.extra.get(&b"meta version"[..]), Some(&Entry::Integer(2)));
+        let pieces_root = file_tree.get("a.bin").and_then(|file| file.get("")?.get("pieces root"));
+
+        assert_eq!(pieces_root, Some(&Entry::String(BString(root.to_vec()))));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn piece_length_is_chosen_by_size() {
+        assert_eq!(MetainfoBuilder::auto_piece_length(0), MetainfoBuilder::MIN_PIECE_LENGTH);
+        assert_eq!(MetainfoBuilder::auto_piece_length(1500 << 20), 1 << 20);
+        assert_eq!(MetainfoBuilder::auto_piece_length(BInt::MAX / 2), MetainfoBuilder::MAX_PIECE_LENGTH);
+    }
+}
@@ -0,0 +1,197 @@
+//! Canonical bencoded representation of metainfo, used for saving it and computing info-hash.
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+use super::encoding::{BDictionary, BEncode, Entry};
+use super::{BInt, BString, FileInfo, Files, Info, Metainfo};
+
+impl Metainfo {
+    ///Returns bencoded dictionary representation of `self`.
+    pub fn to_entry(&self) -> Entry {
+        let mut metainfo = self.extra.clone();
+
+        utils::insert(&mut metainfo, "info", Some(self.info.to_entry()));
+        utils::insert(&mut metainfo, "announce", Some(utils::string(&self.announce)));
+        utils::insert(
+            &mut metainfo,
+            "announce-list",
+            self.announce_list.as_ref().map(|tiers| {
+                Entry::List(
+                    tiers
+                        .iter()
+                        .map(|tier| Entry::List(tier.iter().map(|url| utils::string(url)).collect()))
+                        .collect(),
+                )
+            }),
+        );
+        utils::insert(&mut metainfo, "creation date", self.creation_date.map(Entry::Integer));
+        utils::insert(&mut metainfo, "comment", self.comment.as_deref().map(utils::string));
+        utils::insert(&mut metainfo, "created by", self.created_by.as_deref().map(utils::string));
+        utils::insert(&mut metainfo, "encoding", self.encoding.as_deref().map(utils::string));
+
+        Entry::Dictionary(metainfo)
+    }
+
+    ///Returns SHA-1 of `info` dictionary, identifying torrent in handshakes and tracker announces.
+    ///
+    ///Hash is computed over [`Metainfo::raw_info`] if it is known, so it always matches one of parsed file.
+    ///Once [`Metainfo::info`] is changed, hash of its canonical encoding is returned instead.
+    pub fn info_hash(&self) -> [u8; 20] {
+        match self.raw_info() {
+            Some(raw_info) => Sha1::digest(raw_info).into(),
+            None => self.info.info_hash(),
+        }
+    }
+
+    ///Returns SHA-256 of `info` dictionary, identifying v2 torrent (BEP 52). Same as [`Metainfo::info_hash`],
+    ///it's computed over [`Metainfo::raw_info`] if it is known and `info` wasn't changed.
+    pub fn info_hash_v2(&self) -> [u8; 32] {
+        match self.raw_info() {
+            Some(raw_info) => Sha256::digest(raw_info).into(),
+            None => self.info.info_hash_v2(),
+        }
+    }
+
+    ///Returns v2 info-hash, truncated to 20 bytes, as it's sent in handshakes, tracker announces and DHT
+    ///for v2 and hybrid torrents.
+    pub fn info_hash_v2_truncated(&self) -> [u8; 20] {
+        utils::truncate(&self.info_hash_v2())
+    }
+}
+
+impl Info {
+    ///Returns bencoded dictionary representation of `self`.
+    pub fn to_entry(&self) -> Entry {
+        let mut info = self.extra.clone();
+
+        utils::insert(&mut info, "piece length", Some(Entry::Integer(self.piece_length)));
+        utils::insert(&mut info, "pieces", Some(Entry::String(self.pieces.clone())));
+        utils::insert(&mut info, "private", self.private.map(|private| Entry::Integer(private as BInt)));
+        utils::insert(&mut info, "name", Some(utils::string(&self.name)));
+
+        match &self.files {
+            Files::Multiple { files } => {
+                let files = files.iter().map(FileInfo::to_entry).collect();
+                utils::insert(&mut info, "files", Some(Entry::List(files)));
+            }
+            Files::Single { length, md5sum } => {
+                utils::insert(&mut info, "length", Some(Entry::Integer(*length)));
+                utils::insert(&mut info, "md5sum", md5sum.clone().map(Entry::String));
+            }
+        }
+
+        Entry::Dictionary(info)
+    }
+
+    ///Returns SHA-1 of canonically bencoded `self` (with keys sorted).
+    ///
+    ///Matches info-hash of parsed file only if its `info` dictionary was canonical, prefer [`Metainfo::info_hash`].
+    pub fn info_hash(&self) -> [u8; 20] {
+        Sha1::digest(self.to_entry().encode()).into()
+    }
+
+    ///Returns SHA-256 of canonically bencoded `self`. Same as [`Info::info_hash`], prefer [`Metainfo::info_hash_v2`].
+    pub fn info_hash_v2(&self) -> [u8; 32] {
+        Sha256::digest(self.to_entry().encode()).into()
+    }
+}
+
+impl FileInfo {
+    ///Returns bencoded dictionary representation of `self`.
+    pub fn to_entry(&self) -> Entry {
+        let mut info = BDictionary::new();
+
+        utils::insert(&mut info, "length", Some(Entry::Integer(self.length)));
+        utils::insert(&mut info, "md5sum", self.md5sum.clone().map(Entry::String));
+        utils::insert(
+            &mut info,
+            "path",
+            Some(Entry::List(self.path.iter().map(|part| utils::string(part)).collect())),
+        );
+        utils::insert(&mut info, "attr", self.attr.as_deref().map(utils::string));
+
+        Entry::Dictionary(info)
+    }
+}
+
+mod utils {
+    use super::*;
+
+    pub fn insert(dictionary: &mut BDictionary, key: &str, value: Option<Entry>) {
+        if let Some(value) = value {
+            dictionary.insert(BString(key.as_bytes().to_vec()), value);
+        }
+    }
+
+    pub fn string(value: &str) -> Entry {
+        Entry::String(BString(value.as_bytes().to_vec()))
+    }
+
+    pub fn truncate(hash: &[u8; 32]) -> [u8; 20] {
+        hash[..20].try_into().expect("hash is longer than 20 bytes")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use super::*;
+    use crate::bencoded::encoding::borrowed;
+    use crate::test_utils;
+
+    static SAMPLE_TORRENT: &[u8] = include_bytes!("sample.torrent");
+
+    #[test]
+    fn info_hash_is_computed() {
+        let entry = borrowed::Entry::from_bytes(SAMPLE_TORRENT).unwrap().to_owned_entry();
+        let Entry::Dictionary(mut metainfo) = entry else { panic!("metainfo is not a dictionary") };
+        let info = metainfo.remove(&b"info"[..]).unwrap();
+        let Entry::Dictionary(mut info) = info else { panic!("info is not a dictionary") };
+
+        let info = Info {
+            piece_length: info.remove(&b"piece length"[..]).unwrap().parse().unwrap(),
+            pieces: info.remove(&b"pieces"[..]).unwrap().parse().unwrap(),
+            private: None,
+            name: info.remove(&b"name"[..]).unwrap().parse().unwrap(),
+            files: Files::Single {
+                length: info.remove(&b"length"[..]).unwrap().parse().unwrap(),
+                md5sum: None,
+            },
+            extra: info,
+        };
+
+        assert_eq!(info.info_hash(), hex!("d0d14c926e6e99761a2fdcff27b403d96376eff6"));
+        assert_eq!(
+            info.info_hash_v2(),
+            hex!("b342faffa94bd1d5f202114cc1118314fdfa16ecc0f17e6b13b04dfd15572a2a")
+        );
+
+        let metainfo = Metainfo::from_raw_info(info.clone(), BString(info.to_entry().encode().into_vec()));
+        assert_eq!(metainfo.info_hash_v2_truncated(), hex!("b342faffa94bd1d5f202114cc1118314fdfa16ec"));
+    }
+
+    #[test]
+    fn info_hash_follows_edited_info() {
+        // Keys are not sorted, so canonical encoding differs from raw one
+        let raw_info = b"d4:name1:a6:lengthi20e12:piece lengthi16e6:pieces20:aaaaaaaaaaaaaaaaaaaae";
+        let files = Files::Single {
+            length: 20,
+            md5sum: None,
+        };
+        let info = Info {
+            pieces: BString(vec![b'a'; 20]),
+            name: "a".to_owned(),
+            ..test_utils::info(16, files)
+        };
+        let mut metainfo = Metainfo::from_raw_info(info, BString(raw_info.to_vec()));
+
+        assert_eq!(metainfo.info_hash(), <[u8; 20]>::from(Sha1::digest(raw_info)));
+        assert_eq!(metainfo.info_hash_v2(), <[u8; 32]>::from(Sha256::digest(raw_info)));
+
+        metainfo.info.name = "renamed".to_owned();
+
+        assert_eq!(metainfo.info_hash(), metainfo.info.info_hash());
+        assert_eq!(metainfo.info_hash_v2(), metainfo.info.info_hash_v2());
+    }
+}
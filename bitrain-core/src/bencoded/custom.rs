@@ -1,17 +1,90 @@
+use std::io::{self, Read, Write};
 
-use super::BInt;
-pub type BStr = [u8];
-pub type BString = Box<[u8]>;
+mod convert;
 
+pub use convert::{FromEntry, ToEntry};
 
-#[cfg(feature = "custom-bencode")]
-use super::encoding::*;
+use super::encoding::{self, borrowed, BDictionary, BEncode, BList, Entry, Limits};
+use super::{BInt, FileInfo, Files, Info, Metainfo, Parser, Saver};
+
+/// Used for parsing and saving `.torrent` files with built-in bencode implementation (see [`Parser`], [`Saver`]),
+/// for consumers, who opt out of `serde`.
+pub struct Custom;
+
+impl Custom {
+    ///Same as [`Parser::parse`], but with custom decoding `limits` instead of default ones.
+    pub fn parse_with_limits(&self, mut source: impl Read, limits: Limits) -> Result<Metainfo> {
+        let mut bytes = vec![];
+        source.read_to_end(&mut bytes)?;
+
+        let entry = borrowed::Entry::from_bytes_with_limits(&bytes, limits)?;
+        let mut metainfo = Metainfo::parse(entry.to_owned_entry())?;
+        metainfo.record_raw(&bytes);
+
+        Ok(metainfo)
+    }
+}
+
+impl Parser<Metainfo> for Custom {
+    type Err = CustomParseError;
+
+    fn parse(&self, source: impl Read) -> std::result::Result<Metainfo, Self::Err> {
+        self.parse_with_limits(source, Limits::default())
+    }
+}
+
+impl Saver<Metainfo> for Custom {
+    type Err = io::Error;
+
+    fn save(&self, item: &Metainfo, mut target: impl Write) -> std::result::Result<(), Self::Err> {
+        let mut bytes = item.to_entry().encode().into_vec();
+        item.restore_raw(&mut bytes);
+
+        target.write_all(&bytes)
+    }
+}
+
+#[derive(Debug)]
+pub enum CustomParseError {
+    Bencode(encoding::Error),
+    MissingField(&'static str),
+    /// Field is present, but has unexpected type or value.
+    InvalidField(&'static str),
+    /// Value has unexpected type or value, outside of any known field.
+    InvalidValue,
+}
+
+impl std::fmt::Display for CustomParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bencode(err) => write!(f, "malformed bencode: {}", err),
+            Self::MissingField(field) => write!(f, "missing field `{}`", field),
+            Self::InvalidField(field) => write!(f, "invalid field `{}`", field),
+            Self::InvalidValue => write!(f, "invalid value"),
+        }
+    }
+}
+
+impl std::error::Error for CustomParseError {}
+
+impl From<encoding::Error> for CustomParseError {
+    fn from(err: encoding::Error) -> Self {
+        Self::Bencode(err)
+    }
+}
+
+impl From<io::Error> for CustomParseError {
+    fn from(err: io::Error) -> Self {
+        Self::Bencode(encoding::Error::IO(err))
+    }
+}
+
+type Result<T> = std::result::Result<T, CustomParseError>;
 
-#[cfg(feature = "custom-bencode")]
 impl Metainfo {
-    ///Parses deencoded metadata file and returns `Self`
+    ///Parses decoded metadata file and returns `Self`
     pub fn parse(entry: Entry) -> Result<Self> {
-        let mut metainfo = entry.parse_or_err(Error::InvalidFormat("metainfo"))?;
+        let mut metainfo = entry.parse_or_err(CustomParseError::InvalidField("metainfo"))?;
 
         let info = utils::parse_required(&mut metainfo, "info", Info::parse)?;
         let announce = utils::parse_required_primitive(&mut metainfo, "announce")?;
@@ -20,8 +93,7 @@ impl Metainfo {
             &mut metainfo,
             "announce-list",
         ));
-        let creation_date = utils::parse_optional_primitive(&mut metainfo, "creation date")
-            .map(|secs| NaiveDateTime::from_timestamp(secs, 0));
+        let creation_date = utils::parse_optional_primitive(&mut metainfo, "creation date");
         let comment = utils::parse_optional_primitive(&mut metainfo, "comment");
         let created_by = utils::parse_optional_primitive(&mut metainfo, "created by");
         let encoding = utils::parse_optional_primitive(&mut metainfo, "encoding");
@@ -34,6 +106,9 @@ impl Metainfo {
             comment,
             created_by,
             encoding,
+            extra: metainfo,
+            raw_info: None,
+            raw: None,
         })
     }
 
@@ -41,11 +116,7 @@ impl Metainfo {
         let tiers = blist?
             .into_iter()
             .filter_map(Entry::parse::<BList>)
-            .map(|tier_list| {
-                tier_list
-                    .into_iter()
-                    .map(Entry::parse::<String>)
-            })
+            .map(|tier_list| tier_list.into_iter().map(Entry::parse::<String>))
             .filter_map(Iterator::collect::<Option<Vec<_>>>)
             .collect();
 
@@ -53,10 +124,9 @@ impl Metainfo {
     }
 }
 
-#[cfg(feature = "custom-bencode")]
 impl Info {
     pub fn parse(entry: Entry) -> Result<Self> {
-        let mut info = entry.parse_or_err(Error::InvalidFormat("info"))?;
+        let mut info = entry.parse_or_err(CustomParseError::InvalidField("info"))?;
 
         let piece_length = utils::parse_required_primitive(&mut info, "piece length")?;
         let pieces = utils::parse_required_primitive(&mut info, "pieces")?;
@@ -73,19 +143,16 @@ impl Info {
             private,
             name,
             files,
+            extra: info,
         })
     }
 
-    fn parse_file_info(info: &mut BDictionary) -> Result<Vec<FileInfo>> {
+    fn parse_file_info(info: &mut BDictionary) -> Result<Files> {
         if !info.contains_key("files".as_bytes()) {
             let length = utils::parse_required_primitive(info, "length")?;
             let md5sum = utils::parse_optional_primitive(info, "md5sum");
 
-            Ok(vec![FileInfo {
-                length,
-                md5sum,
-                path: Vec::new(),
-            }])
+            Ok(Files::Single { length, md5sum })
         } else {
             let entries = utils::parse_required_primitive::<BList>(info, "files")?;
 
@@ -94,46 +161,45 @@ impl Info {
                 .map(FileInfo::parse)
                 .collect::<Result<Vec<_>>>()?;
 
-            Ok(files)
+            Ok(Files::Multiple { files })
         }
     }
 }
 
-#[cfg(feature = "custom-bencode")]
 impl FileInfo {
     pub fn parse(entry: Entry) -> Result<Self> {
-        let mut info = entry.parse_or_err(Error::InvalidFormat("files"))?;
+        let mut info = entry.parse_or_err(CustomParseError::InvalidField("files"))?;
 
         let path = utils::parse_required_primitive::<BList>(&mut info, "path")?
             .into_iter()
-            .map(|entry| String::try_from(entry).map_err(|_| Error::InvalidFormat("path")))
+            .map(|entry| String::try_from(entry).map_err(|_| CustomParseError::InvalidField("path")))
             .collect::<Result<Vec<_>>>()?;
         let length = utils::parse_required_primitive(&mut info, "length")?;
         let md5sum = utils::parse_optional_primitive(&mut info, "md5sum");
+        let attr = utils::parse_optional_primitive(&mut info, "attr");
 
         Ok(Self {
             length,
             md5sum,
             path,
+            attr,
         })
     }
+
 }
 
 mod utils {
     use super::*;
 
-    #[cfg(feature = "custom-bencode")]
-    pub fn parse_optional_primitive<T: TryFrom<Entry>>(
-        dictionary: &mut BDictionary,
-        key: &str,
-    ) -> Option<T> {
+    pub fn parse_optional_primitive<T>(dictionary: &mut BDictionary, key: &str) -> Option<T>
+    where
+        Entry: TryInto<T>,
+    {
         dictionary
             .remove(key.as_bytes())
-            .map(|entry| entry.parse::<T>())
-            .flatten()
+            .and_then(|entry| entry.parse::<T>())
     }
 
-    #[cfg(feature = "custom-bencode")]
     pub fn parse_required_primitive<T>(dictionary: &mut BDictionary, key: &'static str) -> Result<T>
     where
         Entry: TryInto<T>,
@@ -141,11 +207,10 @@ mod utils {
         dictionary
             .remove(key.as_bytes())
             .map(|entry| entry.parse::<T>())
-            .ok_or(Error::MissingField(key))?
-            .ok_or(Error::InvalidFormat(key))
+            .ok_or(CustomParseError::MissingField(key))?
+            .ok_or(CustomParseError::InvalidField(key))
     }
 
-    #[cfg(feature = "custom-bencode")]
     pub fn parse_required<T>(
         dictionary: &mut BDictionary,
         key: &'static str,
@@ -153,7 +218,46 @@ mod utils {
     ) -> Result<T> {
         dictionary
             .remove(key.as_bytes())
-            .ok_or(Error::MissingField(key))
+            .ok_or(CustomParseError::MissingField(key))
             .map(parser)?
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SAMPLE_TORRENT: &[u8] = include_bytes!("sample.torrent");
+
+    #[test]
+    fn metainfo_roundtrip() {
+        let metainfo: Metainfo = Custom.parse(SAMPLE_TORRENT).unwrap();
+
+        let mut encoded = vec![];
+        Custom.save(&metainfo, &mut encoded).unwrap();
+
+        assert_eq!(metainfo.info.name, "sample.txt");
+        assert_eq!(metainfo.raw_info(), Some(&*metainfo.info.to_entry().encode()));
+        assert_eq!(encoded, SAMPLE_TORRENT);
+
+        #[cfg(feature = "use-serde")]
+        assert_eq!(Parser::<Metainfo>::parse(&super::super::Serde, SAMPLE_TORRENT).unwrap(), metainfo);
+    }
+
+    #[test]
+    fn unknown_keys_are_preserved() {
+        let torrent = b"d4:infod6:lengthi20e4:name1:a12:piece lengthi16e6:pieces0:6:source3:abce5:nodesle8:announce3:urle";
+        let metainfo: Metainfo = Custom.parse(&torrent[..]).unwrap();
+
+        assert_eq!(metainfo.extra.get(&b"nodes"[..]), Some(&Entry::List(vec![])));
+        assert_eq!(metainfo.info.extra.len(), 1);
+        assert_eq!(metainfo.to_entry(), borrowed::Entry::from_bytes(torrent).unwrap().to_owned_entry());
+    }
+
+    #[test]
+    fn missing_fields_are_reported() {
+        let err = Parser::<Metainfo>::parse(&Custom, &b"d8:announce3:urle"[..]).unwrap_err();
+
+        assert!(matches!(err, CustomParseError::MissingField("info")));
+    }
+}
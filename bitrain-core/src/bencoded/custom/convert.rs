@@ -0,0 +1,190 @@
+//! Conversions between bencoded [`Entry`] and Rust types, used by `#[derive(Bencode, Bdecode)]`.
+use super::{CustomParseError, Result};
+use crate::bencoded::encoding::{BDictionary, BList, Entry};
+use crate::bencoded::{BInt, BString};
+
+/// Type, which can be represented as bencoded [`Entry`]. Can be derived for structs with `#[derive(Bencode)]`.
+pub trait ToEntry {
+    fn to_entry(&self) -> Entry;
+
+    /// Inserts `self` into `dictionary` under `key`. Absent values (i.e. `None`) are not inserted.
+    fn insert_into(&self, dictionary: &mut BDictionary, key: &str) {
+        dictionary.insert(BString::from(key), self.to_entry());
+    }
+}
+
+/// Type, which can be parsed from bencoded [`Entry`]. Can be derived for structs with `#[derive(Bdecode)]`.
+pub trait FromEntry: Sized {
+    /// Converts `entry` into `Self`, returning [`CustomParseError::InvalidValue`] if it has unexpected type.
+    fn from_entry(entry: Entry) -> Result<Self>;
+
+    /// Removes value under `key` from `dictionary` and converts it into `Self`.
+    fn take_from(dictionary: &mut BDictionary, key: &'static str) -> Result<Self> {
+        let entry = dictionary
+            .remove(key.as_bytes())
+            .ok_or(CustomParseError::MissingField(key))?;
+
+        Self::from_entry(entry).map_err(|err| match err {
+            CustomParseError::InvalidValue => CustomParseError::InvalidField(key),
+            err => err,
+        })
+    }
+}
+
+impl ToEntry for Entry {
+    fn to_entry(&self) -> Entry {
+        self.clone()
+    }
+}
+
+impl FromEntry for Entry {
+    fn from_entry(entry: Entry) -> Result<Self> {
+        Ok(entry)
+    }
+}
+
+impl ToEntry for BInt {
+    fn to_entry(&self) -> Entry {
+        Entry::Integer(*self)
+    }
+}
+
+impl ToEntry for BString {
+    fn to_entry(&self) -> Entry {
+        Entry::String(self.clone())
+    }
+}
+
+impl ToEntry for str {
+    fn to_entry(&self) -> Entry {
+        Entry::String(BString::from(self))
+    }
+}
+
+impl ToEntry for String {
+    fn to_entry(&self) -> Entry {
+        self.as_str().to_entry()
+    }
+}
+
+impl<T: ToEntry> ToEntry for [T] {
+    fn to_entry(&self) -> Entry {
+        Entry::List(self.iter().map(ToEntry::to_entry).collect())
+    }
+}
+
+impl<T: ToEntry> ToEntry for Vec<T> {
+    fn to_entry(&self) -> Entry {
+        self.as_slice().to_entry()
+    }
+}
+
+impl ToEntry for BDictionary {
+    fn to_entry(&self) -> Entry {
+        Entry::Dictionary(self.clone())
+    }
+}
+
+impl<T: ToEntry> ToEntry for Option<T> {
+    ///Only meaningful for `Some`, as bencode has no null value. `None` is encoded as empty list.
+    fn to_entry(&self) -> Entry {
+        match self {
+            Some(value) => value.to_entry(),
+            None => Entry::List(vec![]),
+        }
+    }
+
+    fn insert_into(&self, dictionary: &mut BDictionary, key: &str) {
+        if let Some(value) = self {
+            value.insert_into(dictionary, key)
+        }
+    }
+}
+
+macro_rules! impl_from_entry {
+    ($($ty:ty),*) => {$(
+        impl FromEntry for $ty {
+            fn from_entry(entry: Entry) -> Result<Self> {
+                entry.parse_or_err(CustomParseError::InvalidValue)
+            }
+        }
+    )*};
+}
+
+impl_from_entry!(BInt, BString, String, BDictionary);
+
+impl<T: FromEntry> FromEntry for Vec<T> {
+    fn from_entry(entry: Entry) -> Result<Self> {
+        entry
+            .parse_or_err::<BList, _>(CustomParseError::InvalidValue)?
+            .into_iter()
+            .map(T::from_entry)
+            .collect()
+    }
+}
+
+impl<T: FromEntry> FromEntry for Option<T> {
+    fn from_entry(entry: Entry) -> Result<Self> {
+        T::from_entry(entry).map(Some)
+    }
+
+    fn take_from(dictionary: &mut BDictionary, key: &'static str) -> Result<Self> {
+        if dictionary.contains_key(key.as_bytes()) {
+            T::take_from(dictionary, key).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitrain_derive::{Bdecode, Bencode};
+
+    use super::*;
+    use crate::bencoded::encoding::{borrowed, BEncode};
+
+    #[derive(Debug, PartialEq, Bencode, Bdecode)]
+    #[bencode(mod_path = "crate::bencoded")]
+    struct File {
+        length: BInt,
+        path: Vec<String>,
+        md5sum: Option<BString>,
+    }
+
+    #[derive(Debug, PartialEq, Bencode, Bdecode)]
+    #[bencode(mod_path = "crate::bencoded")]
+    struct Info {
+        name: String,
+        #[bencode(rename = "piece length")]
+        piece_length: BInt,
+        files: Vec<File>,
+        #[bencode(extra)]
+        extra: BDictionary,
+    }
+
+    #[test]
+    fn derived_struct_roundtrip() {
+        let bytes = b"d5:filesld6:lengthi3e4:pathl1:a1:beee4:name4:test12:piece lengthi16e7:privatei1ee";
+        let entry = borrowed::Entry::from_bytes(bytes).unwrap().to_owned_entry();
+        let info = Info::from_entry(entry).unwrap();
+
+        assert_eq!(info.piece_length, 16);
+        assert_eq!(
+            info.files,
+            [File { length: 3, path: vec!["a".into(), "b".into()], md5sum: None }]
+        );
+        assert_eq!(info.extra.get(&b"private"[..]), Some(&Entry::Integer(1)));
+        assert_eq!(&*info.to_entry().encode(), bytes);
+    }
+
+    #[test]
+    fn field_errors_are_reported() {
+        let missing = borrowed::Entry::from_bytes(b"d6:lengthi3ee").unwrap().to_owned_entry();
+        let invalid = borrowed::Entry::from_bytes(b"d6:lengthi3e4:pathi1ee").unwrap().to_owned_entry();
+
+        assert!(matches!(File::from_entry(missing), Err(CustomParseError::MissingField("path"))));
+        assert!(matches!(File::from_entry(invalid), Err(CustomParseError::InvalidField("path"))));
+        assert!(matches!(File::from_entry(Entry::Integer(1)), Err(CustomParseError::InvalidValue)));
+    }
+}
@@ -0,0 +1,172 @@
+use super::encoding::Entry;
+use super::{BString, Info, Metainfo};
+
+/// Editing helpers, which keep [`Metainfo::raw_info`] intact, unless `info` dictionary itself is changed.
+///
+/// Changing `info` (i.e. with [`Metainfo::set_private`] or [`Metainfo::set_source`]) changes info-hash,
+/// so saved file describes different torrent.
+impl Metainfo {
+    /// Adds tracker `url` to `tier` of announce list (BEP 12), appending new tier if `tier` is out of range.
+    ///
+    /// Announce list is created from `announce` if it is missing, as clients ignore `announce` in presence of list.
+    pub fn add_tracker(&mut self, tier: usize, url: impl Into<String>) {
+        let url = url.into();
+
+        if self.announce.is_empty() {
+            self.announce = url.clone();
+        }
+
+        let announce = &self.announce;
+        let tiers = self
+            .announce_list
+            .get_or_insert_with(|| vec![vec![announce.clone()]]);
+
+        match tiers.get_mut(tier) {
+            Some(tier) if !tier.contains(&url) => tier.push(url),
+            Some(_) => {}
+            None => tiers.push(vec![url]),
+        }
+    }
+
+    /// Removes tracker `url` from `announce` and all tiers of announce list, dropping emptied tiers.
+    /// Returns `false` if tracker wasn't found.
+    pub fn remove_tracker(&mut self, url: &str) -> bool {
+        let mut removed = false;
+
+        if let Some(tiers) = &mut self.announce_list {
+            for tier in tiers.iter_mut() {
+                let length = tier.len();
+                tier.retain(|tracker| tracker != url);
+                removed |= tier.len() != length;
+            }
+
+            tiers.retain(|tier| !tier.is_empty());
+        }
+
+        if self.announce == url {
+            removed = true;
+            self.announce = self
+                .announce_list
+                .iter()
+                .flatten()
+                .flatten()
+                .next()
+                .cloned()
+                .unwrap_or_default();
+        }
+
+        removed
+    }
+
+    pub fn set_comment(&mut self, comment: Option<String>) {
+        self.comment = comment;
+    }
+
+    /// Sets `private` flag (BEP 27) of `info`, removing it for `false`. Changes info-hash.
+    pub fn set_private(&mut self, private: bool) {
+        self.edit_info(|info| info.private = private.then_some(true));
+    }
+
+    /// Value of `source` key of `info`, used by private trackers to give torrents distinct info-hash.
+    pub fn source(&self) -> Option<&str> {
+        self.info.extra.get(&b"source"[..])?.as_str()
+    }
+
+    /// Sets or removes `source` key of `info`. Changes info-hash.
+    pub fn set_source(&mut self, source: Option<String>) {
+        self.edit_info(|info| match source {
+            Some(source) => {
+                info.extra
+                    .insert(BString(b"source".to_vec()), Entry::String(BString(source.into_bytes())));
+            }
+            None => {
+                info.extra.remove(&b"source"[..]);
+            }
+        });
+    }
+
+    /// Applies `edit` to `info`, dropping [`Metainfo::raw_info`] if it actually changed.
+    pub fn edit_info(&mut self, edit: impl FnOnce(&mut Info)) {
+        let original = self.info.clone();
+        edit(&mut self.info);
+
+        if self.info != original {
+            self.raw_info = None;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "use-serde"))]
+mod tests {
+    use crate::bencoded::encoding::Limits;
+    use crate::bencoded::Serde;
+
+    // Keys of `info` are not sorted, so re-encoding it would change info-hash
+    static UNSORTED_TORRENT: &[u8] = b"d8:announce3:url\
+        4:infod4:name1:a6:lengthi20e12:piece lengthi16e6:pieces20:aaaaaaaaaaaaaaaaaaaae\
+        e";
+
+    #[test]
+    fn info_hash_is_preserved() {
+        let mut metainfo = Serde.parse_metainfo(UNSORTED_TORRENT, Limits::default()).unwrap();
+        let info_hash = metainfo.info_hash();
+
+        metainfo.add_tracker(1, "udp://backup");
+        metainfo.set_comment(Some("edited".to_owned()));
+        assert!(metainfo.remove_tracker("url"));
+
+        let mut saved = vec![];
+        Serde.save_metainfo(&metainfo, &mut saved).unwrap();
+        let mut edited = Serde.parse_metainfo(&*saved, Limits::default()).unwrap();
+
+        assert_eq!(edited.info_hash(), info_hash);
+        assert_eq!(edited.announce, "udp://backup");
+        assert_eq!(edited.announce_list, Some(vec![vec!["udp://backup".to_owned()]]));
+        assert_eq!(edited.comment.as_deref(), Some("edited"));
+
+        edited.set_source(Some("tracker".to_owned()));
+
+        assert_eq!(edited.source(), Some("tracker"));
+        assert_eq!(edited.raw_info(), None);
+        assert_ne!(edited.info_hash(), info_hash);
+    }
+
+    #[test]
+    fn unchanged_file_is_saved_byte_exact() {
+        // Outer keys are unsorted as well, and `x-custom` is unknown
+        let source = b"d8:x-customi7e8:announce3:url\
+            4:infod4:name1:a6:lengthi20e12:piece lengthi16e6:pieces20:aaaaaaaaaaaaaaaaaaaae\
+            e";
+        let mut metainfo = Serde.parse_metainfo(&source[..], Limits::default()).unwrap();
+        assert!(metainfo.is_byte_exact());
+
+        let mut saved = vec![];
+        Serde.save_metainfo(&metainfo, &mut saved).unwrap();
+        assert_eq!(saved, source);
+
+        metainfo.set_comment(Some("edited".to_owned()));
+        assert!(!metainfo.is_byte_exact());
+
+        saved.clear();
+        Serde.save_metainfo(&metainfo, &mut saved).unwrap();
+        let edited = Serde.parse_metainfo(&*saved, Limits::default()).unwrap();
+        assert_eq!(edited.raw_info(), metainfo.raw_info());
+        assert_eq!(edited.extra, metainfo.extra);
+    }
+
+    #[test]
+    fn directly_edited_info_is_saved() {
+        let mut metainfo = Serde.parse_metainfo(UNSORTED_TORRENT, Limits::default()).unwrap();
+        metainfo.info.name = "renamed".to_owned();
+        metainfo.info.piece_length = 32768;
+        assert_eq!(metainfo.raw_info(), None);
+        assert!(!metainfo.is_byte_exact());
+
+        let mut saved = vec![];
+        Serde.save_metainfo(&metainfo, &mut saved).unwrap();
+        let edited = Serde.parse_metainfo(&*saved, Limits::default()).unwrap();
+
+        assert_eq!(edited.info.name, "renamed");
+        assert_eq!(edited.info.piece_length, 32768);
+    }
+}
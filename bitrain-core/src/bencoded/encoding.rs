@@ -1,8 +1,15 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::io::Write;
+use std::iter::Peekable;
 use std::slice::from_ref;
 
-use super::{BInt, BStr, BString};
+use super::{BInt, BString};
+
+pub mod borrowed;
+pub mod events;
+pub mod inspect;
+
+pub type BStr = [u8];
 
 mod delimiters {
     pub const INT_PREFIX: u8 = b'i';
@@ -36,9 +43,10 @@ pub trait BEncode: Sized {
 
 pub type BList = Vec<Entry>;
 pub type BSlice = [Entry];
-pub type BDictionary = HashMap<BString, Entry>;
+///Dictionary, which keeps its keys sorted by their bytes, as bencode requires, so it's encoded as is.
+pub type BDictionary = BTreeMap<BString, Entry>;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Entry {
     Integer(BInt),
     String(BString),
@@ -60,6 +68,119 @@ impl Entry {
     {
         self.try_into().ok()
     }
+
+    pub fn as_int(&self) -> Option<BInt> {
+        match self {
+            Self::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&BStr> {
+        match self {
+            Self::String(s) => Some(&s.0),
+            _ => None,
+        }
+    }
+
+    ///Returns string value, if it is valid UTF-8.
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_bytes().and_then(|bytes| std::str::from_utf8(bytes).ok())
+    }
+
+    pub fn as_list(&self) -> Option<&BSlice> {
+        match self {
+            Self::List(l) => Some(l),
+            _ => None,
+        }
+    }
+
+    pub fn as_dict(&self) -> Option<&BDictionary> {
+        match self {
+            Self::Dictionary(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    ///Returns value under `key`, if `self` is dictionary.
+    pub fn get(&self, key: &str) -> Option<&Entry> {
+        self.as_dict()?.get(key.as_bytes())
+    }
+
+    ///Navigates nested values by dot-separated `path` of dictionary keys and list indices
+    ///(i.e. `"info.files.0.length"`). Empty path refers to `self`.
+    ///
+    ///Keys, containing dots, can't be addressed this way, use [`Entry::get`] for them.
+    pub fn get_path(&self, path: &str) -> Option<&Entry> {
+        if path.is_empty() {
+            return Some(self);
+        }
+
+        path.split('.').try_fold(self, |entry, segment| match entry {
+            Self::Dictionary(d) => d.get(segment.as_bytes()),
+            Self::List(l) => segment.parse::<usize>().ok().and_then(|i| l.get(i)),
+            _ => None,
+        })
+    }
+}
+
+/// Builds [`Entry`] from JSON-like literal: `{ "key": value }` for dictionaries, `[value]` for lists,
+/// anything, which converts into [`Entry`], for other values (i.e. integers, strings, byte strings).
+///
+/// Values, which aren't single token trees, should be put in parentheses:
+///
+/// ```
+/// # use bitrain_core::bencode;
+/// let peers = [127, 0, 0, 1, 0x1A, 0xE1];
+/// let responce = bencode!({ "interval": 1800, "peers": (&peers[..]), "tracker id": b"id", "urls": ["a", "b"] });
+/// assert_eq!(responce.get("interval").and_then(|interval| interval.as_int()), Some(1800));
+/// ```
+#[macro_export]
+macro_rules! bencode {
+    ({ $($key:literal : $value:tt),* $(,)? }) => {{
+        #[allow(unused_mut)]
+        let mut dictionary = $crate::bencoded::encoding::BDictionary::new();
+        $(dictionary.insert($crate::bencoded::BString::from($key), $crate::bencode!($value));)*
+        $crate::bencoded::encoding::Entry::Dictionary(dictionary)
+    }};
+    ([ $($value:tt),* $(,)? ]) => {
+        $crate::bencoded::encoding::Entry::List(vec![$($crate::bencode!($value)),*])
+    };
+    (( $($value:tt)+ )) => {
+        $crate::bencoded::encoding::Entry::from($($value)+)
+    };
+    ($value:expr) => {
+        $crate::bencoded::encoding::Entry::from($value)
+    };
+}
+
+macro_rules! entry_conversions {
+    {$($kind:ty => $variant:ident),+} => {
+        $(
+            impl From<$kind> for Entry {
+                fn from(val: $kind) -> Self {
+                    Self::$variant(val.into())
+                }
+            }
+        )*
+    };
+}
+
+entry_conversions! {
+    BInt => Integer,
+    BString => String,
+    Vec<u8> => String,
+    &[u8] => String,
+    String => String,
+    &str => String,
+    BList => List,
+    BDictionary => Dictionary
+}
+
+impl<const N: usize> From<&[u8; N]> for Entry {
+    fn from(val: &[u8; N]) -> Self {
+        Self::String(val.into())
+    }
 }
 
 impl TryFrom<Entry> for BDictionary {
@@ -116,27 +237,29 @@ impl TryFrom<Entry> for String {
     fn try_from(value: Entry) -> std::result::Result<Self, Self::Error> {
         let bstring = BString::try_from(value)?;
 
-        if std::str::from_utf8(&bstring).is_ok() {
-            Ok(unsafe { String::from_utf8_unchecked(Vec::from(bstring)) })
-        } else {
-            Err(Entry::String(bstring))
-        }
+        String::from_utf8(bstring.0).map_err(|err| Entry::String(BString(err.into_bytes())))
     }
 }
 
 impl BDecode for Entry {
     fn decode(bytes: &mut impl Iterator<Item = u8>) -> Result<Self> {
-        let mut peekable = bytes.peekable();
-
-        match peekable.peek() {
-            Some(&delimiters::INT_PREFIX) => Ok(Self::Integer(BInt::decode(&mut peekable)?)),
-            Some(&delimiters::LIST_PREFIX) => Ok(Self::List(Vec::<Entry>::decode(&mut peekable)?)),
-            Some(&delimiters::DICTIONARY_PREFIX) => Ok(Self::Dictionary(
-                HashMap::<BString, Entry>::decode(&mut peekable)?,
-            )),
-            Some(_) => Ok(Self::String(BString::decode(&mut peekable)?)),
-            None => Err(Error::InvalidFormat),
-        }
+        Reader::new(bytes).entry()
+    }
+}
+
+impl Entry {
+    ///Same as [`BDecode::decode`], but with custom `limits` instead of default ones.
+    pub fn decode_with_limits(bytes: &mut impl Iterator<Item = u8>, limits: Limits) -> Result<Self> {
+        Reader::with_limits(bytes, limits).entry()
+    }
+
+    ///Same as [`BDecode::decode`], but rejects dictionaries with duplicate or unsorted keys,
+    ///so only canonical bencode is accepted.
+    pub fn decode_strict(bytes: &mut impl Iterator<Item = u8>) -> Result<Self> {
+        let mut reader = Reader::new(bytes);
+        reader.strict = true;
+
+        reader.entry()
     }
 }
 
@@ -144,8 +267,8 @@ impl BEncode for &Entry {
     fn encode_into_stream(self, stream: &mut impl Write) -> std::io::Result<()> {
         match self {
             Entry::Integer(i) => i.encode_into_stream(stream),
-            Entry::String(s) => s.encode_into_stream(stream),
-            Entry::List(l) => l.encode_into_stream(stream),
+            Entry::String(s) => s.0.as_slice().encode_into_stream(stream),
+            Entry::List(l) => l.as_slice().encode_into_stream(stream),
             Entry::Dictionary(d) => d.encode_into_stream(stream),
         }
     }
@@ -153,15 +276,8 @@ impl BEncode for &Entry {
 
 impl BDecode for BInt {
     fn decode(bytes: &mut impl Iterator<Item = u8>) -> Result<Self> {
-        if bytes.next() != Some(delimiters::INT_PREFIX) {
-            return Err(Error::InvalidFormat);
-        };
-
-        let repr = utils::collect_up_to(bytes, delimiters::END_SUFFIX);
-
         //MBDO: Check for leading zeroes
-
-        utils::parse_utf8_bytes(&repr)
+        Reader::new(bytes).int()
     }
 }
 
@@ -177,16 +293,7 @@ impl BEncode for BInt {
 
 impl BDecode for BString {
     fn decode(bytes: &mut impl Iterator<Item = u8>) -> Result<Self> {
-        let len_buf = utils::collect_up_to(bytes, delimiters::STRING_INFIX);
-        let len = utils::parse_utf8_bytes::<usize>(&len_buf)?;
-
-        let repr = bytes.take(len).collect::<Vec<_>>();
-
-        if repr.len() == len {
-            Ok(repr.into_boxed_slice())
-        } else {
-            Err(Error::UnexpectedEOF)
-        }
+        Reader::new(bytes).string()
     }
 }
 
@@ -202,22 +309,7 @@ impl BEncode for &BStr {
 
 impl BDecode for BList {
     fn decode(bytes: &mut impl Iterator<Item = u8>) -> Result<Self> {
-        if bytes.next() != Some(delimiters::LIST_PREFIX) {
-            return Err(Error::InvalidFormat);
-        };
-
-        let mut peekable = bytes.by_ref().peekable();
-        let mut list = vec![];
-
-        loop {
-            match peekable.peek() {
-                Some(&delimiters::END_SUFFIX) => break,
-                Some(_) => list.push(Entry::decode(&mut peekable)?),
-                None => return Err(Error::UnexpectedEOF),
-            };
-        }
-
-        Ok(list)
+        Reader::new(bytes).list()
     }
 }
 
@@ -237,30 +329,7 @@ impl BEncode for &BSlice {
 
 impl BDecode for BDictionary {
     fn decode(bytes: &mut impl Iterator<Item = u8>) -> Result<Self> {
-        if bytes.next() != Some(delimiters::LIST_PREFIX) {
-            return Err(Error::InvalidFormat);
-        };
-
-        let mut peekable = bytes.by_ref().peekable();
-        let mut dictionary = HashMap::new();
-
-        loop {
-            let peek = peekable.peek();
-
-            match peek {
-                Some(&delimiters::END_SUFFIX) => break,
-                Some(_) => {
-                    let key = BString::decode(&mut peekable)?;
-                    let value = Entry::decode(&mut peekable)?;
-
-                    //MBDO: Treat repeated key/value pairs as error?
-                    dictionary.insert(key, value);
-                }
-                None => return Err(Error::UnexpectedEOF),
-            };
-        }
-
-        Ok(dictionary)
+        Reader::new(bytes).dictionary()
     }
 }
 
@@ -282,22 +351,254 @@ impl<K: AsRef<BStr>> BEncode for &mut [(&K, &Entry)] {
 }
 
 impl BEncode for &BDictionary {
-    fn encode(self) -> Box<[u8]> {
-        self.into_iter().collect::<Vec<_>>().encode()
+    /// Keys are already sorted, so entries are written in order of iteration.
+    fn encode_into_stream(self, stream: &mut impl Write) -> std::io::Result<()> {
+        stream.write_all(from_ref(&delimiters::DICTIONARY_PREFIX))?;
+
+        for (key, val) in self {
+            key.as_ref().encode_into_stream(stream)?;
+            val.encode_into_stream(stream)?;
+        }
+
+        stream.write_all(from_ref(&delimiters::END_SUFFIX))
     }
+}
 
-    fn encode_into_stream(self, stream: &mut impl Write) -> std::io::Result<()> {
-        self.into_iter()
-            .collect::<Vec<_>>()
-            .encode_into_stream(stream)
+/// Decoder of owned values from byte iterator, which keeps track of offset for error reporting.
+struct Reader<I: Iterator<Item = u8>> {
+    bytes: Peekable<I>,
+    offset: usize,
+    limits: Limits,
+    depth: usize,
+    elements: usize,
+    strict: bool,
+}
+
+impl<I: Iterator<Item = u8>> Reader<I> {
+    fn new(bytes: I) -> Self {
+        Self::with_limits(bytes, Limits::default())
+    }
+
+    fn with_limits(bytes: I, limits: Limits) -> Self {
+        Self {
+            bytes: bytes.peekable(),
+            offset: 0,
+            limits,
+            depth: 0,
+            elements: 0,
+            strict: false,
+        }
+    }
+
+    fn peek(&mut self, expected: Expected) -> Result<u8> {
+        self.bytes.peek().copied().ok_or(Error::UnexpectedEOF {
+            offset: self.offset,
+            expected,
+        })
+    }
+
+    fn next(&mut self, expected: Expected) -> Result<u8> {
+        let byte = self.peek(expected)?;
+        self.bytes.next();
+        self.offset += 1;
+
+        Ok(byte)
+    }
+
+    fn expect(&mut self, delimiter: u8) -> Result<()> {
+        let offset = self.offset;
+        let expected = Expected::Delimiter(delimiter);
+
+        match self.next(expected)? {
+            found if found == delimiter => Ok(()),
+            found => Err(Error::InvalidFormat {
+                offset,
+                expected,
+                found,
+            }),
+        }
+    }
+
+    fn take_until(&mut self, delimiter: u8) -> Result<Vec<u8>> {
+        let mut bytes = vec![];
+
+        loop {
+            match self.next(Expected::Delimiter(delimiter))? {
+                byte if byte == delimiter => return Ok(bytes),
+                byte => bytes.push(byte),
+            }
+        }
+    }
+
+    fn entry(&mut self) -> Result<Entry> {
+        self.elements += 1;
+        self.limits.check_elements(self.elements, self.offset)?;
+
+        match self.peek(Expected::Value)? {
+            delimiters::INT_PREFIX => self.int().map(Entry::Integer),
+            delimiters::LIST_PREFIX => self.list().map(Entry::List),
+            delimiters::DICTIONARY_PREFIX => self.dictionary().map(Entry::Dictionary),
+            b'0'..=b'9' => self.string().map(Entry::String),
+            found => Err(Error::InvalidFormat {
+                offset: self.offset,
+                expected: Expected::Value,
+                found,
+            }),
+        }
+    }
+
+    fn int(&mut self) -> Result<BInt> {
+        self.expect(delimiters::INT_PREFIX)?;
+
+        let offset = self.offset;
+        let repr = self.take_until(delimiters::END_SUFFIX)?;
+
+        utils::parse_utf8_bytes(&repr, offset)
+    }
+
+    fn string(&mut self) -> Result<BString> {
+        let offset = self.offset;
+        let len_buf = self.take_until(delimiters::STRING_INFIX)?;
+        let len = utils::parse_utf8_bytes::<usize>(&len_buf, offset)?;
+        self.limits.check_string_length(len, offset)?;
+
+        let mut repr = vec![];
+        for remaining in (1..=len).rev() {
+            repr.push(self.next(Expected::StringBytes(remaining))?);
+        }
+
+        Ok(BString(repr))
+    }
+
+    fn list(&mut self) -> Result<BList> {
+        self.depth += 1;
+        self.limits.check_depth(self.depth, self.offset)?;
+        self.expect(delimiters::LIST_PREFIX)?;
+
+        let mut list = vec![];
+
+        while self.peek(Expected::Value)? != delimiters::END_SUFFIX {
+            list.push(self.entry()?);
+        }
+
+        self.bytes.next();
+        self.offset += 1;
+        self.depth -= 1;
+
+        Ok(list)
+    }
+
+    fn dictionary(&mut self) -> Result<BDictionary> {
+        self.depth += 1;
+        self.limits.check_depth(self.depth, self.offset)?;
+        self.expect(delimiters::DICTIONARY_PREFIX)?;
+
+        let mut dictionary = BTreeMap::new();
+        let mut previous: Option<BString> = None;
+
+        loop {
+            match self.peek(Expected::Key)? {
+                delimiters::END_SUFFIX => break,
+                b'0'..=b'9' => {
+                    let offset = self.offset;
+                    let key = self.string()?;
+
+                    if self.strict {
+                        utils::check_key_order(previous.as_ref().map(AsRef::as_ref), &key.0, offset)?;
+                        previous = Some(key.clone());
+                    }
+
+                    let value = self.entry()?;
+                    dictionary.insert(key, value);
+                }
+                found => {
+                    return Err(Error::InvalidFormat {
+                        offset: self.offset,
+                        expected: Expected::Key,
+                        found,
+                    })
+                }
+            }
+        }
+
+        self.bytes.next();
+        self.offset += 1;
+        self.depth -= 1;
+
+        Ok(dictionary)
     }
 }
 
+/// Error of bencode decoding. Offsets are counted in bytes from the start of decoded input.
+#[derive(Debug)]
 pub enum Error {
     IO(std::io::Error),
-    InvalidFormat,
-    InvalidValue,
-    UnexpectedEOF,
+    /// Byte at `offset` doesn't fit bencode grammar.
+    InvalidFormat {
+        offset: usize,
+        expected: Expected,
+        found: u8,
+    },
+    /// Integer or string length, starting at `offset`, is not a valid number.
+    InvalidValue { offset: usize },
+    /// Input ended at `offset` before value was complete.
+    UnexpectedEOF { offset: usize, expected: Expected },
+    /// Dictionary key at `offset` repeats previous one (strict decoding only).
+    DuplicateKey { offset: usize },
+    /// Dictionary key at `offset` is not greater than previous one (strict decoding only).
+    UnsortedKey { offset: usize },
+    /// Value, starting at `offset`, exceeds decoding [`Limits`].
+    LimitExceeded { offset: usize, limit: Limit },
+}
+
+impl Error {
+    /// Returns offset of malformed input, if error is caused by one.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            Self::IO(_) => None,
+            Self::InvalidFormat { offset, .. }
+            | Self::InvalidValue { offset }
+            | Self::UnexpectedEOF { offset, .. }
+            | Self::DuplicateKey { offset }
+            | Self::UnsortedKey { offset }
+            | Self::LimitExceeded { offset, .. } => Some(*offset),
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IO(err) => write!(f, "io error: {}", err),
+            Self::InvalidFormat {
+                offset,
+                expected,
+                found,
+            } => write!(
+                f,
+                "expected {}, found {} at byte {}",
+                expected,
+                std::ascii::escape_default(*found),
+                offset
+            ),
+            Self::InvalidValue { offset } => write!(f, "invalid number at byte {}", offset),
+            Self::UnexpectedEOF { offset, expected } => {
+                write!(f, "unexpected end of input at byte {}, expected {}", offset, expected)
+            }
+            Self::DuplicateKey { offset } => write!(f, "duplicate dictionary key at byte {}", offset),
+            Self::UnsortedKey { offset } => write!(f, "unsorted dictionary key at byte {}", offset),
+            Self::LimitExceeded { offset, limit } => write!(f, "{} exceeded at byte {}", limit, offset),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IO(err) => Some(err),
+            _ => None,
+        }
+    }
 }
 
 impl From<std::io::Error> for Error {
@@ -306,9 +607,102 @@ impl From<std::io::Error> for Error {
     }
 }
 
-impl From<std::str::Utf8Error> for Error {
-    fn from(_: std::str::Utf8Error) -> Self {
-        Self::InvalidValue
+/// Token, decoder expected to find in input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expected {
+    /// Start of any value.
+    Value,
+    /// Dictionary key (string) or end of dictionary.
+    Key,
+    Delimiter(u8),
+    /// Remaining bytes of string.
+    StringBytes(usize),
+    /// End of input after complete value.
+    End,
+}
+
+impl std::fmt::Display for Expected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Value => f.write_str("value"),
+            Self::Key => f.write_str("dictionary key"),
+            Self::Delimiter(delimiter) => write!(f, "'{}'", std::ascii::escape_default(*delimiter)),
+            Self::StringBytes(remaining) => write!(f, "{} more string bytes", remaining),
+            Self::End => f.write_str("end of input"),
+        }
+    }
+}
+
+/// Bounds, enforced while decoding untrusted input (i.e. `.torrent` files or tracker responces),
+/// so malicious data can't cause unbounded recursion or huge allocations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum nesting of lists and dictionaries.
+    pub max_depth: usize,
+    /// Maximum length of single string in bytes.
+    pub max_string_length: usize,
+    /// Maximum total number of values (including nested ones).
+    pub max_elements: usize,
+}
+
+impl Limits {
+    /// Limits, which are never exceeded.
+    pub const UNLIMITED: Self = Self {
+        max_depth: usize::MAX,
+        max_string_length: usize::MAX,
+        max_elements: usize::MAX,
+    };
+
+    fn check_depth(&self, depth: usize, offset: usize) -> Result<()> {
+        Self::check(depth, self.max_depth, offset, Limit::Depth)
+    }
+
+    fn check_string_length(&self, length: usize, offset: usize) -> Result<()> {
+        Self::check(length, self.max_string_length, offset, Limit::StringLength)
+    }
+
+    fn check_elements(&self, elements: usize, offset: usize) -> Result<()> {
+        Self::check(elements, self.max_elements, offset, Limit::Elements)
+    }
+
+    fn check(value: usize, max: usize, offset: usize, limit: fn(usize) -> Limit) -> Result<()> {
+        if value > max {
+            Err(Error::LimitExceeded {
+                offset,
+                limit: limit(max),
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Generous enough for any real-world torrent: 64 levels of nesting, 64 MiB strings and 4M values.
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_depth: 64,
+            max_string_length: 64 << 20,
+            max_elements: 4 << 20,
+        }
+    }
+}
+
+/// Limit from [`Limits`], which was exceeded, with its configured value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Depth(usize),
+    StringLength(usize),
+    Elements(usize),
+}
+
+impl std::fmt::Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Depth(max) => write!(f, "nesting depth limit of {}", max),
+            Self::StringLength(max) => write!(f, "string length limit of {} bytes", max),
+            Self::Elements(max) => write!(f, "limit of {} values", max),
+        }
     }
 }
 
@@ -317,15 +711,122 @@ pub mod utils {
         entries.sort_by(|left, right| left.0.as_ref().cmp(right.0.as_ref()));
     }
 
-    pub fn parse_utf8_bytes<T: std::str::FromStr>(bytes: &[u8]) -> super::Result<T> {
-        std::str::from_utf8(bytes)?
-            .parse::<T>()
-            .map_err(|_| super::Error::InvalidValue)
+    /// Checks, that `key` at `offset` goes strictly after `previous` one, as canonical bencode requires.
+    pub fn check_key_order(previous: Option<&[u8]>, key: &[u8], offset: usize) -> super::Result<()> {
+        match previous.map(|previous| previous.cmp(key)) {
+            Some(std::cmp::Ordering::Equal) => Err(super::Error::DuplicateKey { offset }),
+            Some(std::cmp::Ordering::Greater) => Err(super::Error::UnsortedKey { offset }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Parses number, starting at `offset` of input.
+    pub fn parse_utf8_bytes<T: std::str::FromStr>(bytes: &[u8], offset: usize) -> super::Result<T> {
+        std::str::from_utf8(bytes)
+            .ok()
+            .and_then(|repr| repr.parse::<T>().ok())
+            .ok_or(super::Error::InvalidValue { offset })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn nested_values_are_decoded() {
+        let entry = Entry::decode(&mut b"d4:listli1e3:abce3:numi42ee".iter().copied()).unwrap();
+        let mut dictionary = entry.parse::<BDictionary>().unwrap();
+
+        let list = dictionary.remove(&b"list"[..]).unwrap().parse::<BList>().unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(dictionary.remove(&b"num"[..]).unwrap().parse::<BInt>(), Some(42));
+    }
+
+    #[test]
+    fn dictionaries_are_kept_sorted() {
+        let mut dictionary = BDictionary::new();
+        for key in ["b", "ab", "a", "B"] {
+            dictionary.insert(BString(key.as_bytes().to_vec()), Entry::Integer(0));
+        }
+
+        let keys = dictionary.keys().map(|key| key.0.as_slice()).collect::<Vec<_>>();
+        assert_eq!(keys, [&b"B"[..], b"a", b"ab", b"b"]);
+        assert_eq!(&*Entry::Dictionary(dictionary).encode(), b"d1:Bi0e1:ai0e2:abi0e1:bi0ee");
+
+        let unsorted = b"d1:bi1e1:ai2ee";
+        let borrowed = borrowed::Entry::from_bytes(unsorted).unwrap();
+        assert_eq!(&*borrowed.encode(), b"d1:ai2e1:bi1ee");
+    }
+
+    #[test]
+    fn literals_are_built() {
+        let nested = crate::bencode!({ "list": [1, "abc", b"\xFF"], "num": 42, "dict": {} });
+        let empty = crate::bencode!([]);
+
+        assert_eq!(&*nested.encode(), b"d4:dictde4:listli1e3:abc1:\xFFe3:numi42ee");
+        assert_eq!(empty, Entry::List(vec![]));
+        assert_eq!(crate::bencode!((40 + 2)), Entry::Integer(42));
+    }
+
+    #[test]
+    fn values_are_found_by_path() {
+        let entry = Entry::decode(&mut b"d4:infod5:filesld6:lengthi7e4:pathl1:a1:beeeee".iter().copied()).unwrap();
+
+        assert_eq!(entry.get_path("info.files.0.length").and_then(Entry::as_int), Some(7));
+        assert_eq!(entry.get_path("info.files.0.path.1").and_then(Entry::as_str), Some("b"));
+        assert_eq!(entry.get_path("info.files.0.path").and_then(Entry::as_list).map(<[_]>::len), Some(2));
+        assert!(entry.get_path("info.files.1").is_none());
+        assert!(entry.get_path("info.files.first").is_none());
+        assert!(entry.get_path("info.files.0.length.0").is_none());
+        assert!(entry.get("info").and_then(Entry::as_dict).is_some());
+    }
+
+    #[rstest]
+    #[case::depth(b"llli1eeee", Limit::Depth(2), 2)]
+    #[case::string_length(b"l3:abc4:abcde", Limit::StringLength(3), 6)]
+    #[case::elements(b"li1ei2ei3ee", Limit::Elements(3), 7)]
+    fn limits_are_enforced(#[case] bytes: &[u8], #[case] limit: Limit, #[case] offset: usize) {
+        let limits = Limits {
+            max_depth: 2,
+            max_string_length: 3,
+            max_elements: 3,
+        };
+
+        let owned = Entry::decode_with_limits(&mut bytes.iter().copied(), limits).unwrap_err();
+        let borrowed = borrowed::Entry::from_bytes_with_limits(bytes, limits).unwrap_err();
+
+        assert!(matches!(owned, Error::LimitExceeded { offset: o, limit: l } if o == offset && l == limit));
+        assert_eq!(owned.to_string(), borrowed.to_string());
+    }
+
+    #[rstest]
+    #[case::canonical(b"d1:ai1e1:bd1:ci1eee", None)]
+    #[case::duplicate(b"d1:ai1e1:ai2ee", Some("duplicate dictionary key at byte 7"))]
+    #[case::unsorted(b"d1:bi1e1:ad1:ci1eee", Some("unsorted dictionary key at byte 7"))]
+    #[case::nested_unsorted(b"d1:ad1:ci1e1:bi1eee", Some("unsorted dictionary key at byte 11"))]
+    fn strict_mode_checks_keys(#[case] bytes: &[u8], #[case] message: Option<&str>) {
+        let owned = Entry::decode_strict(&mut bytes.iter().copied());
+        let borrowed = borrowed::Entry::from_bytes_strict(bytes);
+
+        assert_eq!(owned.err().map(|err| err.to_string()).as_deref(), message);
+        assert_eq!(borrowed.err().map(|err| err.to_string()).as_deref(), message);
+        assert!(Entry::decode(&mut bytes.iter().copied()).is_ok());
     }
 
-    pub fn collect_up_to(iter: &mut impl Iterator<Item = u8>, delimiter: u8) -> Vec<u8> {
-        iter.by_ref()
-            .take_while(|&b| b != delimiter)
-            .collect::<Vec<_>>()
+    #[rstest]
+    #[case::bad_value(b"d3:keyxe", 6, "expected value, found x at byte 6")]
+    #[case::bad_key(b"di1ei2ee", 1, "expected dictionary key, found i at byte 1")]
+    #[case::bad_number(b"li1ei-1ee", 5, "invalid number at byte 5")]
+    #[case::truncated_string(b"l5:abc", 6, "unexpected end of input at byte 6, expected 2 more string bytes")]
+    #[case::truncated_list(b"li1e", 4, "unexpected end of input at byte 4, expected value")]
+    fn errors_report_offset(#[case] bytes: &[u8], #[case] offset: usize, #[case] message: &str) {
+        let owned = Entry::decode(&mut bytes.iter().copied()).unwrap_err();
+        let borrowed = borrowed::Entry::from_bytes(bytes).unwrap_err();
+
+        assert_eq!(owned.offset(), Some(offset));
+        assert_eq!(owned.to_string(), message);
+        assert_eq!(borrowed.to_string(), message);
     }
 }
@@ -0,0 +1,334 @@
+//! Bencoded values, which borrow strings from parsed buffer instead of copying them.
+//!
+//! Parsing `.torrent` into [`Entry`] allocates only for lists and dictionaries, so huge strings
+//! (i.e. `pieces`) cost nothing. Use [`Entry::to_owned_entry`] to detach value from buffer.
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::ops::Range;
+use std::slice::from_ref;
+
+use super::{delimiters, utils, BEncode, BInt, BString, Error, Expected, Limits, Result};
+
+///Bencoded string, borrowed from parsed buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BStrRef<'a>(pub &'a [u8]);
+
+impl<'a> BStrRef<'a> {
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    ///Returns string as `str`, if it is valid UTF-8.
+    pub fn as_str(&self) -> Option<&'a str> {
+        std::str::from_utf8(self.0).ok()
+    }
+
+    pub fn to_bstring(&self) -> BString {
+        BString(self.0.to_vec())
+    }
+}
+
+impl AsRef<[u8]> for BStrRef<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.0
+    }
+}
+
+impl std::borrow::Borrow<[u8]> for BStrRef<'_> {
+    fn borrow(&self) -> &[u8] {
+        self.0
+    }
+}
+
+pub type BList<'a> = Vec<Entry<'a>>;
+pub type BDictionary<'a> = BTreeMap<BStrRef<'a>, Entry<'a>>;
+
+///Bencoded value, borrowing its strings from parsed buffer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Entry<'a> {
+    Integer(BInt),
+    String(BStrRef<'a>),
+    List(BList<'a>),
+    Dictionary(BDictionary<'a>),
+}
+
+impl<'a> Entry<'a> {
+    ///Parses single value, which must span the whole `bytes`.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self> {
+        Self::from_bytes_with_limits(bytes, Limits::default())
+    }
+
+    ///Same as [`Entry::from_bytes`], but with custom `limits` instead of default ones.
+    pub fn from_bytes_with_limits(bytes: &'a [u8], limits: Limits) -> Result<Self> {
+        Decoder::new(bytes, limits).complete()
+    }
+
+    ///Same as [`Entry::from_bytes`], but rejects dictionaries with duplicate or unsorted keys,
+    ///i.e. to check, that `info` dictionary is canonical.
+    pub fn from_bytes_strict(bytes: &'a [u8]) -> Result<Self> {
+        let mut decoder = Decoder::new(bytes, Limits::default());
+        decoder.strict = true;
+        decoder.complete()
+    }
+
+    ///Parses value at the start of `bytes`, returning it together with number of consumed bytes.
+    pub fn decode_prefix(bytes: &'a [u8]) -> Result<(Self, usize)> {
+        let mut decoder = Decoder::new(bytes, Limits::default());
+        let entry = decoder.entry()?;
+
+        Ok((entry, decoder.pos))
+    }
+
+    ///Copies all strings, detaching value from parsed buffer.
+    pub fn to_owned_entry(&self) -> super::Entry {
+        match self {
+            Self::Integer(i) => super::Entry::Integer(*i),
+            Self::String(s) => super::Entry::String(s.to_bstring()),
+            Self::List(l) => super::Entry::List(l.iter().map(Self::to_owned_entry).collect()),
+            Self::Dictionary(d) => super::Entry::Dictionary(
+                d.iter()
+                    .map(|(key, value)| (key.to_bstring(), value.to_owned_entry()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl BEncode for &Entry<'_> {
+    fn encode_into_stream(self, stream: &mut impl Write) -> std::io::Result<()> {
+        match self {
+            Entry::Integer(i) => i.encode_into_stream(stream),
+            Entry::String(s) => s.0.encode_into_stream(stream),
+            Entry::List(l) => {
+                stream.write_all(from_ref(&delimiters::LIST_PREFIX))?;
+
+                for item in l {
+                    item.encode_into_stream(stream)?;
+                }
+
+                stream.write_all(from_ref(&delimiters::END_SUFFIX))
+            }
+            Entry::Dictionary(d) => {
+                stream.write_all(from_ref(&delimiters::DICTIONARY_PREFIX))?;
+
+                for (key, value) in d {
+                    key.0.encode_into_stream(stream)?;
+                    value.encode_into_stream(stream)?;
+                }
+
+                stream.write_all(from_ref(&delimiters::END_SUFFIX))
+            }
+        }
+    }
+}
+
+///Returns byte range of value under `key` in dictionary, encoded in `bytes`.
+///
+///Useful when exact bytes of value are needed, i.e. for computing info-hash of `.torrent` file.
+pub fn dictionary_value_span(bytes: &[u8], key: &[u8]) -> Result<Option<Range<usize>>> {
+    let mut decoder = Decoder::new(bytes, Limits::UNLIMITED);
+
+    match decoder.peek(Expected::Value)? {
+        delimiters::DICTIONARY_PREFIX => decoder.pos += 1,
+        found => return Err(decoder.invalid(Expected::Value, found)),
+    }
+
+    loop {
+        match decoder.peek(Expected::Key)? {
+            delimiters::END_SUFFIX => return Ok(None),
+            b'0'..=b'9' => {
+                let current = decoder.string()?;
+                let start = decoder.pos;
+                decoder.entry()?;
+
+                if current.0 == key {
+                    return Ok(Some(start..decoder.pos));
+                }
+            }
+            found => return Err(decoder.invalid(Expected::Key, found)),
+        }
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    limits: Limits,
+    depth: usize,
+    elements: usize,
+    strict: bool,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(bytes: &'a [u8], limits: Limits) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            limits,
+            depth: 0,
+            elements: 0,
+            strict: false,
+        }
+    }
+
+    ///Parses single value, which must span the whole input.
+    fn complete(&mut self) -> Result<Entry<'a>> {
+        let entry = self.entry()?;
+
+        match self.bytes.get(self.pos) {
+            None => Ok(entry),
+            Some(&found) => Err(self.invalid(Expected::End, found)),
+        }
+    }
+
+    fn peek(&self, expected: Expected) -> Result<u8> {
+        self.bytes.get(self.pos).copied().ok_or(Error::UnexpectedEOF {
+            offset: self.pos,
+            expected,
+        })
+    }
+
+    fn entry(&mut self) -> Result<Entry<'a>> {
+        self.elements += 1;
+        self.limits.check_elements(self.elements, self.pos)?;
+
+        match self.peek(Expected::Value)? {
+            delimiters::INT_PREFIX => {
+                self.pos += 1;
+                let offset = self.pos;
+                let repr = self.take_until(delimiters::END_SUFFIX)?;
+
+                utils::parse_utf8_bytes(repr, offset).map(Entry::Integer)
+            }
+            delimiters::LIST_PREFIX => {
+                self.enter()?;
+                self.pos += 1;
+                let mut list = vec![];
+
+                while self.peek(Expected::Value)? != delimiters::END_SUFFIX {
+                    list.push(self.entry()?);
+                }
+
+                self.pos += 1;
+                self.depth -= 1;
+                Ok(Entry::List(list))
+            }
+            delimiters::DICTIONARY_PREFIX => {
+                self.enter()?;
+                self.pos += 1;
+                let mut dictionary = BTreeMap::new();
+                let mut previous: Option<BStrRef> = None;
+
+                loop {
+                    match self.peek(Expected::Key)? {
+                        delimiters::END_SUFFIX => break,
+                        b'0'..=b'9' => {
+                            let offset = self.pos;
+                            let key = self.string()?;
+
+                            if self.strict {
+                                utils::check_key_order(previous.map(|previous| previous.0), key.0, offset)?;
+                                previous = Some(key);
+                            }
+
+                            let value = self.entry()?;
+
+                            dictionary.insert(key, value);
+                        }
+                        found => return Err(self.invalid(Expected::Key, found)),
+                    }
+                }
+
+                self.pos += 1;
+                self.depth -= 1;
+                Ok(Entry::Dictionary(dictionary))
+            }
+            b'0'..=b'9' => self.string().map(Entry::String),
+            found => Err(self.invalid(Expected::Value, found)),
+        }
+    }
+
+    fn string(&mut self) -> Result<BStrRef<'a>> {
+        let offset = self.pos;
+        let len_buf = self.take_until(delimiters::STRING_INFIX)?;
+        let len = utils::parse_utf8_bytes::<usize>(len_buf, offset)?;
+        self.limits.check_string_length(len, offset)?;
+        let rest = &self.bytes[self.pos..];
+
+        if rest.len() < len {
+            return Err(Error::UnexpectedEOF {
+                offset: self.bytes.len(),
+                expected: Expected::StringBytes(len - rest.len()),
+            });
+        }
+
+        self.pos += len;
+        Ok(BStrRef(&rest[..len]))
+    }
+
+    fn enter(&mut self) -> Result<()> {
+        self.depth += 1;
+        self.limits.check_depth(self.depth, self.pos)
+    }
+
+    fn take_until(&mut self, delimiter: u8) -> Result<&'a [u8]> {
+        let rest = &self.bytes[self.pos..];
+        let len = rest
+            .iter()
+            .position(|&b| b == delimiter)
+            .ok_or(Error::UnexpectedEOF {
+                offset: self.bytes.len(),
+                expected: Expected::Delimiter(delimiter),
+            })?;
+
+        self.pos += len + 1;
+        Ok(&rest[..len])
+    }
+
+    fn invalid(&self, expected: Expected, found: u8) -> Error {
+        Error::InvalidFormat {
+            offset: self.pos,
+            expected,
+            found,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SAMPLE_TORRENT: &[u8] = include_bytes!("../sample.torrent");
+
+    #[test]
+    fn strings_are_borrowed_from_buffer() {
+        let entry = Entry::from_bytes(SAMPLE_TORRENT).unwrap();
+
+        let Entry::Dictionary(metainfo) = &entry else { panic!("metainfo is not a dictionary") };
+        let Some(Entry::Dictionary(info)) = metainfo.get(&b"info"[..]) else { panic!("no info") };
+        let Some(Entry::String(pieces)) = info.get(&b"pieces"[..]) else { panic!("no pieces") };
+
+        assert!(SAMPLE_TORRENT.as_ptr_range().contains(&pieces.0.as_ptr()));
+        assert_eq!(&*entry.encode(), SAMPLE_TORRENT);
+    }
+
+    #[test]
+    fn value_span_is_found() {
+        let bytes = b"d1:ai1e4:infod6:lengthi20eee";
+        let span = dictionary_value_span(bytes, b"info").unwrap().unwrap();
+
+        assert_eq!(&bytes[span], b"d6:lengthi20ee");
+        assert_eq!(dictionary_value_span(bytes, b"nodes").unwrap(), None);
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        assert!(matches!(Entry::from_bytes(b"d3:key"), Err(Error::UnexpectedEOF { offset: 6, .. })));
+        assert!(matches!(Entry::from_bytes(b"5:abc"), Err(Error::UnexpectedEOF { offset: 5, .. })));
+        assert!(matches!(
+            Entry::from_bytes(b"i1ei2e"),
+            Err(Error::InvalidFormat { offset: 3, expected: Expected::End, found: b'i' })
+        ));
+        assert!(matches!(Entry::from_bytes(b"x"), Err(Error::InvalidFormat { offset: 0, .. })));
+    }
+}
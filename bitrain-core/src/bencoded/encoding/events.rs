@@ -0,0 +1,307 @@
+//! Event-driven bencode parser, which never materializes value tree.
+//!
+//! [`Events`] pulls bytes from [`BufRead`] and reports structure of document as sequence of [`Event`]s,
+//! with strings delivered in chunks of at most buffer size. Together with [`Events::raw`] this allows
+//! i.e. hashing `info` dictionary of huge `.torrent` on the fly.
+use std::io::BufRead;
+
+use super::{delimiters, utils, BInt, Error, Expected, Limits, Result};
+
+///Single step of bencoded document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a> {
+    ListStart,
+    DictStart,
+    ///Key of current dictionary, value events follow.
+    Key(&'a [u8]),
+    Int(BInt),
+    ///Start of string of given length, followed by [`Event::StrChunk`]s with its contents
+    ///(none for empty string).
+    StrStart(usize),
+    StrChunk(&'a [u8]),
+    ///End of current list or dictionary.
+    End,
+}
+
+///Pull parser, emitting [`Event`]s of single bencoded value.
+pub struct Events<R: BufRead> {
+    source: R,
+    offset: usize,
+    stack: Vec<Container>,
+    string_remaining: usize,
+    finished: bool,
+    raw: Vec<u8>,
+    limits: Limits,
+    elements: usize,
+}
+
+enum Container {
+    List,
+    Dictionary { key_next: bool },
+}
+
+impl<R: BufRead> Events<R> {
+    pub fn new(source: R) -> Self {
+        Self::with_limits(source, Limits::default())
+    }
+
+    pub fn with_limits(source: R, limits: Limits) -> Self {
+        Self {
+            source,
+            offset: 0,
+            stack: vec![],
+            string_remaining: 0,
+            finished: false,
+            raw: vec![],
+            limits,
+            elements: 0,
+        }
+    }
+
+    ///Number of bytes, consumed from source.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    ///Number of lists and dictionaries, which are currently open.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    ///Raw input bytes, consumed by last event.
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+
+    pub fn into_inner(self) -> R {
+        self.source
+    }
+
+    ///Returns next event, or `None` after value is complete. Bytes after value are not consumed.
+    pub fn next_event(&mut self) -> Result<Option<Event<'_>>> {
+        self.raw.clear();
+
+        if self.string_remaining > 0 {
+            return self.chunk().map(Some);
+        }
+
+        if self.finished {
+            return Ok(None);
+        }
+
+        match self.stack.last() {
+            Some(Container::Dictionary { key_next: true }) => match self.peek(Expected::Key)? {
+                delimiters::END_SUFFIX => self.end().map(Some),
+                b'0'..=b'9' => self.key().map(Some),
+                found => Err(self.invalid(Expected::Key, found)),
+            },
+            _ => match self.peek(Expected::Value)? {
+                delimiters::END_SUFFIX if matches!(self.stack.last(), Some(Container::List)) => {
+                    self.end().map(Some)
+                }
+                delimiters::INT_PREFIX => self.int().map(Some),
+                delimiters::LIST_PREFIX => self.open(Container::List, Event::ListStart).map(Some),
+                delimiters::DICTIONARY_PREFIX => self
+                    .open(Container::Dictionary { key_next: true }, Event::DictStart)
+                    .map(Some),
+                b'0'..=b'9' => self.string_start().map(Some),
+                found => Err(self.invalid(Expected::Value, found)),
+            },
+        }
+    }
+
+    fn chunk(&mut self) -> Result<Event<'_>> {
+        let available = self.source.fill_buf()?;
+
+        if available.is_empty() {
+            return Err(Error::UnexpectedEOF {
+                offset: self.offset,
+                expected: Expected::StringBytes(self.string_remaining),
+            });
+        }
+
+        let len = available.len().min(self.string_remaining);
+        self.raw.extend_from_slice(&available[..len]);
+        self.source.consume(len);
+        self.offset += len;
+        self.string_remaining -= len;
+
+        if self.string_remaining == 0 {
+            self.value_complete();
+        }
+
+        Ok(Event::StrChunk(&self.raw))
+    }
+
+    fn key(&mut self) -> Result<Event<'_>> {
+        self.element()?;
+
+        let len = self.string_len()?;
+        let start = self.raw.len();
+
+        for remaining in (1..=len).rev() {
+            self.next(Expected::StringBytes(remaining))?;
+        }
+
+        if let Some(Container::Dictionary { key_next }) = self.stack.last_mut() {
+            *key_next = false;
+        }
+
+        Ok(Event::Key(&self.raw[start..]))
+    }
+
+    fn int(&mut self) -> Result<Event<'_>> {
+        self.element()?;
+        self.next(Expected::Value)?;
+
+        let offset = self.offset;
+        self.take_until(delimiters::END_SUFFIX)?;
+        let int = utils::parse_utf8_bytes(&self.raw[1..self.raw.len() - 1], offset)?;
+
+        self.value_complete();
+        Ok(Event::Int(int))
+    }
+
+    fn string_start(&mut self) -> Result<Event<'_>> {
+        self.element()?;
+        self.string_remaining = self.string_len()?;
+
+        if self.string_remaining == 0 {
+            self.value_complete();
+        }
+
+        Ok(Event::StrStart(self.string_remaining))
+    }
+
+    fn open(&mut self, container: Container, event: Event<'static>) -> Result<Event<'_>> {
+        self.element()?;
+        self.limits.check_depth(self.stack.len() + 1, self.offset)?;
+        self.next(Expected::Value)?;
+
+        self.stack.push(container);
+        Ok(event)
+    }
+
+    fn end(&mut self) -> Result<Event<'_>> {
+        self.next(Expected::Value)?;
+        self.stack.pop();
+
+        self.value_complete();
+        Ok(Event::End)
+    }
+
+    fn element(&mut self) -> Result<()> {
+        self.elements += 1;
+        self.limits.check_elements(self.elements, self.offset)
+    }
+
+    fn value_complete(&mut self) {
+        match self.stack.last_mut() {
+            Some(Container::Dictionary { key_next }) => *key_next = true,
+            Some(Container::List) => {}
+            None => self.finished = true,
+        }
+    }
+
+    fn string_len(&mut self) -> Result<usize> {
+        let offset = self.offset;
+        self.take_until(delimiters::STRING_INFIX)?;
+
+        let len = utils::parse_utf8_bytes(&self.raw[..self.raw.len() - 1], offset)?;
+        self.limits.check_string_length(len, offset)?;
+
+        Ok(len)
+    }
+
+    fn peek(&mut self, expected: Expected) -> Result<u8> {
+        match self.source.fill_buf()?.first() {
+            Some(&byte) => Ok(byte),
+            None => Err(Error::UnexpectedEOF {
+                offset: self.offset,
+                expected,
+            }),
+        }
+    }
+
+    fn next(&mut self, expected: Expected) -> Result<u8> {
+        let byte = self.peek(expected)?;
+        self.source.consume(1);
+        self.offset += 1;
+        self.raw.push(byte);
+
+        Ok(byte)
+    }
+
+    fn take_until(&mut self, delimiter: u8) -> Result<()> {
+        while self.next(Expected::Delimiter(delimiter))? != delimiter {}
+
+        Ok(())
+    }
+
+    fn invalid(&self, expected: Expected, found: u8) -> Error {
+        Error::InvalidFormat {
+            offset: self.offset,
+            expected,
+            found,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use super::super::borrowed::dictionary_value_span;
+    use super::*;
+
+    static SAMPLE_TORRENT: &[u8] = include_bytes!("../sample.torrent");
+
+    #[test]
+    fn info_is_collected_from_events() {
+        let mut events = Events::new(BufReader::with_capacity(7, SAMPLE_TORRENT));
+        let mut info = vec![];
+        let mut in_info = false;
+
+        while let Some(event) = events.next_event().unwrap() {
+            let is_key = event == Event::Key(b"info");
+            let is_info_key = is_key && events.depth() == 1;
+
+            if in_info {
+                info.extend_from_slice(events.raw());
+                in_info = events.depth() > 1;
+            }
+
+            in_info |= is_info_key;
+        }
+
+        let span = dictionary_value_span(SAMPLE_TORRENT, b"info").unwrap().unwrap();
+        assert_eq!(info, &SAMPLE_TORRENT[span]);
+        assert_eq!(events.offset(), SAMPLE_TORRENT.len());
+    }
+
+    #[test]
+    fn events_follow_structure() {
+        let mut events = Events::new(&b"d1:ali1e0:e1:b3:xyze"[..]);
+        let mut collected = vec![];
+
+        while let Some(event) = events.next_event().unwrap() {
+            collected.push(format!("{:?}", event));
+        }
+
+        assert_eq!(
+            collected,
+            [
+                "DictStart",
+                "Key([97])",
+                "ListStart",
+                "Int(1)",
+                "StrStart(0)",
+                "End",
+                "Key([98])",
+                "StrStart(3)",
+                "StrChunk([120, 121, 122])",
+                "End"
+            ]
+        );
+    }
+}
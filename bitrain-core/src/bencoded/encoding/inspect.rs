@@ -0,0 +1,224 @@
+//! Human-readable renderings of [`Entry`] trees for debugging and CLI tooling.
+//!
+//! Dictionary keys are always printed in sorted order, so output is stable between runs.
+//! Strings, which are not valid UTF-8 (i.e. `pieces`), are rendered as hex or base64.
+use std::fmt::{self, Display, Formatter, Write};
+
+use super::{BStr, Entry};
+
+///Number of leading bytes of binary string, shown by [`Pretty`].
+const PRETTY_BINARY_LIMIT: usize = 32;
+
+///Encoding of non UTF-8 strings in JSON output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BinaryFormat {
+    ///`"hex:0a1b..."`
+    #[default]
+    Hex,
+    ///`"base64:Chs..."`
+    Base64,
+}
+
+///Indented text representation of [`Entry`], returned by [`Entry::pretty`].
+///
+///Binary strings are shown as `<N bytes: 0a1b...>`, truncated to first 32 bytes.
+pub struct Pretty<'a>(&'a Entry);
+
+impl Display for Pretty<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        utils::pretty(self.0, 0, f)
+    }
+}
+
+impl Entry {
+    ///Returns value, which displays `self` as indented tree.
+    pub fn pretty(&self) -> Pretty<'_> {
+        Pretty(self)
+    }
+
+    ///Renders `self` as JSON, encoding non UTF-8 strings and keys according to `binary`.
+    pub fn to_json(&self, binary: BinaryFormat) -> String {
+        let mut json = String::new();
+        //Writing into `String` can't fail
+        utils::json(self, binary, &mut json).unwrap();
+
+        json
+    }
+}
+
+mod utils {
+    use super::*;
+
+    const INDENT: &str = "  ";
+
+    pub fn pretty(entry: &Entry, depth: usize, f: &mut Formatter<'_>) -> fmt::Result {
+        match entry {
+            Entry::Integer(i) => write!(f, "{}", i),
+            Entry::String(s) => pretty_string(&s.0, f),
+            Entry::List(l) if l.is_empty() => f.write_str("[]"),
+            Entry::List(l) => {
+                f.write_str("[\n")?;
+
+                for item in l {
+                    indent(depth + 1, f)?;
+                    pretty(item, depth + 1, f)?;
+                    f.write_str(",\n")?;
+                }
+
+                indent(depth, f)?;
+                f.write_str("]")
+            }
+            Entry::Dictionary(d) if d.is_empty() => f.write_str("{}"),
+            Entry::Dictionary(d) => {
+                f.write_str("{\n")?;
+
+                for (key, value) in d {
+                    indent(depth + 1, f)?;
+                    pretty_string(&key.0, f)?;
+                    f.write_str(": ")?;
+                    pretty(value, depth + 1, f)?;
+                    f.write_str(",\n")?;
+                }
+
+                indent(depth, f)?;
+                f.write_str("}")
+            }
+        }
+    }
+
+    fn pretty_string(bytes: &BStr, f: &mut Formatter<'_>) -> fmt::Result {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => write!(f, "{:?}", s),
+            Err(_) => {
+                write!(f, "<{} bytes: ", bytes.len())?;
+                hex(&bytes[..bytes.len().min(PRETTY_BINARY_LIMIT)], f)?;
+
+                if bytes.len() > PRETTY_BINARY_LIMIT {
+                    f.write_str("...")?;
+                }
+
+                f.write_str(">")
+            }
+        }
+    }
+
+    fn indent(depth: usize, f: &mut Formatter<'_>) -> fmt::Result {
+        (0..depth).try_for_each(|_| f.write_str(INDENT))
+    }
+
+    pub fn json(entry: &Entry, binary: BinaryFormat, out: &mut String) -> fmt::Result {
+        match entry {
+            Entry::Integer(i) => write!(out, "{}", i),
+            Entry::String(s) => json_string(&s.0, binary, out),
+            Entry::List(l) => {
+                out.push('[');
+
+                for (i, item) in l.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+
+                    json(item, binary, out)?;
+                }
+
+                out.push(']');
+                Ok(())
+            }
+            Entry::Dictionary(d) => {
+                out.push('{');
+
+                for (i, (key, value)) in d.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+
+                    json_string(&key.0, binary, out)?;
+                    out.push(':');
+                    json(value, binary, out)?;
+                }
+
+                out.push('}');
+                Ok(())
+            }
+        }
+    }
+
+    fn json_string(bytes: &BStr, binary: BinaryFormat, out: &mut String) -> fmt::Result {
+        out.push('"');
+
+        match (std::str::from_utf8(bytes), binary) {
+            (Ok(s), _) => {
+                for c in s.chars() {
+                    match c {
+                        '"' => out.push_str("\\\""),
+                        '\\' => out.push_str("\\\\"),
+                        '\n' => out.push_str("\\n"),
+                        '\r' => out.push_str("\\r"),
+                        '\t' => out.push_str("\\t"),
+                        c if c.is_control() => write!(out, "\\u{:04x}", c as u32)?,
+                        c => out.push(c),
+                    }
+                }
+            }
+            (Err(_), BinaryFormat::Hex) => {
+                out.push_str("hex:");
+                hex(bytes, out)?;
+            }
+            (Err(_), BinaryFormat::Base64) => {
+                out.push_str("base64:");
+                base64(bytes, out);
+            }
+        }
+
+        out.push('"');
+        Ok(())
+    }
+
+    fn hex(bytes: &BStr, out: &mut impl Write) -> fmt::Result {
+        bytes.iter().try_for_each(|b| write!(out, "{:02x}", b))
+    }
+
+    fn base64(bytes: &BStr, out: &mut String) {
+        const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+        for chunk in bytes.chunks(3) {
+            let n = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+                } else {
+                    out.push('=');
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::BDecode;
+    use super::*;
+    use rstest::rstest;
+
+    fn sample() -> Entry {
+        Entry::decode(&mut b"d4:listli1e1:\"e3:raw3:\xff\x00\x01e".iter().copied()).unwrap()
+    }
+
+    #[rstest]
+    #[case::hex(BinaryFormat::Hex, r#"{"list":[1,"\""],"raw":"hex:ff0001"}"#)]
+    #[case::base64(BinaryFormat::Base64, r#"{"list":[1,"\""],"raw":"base64:/wAB"}"#)]
+    fn json_is_rendered(#[case] binary: BinaryFormat, #[case] json: &str) {
+        assert_eq!(sample().to_json(binary), json);
+    }
+
+    #[test]
+    fn pretty_is_indented() {
+        let expected = "{\n  \"list\": [\n    1,\n    \"\\\"\",\n  ],\n  \"raw\": <3 bytes: ff0001>,\n}";
+
+        assert_eq!(sample().pretty().to_string(), expected);
+    }
+}
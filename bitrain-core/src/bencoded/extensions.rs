@@ -0,0 +1,151 @@
+//! Typed access to keys of metainfo, which are defined by extensions and kept in [`Metainfo::extra`].
+use super::encoding::Entry;
+use super::{BString, Metainfo};
+
+impl Metainfo {
+    /// Webseed URLs from `url-list` key (BEP 19), which may hold either single URL or list of them.
+    /// Empty and non UTF-8 URLs are skipped.
+    pub fn url_list(&self) -> Vec<String> {
+        utils::urls(self.extra.get(&b"url-list"[..]))
+    }
+
+    /// Sets `url-list` key (BEP 19), removing it if `urls` are empty.
+    pub fn set_url_list(&mut self, urls: Vec<String>) {
+        utils::set_urls(self, "url-list", urls)
+    }
+
+    /// HTTP seed URLs from `httpseeds` key (BEP 17). Empty and non UTF-8 URLs are skipped.
+    pub fn http_seeds(&self) -> Vec<String> {
+        utils::urls(self.extra.get(&b"httpseeds"[..]))
+    }
+
+    /// Sets `httpseeds` key (BEP 17), removing it if `urls` are empty.
+    pub fn set_http_seeds(&mut self, urls: Vec<String>) {
+        utils::set_urls(self, "httpseeds", urls)
+    }
+
+    /// DHT bootstrap nodes from `nodes` key (BEP 5) of trackerless torrent, as host and port pairs.
+    /// Malformed nodes are skipped.
+    pub fn nodes(&self) -> Vec<(String, u16)> {
+        let Some(Entry::List(nodes)) = self.extra.get(&b"nodes"[..]) else {
+            return vec![];
+        };
+
+        nodes.iter().filter_map(utils::node).collect()
+    }
+
+    /// Sets `nodes` key (BEP 5), removing it if `nodes` are empty.
+    pub fn set_nodes(&mut self, nodes: Vec<(String, u16)>) {
+        let key = BString(b"nodes".to_vec());
+
+        if nodes.is_empty() {
+            self.extra.remove(&key);
+        } else {
+            let nodes = nodes
+                .into_iter()
+                .map(|(host, port)| {
+                    Entry::List(vec![Entry::String(BString(host.into_bytes())), Entry::Integer(port.into())])
+                })
+                .collect();
+
+            self.extra.insert(key, Entry::List(nodes));
+        }
+    }
+}
+
+mod utils {
+    use super::*;
+
+    pub fn urls(entry: Option<&Entry>) -> Vec<String> {
+        let urls = match entry {
+            Some(Entry::List(list)) => list.iter().filter_map(Entry::as_str).collect(),
+            Some(entry) => entry.as_str().into_iter().collect(),
+            None => vec![],
+        };
+
+        urls.into_iter()
+            .filter(|url| !url.is_empty())
+            .map(ToOwned::to_owned)
+            .collect()
+    }
+
+    /// Parses `[host, port]` pair of `nodes` list.
+    pub fn node(entry: &Entry) -> Option<(String, u16)> {
+        match entry.as_list()? {
+            [host, port] => Some((host.as_str()?.to_owned(), u16::try_from(port.as_int()?).ok()?)),
+            _ => None,
+        }
+    }
+
+    pub fn set_urls(metainfo: &mut Metainfo, key: &str, urls: Vec<String>) {
+        let key = BString(key.as_bytes().to_vec());
+
+        if urls.is_empty() {
+            metainfo.extra.remove(&key);
+        } else {
+            let urls = urls
+                .into_iter()
+                .map(|url| Entry::String(BString(url.into_bytes())))
+                .collect();
+
+            metainfo.extra.insert(key, Entry::List(urls));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::bencoded::encoding::borrowed;
+    use crate::bencoded::Files;
+    use crate::test_utils;
+
+    fn metainfo(extra: &[u8]) -> Metainfo {
+        let Entry::Dictionary(extra) = borrowed::Entry::from_bytes(extra).unwrap().to_owned_entry() else {
+            panic!("extra is not a dictionary")
+        };
+
+        Metainfo {
+            info: test_utils::info(
+                16,
+                Files::Single {
+                    length: 0,
+                    md5sum: None,
+                },
+            ),
+            announce: "http://tracker.example/announce".to_owned(),
+            announce_list: None,
+            creation_date: None,
+            comment: None,
+            created_by: None,
+            encoding: None,
+            extra,
+            raw_info: None,
+            raw: None,
+        }
+    }
+
+    #[rstest]
+    #[case(b"d8:url-list3:webe", &["web"])]
+    #[case(b"d8:url-listl4:web14:web20:ee", &["web1", "web2"])]
+    #[case(b"d8:url-list0:e", &[])]
+    #[case(b"de", &[])]
+    fn webseeds_are_parsed(#[case] extra: &[u8], #[case] expected: &[&str]) {
+        let mut metainfo = metainfo(extra);
+        assert_eq!(metainfo.url_list(), expected);
+
+        metainfo.set_http_seeds(expected.iter().map(|url| url.to_string()).collect());
+        assert_eq!(metainfo.http_seeds(), expected);
+    }
+
+    #[test]
+    fn nodes_are_parsed() {
+        let mut metainfo = metainfo(b"d5:nodesll9:127.0.0.1i6881eel4:hosti70000eeee");
+        assert_eq!(metainfo.nodes(), [("127.0.0.1".to_owned(), 6881)]);
+
+        metainfo.set_nodes(vec![("router.example".to_owned(), 6881)]);
+        assert_eq!(metainfo.nodes(), [("router.example".to_owned(), 6881)]);
+    }
+}
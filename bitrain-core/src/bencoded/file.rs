@@ -0,0 +1,143 @@
+//! Loading and saving of `.torrent` files with enabled parsing backend.
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use super::{Metainfo, Parser, Saver};
+
+// `serde` is preferred, if both backends are enabled
+#[cfg(feature = "use-serde")]
+use super::Serde as Backend;
+#[cfg(not(feature = "use-serde"))]
+use super::Custom as Backend;
+
+/// Error of [`Metainfo::load`] and [`Metainfo::save`].
+#[derive(Debug)]
+pub enum FileError {
+    Io(io::Error),
+    /// File of given size exceeds size limit.
+    TooLarge(u64),
+    Parse(<Backend as Parser<Metainfo>>::Err),
+    Save(<Backend as Saver<Metainfo>>::Err),
+}
+
+impl Metainfo {
+    /// Size limit of files, loaded by [`Metainfo::load`]. Even torrents of terabytes rarely exceed few megabytes.
+    pub const DEFAULT_MAX_FILE_SIZE: u64 = 1 << 24;
+
+    /// Loads `.torrent` file at `path`.
+    ///
+    /// ## Errors
+    ///
+    /// Fails if file can't be read, exceeds [`Metainfo::DEFAULT_MAX_FILE_SIZE`] or can't be parsed.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FileError> {
+        Self::load_with_max_size(path, Self::DEFAULT_MAX_FILE_SIZE)
+    }
+
+    /// Same as [`Metainfo::load`], but with custom size limit.
+    pub fn load_with_max_size(path: impl AsRef<Path>, max_size: u64) -> Result<Self, FileError> {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        if size > max_size {
+            return Err(FileError::TooLarge(size));
+        }
+
+        // File can grow after its size is checked
+        let mut bytes = Vec::with_capacity(size as usize);
+        file.take(max_size + 1).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 > max_size {
+            return Err(FileError::TooLarge(bytes.len() as u64));
+        }
+
+        utils::parse(&bytes).map_err(FileError::Parse)
+    }
+
+    /// Saves `self` into file at `path`, replacing it, if it exists.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FileError> {
+        let file = File::create(path)?;
+
+        utils::save(self, file).map_err(FileError::Save)
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::TooLarge(size) => write!(f, "file of {} bytes exceeds size limit", size),
+            Self::Parse(err) => write!(f, "malformed torrent: {}", err),
+            Self::Save(err) => write!(f, "failed to save torrent: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for FileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Parse(err) => Some(err),
+            Self::Save(err) => Some(err),
+            Self::TooLarge(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for FileError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+mod utils {
+    use super::*;
+
+    /// Parses metainfo, recording its bytes.
+    #[cfg(feature = "use-serde")]
+    pub fn parse(bytes: &[u8]) -> Result<Metainfo, <Backend as Parser<Metainfo>>::Err> {
+        Backend.parse_metainfo(bytes, super::super::encoding::Limits::default())
+    }
+
+    /// Saves metainfo, reproducing recorded bytes.
+    #[cfg(feature = "use-serde")]
+    pub fn save(metainfo: &Metainfo, target: impl Write) -> Result<(), <Backend as Saver<Metainfo>>::Err> {
+        Backend.save_metainfo(metainfo, target)
+    }
+
+    #[cfg(not(feature = "use-serde"))]
+    pub fn parse(bytes: &[u8]) -> Result<Metainfo, <Backend as Parser<Metainfo>>::Err> {
+        Backend.parse(bytes)
+    }
+
+    #[cfg(not(feature = "use-serde"))]
+    pub fn save(metainfo: &Metainfo, target: impl Write) -> Result<(), <Backend as Saver<Metainfo>>::Err> {
+        Backend.save(metainfo, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::temp_dir;
+    use std::fs;
+
+    static SAMPLE_TORRENT: &[u8] = include_bytes!("sample.torrent");
+
+    #[test]
+    fn torrent_is_loaded_and_saved() {
+        let dir = temp_dir("file");
+        let source = dir.join("sample.torrent");
+        let target = dir.join("saved.torrent");
+        fs::write(&source, SAMPLE_TORRENT).unwrap();
+
+        let metainfo = Metainfo::load(&source).unwrap();
+        metainfo.save(&target).unwrap();
+        let err = Metainfo::load_with_max_size(&source, 16).unwrap_err();
+
+        assert_eq!(fs::read(&target).unwrap(), SAMPLE_TORRENT);
+        assert!(matches!(err, FileError::TooLarge(size) if size == SAMPLE_TORRENT.len() as u64));
+        assert!(matches!(Metainfo::load(dir.join("missing.torrent")), Err(FileError::Io(_))));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
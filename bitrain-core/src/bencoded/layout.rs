@@ -0,0 +1,262 @@
+use super::{BInt, Files, Info};
+
+/// Placement of torrent content in files, derived from [`Info`].
+///
+/// Content is treated as one continuous stream, composed of files in the order they are listed,
+/// which is split into pieces of equal length (except the last one). Padding files take their place in
+/// the stream, but are never mapped to segments of disk I/O.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    piece_length: BInt,
+    /// Offsets of file starts in content stream.
+    offsets: Vec<BInt>,
+    lengths: Vec<BInt>,
+    /// Files, which are padding (BEP 47) and aren't stored on disk.
+    padding: Vec<bool>,
+}
+
+/// Continuous range of bytes inside one file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileSegment {
+    /// Index of file in `info` dictionary (`0` for single file torrent).
+    pub file: usize,
+    pub offset: BInt,
+    pub length: BInt,
+}
+
+/// Continuous range of bytes inside one piece.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PieceSegment {
+    pub piece: BInt,
+    pub offset: BInt,
+    pub length: BInt,
+}
+
+impl Layout {
+    /// # Panics
+    ///
+    /// Panics if piece length of `info` is zero, which [`Metainfo::validate`](super::Metainfo::validate) reports.
+    pub fn new(info: &Info) -> Self {
+        assert!(info.piece_length > 0, "piece length is zero");
+
+        let (lengths, padding) = match &info.files {
+            Files::Single { length, .. } => (vec![*length], vec![false]),
+            Files::Multiple { files } => files.iter().map(|file| (file.length, file.is_padding())).unzip(),
+        };
+
+        let offsets = lengths
+            .iter()
+            .scan(0, |offset, length| {
+                let start = *offset;
+                *offset += length;
+                Some(start)
+            })
+            .collect();
+
+        Self {
+            piece_length: info.piece_length,
+            offsets,
+            lengths,
+            padding,
+        }
+    }
+
+    pub fn piece_length(&self) -> BInt {
+        self.piece_length
+    }
+
+    /// Total length of content in bytes.
+    pub fn total_length(&self) -> BInt {
+        self.lengths.iter().sum()
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.lengths.len()
+    }
+
+    /// Offset of `file` start in content stream, `None` if file doesn't exist.
+    pub fn file_offset(&self, file: usize) -> Option<BInt> {
+        self.offsets.get(file).copied()
+    }
+
+    pub fn file_length(&self, file: usize) -> Option<BInt> {
+        self.lengths.get(file).copied()
+    }
+
+    /// Returns `true` if `file` is padding file, `false` if it is not or doesn't exist.
+    pub fn is_padding(&self, file: usize) -> bool {
+        self.padding.get(file).copied().unwrap_or(false)
+    }
+
+    pub fn piece_count(&self) -> BInt {
+        self.total_length().div_ceil(self.piece_length)
+    }
+
+    /// Length of `piece`, which is less than piece length for the last piece. `None` if piece is out of range.
+    pub fn piece_size(&self, piece: BInt) -> Option<BInt> {
+        let start = piece.checked_mul(self.piece_length)?;
+        let total_length = self.total_length();
+
+        (start < total_length).then(|| (total_length - start).min(self.piece_length))
+    }
+
+    /// Maps `length` bytes at `offset` inside `piece` to segments of files, they are stored in.
+    /// Empty and padding files are skipped, as their bytes (zeros for padding) aren't stored on disk.
+    ///
+    /// Returns `None` if range doesn't fit into piece.
+    pub fn file_segments(&self, piece: BInt, offset: BInt, length: BInt) -> Option<Vec<FileSegment>> {
+        let piece_size = self.piece_size(piece)?;
+
+        if offset.checked_add(length)? > piece_size {
+            return None;
+        }
+
+        let mut position = piece * self.piece_length + offset;
+        let mut remaining = length;
+        let mut segments = vec![];
+
+        // First file, which ends after position
+        let first = self
+            .offsets
+            .iter()
+            .zip(&self.lengths)
+            .position(|(offset, length)| offset + length > position)
+            .unwrap_or(self.lengths.len());
+
+        for file in first..self.lengths.len() {
+            if remaining == 0 {
+                break;
+            }
+
+            let file_offset = position - self.offsets[file];
+            let segment_length = (self.lengths[file] - file_offset).min(remaining);
+
+            if segment_length > 0 && !self.padding[file] {
+                segments.push(FileSegment {
+                    file,
+                    offset: file_offset,
+                    length: segment_length,
+                });
+            }
+
+            position += segment_length;
+            remaining -= segment_length;
+        }
+
+        Some(segments)
+    }
+
+    /// Maps `length` bytes at `offset` inside `file` to segments of pieces, which contain them.
+    ///
+    /// Returns `None` if file doesn't exist or range doesn't fit into it.
+    pub fn piece_segments(&self, file: usize, offset: BInt, length: BInt) -> Option<Vec<PieceSegment>> {
+        if offset.checked_add(length)? > *self.lengths.get(file)? {
+            return None;
+        }
+
+        let mut position = self.offsets[file] + offset;
+        let mut remaining = length;
+        let mut segments = vec![];
+
+        while remaining > 0 {
+            let piece = position / self.piece_length;
+            let piece_offset = position % self.piece_length;
+            let segment_length = (self.piece_length - piece_offset).min(remaining);
+
+            segments.push(PieceSegment {
+                piece,
+                offset: piece_offset,
+                length: segment_length,
+            });
+
+            position += segment_length;
+            remaining -= segment_length;
+        }
+
+        Some(segments)
+    }
+}
+
+impl Info {
+    /// Returns [`Layout`] of content, described by `self`.
+    pub fn layout(&self) -> Layout {
+        Layout::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::bencoded::FileInfo;
+    use crate::test_utils;
+
+    /// Files of lengths 3, 0, 6 and 2 in pieces of 4 bytes.
+    fn layout() -> Layout {
+        padded_layout(&[3, 0, 6, 2], &[])
+    }
+
+    fn padded_layout(lengths: &[BInt], padding: &[usize]) -> Layout {
+        let files = lengths
+            .iter()
+            .enumerate()
+            .map(|(i, length)| FileInfo {
+                length: *length,
+                md5sum: None,
+                path: vec![i.to_string()],
+                attr: padding.contains(&i).then(|| "p".to_owned()),
+            })
+            .collect();
+
+        test_utils::info(4, Files::Multiple { files }).layout()
+    }
+
+    fn file(file: usize, offset: BInt, length: BInt) -> FileSegment {
+        FileSegment { file, offset, length }
+    }
+
+    fn piece(piece: BInt, offset: BInt, length: BInt) -> PieceSegment {
+        PieceSegment { piece, offset, length }
+    }
+
+    #[rstest]
+    #[case(0, 0, 4, Some(vec![file(0, 0, 3), file(2, 0, 1)]))]
+    #[case(1, 1, 3, Some(vec![file(2, 2, 3)]))]
+    #[case(2, 0, 3, Some(vec![file(2, 5, 1), file(3, 0, 2)]))]
+    #[case(2, 0, 4, None)]
+    #[case(3, 0, 1, None)]
+    fn pieces_are_mapped_to_files(
+        #[case] index: BInt,
+        #[case] offset: BInt,
+        #[case] length: BInt,
+        #[case] expected: Option<Vec<FileSegment>>,
+    ) {
+        assert_eq!(layout().file_segments(index, offset, length), expected);
+    }
+
+    #[rstest]
+    #[case(2, 0, 6, Some(vec![piece(0, 3, 1), piece(1, 0, 4), piece(2, 0, 1)]))]
+    #[case(1, 0, 0, Some(vec![]))]
+    #[case(3, 1, 2, None)]
+    #[case(4, 0, 0, None)]
+    fn files_are_mapped_to_pieces(
+        #[case] index: usize,
+        #[case] offset: BInt,
+        #[case] length: BInt,
+        #[case] expected: Option<Vec<PieceSegment>>,
+    ) {
+        assert_eq!(layout().piece_segments(index, offset, length), expected);
+    }
+
+    #[test]
+    fn padding_is_not_mapped() {
+        let layout = padded_layout(&[3, 1, 2], &[1]);
+
+        assert!(layout.is_padding(1));
+        assert_eq!(layout.piece_count(), 2);
+        assert_eq!(layout.file_segments(0, 0, 4), Some(vec![file(0, 0, 3)]));
+        assert_eq!(layout.file_segments(1, 0, 2), Some(vec![file(2, 0, 2)]));
+        assert_eq!(layout.piece_segments(1, 0, 1), Some(vec![piece(0, 3, 1)]));
+    }
+}
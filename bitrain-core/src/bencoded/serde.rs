@@ -1,6 +1,10 @@
-use super::{Parser, Saver, BString};
-use serde::{de::DeserializeOwned, Serialize};
+use super::encoding::{self, borrowed, BDictionary, Entry, Limits};
+use super::{Parser, Saver, BString, Metainfo};
+use serde::de::{self, DeserializeOwned, MapAccess, SeqAccess, Unexpected, Visitor};
+use serde::ser;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_bencoded::{DeError, SerError};
+use std::fmt;
 use std::io::{self, Read, Write};
 
 impl From<serde_bytes::ByteBuf> for BString {
@@ -9,9 +13,84 @@ impl From<serde_bytes::ByteBuf> for BString {
     }
 }
 
-impl Into<serde_bytes::ByteBuf> for BString {
-    fn into(self) -> serde_bytes::ByteBuf {
-        serde_bytes::ByteBuf::from(self.0)
+impl From<BString> for serde_bytes::ByteBuf {
+    fn from(bstring: BString) -> Self {
+        serde_bytes::ByteBuf::from(bstring.0)
+    }
+}
+
+/// Allows to (de)serialize bencode of unknown schema (i.e. extension messages) and probe it afterwards.
+impl Serialize for Entry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Entry::Integer(i) => serializer.serialize_u64(*i),
+            Entry::String(s) => serializer.serialize_bytes(&s.0),
+            Entry::List(l) => serializer.collect_seq(l),
+            // Keys are sorted, as bencode requires
+            Entry::Dictionary(d) => serializer.collect_map(d),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Entry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(EntryVisitor)
+    }
+}
+
+struct EntryVisitor;
+
+impl<'de> Visitor<'de> for EntryVisitor {
+    type Value = Entry;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("bencoded value")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(Entry::Integer(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        u64::try_from(v)
+            .map(Entry::Integer)
+            .map_err(|_| E::invalid_value(Unexpected::Signed(v), &self))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(Entry::String(BString(v.to_vec())))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(Entry::String(BString(v)))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        self.visit_bytes(v.as_bytes())
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        self.visit_byte_buf(v.into_bytes())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut list = vec![];
+
+        while let Some(entry) = seq.next_element()? {
+            list.push(entry);
+        }
+
+        Ok(Entry::List(list))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut dictionary = BDictionary::new();
+
+        while let Some((key, value)) = map.next_entry::<BString, Entry>()? {
+            dictionary.insert(key, value);
+        }
+
+        Ok(Entry::Dictionary(dictionary))
     }
 }
 
@@ -25,31 +104,88 @@ impl Into<serde_bytes::ByteBuf> for BString {
 /// 70KB in size, which is afordable amount of runtime memory allocation in most cases.
 pub struct Serde;
 
+impl Serde {
+    /// Same as [`Parser::parse`], but with custom decoding `limits` instead of default ones.
+    ///
+    /// `serde_bencoded` can't enforce limits itself, so input is validated with built-in decoder first.
+    pub fn parse_with_limits<D: DeserializeOwned>(&self, source: impl Read, limits: Limits) -> Result<D, ParseError> {
+        let bytes = utils::read_validated(source, limits)?;
+
+        serde_bencoded::from_bytes(&bytes).map_err(Into::into)
+    }
+
+    /// Parses [`Metainfo`], recording bytes of parsed file, so info-hash is computed over them and unchanged
+    /// metainfo is saved byte for byte.
+    ///
+    /// [`Parser::parse`] doesn't record them, as it's generic over parsed type.
+    pub fn parse_metainfo(&self, source: impl Read, limits: Limits) -> Result<Metainfo, ParseError> {
+        let bytes = utils::read_validated(source, limits)?;
+
+        let mut metainfo: Metainfo = serde_bencoded::from_bytes(&bytes)?;
+        metainfo.record_raw(&bytes);
+
+        Ok(metainfo)
+    }
+
+    /// Saves [`Metainfo`], reproducing bytes, recorded by [`Serde::parse_metainfo`]: the whole file, if metainfo
+    /// wasn't changed, or `info` dictionary otherwise, so info-hash is preserved.
+    pub fn save_metainfo(&self, metainfo: &Metainfo, mut target: impl Write) -> Result<(), SerError> {
+        let mut bytes = serde_bencoded::to_vec(metainfo)?;
+        metainfo.restore_raw(&mut bytes);
+
+        target.write_all(&bytes).map_err(ser::Error::custom)
+    }
+}
+
 impl<D: DeserializeOwned> Parser<D> for Serde {
     type Err = ParseError;
     ///
     /// ## Errors
     ///
-    /// For information on failure cases see [`serde_bencoded::DeError`].
-    fn parse(&self, mut source: impl Read) -> Result<D, Self::Err> {
-        let mut bytes = vec![];
-        source.read_to_end(&mut bytes)?;
-
-        serde_bencoded::from_bytes(&bytes).map_err(Into::into)
+    /// For information on failure cases see [`serde_bencoded::DeError`] and [`encoding::Error`].
+    fn parse(&self, source: impl Read) -> Result<D, Self::Err> {
+        self.parse_with_limits(source, Limits::default())
     }
 }
 
 #[derive(Debug)]
 pub enum ParseError {
     IO(io::Error),
+    /// Input is malformed or exceeds decoding limits.
+    Bencode(encoding::Error),
     De(DeError),
 }
 
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IO(err) => write!(f, "{}", err),
+            Self::Bencode(err) => write!(f, "{}", err),
+            Self::De(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IO(err) => Some(err),
+            Self::Bencode(err) => Some(err),
+            Self::De(err) => Some(err),
+        }
+    }
+}
+
 impl From<io::Error> for ParseError {
     fn from(err: io::Error) -> Self {
         Self::IO(err)
     }
 }
+impl From<encoding::Error> for ParseError {
+    fn from(err: encoding::Error) -> Self {
+        Self::Bencode(err)
+    }
+}
 impl From<DeError> for ParseError {
     fn from(err: DeError) -> Self {
         Self::De(err)
@@ -66,6 +202,32 @@ impl<T: Serialize> Saver<T> for Serde {
     }
 }
 
+pub(super) mod utils {
+    use super::*;
+
+    /// Reads all of `source`, checking, that it's valid bencode within `limits`.
+    pub fn read_validated(mut source: impl Read, limits: Limits) -> Result<Vec<u8>, ParseError> {
+        let mut bytes = vec![];
+        source.read_to_end(&mut bytes)?;
+
+        borrowed::Entry::from_bytes_with_limits(&bytes, limits)?;
+
+        Ok(bytes)
+    }
+
+    /// Untagged [`Files`](super::super::Files) doesn't consume its keys from flattened map,
+    /// so they have to be dropped from `extra` of `Info` manually.
+    pub fn deserialize_info_extra<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BDictionary, D::Error> {
+        let mut extra = BDictionary::deserialize(deserializer)?;
+
+        for key in ["files", "length", "md5sum"] {
+            extra.remove(key.as_bytes());
+        }
+
+        Ok(extra)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::fmt::Debug;
@@ -76,6 +238,14 @@ mod test {
     use rstest::*;
 
     static SAMPLE_TORRENT: &[u8] = include_bytes!("sample.torrent");
+    // `info` value, which goes right after `4:info` key and before closing `e` of metainfo
+    const SAMPLE_INFO_SPAN: std::ops::Range<usize> = 83..181;
+    static SAMPLE_TRACKER_RESPONCE: &[u8] = b"d8:completei1e10:incompletei0e8:intervali1800e\
+        5:peers6:\x0a\x00\x00\x01\x1a\xe1\
+        6:peers618:\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x1a\xe1e";
+    static TORRENT_WITH_EXTRA_KEYS: &[u8] = b"d8:announce3:url\
+        4:infod6:lengthi20e4:name1:a12:piece lengthi16e6:pieces0:6:source3:abce\
+        8:url-listl3:webee";
 
     #[fixture]
     fn info() -> Info {
@@ -90,6 +260,7 @@ mod test {
                 length: 20,
                 md5sum: None,
             },
+            extra: BDictionary::new(),
         }
     }
 
@@ -97,6 +268,10 @@ mod test {
     #[once]
     fn metainfo(info: Info) -> Metainfo {
         Metainfo {
+            raw_info: Some(RawInfo {
+                parsed: info.clone(),
+                bytes: BString(SAMPLE_TORRENT[SAMPLE_INFO_SPAN].to_vec()),
+            }),
             info,
             announce: "udp://tracker.openbittorrent.com:80".to_owned(),
             announce_list: None,
@@ -104,11 +279,40 @@ mod test {
             comment: None,
             created_by: None,
             encoding: None,
+            extra: BDictionary::new(),
+            raw: Some(BString(SAMPLE_TORRENT.to_vec())),
         }
     }
 
+    #[fixture]
+    fn tracker_responce() -> TrackerResponce {
+        TrackerResponce::Success(AnnounceResponce {
+            info: TrackerInfo {
+                interval: 1800,
+                min_interval: None,
+                id: None,
+                complete: 1,
+                incomplete: 0,
+                warning_message: None,
+            },
+            peers: PeerList::Compact(BString(vec![10, 0, 0, 1, 0x1a, 0xe1])),
+            peers6: Some(CompactPeers6(BString(Vec::from(hex!(
+                "00000000000000000000000000000001 1ae1"
+            ))))),
+        })
+    }
+
+    #[fixture]
+    fn tracker_failure() -> TrackerResponce {
+        TrackerResponce::Error(TrackerFailure {
+            failure_reason: BString(b"unregistered torrent".to_vec()),
+        })
+    }
+
     #[rstest]
     #[case::metainfo(metainfo(info()), SAMPLE_TORRENT)]
+    #[case::tracker_responce(tracker_responce(), SAMPLE_TRACKER_RESPONCE)]
+    #[case::tracker_failure(tracker_failure(), b"d14:failure reason20:unregistered torrente")]
     fn decoding<T: PartialEq + Debug>(#[case] item: T, #[case] bytes: &[u8])
     where
         Serde: Parser<T>,
@@ -118,8 +322,45 @@ mod test {
         assert_eq!(decoded, item);
     }
 
+    #[rstest]
+    #[case::metainfo(SAMPLE_TORRENT)]
+    #[case::tracker_responce(SAMPLE_TRACKER_RESPONCE)]
+    fn dynamic_entry_roundtrip(#[case] bytes: &[u8]) {
+        let entry: Entry = Serde.parse(bytes).unwrap();
+
+        let mut encoded = vec![];
+        Serde.save(&entry, &mut encoded).unwrap();
+
+        assert!(matches!(entry, Entry::Dictionary(_)));
+        assert_eq!(encoded, bytes);
+    }
+
+    #[test]
+    fn unknown_keys_are_preserved() {
+        let metainfo: Metainfo = Serde.parse(TORRENT_WITH_EXTRA_KEYS).unwrap();
+
+        let mut encoded = vec![];
+        Serde.save(&metainfo, &mut encoded).unwrap();
+
+        assert_eq!(metainfo.extra.keys().collect::<Vec<_>>(), [&BString(b"url-list".to_vec())]);
+        assert_eq!(metainfo.info.extra.keys().collect::<Vec<_>>(), [&BString(b"source".to_vec())]);
+        assert_eq!(encoded, TORRENT_WITH_EXTRA_KEYS);
+    }
+
+    #[test]
+    fn limits_are_enforced() {
+        let limits = Limits {
+            max_string_length: 16,
+            ..Limits::default()
+        };
+        let err = Serde.parse_with_limits::<Metainfo>(SAMPLE_TORRENT, limits).unwrap_err();
+
+        assert!(matches!(err, ParseError::Bencode(crate::bencoded::encoding::Error::LimitExceeded { .. })));
+    }
+
     #[rstest]
     #[case::metainfo(metainfo(info()), SAMPLE_TORRENT)]
+    #[case::tracker_responce(tracker_responce(), SAMPLE_TRACKER_RESPONCE)]
     fn encoding<T>(#[case] item: T, #[case] bytes: &[u8])
     where
         Serde: Saver<T>,
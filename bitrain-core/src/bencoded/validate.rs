@@ -0,0 +1,159 @@
+use std::fmt;
+
+use super::{BInt, Files, Metainfo};
+
+/// Inconsistency of [`Metainfo`], found by [`Metainfo::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// Piece length is zero.
+    ZeroPieceLength,
+    /// Length of `pieces` is not multiple of 20.
+    InvalidPiecesLength(usize),
+    /// Number of hashes in `pieces` doesn't match total length, split by piece length.
+    PieceCountMismatch { expected: BInt, actual: BInt },
+    /// Multi-file torrent contains no files.
+    NoFiles,
+    /// Path of file at `file` index has no components.
+    EmptyPath { file: usize },
+    /// Path component of file at `file` index is empty or refers to current or parent directory.
+    InvalidPathComponent { file: usize, component: String },
+    /// Total length of files overflows [`BInt`].
+    LengthOverflow,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroPieceLength => f.write_str("piece length is zero"),
+            Self::InvalidPiecesLength(length) => {
+                write!(f, "pieces length {} is not multiple of 20", length)
+            }
+            Self::PieceCountMismatch { expected, actual } => {
+                write!(f, "expected {} piece hashes, found {}", expected, actual)
+            }
+            Self::NoFiles => f.write_str("torrent contains no files"),
+            Self::EmptyPath { file } => write!(f, "path of file {} is empty", file),
+            Self::InvalidPathComponent { file, component } => {
+                write!(f, "path of file {} contains invalid component {:?}", file, component)
+            }
+            Self::LengthOverflow => f.write_str("total length of files overflows"),
+        }
+    }
+}
+
+impl Metainfo {
+    /// Checks consistency of `info` dictionary, returning all found violations (empty if metainfo is valid).
+    pub fn validate(&self) -> Vec<Violation> {
+        let info = &self.info;
+        let mut violations = vec![];
+
+        if info.piece_length == 0 {
+            violations.push(Violation::ZeroPieceLength);
+        }
+
+        if !info.pieces.0.len().is_multiple_of(20) {
+            violations.push(Violation::InvalidPiecesLength(info.pieces.0.len()));
+        }
+
+        let total_length = match &info.files {
+            Files::Single { length, .. } => Some(*length),
+            Files::Multiple { files } => {
+                if files.is_empty() {
+                    violations.push(Violation::NoFiles);
+                }
+
+                for (i, file) in files.iter().enumerate() {
+                    if file.path.is_empty() {
+                        violations.push(Violation::EmptyPath { file: i });
+                    }
+
+                    let invalid = file
+                        .path
+                        .iter()
+                        .filter(|component| matches!(component.as_str(), "" | "." | ".."));
+
+                    for component in invalid {
+                        violations.push(Violation::InvalidPathComponent {
+                            file: i,
+                            component: component.to_owned(),
+                        });
+                    }
+                }
+
+                files.iter().try_fold(0 as BInt, |total, file| total.checked_add(file.length))
+            }
+        };
+
+        match total_length {
+            Some(total_length) if info.piece_length > 0 => {
+                let expected = total_length.div_ceil(info.piece_length);
+                let actual = info.piece_count() as BInt;
+
+                if expected != actual {
+                    violations.push(Violation::PieceCountMismatch { expected, actual });
+                }
+            }
+            Some(_) => {}
+            None => violations.push(Violation::LengthOverflow),
+        }
+
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bencoded::{encoding::BDictionary, BString, FileInfo, Info};
+    use crate::test_utils;
+
+    fn metainfo(pieces: usize, files: Files) -> Metainfo {
+        Metainfo {
+            info: Info {
+                pieces: BString(vec![0; pieces]),
+                ..test_utils::info(4, files)
+            },
+            announce: "http://tracker.example/announce".to_owned(),
+            announce_list: None,
+            creation_date: None,
+            comment: None,
+            created_by: None,
+            encoding: None,
+            extra: BDictionary::new(),
+            raw_info: None,
+            raw: None,
+        }
+    }
+
+    #[test]
+    fn violations_are_collected() {
+        let valid = metainfo(40, Files::Single { length: 5, md5sum: None });
+        assert_eq!(valid.validate(), []);
+
+        let file = |path: &[&str]| FileInfo {
+            length: 6,
+            md5sum: None,
+            path: path.iter().map(|part| part.to_string()).collect(),
+            attr: None,
+        };
+        let invalid = metainfo(
+            25,
+            Files::Multiple {
+                files: vec![file(&[]), file(&["a", ".."])],
+            },
+        );
+
+        assert_eq!(
+            invalid.validate(),
+            [
+                Violation::InvalidPiecesLength(25),
+                Violation::EmptyPath { file: 0 },
+                Violation::InvalidPathComponent {
+                    file: 1,
+                    component: "..".to_owned()
+                },
+                Violation::PieceCountMismatch { expected: 3, actual: 1 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Borrowed views of metainfo, which refer to parsed buffer instead of copying strings out of it.
+use std::borrow::Cow;
+
+use serde_derive::Deserialize;
+use sha1::{Digest, Sha1};
+
+use super::encoding::{borrowed, BDictionary, Limits};
+use super::serde::ParseError;
+use super::{BInt, Files, Info, Metainfo};
+
+///Borrowed counterpart of [`Metainfo`], parsed from in-memory buffer without copying `pieces` and other strings.
+///
+///Useful for inspecting large torrents (i.e. listing or indexing them), where `pieces` can take megabytes.
+///Can be turned into [`Metainfo`] with [`MetainfoRef::into_owned`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MetainfoRef<'a> {
+    #[serde(borrow)]
+    pub info: InfoRef<'a>,
+    #[serde(borrow)]
+    pub announce: Cow<'a, str>,
+    #[serde(borrow, rename = "announce-list")]
+    pub announce_list: Option<Vec<Vec<Cow<'a, str>>>>,
+    #[serde(rename = "creation date")]
+    pub creation_date: Option<BInt>,
+    #[serde(borrow)]
+    pub comment: Option<Cow<'a, str>>,
+    #[serde(borrow, rename = "created by")]
+    pub created_by: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    pub encoding: Option<Cow<'a, str>>,
+    ///Keys, not modeled by this struct, same as [`Metainfo::extra`].
+    #[serde(flatten)]
+    pub extra: BDictionary,
+    #[serde(skip)]
+    raw: Option<&'a [u8]>,
+    #[serde(skip)]
+    raw_info: Option<&'a [u8]>,
+}
+
+///Borrowed counterpart of [`Info`].
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct InfoRef<'a> {
+    #[serde(rename = "piece length")]
+    pub piece_length: BInt,
+    #[serde(borrow, with = "serde_bytes")]
+    pub pieces: Cow<'a, [u8]>,
+    pub private: Option<bool>,
+    #[serde(borrow)]
+    pub name: Cow<'a, str>,
+    #[serde(flatten)]
+    pub files: Files,
+    #[serde(flatten, deserialize_with = "super::serde::utils::deserialize_info_extra")]
+    pub extra: BDictionary,
+}
+
+impl<'a> MetainfoRef<'a> {
+    ///Parses metainfo from `bytes`, borrowing its strings.
+    ///
+    /// ## Errors
+    ///
+    ///Same as of [`Serde::parse_metainfo`](super::Serde::parse_metainfo).
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, ParseError> {
+        Self::from_bytes_with_limits(bytes, Limits::default())
+    }
+
+    ///Same as [`MetainfoRef::from_bytes`], but with custom decoding `limits` instead of default ones.
+    pub fn from_bytes_with_limits(bytes: &'a [u8], limits: Limits) -> Result<Self, ParseError> {
+        borrowed::Entry::from_bytes_with_limits(bytes, limits)?;
+
+        let mut metainfo: Self = serde_bencoded::from_bytes(bytes)?;
+        metainfo.raw = Some(bytes);
+        metainfo.raw_info = borrowed::dictionary_value_span(bytes, b"info")
+            .ok()
+            .flatten()
+            .map(|span| &bytes[span]);
+
+        Ok(metainfo)
+    }
+
+    ///Returns `info` dictionary exactly as it was in parsed buffer, same as [`Metainfo::raw_info`].
+    pub fn raw_info(&self) -> Option<&'a [u8]> {
+        self.raw_info
+    }
+
+    ///Returns SHA-1 of `info` dictionary, same as [`Metainfo::info_hash`].
+    pub fn info_hash(&self) -> [u8; 20] {
+        match self.raw_info {
+            Some(raw_info) => Sha1::digest(raw_info).into(),
+            None => self.info.clone().into_owned().info_hash(),
+        }
+    }
+
+    ///Copies borrowed strings, keeping parsed bytes, so saved metainfo is the same as if it was parsed by
+    ///[`Serde::parse_metainfo`](super::Serde::parse_metainfo).
+    pub fn into_owned(self) -> Metainfo {
+        let mut metainfo = Metainfo {
+            info: self.info.into_owned(),
+            announce: self.announce.into_owned(),
+            announce_list: self
+                .announce_list
+                .map(|tiers| tiers.into_iter().map(|tier| tier.into_iter().map(Cow::into_owned).collect()).collect()),
+            creation_date: self.creation_date,
+            comment: self.comment.map(Cow::into_owned),
+            created_by: self.created_by.map(Cow::into_owned),
+            encoding: self.encoding.map(Cow::into_owned),
+            extra: self.extra,
+            raw_info: None,
+            raw: None,
+        };
+
+        if let Some(raw) = self.raw {
+            metainfo.record_raw(raw);
+        }
+
+        metainfo
+    }
+}
+
+impl InfoRef<'_> {
+    ///Number of piece hashes in `pieces`, same as [`Info::piece_count`].
+    pub fn piece_count(&self) -> usize {
+        self.pieces.len() / 20
+    }
+
+    ///Iterates over SHA1 hashes of all pieces in order.
+    pub fn piece_hashes(&self) -> impl ExactSizeIterator<Item = &[u8; 20]> + '_ {
+        self.pieces
+            .chunks_exact(20)
+            .map(|hash| hash.try_into().expect("chunk is 20 bytes long"))
+    }
+
+    pub fn into_owned(self) -> Info {
+        Info {
+            piece_length: self.piece_length,
+            pieces: self.pieces.into_owned().into(),
+            private: self.private,
+            name: self.name.into_owned(),
+            files: self.files,
+            extra: self.extra,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bencoded::Serde;
+
+    static SAMPLE_TORRENT: &[u8] = include_bytes!("sample.torrent");
+
+    #[test]
+    fn pieces_are_borrowed() {
+        let view = MetainfoRef::from_bytes(SAMPLE_TORRENT).unwrap();
+        let parsed = Serde.parse_metainfo(SAMPLE_TORRENT, Limits::default()).unwrap();
+
+        assert!(matches!(view.info.pieces, Cow::Borrowed(_)));
+        assert!(SAMPLE_TORRENT.as_ptr_range().contains(&view.info.pieces.as_ptr()));
+        assert!(matches!(view.announce, Cow::Borrowed(_)));
+        assert_eq!(view.info.piece_count(), 1);
+        assert_eq!(view.info_hash(), parsed.info_hash());
+        let owned = view.into_owned();
+        assert_eq!(owned.raw(), parsed.raw());
+        assert_eq!(owned, parsed);
+    }
+}
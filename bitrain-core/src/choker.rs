@@ -0,0 +1,292 @@
+//! Choice of peers to upload to.
+use std::{
+    cmp::Reverse,
+    time::{Duration, Instant},
+};
+
+use rand::seq::SliceRandom;
+
+use crate::messages::Message;
+
+/// Tit-for-tat choking of peers of type `P` (i.e. their addresses).
+///
+/// Every [round](`Self::ROUND_INTERVAL`) interested peers, which uploaded to us the most since the previous
+/// round (or which we uploaded to the most, while seeding), are unchoked in regular slots. Additionally one
+/// random interested peer is unchoked optimistically and kept for [`Self::OPTIMISTIC_INTERVAL`],
+/// so new peers get a chance to prove themselves and better peers can be discovered.
+#[derive(Debug, Clone)]
+pub struct Choker<P> {
+    slots: usize,
+    peers: Vec<PeerState<P>>,
+    optimistic: Option<P>,
+    last_round: Option<Instant>,
+    last_optimistic: Option<Instant>,
+}
+
+#[derive(Debug, Clone)]
+struct PeerState<P> {
+    peer: P,
+    interested: bool,
+    choked: bool,
+    snubbed: bool,
+    /// Bytes of blocks, transferred since the last round.
+    downloaded: u64,
+    uploaded: u64,
+}
+
+impl<P: Clone + PartialEq> Choker<P> {
+    pub const ROUND_INTERVAL: Duration = Duration::from_secs(10);
+    pub const OPTIMISTIC_INTERVAL: Duration = Duration::from_secs(30);
+    /// Number of regular unchoke slots, besides optimistic one.
+    pub const DEFAULT_SLOTS: usize = 4;
+
+    pub fn new(slots: usize) -> Self {
+        Self {
+            slots,
+            peers: vec![],
+            optimistic: None,
+            last_round: None,
+            last_optimistic: None,
+        }
+    }
+
+    /// Reports connected peer, which is choked, until the next round unchokes it.
+    pub fn add_peer(&mut self, peer: P) {
+        if self.state(&peer).is_none() {
+            self.peers.push(PeerState {
+                peer,
+                interested: false,
+                choked: true,
+                snubbed: false,
+                downloaded: 0,
+                uploaded: 0,
+            });
+        }
+    }
+
+    /// Reports disconnected peer. Its slot is given to other peer in the next round.
+    pub fn remove_peer(&mut self, peer: &P) {
+        self.peers.retain(|state| state.peer != *peer);
+
+        if self.optimistic.as_ref() == Some(peer) {
+            self.optimistic = None;
+        }
+    }
+
+    pub fn set_interested(&mut self, peer: &P, interested: bool) {
+        if let Some(state) = self.state_mut(peer) {
+            state.interested = interested;
+        }
+    }
+
+    /// Marks peer, which stopped delivering requested blocks (see
+    /// [`RequestTracker::snub`](`crate::picker::RequestTracker::snub`)). Snubbed peers aren't given regular slots,
+    /// though they can still be unchoked optimistically.
+    pub fn set_snubbed(&mut self, peer: &P, snubbed: bool) {
+        if let Some(state) = self.state_mut(peer) {
+            state.snubbed = snubbed;
+        }
+    }
+
+    /// Accounts `bytes` of blocks, recieved from `peer`.
+    pub fn record_download(&mut self, peer: &P, bytes: u64) {
+        if let Some(state) = self.state_mut(peer) {
+            state.downloaded += bytes;
+        }
+    }
+
+    /// Accounts `bytes` of blocks, sent to `peer`.
+    pub fn record_upload(&mut self, peer: &P, bytes: u64) {
+        if let Some(state) = self.state_mut(peer) {
+            state.uploaded += bytes;
+        }
+    }
+
+    /// `true` for peers, which aren't unchoked, including unknown ones.
+    pub fn is_choked(&self, peer: &P) -> bool {
+        self.state(peer).is_none_or(|state| state.choked)
+    }
+
+    /// Peer, which is unchoked optimistically.
+    pub fn optimistic(&self) -> Option<&P> {
+        self.optimistic.as_ref()
+    }
+
+    /// `true` if [`Self::ROUND_INTERVAL`] has passed since the last round.
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.last_round.is_none_or(|last| now >= last + Self::ROUND_INTERVAL)
+    }
+
+    /// Runs choking round, returning `Choke` and `Unchoke` messages for peers, which change their state.
+    ///
+    /// Peers are ranked by transfer since the last round: download from them, or upload to them when `seeding`,
+    /// since uploads are the only measure of peers, which have nothing to give back.
+    pub fn rechoke(&mut self, seeding: bool, now: Instant) -> Vec<(P, Message)> {
+        let optimistic_is_due = self.last_optimistic.is_none_or(|last| now >= last + Self::OPTIMISTIC_INTERVAL);
+        let optimistic_is_lost = self
+            .optimistic
+            .as_ref()
+            .is_none_or(|peer| self.state(peer).is_none_or(|state| !state.interested));
+        let rotate = optimistic_is_due || optimistic_is_lost;
+
+        // Optimistic peer competes for regular slot only once its own slot expires
+        let mut ranked = self
+            .peers
+            .iter()
+            .filter(|state| state.interested && !state.snubbed)
+            .filter(|state| rotate || self.optimistic.as_ref() != Some(&state.peer))
+            .map(|state| (&state.peer, if seeding { state.uploaded } else { state.downloaded }))
+            .collect::<Vec<_>>();
+        // Stable sort keeps order of connection among equals
+        ranked.sort_by_key(|(_, transferred)| Reverse(*transferred));
+
+        let regular = ranked
+            .into_iter()
+            .take(self.slots)
+            .map(|(peer, _)| peer.clone())
+            .collect::<Vec<_>>();
+
+        if rotate {
+            self.optimistic = self.pick_optimistic(&regular);
+            self.last_optimistic = Some(now);
+        }
+
+        let mut messages = vec![];
+        for state in &mut self.peers {
+            let choked = !regular.contains(&state.peer) && self.optimistic.as_ref() != Some(&state.peer);
+
+            if choked != state.choked {
+                state.choked = choked;
+                messages.push((state.peer.clone(), if choked { Message::Choke } else { Message::Unchoke }));
+            }
+
+            state.downloaded = 0;
+            state.uploaded = 0;
+        }
+
+        self.last_round = Some(now);
+        messages
+    }
+
+    /// Random interested peer, which isn't unchoked in `regular` slot, preferring other peer than current one.
+    fn pick_optimistic(&self, regular: &[P]) -> Option<P> {
+        let candidates = self
+            .peers
+            .iter()
+            .filter(|state| state.interested && !regular.contains(&state.peer))
+            .map(|state| &state.peer)
+            .collect::<Vec<_>>();
+
+        let rotated = candidates
+            .iter()
+            .filter(|peer| self.optimistic.as_ref() != Some(**peer))
+            .copied()
+            .collect::<Vec<_>>();
+
+        match rotated.is_empty() {
+            true => candidates.first().map(|peer| (*peer).clone()),
+            false => rotated.choose(&mut rand::thread_rng()).map(|peer| (*peer).clone()),
+        }
+    }
+
+    fn state(&self, peer: &P) -> Option<&PeerState<P>> {
+        self.peers.iter().find(|state| state.peer == *peer)
+    }
+
+    fn state_mut(&mut self, peer: &P) -> Option<&mut PeerState<P>> {
+        self.peers.iter_mut().find(|state| state.peer == *peer)
+    }
+}
+
+impl<P: Clone + PartialEq> Default for Choker<P> {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_SLOTS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn choker(peers: usize) -> Choker<usize> {
+        let mut choker = Choker::new(2);
+        for peer in 0..peers {
+            choker.add_peer(peer);
+            choker.set_interested(&peer, true);
+        }
+
+        choker
+    }
+
+    #[test]
+    fn best_uploaders_are_unchoked() {
+        let mut choker = choker(4);
+        let now = Instant::now();
+        choker.record_download(&1, 300);
+        choker.record_download(&2, 200);
+        choker.record_download(&3, 100);
+        choker.record_upload(&0, 1000);
+
+        let messages = choker.rechoke(false, now);
+        let optimistic = *choker.optimistic().unwrap();
+
+        assert!(!choker.is_choked(&1) && !choker.is_choked(&2));
+        assert!(optimistic == 0 || optimistic == 3);
+        assert_eq!(messages.len(), 3);
+        assert!(messages.iter().all(|(_, message)| *message == Message::Unchoke));
+        assert!(!choker.is_due(now + Duration::from_secs(5)));
+
+        // Seeding ranks peers by upload
+        choker.record_upload(&0, 1000);
+        choker.record_upload(&3, 500);
+        choker.rechoke(true, now + Choker::<usize>::ROUND_INTERVAL);
+
+        assert!(!choker.is_choked(&0) && !choker.is_choked(&3));
+        assert_eq!([1, 2].iter().filter(|peer| choker.is_choked(peer)).count(), 1);
+    }
+
+    #[test]
+    fn optimistic_unchoke_rotates() {
+        let mut choker = choker(3);
+        choker.set_interested(&0, false);
+        let now = Instant::now();
+
+        choker.rechoke(false, now);
+        // Peer 0 isn't interested, so peers 1 and 2 take regular slots
+        assert_eq!(choker.optimistic(), None);
+
+        choker.set_interested(&0, true);
+        choker.record_download(&1, 100);
+        choker.record_download(&2, 100);
+        let messages = choker.rechoke(false, now + Choker::<usize>::ROUND_INTERVAL);
+        assert_eq!(messages, vec![(0, Message::Unchoke)]);
+        assert_eq!(choker.optimistic(), Some(&0));
+
+        // Optimistic peer is kept until its interval expires, though it uploads nothing
+        choker.record_download(&1, 100);
+        choker.record_download(&2, 100);
+        assert!(choker.rechoke(false, now + 2 * Choker::<usize>::ROUND_INTERVAL).is_empty());
+
+        choker.record_download(&0, 500);
+        choker.record_download(&1, 100);
+        let messages = choker.rechoke(false, now + 4 * Choker::<usize>::ROUND_INTERVAL);
+        assert_eq!(messages, vec![]);
+        assert_eq!(choker.optimistic(), Some(&2));
+
+        choker.remove_peer(&2);
+        assert_eq!(choker.optimistic(), None);
+    }
+
+    #[test]
+    fn snubbed_peer_is_unchoked_only_optimistically() {
+        let mut choker = choker(2);
+        choker.slots = 1;
+        choker.set_snubbed(&0, true);
+        choker.record_download(&0, 1000);
+
+        choker.rechoke(false, Instant::now());
+
+        assert_eq!(choker.optimistic(), Some(&0));
+        assert!(!choker.is_choked(&1));
+    }
+}
@@ -0,0 +1,17 @@
+//! Mainline DHT node, used to find peers of trackerless torrents and to store arbitrary items.
+//!
+//! For more info see <https://www.bittorrent.org/beps/bep_0005.html> and
+//! <https://www.bittorrent.org/beps/bep_0044.html>.
+mod item;
+mod krpc;
+mod lookup;
+mod node;
+mod node_id;
+mod routing;
+
+pub use item::{Item, ItemError, ItemStore, MutableItem};
+pub use krpc::{Body, KrpcError, KrpcParseError, Message, Query, Responce, TransactionId, Transactions};
+pub use lookup::{Lookup, LookupResult};
+pub use node::DhtNode;
+pub use node_id::NodeId;
+pub use routing::{Contact, RoutingTable};
@@ -0,0 +1,295 @@
+use std::{collections::HashMap, fmt};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use sha1::{Digest, Sha1};
+
+use super::{KrpcError, NodeId, Responce};
+use crate::bencoded::encoding::{BEncode, Entry};
+use crate::bencoded::{BInt, BString};
+
+/// Arbitrary bencoded value, stored in DHT (BEP 44).
+///
+/// Immutable item is addressed by SHA-1 of its value, mutable one by SHA-1 of public key and salt,
+/// so its owner can replace value with newer signed one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    Immutable(Entry),
+    Mutable(MutableItem),
+}
+
+/// Value, signed with ed25519 key of its owner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MutableItem {
+    /// Public key of owner.
+    pub key: [u8; 32],
+    /// Optional salt, so that one key can own several items. Empty if not used.
+    pub salt: BString,
+    /// Sequence number, which must increase with every new value.
+    pub seq: BInt,
+    pub value: Entry,
+    pub signature: [u8; 64],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemError {
+    /// Encoded value is longer, than [`Item::MAX_VALUE_SIZE`].
+    ValueTooBig,
+    /// Salt is longer, than [`MutableItem::MAX_SALT_SIZE`].
+    SaltTooBig,
+    InvalidSignature,
+    /// Compare-and-swap `put` expected different sequence number of stored item.
+    CasMismatch,
+    /// Stored item is newer.
+    SeqLessThanCurrent,
+}
+
+impl ItemError {
+    /// KRPC error code of this error.
+    pub fn code(&self) -> BInt {
+        match self {
+            Self::ValueTooBig => 205,
+            Self::InvalidSignature => 206,
+            Self::SaltTooBig => 207,
+            Self::CasMismatch => 301,
+            Self::SeqLessThanCurrent => 302,
+        }
+    }
+}
+
+impl fmt::Display for ItemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ValueTooBig => write!(f, "message (v field) too big"),
+            Self::SaltTooBig => write!(f, "salt (salt field) too big"),
+            Self::InvalidSignature => write!(f, "invalid signature"),
+            Self::CasMismatch => write!(f, "the CAS hash mismatched, re-read value and try again"),
+            Self::SeqLessThanCurrent => write!(f, "sequence number less than current"),
+        }
+    }
+}
+
+impl std::error::Error for ItemError {}
+
+impl From<ItemError> for KrpcError {
+    fn from(err: ItemError) -> Self {
+        KrpcError::new(err.code(), err.to_string())
+    }
+}
+
+impl Item {
+    /// Maximum length of encoded value.
+    pub const MAX_VALUE_SIZE: usize = 1000;
+
+    pub fn value(&self) -> &Entry {
+        match self {
+            Self::Immutable(value) => value,
+            Self::Mutable(item) => &item.value,
+        }
+    }
+
+    /// Id, item is stored under.
+    pub fn target(&self) -> NodeId {
+        match self {
+            Self::Immutable(value) => NodeId(Sha1::digest(value.encode()).into()),
+            Self::Mutable(item) => item.target(),
+        }
+    }
+
+    /// Checks size limits and, for mutable item, its signature.
+    pub fn verify(&self) -> Result<(), ItemError> {
+        match self {
+            Self::Immutable(value) if value.encode().len() > Self::MAX_VALUE_SIZE => Err(ItemError::ValueTooBig),
+            Self::Immutable(_) => Ok(()),
+            Self::Mutable(item) => item.verify(),
+        }
+    }
+
+    /// Item from `get` responce, if it has one. Responces don't carry salt of mutable item,
+    /// so it must be supplied by requester.
+    ///
+    /// Item is not verified, nor checked to match requested target.
+    pub fn from_responce(responce: &Responce, salt: &BString) -> Option<Self> {
+        let value = responce.value.clone()?;
+
+        match (responce.key, responce.seq, responce.signature) {
+            (Some(key), Some(seq), Some(signature)) => Some(Self::Mutable(MutableItem {
+                key,
+                salt: salt.clone(),
+                seq,
+                value,
+                signature,
+            })),
+            (None, None, None) => Some(Self::Immutable(value)),
+            _ => None,
+        }
+    }
+}
+
+impl MutableItem {
+    /// Maximum length of salt.
+    pub const MAX_SALT_SIZE: usize = 64;
+
+    /// Signs `value` with owner `key`.
+    pub fn sign(key: &SigningKey, salt: BString, seq: BInt, value: Entry) -> Self {
+        let signature = key.sign(&utils::signed_bytes(&salt, seq, &value));
+
+        Self {
+            key: key.verifying_key().to_bytes(),
+            salt,
+            seq,
+            value,
+            signature: signature.to_bytes(),
+        }
+    }
+
+    /// Id of mutable item, owned by `key` and stored with `salt`.
+    pub fn target_of(key: &[u8; 32], salt: &[u8]) -> NodeId {
+        let mut hasher = Sha1::new();
+        hasher.update(key);
+        hasher.update(salt);
+
+        NodeId(hasher.finalize().into())
+    }
+
+    pub fn target(&self) -> NodeId {
+        Self::target_of(&self.key, &self.salt.0)
+    }
+
+    pub fn verify(&self) -> Result<(), ItemError> {
+        if self.salt.0.len() > Self::MAX_SALT_SIZE {
+            return Err(ItemError::SaltTooBig);
+        }
+
+        if self.value.encode().len() > Item::MAX_VALUE_SIZE {
+            return Err(ItemError::ValueTooBig);
+        }
+
+        let key = VerifyingKey::from_bytes(&self.key).map_err(|_| ItemError::InvalidSignature)?;
+        let signed = utils::signed_bytes(&self.salt, self.seq, &self.value);
+
+        key.verify_strict(&signed, &Signature::from_bytes(&self.signature))
+            .map_err(|_| ItemError::InvalidSignature)
+    }
+}
+
+/// Items, stored by node on behalf of others with `put` queries.
+#[derive(Debug, Clone, Default)]
+pub struct ItemStore {
+    items: HashMap<NodeId, Item>,
+}
+
+impl ItemStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn get(&self, target: &NodeId) -> Option<&Item> {
+        self.items.get(target)
+    }
+
+    /// Verifies and stores `item`, returning its target. Mutable item replaces stored one only if it isn't older
+    /// and, if `cas` is given, stored one has exactly that sequence number.
+    pub fn put(&mut self, item: Item, cas: Option<BInt>) -> Result<NodeId, ItemError> {
+        item.verify()?;
+
+        let target = item.target();
+
+        if let (Item::Mutable(new), Some(Item::Mutable(current))) = (&item, self.items.get(&target)) {
+            if cas.is_some_and(|cas| cas != current.seq) {
+                return Err(ItemError::CasMismatch);
+            }
+
+            if new.seq < current.seq {
+                return Err(ItemError::SeqLessThanCurrent);
+            }
+        }
+
+        self.items.insert(target, item);
+        Ok(target)
+    }
+
+    pub fn remove(&mut self, target: &NodeId) -> Option<Item> {
+        self.items.remove(target)
+    }
+}
+
+mod utils {
+    use super::*;
+
+    /// Bytes, signed by owner of mutable item: `salt` (if any), `seq` and `v` keys of bencoded dictionary,
+    /// without its delimiters.
+    pub fn signed_bytes(salt: &BString, seq: BInt, value: &Entry) -> Vec<u8> {
+        let mut signed = vec![];
+
+        if !salt.0.is_empty() {
+            signed.extend_from_slice(format!("4:salt{}:", salt.0.len()).as_bytes());
+            signed.extend_from_slice(&salt.0);
+        }
+
+        signed.extend_from_slice(format!("3:seqi{}e1:v", seq).as_bytes());
+        signed.extend_from_slice(&value.encode());
+
+        signed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hex_literal::hex;
+
+    use super::*;
+
+    fn value() -> Entry {
+        Entry::String(BString(b"Hello World!".to_vec()))
+    }
+
+    #[test]
+    fn test_vectors_are_verified() {
+        // Test vectors of BEP 44
+        let immutable = Item::Immutable(value());
+        assert_eq!(immutable.target().0, hex!("e5f96f6f38320f0f33959cb4d3d656452117aadb"));
+
+        let salted = MutableItem {
+            key: hex!("77ff84905a91936367c01360803104f92432fcd904a43511876df5cdf3e7e548"),
+            salt: BString(b"foobar".to_vec()),
+            seq: 1,
+            value: value(),
+            signature: hex!(
+                "6834284b6b24c3204eb2fea824d82f88883a3d95e8b4a21b8c0ded553d17d17d"
+                "df9a8a7104b1258f30bed3787e6cb896fca78c58f8e03b5f18f14951a87d9a08"
+            ),
+        };
+
+        assert_eq!(salted.target().0, hex!("411eba73b6f087ca51a3795d9c8c938d365e32c1"));
+        assert_eq!(salted.verify(), Ok(()));
+
+        let forged = MutableItem { seq: 2, ..salted };
+        assert_eq!(forged.verify(), Err(ItemError::InvalidSignature));
+    }
+
+    #[test]
+    fn newer_items_replace_stored_ones() {
+        let key = SigningKey::generate(&mut rand::rngs::OsRng);
+        let salt = BString(b"salt".to_vec());
+        let mut store = ItemStore::new();
+
+        let target = store.put(Item::Mutable(MutableItem::sign(&key, salt.clone(), 1, value())), None).unwrap();
+
+        let older = MutableItem::sign(&key, salt.clone(), 0, value());
+        assert_eq!(store.put(Item::Mutable(older), None), Err(ItemError::SeqLessThanCurrent));
+
+        let newer = MutableItem::sign(&key, salt, 2, Entry::Integer(42));
+        assert_eq!(store.put(Item::Mutable(newer.clone()), Some(0)), Err(ItemError::CasMismatch));
+
+        store.put(Item::Mutable(newer.clone()), Some(1)).unwrap();
+        assert_eq!(store.get(&target), Some(&Item::Mutable(newer)));
+    }
+}
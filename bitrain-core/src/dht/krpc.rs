@@ -0,0 +1,666 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    time::Instant,
+};
+
+use super::{Contact, Item, MutableItem, NodeId};
+use crate::bencoded::encoding::{self, borrowed, BDictionary, BEncode, Entry};
+use crate::bencoded::{BInt, BString};
+
+/// Opaque id of query, echoed in responce or error, so they can be matched with each other.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct TransactionId(pub BString);
+
+/// KRPC message (query, responce or error), exchanged between DHT nodes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub transaction_id: TransactionId,
+    pub body: Body,
+    /// Client version of sender (`v` key), if advertised.
+    pub version: Option<BString>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Body {
+    Query { id: NodeId, query: Query },
+    Responce(Responce),
+    Error(KrpcError),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    Ping,
+    /// Asks for contacts of nodes, closest to `target`.
+    FindNode { target: NodeId },
+    /// Asks for peers of torrent or, if they are unknown, for contacts of nodes, closest to `info_hash`.
+    GetPeers { info_hash: NodeId },
+    /// Announces, that sender downloads torrent. `token` must be one, recieved in responce to `get_peers`.
+    AnnouncePeer {
+        info_hash: NodeId,
+        /// Peer port, ignored if `implied_port` is set (then source port of datagram is used).
+        port: u16,
+        implied_port: bool,
+        token: BString,
+    },
+    /// Asks for item, stored under `target` (BEP 44). Mutable item is returned only if it is newer than `seq`.
+    Get { target: NodeId, seq: Option<BInt> },
+    /// Stores item. `token` must be one, recieved in responce to `get`.
+    Put {
+        token: BString,
+        item: Item,
+        /// Expected sequence number of currently stored mutable item (compare-and-swap).
+        cas: Option<BInt>,
+    },
+}
+
+/// Responce to any query. KRPC responces don't tell which query they answer, so all keys are optional,
+/// except `id`: `ping` and `announce_peer` are answered with `id` only.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Responce {
+    pub id: NodeId,
+    /// Contacts of nodes, closest to target (`nodes` and `nodes6` keys).
+    pub nodes: Vec<Contact>,
+    /// Peers of torrent, requested with `get_peers`.
+    pub values: Vec<SocketAddr>,
+    /// Token for future `announce_peer` or `put`, returned by `get_peers` or `get`.
+    pub token: Option<BString>,
+    /// Value of item, returned by `get`.
+    pub value: Option<Entry>,
+    /// Public key of mutable item owner.
+    pub key: Option<[u8; 32]>,
+    /// Sequence number of mutable item.
+    pub seq: Option<BInt>,
+    /// Signature of mutable item.
+    pub signature: Option<[u8; 64]>,
+}
+
+/// Error, returned instead of responce.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KrpcError {
+    pub code: BInt,
+    pub message: String,
+}
+
+impl KrpcError {
+    pub const GENERIC: BInt = 201;
+    pub const SERVER: BInt = 202;
+    /// Malformed packet, invalid arguments or bad token.
+    pub const PROTOCOL: BInt = 203;
+    pub const METHOD_UNKNOWN: BInt = 204;
+
+    pub fn new(code: BInt, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for KrpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KRPC error {}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for KrpcError {}
+
+#[derive(Debug)]
+pub enum KrpcParseError {
+    Bencode(encoding::Error),
+    MissingField(&'static str),
+    /// Field is present, but has unexpected type or value.
+    InvalidField(&'static str),
+    /// Query has unknown method. Transaction id is kept, so sender can be answered with
+    /// [`KrpcError::METHOD_UNKNOWN`].
+    UnknownMethod {
+        transaction_id: TransactionId,
+        method: String,
+    },
+}
+
+impl fmt::Display for KrpcParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bencode(err) => write!(f, "malformed bencode: {}", err),
+            Self::MissingField(field) => write!(f, "missing field `{}`", field),
+            Self::InvalidField(field) => write!(f, "invalid field `{}`", field),
+            Self::UnknownMethod { method, .. } => write!(f, "unknown method `{}`", method),
+        }
+    }
+}
+
+impl std::error::Error for KrpcParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Bencode(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<encoding::Error> for KrpcParseError {
+    fn from(err: encoding::Error) -> Self {
+        Self::Bencode(err)
+    }
+}
+
+type Result<T> = std::result::Result<T, KrpcParseError>;
+
+impl Message {
+    pub fn query(transaction_id: TransactionId, id: NodeId, query: Query) -> Self {
+        Self {
+            transaction_id,
+            body: Body::Query { id, query },
+            version: None,
+        }
+    }
+
+    pub fn responce(transaction_id: TransactionId, responce: Responce) -> Self {
+        Self {
+            transaction_id,
+            body: Body::Responce(responce),
+            version: None,
+        }
+    }
+
+    pub fn error(transaction_id: TransactionId, error: KrpcError) -> Self {
+        Self {
+            transaction_id,
+            body: Body::Error(error),
+            version: None,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        self.to_entry().encode().into_vec()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let entry = borrowed::Entry::from_bytes(bytes)?.to_owned_entry();
+        let mut message = entry.parse_or_err::<BDictionary, _>(KrpcParseError::InvalidField("message"))?;
+
+        let transaction_id = TransactionId(utils::take(&mut message, "t")?);
+        let version = utils::take_optional(&mut message, "v")?;
+        let kind: BString = utils::take(&mut message, "y")?;
+
+        let body = match kind.as_bytes() {
+            b"q" => {
+                let method: String = utils::take(&mut message, "q")?;
+                let mut arguments: BDictionary = utils::take(&mut message, "a")?;
+                let id = utils::take_id(&mut arguments, "id")?;
+
+                let query = match method.as_str() {
+                    "ping" => Query::Ping,
+                    "find_node" => Query::FindNode {
+                        target: utils::take_id(&mut arguments, "target")?,
+                    },
+                    "get_peers" => Query::GetPeers {
+                        info_hash: utils::take_id(&mut arguments, "info_hash")?,
+                    },
+                    "announce_peer" => Query::AnnouncePeer {
+                        info_hash: utils::take_id(&mut arguments, "info_hash")?,
+                        port: u16::try_from(utils::take::<BInt>(&mut arguments, "port")?)
+                            .map_err(|_| KrpcParseError::InvalidField("port"))?,
+                        implied_port: utils::take_optional::<BInt>(&mut arguments, "implied_port")? == Some(1),
+                        token: utils::take(&mut arguments, "token")?,
+                    },
+                    "get" => Query::Get {
+                        target: utils::take_id(&mut arguments, "target")?,
+                        seq: utils::take_optional(&mut arguments, "seq")?,
+                    },
+                    "put" => {
+                        let token = utils::take(&mut arguments, "token")?;
+                        let value = utils::take(&mut arguments, "v")?;
+                        let cas = utils::take_optional(&mut arguments, "cas")?;
+
+                        let item = match utils::take_array(&mut arguments, "k")? {
+                            Some(key) => Item::Mutable(MutableItem {
+                                key,
+                                salt: utils::take_optional(&mut arguments, "salt")?.unwrap_or_default(),
+                                seq: utils::take(&mut arguments, "seq")?,
+                                value,
+                                signature: utils::take_array(&mut arguments, "sig")?
+                                    .ok_or(KrpcParseError::MissingField("sig"))?,
+                            }),
+                            None => Item::Immutable(value),
+                        };
+
+                        Query::Put { token, item, cas }
+                    }
+                    _ => return Err(KrpcParseError::UnknownMethod { transaction_id, method }),
+                };
+
+                Body::Query { id, query }
+            }
+            b"r" => Body::Responce(Responce::from_dictionary(utils::take(&mut message, "r")?)?),
+            b"e" => {
+                let error: Vec<Entry> = utils::take(&mut message, "e")?;
+
+                match &error[..] {
+                    [Entry::Integer(code), Entry::String(text)] => Body::Error(KrpcError {
+                        code: *code,
+                        message: text.to_string_lossy().into_owned(),
+                    }),
+                    _ => return Err(KrpcParseError::InvalidField("e")),
+                }
+            }
+            _ => return Err(KrpcParseError::InvalidField("y")),
+        };
+
+        Ok(Self {
+            transaction_id,
+            body,
+            version,
+        })
+    }
+
+    fn to_entry(&self) -> Entry {
+        let mut message = BDictionary::new();
+
+        utils::insert(&mut message, "t", Entry::String(self.transaction_id.0.clone()));
+
+        if let Some(version) = &self.version {
+            utils::insert(&mut message, "v", Entry::String(version.clone()));
+        }
+
+        match &self.body {
+            Body::Query { id, query } => {
+                let mut arguments = BDictionary::new();
+                utils::insert(&mut arguments, "id", utils::id(id));
+
+                let method = match query {
+                    Query::Ping => "ping",
+                    Query::FindNode { target } => {
+                        utils::insert(&mut arguments, "target", utils::id(target));
+                        "find_node"
+                    }
+                    Query::GetPeers { info_hash } => {
+                        utils::insert(&mut arguments, "info_hash", utils::id(info_hash));
+                        "get_peers"
+                    }
+                    Query::AnnouncePeer {
+                        info_hash,
+                        port,
+                        implied_port,
+                        token,
+                    } => {
+                        utils::insert(&mut arguments, "info_hash", utils::id(info_hash));
+                        utils::insert(&mut arguments, "port", Entry::Integer(*port as BInt));
+                        utils::insert(&mut arguments, "implied_port", Entry::Integer(*implied_port as BInt));
+                        utils::insert(&mut arguments, "token", Entry::String(token.clone()));
+                        "announce_peer"
+                    }
+                    Query::Get { target, seq } => {
+                        utils::insert(&mut arguments, "target", utils::id(target));
+
+                        if let Some(seq) = seq {
+                            utils::insert(&mut arguments, "seq", Entry::Integer(*seq));
+                        }
+
+                        "get"
+                    }
+                    Query::Put { token, item, cas } => {
+                        utils::insert(&mut arguments, "token", Entry::String(token.clone()));
+                        utils::insert(&mut arguments, "v", item.value().clone());
+
+                        if let Some(cas) = cas {
+                            utils::insert(&mut arguments, "cas", Entry::Integer(*cas));
+                        }
+
+                        if let Item::Mutable(item) = item {
+                            utils::insert(&mut arguments, "k", Entry::String(BString(item.key.to_vec())));
+                            utils::insert(&mut arguments, "seq", Entry::Integer(item.seq));
+                            utils::insert(&mut arguments, "sig", Entry::String(BString(item.signature.to_vec())));
+
+                            if !item.salt.0.is_empty() {
+                                utils::insert(&mut arguments, "salt", Entry::String(item.salt.clone()));
+                            }
+                        }
+
+                        "put"
+                    }
+                };
+
+                utils::insert(&mut message, "y", utils::string("q"));
+                utils::insert(&mut message, "q", utils::string(method));
+                utils::insert(&mut message, "a", Entry::Dictionary(arguments));
+            }
+            Body::Responce(responce) => {
+                utils::insert(&mut message, "y", utils::string("r"));
+                utils::insert(&mut message, "r", responce.to_entry());
+            }
+            Body::Error(error) => {
+                let error = vec![Entry::Integer(error.code), utils::string(&error.message)];
+
+                utils::insert(&mut message, "y", utils::string("e"));
+                utils::insert(&mut message, "e", Entry::List(error));
+            }
+        }
+
+        Entry::Dictionary(message)
+    }
+}
+
+impl Responce {
+    /// Length of compact IPv4 node info: id, address and port.
+    pub const COMPACT_NODE_LEN: usize = 26;
+    /// Length of compact IPv6 node info (BEP 32).
+    pub const COMPACT_NODE6_LEN: usize = 38;
+
+    pub fn new(id: NodeId) -> Self {
+        Self {
+            id,
+            ..Self::default()
+        }
+    }
+
+    fn from_dictionary(mut responce: BDictionary) -> Result<Self> {
+        let id = utils::take_id(&mut responce, "id")?;
+        let nodes = utils::take_optional::<BString>(&mut responce, "nodes")?.unwrap_or_default();
+        let nodes6 = utils::take_optional::<BString>(&mut responce, "nodes6")?.unwrap_or_default();
+        let values = utils::take_optional::<Vec<Entry>>(&mut responce, "values")?.unwrap_or_default();
+        let token = utils::take_optional(&mut responce, "token")?;
+        let value = responce.remove(&b"v"[..]);
+        let key = utils::take_array(&mut responce, "k")?;
+        let seq = utils::take_optional(&mut responce, "seq")?;
+        let signature = utils::take_array(&mut responce, "sig")?;
+
+        let nodes = nodes
+            .0
+            .chunks_exact(Self::COMPACT_NODE_LEN)
+            .chain(nodes6.0.chunks_exact(Self::COMPACT_NODE6_LEN))
+            .map(|node| Contact::new(NodeId(node[..20].try_into().unwrap()), utils::decode_addr(&node[20..])))
+            .collect();
+
+        // Malformed peers are skipped, so valid ones are not lost
+        let values = values
+            .iter()
+            .filter_map(Entry::as_bytes)
+            .filter(|peer| matches!(peer.len(), 6 | 18))
+            .map(utils::decode_addr)
+            .collect();
+
+        Ok(Self {
+            id,
+            nodes,
+            values,
+            token,
+            value,
+            key,
+            seq,
+            signature,
+        })
+    }
+
+    fn to_entry(&self) -> Entry {
+        let mut responce = BDictionary::new();
+        utils::insert(&mut responce, "id", utils::id(&self.id));
+
+        let (nodes, nodes6): (Vec<&Contact>, Vec<&Contact>) = self.nodes.iter().partition(|node| node.addr.is_ipv4());
+
+        for (key, nodes) in [("nodes", nodes), ("nodes6", nodes6)] {
+            if !nodes.is_empty() {
+                let compact = nodes
+                    .iter()
+                    .flat_map(|node| [&node.id.0[..], &utils::encode_addr(&node.addr)].concat())
+                    .collect();
+
+                utils::insert(&mut responce, key, Entry::String(BString(compact)));
+            }
+        }
+
+        if !self.values.is_empty() {
+            let values = self
+                .values
+                .iter()
+                .map(|peer| Entry::String(BString(utils::encode_addr(peer))))
+                .collect();
+
+            utils::insert(&mut responce, "values", Entry::List(values));
+        }
+
+        if let Some(token) = &self.token {
+            utils::insert(&mut responce, "token", Entry::String(token.clone()));
+        }
+
+        if let Some(value) = &self.value {
+            utils::insert(&mut responce, "v", value.clone());
+        }
+
+        if let Some(key) = &self.key {
+            utils::insert(&mut responce, "k", Entry::String(BString(key.to_vec())));
+        }
+
+        if let Some(seq) = self.seq {
+            utils::insert(&mut responce, "seq", Entry::Integer(seq));
+        }
+
+        if let Some(signature) = &self.signature {
+            utils::insert(&mut responce, "sig", Entry::String(BString(signature.to_vec())));
+        }
+
+        Entry::Dictionary(responce)
+    }
+}
+
+/// Allocates transaction ids for outgoing queries and matches incoming responces with them.
+///
+/// Every pending query carries `context` of type `T` (i.e. id of queried node), which is given back,
+/// when query is answered or expires.
+#[derive(Debug, Clone)]
+pub struct Transactions<T> {
+    next: u16,
+    pending: HashMap<TransactionId, Pending<T>>,
+}
+
+#[derive(Debug, Clone)]
+struct Pending<T> {
+    addr: SocketAddr,
+    deadline: Instant,
+    context: T,
+}
+
+impl<T> Default for Transactions<T> {
+    fn default() -> Self {
+        Self {
+            next: rand::random(),
+            pending: HashMap::new(),
+        }
+    }
+}
+
+impl<T> Transactions<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of queries, awaiting responce.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Registers query to `addr`, which expires at `deadline`, and returns its transaction id.
+    pub fn start(&mut self, addr: SocketAddr, deadline: Instant, context: T) -> TransactionId {
+        let mut id = TransactionId(BString(self.next.to_be_bytes().to_vec()));
+
+        // Ids wrap around, so long-pending transaction can still hold the next one
+        while self.pending.contains_key(&id) {
+            self.next = self.next.wrapping_add(1);
+            id = TransactionId(BString(self.next.to_be_bytes().to_vec()));
+        }
+
+        self.next = self.next.wrapping_add(1);
+        self.pending.insert(id.clone(), Pending { addr, deadline, context });
+
+        id
+    }
+
+    /// Completes transaction `id`, returning its context. Messages from address, other than the one,
+    /// query was sent to, are not accepted.
+    pub fn finish(&mut self, id: &TransactionId, from: SocketAddr) -> Option<T> {
+        match self.pending.get(id) {
+            Some(pending) if pending.addr == from => self.pending.remove(id).map(|pending| pending.context),
+            _ => None,
+        }
+    }
+
+    /// Removes transactions, which expired by `now`, returning their addresses and contexts.
+    pub fn expire(&mut self, now: Instant) -> Vec<(SocketAddr, T)> {
+        let expired = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| pending.deadline <= now)
+            .map(|(id, _)| id.clone())
+            .collect::<Vec<_>>();
+
+        expired
+            .into_iter()
+            .filter_map(|id| self.pending.remove(&id))
+            .map(|pending| (pending.addr, pending.context))
+            .collect()
+    }
+
+    /// Earliest deadline of pending transactions.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.pending.values().map(|pending| pending.deadline).min()
+    }
+}
+
+mod utils {
+    use super::*;
+
+    pub fn insert(dictionary: &mut BDictionary, key: &str, value: Entry) {
+        dictionary.insert(BString(key.as_bytes().to_vec()), value);
+    }
+
+    pub fn string(value: &str) -> Entry {
+        Entry::String(BString(value.as_bytes().to_vec()))
+    }
+
+    pub fn id(id: &NodeId) -> Entry {
+        Entry::String(BString(id.0.to_vec()))
+    }
+
+    pub fn take_optional<T: TryFrom<Entry>>(dictionary: &mut BDictionary, key: &'static str) -> Result<Option<T>> {
+        dictionary
+            .remove(key.as_bytes())
+            .map(|entry| entry.parse_or_err(KrpcParseError::InvalidField(key)))
+            .transpose()
+    }
+
+    pub fn take<T: TryFrom<Entry>>(dictionary: &mut BDictionary, key: &'static str) -> Result<T> {
+        take_optional(dictionary, key)?.ok_or(KrpcParseError::MissingField(key))
+    }
+
+    pub fn take_id(dictionary: &mut BDictionary, key: &'static str) -> Result<NodeId> {
+        let id: BString = take(dictionary, key)?;
+
+        id.0.try_into()
+            .map(NodeId)
+            .map_err(|_| KrpcParseError::InvalidField(key))
+    }
+
+    /// Takes byte string of exactly `N` bytes (i.e. key or signature).
+    pub fn take_array<const N: usize>(dictionary: &mut BDictionary, key: &'static str) -> Result<Option<[u8; N]>> {
+        take_optional::<BString>(dictionary, key)?
+            .map(|bytes| bytes.0.try_into().map_err(|_| KrpcParseError::InvalidField(key)))
+            .transpose()
+    }
+
+    /// Decodes compact IPv4 (6 bytes) or IPv6 (18 bytes) address.
+    pub fn decode_addr(bytes: &[u8]) -> SocketAddr {
+        let (ip, port) = bytes.split_at(bytes.len() - 2);
+        let port = u16::from_be_bytes([port[0], port[1]]);
+
+        match <[u8; 4]>::try_from(ip) {
+            Ok(ip) => SocketAddrV4::new(Ipv4Addr::from(ip), port).into(),
+            Err(_) => SocketAddrV6::new(Ipv6Addr::from(<[u8; 16]>::try_from(ip).unwrap()), port, 0, 0).into(),
+        }
+    }
+
+    pub fn encode_addr(addr: &SocketAddr) -> Vec<u8> {
+        let ip = match addr {
+            SocketAddr::V4(addr) => addr.ip().octets().to_vec(),
+            SocketAddr::V6(addr) => addr.ip().octets().to_vec(),
+        };
+
+        [ip, addr.port().to_be_bytes().to_vec()].concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rstest::rstest;
+
+    use super::*;
+
+    fn tid() -> TransactionId {
+        TransactionId(BString(b"aa".to_vec()))
+    }
+
+    #[rstest]
+    #[case::ping(b"d1:ad2:id20:abcdefghij0123456789e1:q4:ping1:t2:aa1:y1:qe", Message::query(tid(), NodeId(*b"abcdefghij0123456789"), Query::Ping))]
+    #[case::find_node(
+        b"d1:rd2:id20:0123456789abcdefghij5:nodes26:mnopqrstuvwxyz123456\x7f\x00\x00\x01\x1a\xe1e1:t2:aa1:y1:re",
+        Message::responce(tid(), Responce {
+            nodes: vec![Contact::new(NodeId(*b"mnopqrstuvwxyz123456"), SocketAddr::from(([127, 0, 0, 1], 6881)))],
+            ..Responce::new(NodeId(*b"0123456789abcdefghij"))
+        })
+    )]
+    #[case::get_peers(
+        b"d1:rd2:id20:abcdefghij01234567895:token8:aoeusnth6:valuesl6:axje.u6:idhtnmee1:t2:aa1:y1:re",
+        Message::responce(tid(), Responce {
+            values: vec![SocketAddr::from(([97, 120, 106, 101], 11893)), SocketAddr::from(([105, 100, 104, 116], 28269))],
+            token: Some(BString(b"aoeusnth".to_vec())),
+            ..Responce::new(NodeId(*b"abcdefghij0123456789"))
+        })
+    )]
+    #[case::get(
+        b"d1:ad2:id20:abcdefghij01234567893:seqi4e6:target20:mnopqrstuvwxyz123456e1:q3:get1:t2:aa1:y1:qe",
+        Message::query(tid(), NodeId(*b"abcdefghij0123456789"), Query::Get { target: NodeId(*b"mnopqrstuvwxyz123456"), seq: Some(4) })
+    )]
+    #[case::put(
+        b"d1:ad2:id20:abcdefghij01234567895:token8:aoeusnth1:vi42ee1:q3:put1:t2:aa1:y1:qe",
+        Message::query(tid(), NodeId(*b"abcdefghij0123456789"), Query::Put {
+            token: BString(b"aoeusnth".to_vec()),
+            item: Item::Immutable(Entry::Integer(42)),
+            cas: None,
+        })
+    )]
+    #[case::error(b"d1:eli201e23:A Generic Error Ocurrede1:t2:aa1:y1:ee", Message::error(tid(), KrpcError::new(201, "A Generic Error Ocurred")))]
+    fn messages_roundtrip(#[case] bytes: &[u8], #[case] message: Message) {
+        assert_eq!(Message::decode(bytes).unwrap(), message);
+        assert_eq!(message.encode(), bytes);
+    }
+
+    #[test]
+    fn unknown_method_keeps_transaction() {
+        let err = Message::decode(b"d1:ad2:id20:abcdefghij0123456789e1:q4:vote1:t2:aa1:y1:qe").unwrap_err();
+
+        assert!(matches!(err, KrpcParseError::UnknownMethod { transaction_id, .. } if transaction_id == tid()));
+    }
+
+    #[test]
+    fn transactions_are_matched() {
+        let now = Instant::now();
+        let addr = SocketAddr::from(([127, 0, 0, 1], 6881));
+        let mut transactions = Transactions::new();
+
+        let first = transactions.start(addr, now + Duration::from_secs(1), 1);
+        let second = transactions.start(addr, now + Duration::from_secs(2), 2);
+
+        assert_ne!(first, second);
+        assert_eq!(transactions.finish(&first, SocketAddr::from(([127, 0, 0, 2], 6881))), None);
+        assert_eq!(transactions.finish(&first, addr), Some(1));
+        assert_eq!(transactions.expire(now + Duration::from_secs(2)), [(addr, 2)]);
+        assert!(transactions.is_empty());
+    }
+}
@@ -0,0 +1,273 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+};
+
+use super::{Contact, Item, NodeId, Responce, RoutingTable};
+use crate::bencoded::BString;
+
+/// Iterative lookup of `target` info-hash with `get_peers` queries or of item with `get` queries.
+///
+/// Lookup does no I/O itself, same as [`RoutingTable`]: consumer sends queries to contacts,
+/// returned by [`next_queries()`](`Lookup::next_queries`), and reports responces or failures back,
+/// until lookup [`is_finished()`](`Lookup::is_finished`). [`DhtNode::run_lookup`](super::DhtNode::run_lookup)
+/// does exactly this over node socket.
+///
+/// Lookup converges, when [`Self::K`] closest known nodes have all responded, keeping at most
+/// [`Self::ALPHA`] queries in flight meanwhile.
+#[derive(Debug, Clone)]
+pub struct Lookup {
+    target: NodeId,
+    /// Candidates, ordered by distance to target.
+    candidates: BTreeMap<NodeId, Candidate>,
+    peers: Vec<SocketAddr>,
+    /// Salt of looked up mutable item.
+    salt: BString,
+    /// Newest valid item, returned by `get`.
+    item: Option<Item>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Unqueried,
+    InFlight,
+    Responded,
+    Failed,
+}
+
+#[derive(Debug, Clone)]
+struct Candidate {
+    contact: Contact,
+    state: State,
+    /// Token for `announce_peer`, returned by node.
+    token: Option<BString>,
+}
+
+/// Outcome of finished (or abandoned) [`Lookup`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LookupResult {
+    /// Peers of torrent, without duplicates.
+    pub peers: Vec<SocketAddr>,
+    /// Up to [`Lookup::K`] closest nodes, which responded, sorted by distance. Peer can be announced
+    /// to them with their tokens.
+    pub closest: Vec<(Contact, Option<BString>)>,
+    /// Item, found by `get` lookup. Mutable one is the newest among returned.
+    pub item: Option<Item>,
+}
+
+impl Lookup {
+    /// Number of queries in flight.
+    pub const ALPHA: usize = 3;
+    /// Number of closest nodes, which must respond for lookup to converge.
+    pub const K: usize = RoutingTable::K;
+
+    /// Starts lookup from `seeds` (i.e. closest nodes of routing table or bootstrap nodes).
+    pub fn new(target: NodeId, seeds: impl IntoIterator<Item = Contact>) -> Self {
+        let mut lookup = Self {
+            target,
+            candidates: BTreeMap::new(),
+            peers: vec![],
+            salt: BString::default(),
+            item: None,
+        };
+
+        lookup.add_candidates(seeds);
+        lookup
+    }
+
+    /// Sets salt of mutable item, looked up with `get` queries. Responces don't carry it, but it is needed
+    /// to verify items.
+    pub fn salt(mut self, salt: BString) -> Self {
+        self.salt = salt;
+        self
+    }
+
+    pub fn target(&self) -> NodeId {
+        self.target
+    }
+
+    /// Contacts to query next, so that at most [`Self::ALPHA`] queries are in flight.
+    /// Returned contacts are considered queried.
+    pub fn next_queries(&mut self) -> Vec<Contact> {
+        let in_flight = self.in_flight();
+        let mut queries = vec![];
+
+        for candidate in self.closest_mut() {
+            if in_flight + queries.len() == Self::ALPHA {
+                break;
+            }
+
+            if candidate.state == State::Unqueried {
+                candidate.state = State::InFlight;
+                queries.push(candidate.contact);
+            }
+        }
+
+        queries
+    }
+
+    /// Reports responce of node `from` to `get_peers` query.
+    pub fn on_responce(&mut self, from: &NodeId, responce: &Responce) {
+        if let Some(candidate) = self.candidates.get_mut(&from.distance(&self.target)) {
+            candidate.state = State::Responded;
+            candidate.token = responce.token.clone();
+        }
+
+        for peer in &responce.values {
+            if !self.peers.contains(peer) {
+                self.peers.push(*peer);
+            }
+        }
+
+        self.add_candidates(responce.nodes.iter().copied());
+
+        // Items, which don't belong to target or are forged, are ignored
+        let Some(item) = Item::from_responce(responce, &self.salt) else { return };
+
+        if item.target() != self.target || item.verify().is_err() {
+            return;
+        }
+
+        let newer = match (&self.item, &item) {
+            (Some(Item::Mutable(current)), Item::Mutable(new)) => new.seq > current.seq,
+            (current, _) => current.is_none(),
+        };
+
+        if newer {
+            self.item = Some(item);
+        }
+    }
+
+    /// Reports node `from`, which didn't respond or responded with error.
+    pub fn on_failure(&mut self, from: &NodeId) {
+        if let Some(candidate) = self.candidates.get_mut(&from.distance(&self.target)) {
+            candidate.state = State::Failed;
+        }
+    }
+
+    /// Returns `true` if closest nodes have all responded or there is nobody left to query.
+    pub fn is_finished(&self) -> bool {
+        self.candidates
+            .values()
+            .filter(|candidate| candidate.state != State::Failed)
+            .take(Self::K)
+            .all(|candidate| candidate.state == State::Responded)
+    }
+
+    /// Peers, discovered so far.
+    pub fn peers(&self) -> &[SocketAddr] {
+        &self.peers
+    }
+
+    /// Item, found so far.
+    pub fn item(&self) -> Option<&Item> {
+        self.item.as_ref()
+    }
+
+    pub fn into_result(self) -> LookupResult {
+        let closest = self
+            .candidates
+            .into_values()
+            .filter(|candidate| candidate.state == State::Responded)
+            .take(Self::K)
+            .map(|candidate| (candidate.contact, candidate.token))
+            .collect();
+
+        LookupResult {
+            peers: self.peers,
+            closest,
+            item: self.item,
+        }
+    }
+
+    fn add_candidates(&mut self, contacts: impl IntoIterator<Item = Contact>) {
+        for contact in contacts {
+            self.candidates
+                .entry(contact.id.distance(&self.target))
+                .or_insert(Candidate {
+                    contact,
+                    state: State::Unqueried,
+                    token: None,
+                });
+        }
+    }
+
+    fn in_flight(&self) -> usize {
+        self.candidates
+            .values()
+            .filter(|candidate| candidate.state == State::InFlight)
+            .count()
+    }
+
+    /// [`Self::K`] closest candidates, which haven't failed.
+    fn closest_mut(&mut self) -> impl Iterator<Item = &mut Candidate> {
+        self.candidates
+            .values_mut()
+            .filter(|candidate| candidate.state != State::Failed)
+            .take(Self::K)
+    }
+}
+
+impl LookupResult {
+    /// Tokens of closest nodes by their ids, to announce peer to them after lookup.
+    pub fn tokens(&self) -> HashMap<NodeId, &BString> {
+        self.closest
+            .iter()
+            .filter_map(|(contact, token)| Some((contact.id, token.as_ref()?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(first: u8) -> Contact {
+        let mut id = [0; 20];
+        id[0] = first;
+
+        Contact::new(NodeId(id), SocketAddr::from(([127, 0, 0, 1], 6000 + first as u16)))
+    }
+
+    #[test]
+    fn lookup_converges_on_closest_nodes() {
+        let mut lookup = Lookup::new(NodeId::default(), (0x80..0x85).map(contact));
+
+        let queries = lookup.next_queries();
+        assert_eq!(queries, [contact(0x80), contact(0x81), contact(0x82)]);
+        assert!(lookup.next_queries().is_empty());
+
+        // First node knows closer ones and the second one knows peer
+        let closer = Responce {
+            nodes: vec![contact(0x01), contact(0x02)],
+            ..Responce::new(queries[0].id)
+        };
+        let peer = SocketAddr::from(([10, 0, 0, 1], 6881));
+        let with_peers = Responce {
+            values: vec![peer],
+            token: Some(BString(b"token".to_vec())),
+            ..Responce::new(queries[1].id)
+        };
+
+        lookup.on_responce(&queries[0].id, &closer);
+        lookup.on_responce(&queries[1].id, &with_peers);
+        lookup.on_failure(&queries[2].id);
+
+        assert_eq!(lookup.next_queries(), [contact(0x01), contact(0x02), contact(0x83)]);
+        assert!(!lookup.is_finished());
+
+        for contact in [contact(0x01), contact(0x02), contact(0x83), contact(0x84)] {
+            lookup.on_responce(&contact.id, &Responce::new(contact.id));
+            lookup.next_queries();
+        }
+
+        assert!(lookup.is_finished());
+
+        let result = lookup.into_result();
+
+        assert_eq!(result.peers, [peer]);
+        assert_eq!(result.closest.len(), 6);
+        assert_eq!(result.closest[0].0, contact(0x01));
+        assert_eq!(result.tokens().get(&contact(0x81).id), Some(&&BString(b"token".to_vec())));
+    }
+}
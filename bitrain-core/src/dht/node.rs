@@ -0,0 +1,370 @@
+use std::{
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use super::{Body, Contact, Item, Lookup, LookupResult, Message, NodeId, Query, RoutingTable, Transactions};
+use crate::bencoded::{BInt, BString};
+use crate::peer::CancelToken;
+
+/// DHT node, bound to UDP socket.
+///
+/// Node owns its [`RoutingTable`] and socket, but doesn't interpret datagrams itself: recieved ones are
+/// handed to consumer, either one at a time with [`recv_until()`](`DhtNode::recv_until`) or by socket loop
+/// of [`run()`](`DhtNode::run`).
+#[derive(Debug)]
+pub struct DhtNode {
+    id: NodeId,
+    socket: UdpSocket,
+    table: RoutingTable,
+    buffer: Vec<u8>,
+}
+
+impl DhtNode {
+    /// Size of recieve buffer. Longer datagrams are truncated.
+    pub const MAX_DATAGRAM_SIZE: usize = 2048;
+    /// Time to wait for responce to single query.
+    pub const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+    pub fn bind(addr: impl ToSocketAddrs, id: NodeId) -> io::Result<Self> {
+        Ok(Self {
+            id,
+            socket: UdpSocket::bind(addr)?,
+            table: RoutingTable::new(id),
+            buffer: vec![0; Self::MAX_DATAGRAM_SIZE],
+        })
+    }
+
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    pub fn routing_table(&self) -> &RoutingTable {
+        &self.table
+    }
+
+    pub fn routing_table_mut(&mut self) -> &mut RoutingTable {
+        &mut self.table
+    }
+
+    pub fn send_to(&self, datagram: &[u8], addr: SocketAddr) -> io::Result<()> {
+        self.socket.send_to(datagram, addr).map(drop)
+    }
+
+    /// Waits for datagram until `deadline`. Returns `None` if deadline passes first.
+    pub fn recv_until(&mut self, deadline: Instant) -> io::Result<Option<(&[u8], SocketAddr)>> {
+        let recieved = utils::recv_until(&self.socket, &mut self.buffer, deadline)?;
+
+        Ok(recieved.map(|(length, addr)| (&self.buffer[..length], addr)))
+    }
+
+    /// Looks up peers of `info_hash`, starting from closest nodes of routing table.
+    /// See [`run_lookup()`](`DhtNode::run_lookup`).
+    pub fn get_peers(&mut self, info_hash: NodeId, deadline: Instant) -> io::Result<LookupResult> {
+        let seeds = self.table.closest(&info_hash, Lookup::K);
+        let mut lookup = Lookup::new(info_hash, seeds);
+
+        self.run_lookup(&mut lookup, Query::GetPeers { info_hash }, deadline)?;
+        Ok(lookup.into_result())
+    }
+
+    /// Looks up item, stored under `target`, starting from closest nodes of routing table. `salt` is the one
+    /// of mutable item, empty for immutable one. See [`run_lookup()`](`DhtNode::run_lookup`).
+    pub fn get(&mut self, target: NodeId, salt: BString, deadline: Instant) -> io::Result<LookupResult> {
+        let seeds = self.table.closest(&target, Lookup::K);
+        let mut lookup = Lookup::new(target, seeds).salt(salt);
+
+        self.run_lookup(&mut lookup, Query::Get { target, seq: None }, deadline)?;
+        Ok(lookup.into_result())
+    }
+
+    /// Stores `item` on nodes, closest to its target, which are found with `get` lookup first.
+    /// `cas` is expected sequence number of currently stored mutable item.
+    ///
+    /// Returns number of nodes, which accepted item by `deadline`.
+    pub fn put(&mut self, item: Item, cas: Option<BInt>, deadline: Instant) -> io::Result<usize> {
+        let target = item.target();
+        let seeds = self.table.closest(&target, Lookup::K);
+        let mut lookup = Lookup::new(target, seeds);
+
+        self.run_lookup(&mut lookup, Query::Get { target, seq: None }, deadline)?;
+
+        let mut transactions = Transactions::new();
+        let now = Instant::now();
+
+        for (contact, token) in lookup.into_result().closest {
+            let Some(token) = token else { continue };
+
+            let transaction_id = transactions.start(contact.addr, now + Self::QUERY_TIMEOUT, contact.id);
+            let query = Query::Put {
+                token,
+                item: item.clone(),
+                cas,
+            };
+
+            let _ = self.send_to(&Message::query(transaction_id, self.id, query).encode(), contact.addr);
+        }
+
+        let mut stored = 0;
+
+        while !transactions.is_empty() {
+            let now = Instant::now();
+
+            for (_, id) in transactions.expire(now) {
+                self.table.on_failure(&id);
+            }
+
+            if now >= deadline {
+                break;
+            }
+
+            let wait = transactions.next_deadline().map_or(deadline, |next| next.min(deadline));
+            let Some((length, addr)) = utils::recv_until(&self.socket, &mut self.buffer, wait)? else { continue };
+
+            let Ok(message) = Message::decode(&self.buffer[..length]) else { continue };
+
+            // Errors (i.e. outdated `cas`) are not failures of node itself
+            if transactions.finish(&message.transaction_id, addr).is_some() && matches!(message.body, Body::Responce(_)) {
+                stored += 1;
+            }
+        }
+
+        Ok(stored)
+    }
+
+    /// Drives `lookup` with `query` (`get_peers` or `get`), until it is finished or `deadline` passes.
+    /// Routing table learns about nodes, which responded or failed to.
+    ///
+    /// Lookup, which wasn't finished by deadline, still holds everything discovered so far.
+    /// Datagrams, other than responces to lookup queries, are dropped meanwhile.
+    pub fn run_lookup(&mut self, lookup: &mut Lookup, query: Query, deadline: Instant) -> io::Result<()> {
+        let mut transactions = Transactions::new();
+
+        loop {
+            let now = Instant::now();
+
+            for (_, id) in transactions.expire(now) {
+                lookup.on_failure(&id);
+                self.table.on_failure(&id);
+            }
+
+            for contact in lookup.next_queries() {
+                let transaction_id = transactions.start(contact.addr, now + Self::QUERY_TIMEOUT, contact.id);
+                let message = Message::query(transaction_id, self.id, query.clone());
+
+                // Failed send is treated as unanswered query, once transaction expires
+                let _ = self.send_to(&message.encode(), contact.addr);
+            }
+
+            if lookup.is_finished() || now >= deadline {
+                return Ok(());
+            }
+
+            let wait = transactions.next_deadline().map_or(deadline, |next| next.min(deadline));
+            let Some((length, addr)) = utils::recv_until(&self.socket, &mut self.buffer, wait)? else { continue };
+
+            let Ok(message) = Message::decode(&self.buffer[..length]) else { continue };
+            let Some(queried) = transactions.finish(&message.transaction_id, addr) else { continue };
+
+            match message.body {
+                Body::Responce(responce) => {
+                    self.table.insert(Contact::new(responce.id, addr), Instant::now());
+                    lookup.on_responce(&queried, &responce);
+                }
+                _ => lookup.on_failure(&queried),
+            }
+        }
+    }
+
+    /// Socket loop: passes every recieved datagram to `handler` together with routing table, sending back
+    /// its reply, if any. Runs until `token` is cancelled, which is reported as [`io::ErrorKind::Interrupted`].
+    pub fn run<H>(&mut self, token: &CancelToken, mut handler: H) -> io::Result<()>
+    where
+        H: FnMut(&mut RoutingTable, &[u8], SocketAddr) -> Option<Vec<u8>>,
+    {
+        loop {
+            token.check()?;
+
+            let deadline = Instant::now() + CancelToken::POLL_INTERVAL;
+            let Some((length, addr)) = utils::recv_until(&self.socket, &mut self.buffer, deadline)? else { continue };
+
+            if let Some(reply) = handler(&mut self.table, &self.buffer[..length], addr) {
+                // Unreachable remote node must not stop the loop
+                let _ = self.socket.send_to(&reply, addr);
+            }
+        }
+    }
+}
+
+mod utils {
+    use super::*;
+
+    /// Recieves datagram into `buffer`, returning its length and sender, or `None` if `deadline` passes first.
+    pub fn recv_until(
+        socket: &UdpSocket,
+        buffer: &mut [u8],
+        deadline: Instant,
+    ) -> io::Result<Option<(usize, SocketAddr)>> {
+        loop {
+            let timeout = match deadline.checked_duration_since(Instant::now()) {
+                Some(timeout) if !timeout.is_zero() => timeout,
+                _ => return Ok(None),
+            };
+
+            socket.set_read_timeout(Some(timeout))?;
+
+            match socket.recv_from(buffer) {
+                Ok(recieved) => return Ok(Some(recieved)),
+                // Platforms differ in which kind is reported for expired socket timeout
+                Err(err) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                    return Ok(None)
+                }
+                // ICMP "port unreachable" of earlier send is reported by some platforms on next recieve
+                Err(err) if err.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use ed25519_dalek::SigningKey;
+
+    use super::*;
+    use crate::bencoded::encoding::Entry;
+    use crate::dht::{ItemStore, MutableItem, Responce};
+
+    #[test]
+    fn loop_replies_until_cancelled() {
+        let mut node = DhtNode::bind("127.0.0.1:0", NodeId::random()).unwrap();
+        let addr = node.local_addr().unwrap();
+        let token = CancelToken::new();
+
+        let loop_token = token.clone();
+        let handle = thread::spawn(move || {
+            node.run(&loop_token, |_, datagram, _| Some(datagram.to_ascii_uppercase()))
+                .unwrap_err()
+        });
+
+        let mut client = DhtNode::bind("127.0.0.1:0", NodeId::random()).unwrap();
+        client.send_to(b"ping", addr).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let (reply, from) = client.recv_until(deadline).unwrap().unwrap();
+
+        assert_eq!((reply, from), (&b"PING"[..], addr));
+
+        token.cancel();
+        assert_eq!(handle.join().unwrap().kind(), io::ErrorKind::Interrupted);
+    }
+
+    #[test]
+    fn peers_are_looked_up() {
+        let info_hash = NodeId::random();
+        let peer = SocketAddr::from(([10, 0, 0, 1], 6881));
+
+        let mut remote = DhtNode::bind("127.0.0.1:0", NodeId::random()).unwrap();
+        let remote_contact = Contact::new(remote.id(), remote.local_addr().unwrap());
+        let token = CancelToken::new();
+
+        let loop_token = token.clone();
+        let handle = thread::spawn(move || {
+            let id = remote.id();
+
+            remote.run(&loop_token, |_, datagram, _| {
+                let query = Message::decode(datagram).ok()?;
+                let responce = Responce {
+                    values: vec![peer],
+                    ..Responce::new(id)
+                };
+
+                Some(Message::responce(query.transaction_id, responce).encode())
+            })
+        });
+
+        let mut node = DhtNode::bind("127.0.0.1:0", NodeId::random()).unwrap();
+        node.routing_table_mut().insert(remote_contact, Instant::now());
+
+        let result = node.get_peers(info_hash, Instant::now() + Duration::from_secs(5)).unwrap();
+        token.cancel();
+        handle.join().unwrap().unwrap_err();
+
+        assert_eq!(result.peers, [peer]);
+        assert_eq!(result.closest, [(remote_contact, None)]);
+    }
+
+    #[test]
+    fn items_are_put_and_got() {
+        let mut remote = DhtNode::bind("127.0.0.1:0", NodeId::random()).unwrap();
+        let remote_contact = Contact::new(remote.id(), remote.local_addr().unwrap());
+        let token = CancelToken::new();
+
+        let loop_token = token.clone();
+        let handle = thread::spawn(move || {
+            let id = remote.id();
+            let mut store = ItemStore::new();
+
+            remote.run(&loop_token, |_, datagram, _| {
+                let Message {
+                    transaction_id,
+                    body: Body::Query { query, .. },
+                    ..
+                } = Message::decode(datagram).ok()?
+                else {
+                    return None;
+                };
+
+                let mut responce = Responce {
+                    token: Some(BString(b"token".to_vec())),
+                    ..Responce::new(id)
+                };
+
+                match query {
+                    Query::Get { target, .. } => match store.get(&target) {
+                        Some(Item::Mutable(item)) => {
+                            responce.value = Some(item.value.clone());
+                            responce.key = Some(item.key);
+                            responce.seq = Some(item.seq);
+                            responce.signature = Some(item.signature);
+                        }
+                        Some(Item::Immutable(value)) => responce.value = Some(value.clone()),
+                        None => {}
+                    },
+                    Query::Put { item, cas, .. } => {
+                        if let Err(err) = store.put(item, cas) {
+                            return Some(Message::error(transaction_id, err.into()).encode());
+                        }
+                    }
+                    _ => return None,
+                }
+
+                Some(Message::responce(transaction_id, responce).encode())
+            })
+        });
+
+        let mut node = DhtNode::bind("127.0.0.1:0", NodeId::random()).unwrap();
+        node.routing_table_mut().insert(remote_contact, Instant::now());
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let key = SigningKey::generate(&mut rand::rngs::OsRng);
+        let salt = BString(b"salt".to_vec());
+        let item = Item::Mutable(MutableItem::sign(&key, salt.clone(), 1, Entry::Integer(42)));
+
+        assert_eq!(node.put(item.clone(), None, deadline).unwrap(), 1);
+        assert_eq!(node.put(item.clone(), Some(0), deadline).unwrap(), 0);
+
+        let result = node.get(item.target(), salt, deadline).unwrap();
+        token.cancel();
+        handle.join().unwrap().unwrap_err();
+
+        assert_eq!(result.item, Some(item));
+    }
+}
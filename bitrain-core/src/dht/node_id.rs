@@ -0,0 +1,81 @@
+use std::fmt;
+
+/// 160-bit identifier of DHT node. Info-hashes share the same space, so they are looked up as node ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct NodeId(pub [u8; 20]);
+
+impl NodeId {
+    pub const LENGTH: usize = 20;
+    /// Number of bits in id.
+    pub const BITS: usize = Self::LENGTH * 8;
+
+    /// Generates random id for new node.
+    pub fn random() -> Self {
+        Self(rand::random())
+    }
+
+    /// XOR distance between `self` and `other`. Distances are compared as big-endian numbers,
+    /// which is exactly how derived [`Ord`] compares ids.
+    pub fn distance(&self, other: &NodeId) -> NodeId {
+        let mut distance = [0; Self::LENGTH];
+
+        for (i, byte) in distance.iter_mut().enumerate() {
+            *byte = self.0[i] ^ other.0[i];
+        }
+
+        Self(distance)
+    }
+
+    /// Number of leading bits, shared with `other` ([`Self::BITS`] for equal ids).
+    pub fn common_prefix(&self, other: &NodeId) -> usize {
+        let distance = self.distance(other);
+
+        match distance.0.iter().position(|byte| *byte != 0) {
+            Some(i) => i * 8 + distance.0[i].leading_zeros() as usize,
+            None => Self::BITS,
+        }
+    }
+}
+
+impl From<[u8; 20]> for NodeId {
+    fn from(id: [u8; 20]) -> Self {
+        Self(id)
+    }
+}
+
+impl AsRef<[u8]> for NodeId {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn id(first: u8, last: u8) -> NodeId {
+        let mut id = [0; 20];
+        id[0] = first;
+        id[19] = last;
+
+        NodeId(id)
+    }
+
+    #[rstest]
+    #[case(id(0, 0), id(0, 0), NodeId::BITS)]
+    #[case(id(0, 0), id(0x80, 0), 0)]
+    #[case(id(0x0f, 0), id(0x0e, 0), 7)]
+    #[case(id(0, 2), id(0, 3), 159)]
+    fn common_prefix_is_counted(#[case] a: NodeId, #[case] b: NodeId, #[case] expected: usize) {
+        assert_eq!(a.common_prefix(&b), expected);
+        assert_eq!(a.distance(&b), b.distance(&a));
+    }
+}
@@ -0,0 +1,208 @@
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use super::NodeId;
+
+/// Address of DHT node together with its id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Contact {
+    pub id: NodeId,
+    pub addr: SocketAddr,
+}
+
+impl Contact {
+    pub fn new(id: NodeId, addr: SocketAddr) -> Self {
+        Self { id, addr }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    contact: Contact,
+    last_seen: Instant,
+    /// Number of consecutive queries, node didn't respond to.
+    failures: u32,
+}
+
+/// Kademlia routing table of known DHT nodes.
+///
+/// Nodes are sorted into k-buckets by length of id prefix, shared with own id, so table knows many
+/// nodes close to itself and few distant ones. Full bucket accepts new node only in place of bad one.
+///
+/// Table does no I/O itself: owner reports nodes, that were seen or failed to respond, and pings
+/// [`questionable`](`RoutingTable::questionable`) ones to keep table fresh.
+#[derive(Debug, Clone)]
+pub struct RoutingTable {
+    own_id: NodeId,
+    buckets: Vec<Vec<Entry>>,
+}
+
+impl RoutingTable {
+    /// Capacity of each bucket.
+    pub const K: usize = 8;
+    /// Node becomes questionable, if it wasn't seen for this long.
+    pub const QUESTIONABLE_AFTER: Duration = Duration::from_secs(15 * 60);
+    /// Node becomes bad and can be replaced, after failing to respond this many times in a row.
+    pub const MAX_FAILURES: u32 = 3;
+
+    pub fn new(own_id: NodeId) -> Self {
+        Self {
+            own_id,
+            buckets: vec![Vec::new(); NodeId::BITS],
+        }
+    }
+
+    pub fn own_id(&self) -> NodeId {
+        self.own_id
+    }
+
+    /// Number of nodes in table.
+    pub fn len(&self) -> usize {
+        self.buckets.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, id: &NodeId) -> bool {
+        self.find(id).is_some()
+    }
+
+    /// Reports node, which responded or sent query at `now`.
+    ///
+    /// Known node is refreshed, unknown one is added if its bucket has room or bad node to replace.
+    /// Returns `false` if node is not in table afterwards (i.e. bucket is full or node has own id).
+    pub fn insert(&mut self, contact: Contact, now: Instant) -> bool {
+        let Some(bucket) = self.bucket_mut(&contact.id) else { return false };
+
+        if let Some(entry) = bucket.iter_mut().find(|entry| entry.contact.id == contact.id) {
+            entry.contact.addr = contact.addr;
+            entry.last_seen = now;
+            entry.failures = 0;
+
+            return true;
+        }
+
+        let entry = Entry {
+            contact,
+            last_seen: now,
+            failures: 0,
+        };
+
+        if bucket.len() < Self::K {
+            bucket.push(entry);
+            return true;
+        }
+
+        match bucket.iter_mut().max_by_key(|entry| entry.failures) {
+            Some(bad) if bad.failures >= Self::MAX_FAILURES => {
+                *bad = entry;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Reports node, which didn't respond to query.
+    pub fn on_failure(&mut self, id: &NodeId) {
+        let entry = self
+            .bucket_mut(id)
+            .and_then(|bucket| bucket.iter_mut().find(|entry| entry.contact.id == *id));
+
+        if let Some(entry) = entry {
+            entry.failures = entry.failures.saturating_add(1);
+        }
+    }
+
+    pub fn remove(&mut self, id: &NodeId) -> Option<Contact> {
+        let bucket = self.bucket_mut(id)?;
+        let i = bucket.iter().position(|entry| entry.contact.id == *id)?;
+
+        Some(bucket.remove(i).contact)
+    }
+
+    /// Up to `count` known nodes, closest to `target`, sorted by distance. Bad nodes are skipped.
+    pub fn closest(&self, target: &NodeId, count: usize) -> Vec<Contact> {
+        let mut contacts = self
+            .entries()
+            .filter(|entry| entry.failures < Self::MAX_FAILURES)
+            .map(|entry| entry.contact)
+            .collect::<Vec<_>>();
+
+        contacts.sort_by_key(|contact| contact.id.distance(target));
+        contacts.truncate(count);
+
+        contacts
+    }
+
+    /// Nodes, which weren't seen for [`Self::QUESTIONABLE_AFTER`] by `now` or failed to respond, and should be pinged.
+    pub fn questionable(&self, now: Instant) -> Vec<Contact> {
+        self.entries()
+            .filter(|entry| entry.failures > 0 || now.saturating_duration_since(entry.last_seen) >= Self::QUESTIONABLE_AFTER)
+            .map(|entry| entry.contact)
+            .collect()
+    }
+
+    /// Iterates over all nodes in table.
+    pub fn iter(&self) -> impl Iterator<Item = &Contact> {
+        self.entries().map(|entry| &entry.contact)
+    }
+
+    fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.buckets.iter().flatten()
+    }
+
+    fn find(&self, id: &NodeId) -> Option<&Entry> {
+        let bucket = self.buckets.get(self.own_id.common_prefix(id))?;
+        bucket.iter().find(|entry| entry.contact.id == *id)
+    }
+
+    /// Bucket of `id`, `None` for own id.
+    fn bucket_mut(&mut self, id: &NodeId) -> Option<&mut Vec<Entry>> {
+        self.buckets.get_mut(self.own_id.common_prefix(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(first: u8, last: u8) -> Contact {
+        let mut id = [0; 20];
+        id[0] = first;
+        id[19] = last;
+
+        Contact::new(NodeId(id), SocketAddr::from(([127, 0, 0, 1], 6881 + last as u16)))
+    }
+
+    #[test]
+    fn full_bucket_replaces_only_bad_nodes() {
+        let now = Instant::now();
+        let mut table = RoutingTable::new(NodeId::default());
+
+        // All nodes share no prefix with own id, so they go into the same bucket
+        for i in 0..RoutingTable::K as u8 {
+            assert!(table.insert(contact(0x80, i), now));
+        }
+
+        assert!(!table.insert(contact(0x80, 100), now));
+        assert!(!table.insert(Contact::new(NodeId::default(), contact(0, 0).addr), now));
+
+        for _ in 0..RoutingTable::MAX_FAILURES {
+            table.on_failure(&contact(0x80, 3).id);
+        }
+
+        assert!(table.insert(contact(0x80, 100), now));
+        assert!(!table.contains(&contact(0x80, 3).id));
+        assert_eq!(table.len(), RoutingTable::K);
+
+        table.insert(contact(0x01, 0), now);
+        let closest = table.closest(&NodeId::default(), 2);
+
+        assert_eq!(closest, [contact(0x01, 0), contact(0x80, 0)]);
+        assert!(table.questionable(now + RoutingTable::QUESTIONABLE_AFTER).contains(&contact(0x01, 0)));
+    }
+}
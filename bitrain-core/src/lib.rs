@@ -1,6 +1,24 @@
 pub mod bencoded;
+pub mod choker;
+pub mod dht;
 pub mod messages;
+pub mod metrics;
 pub mod peer;
+pub mod picker;
+pub mod pool;
+pub mod portmap;
+// Trackers are announced to over HTTP
+#[cfg(feature = "use-serde")]
+pub mod session;
+pub mod storage;
+#[cfg(test)]
+mod test_utils;
+pub mod tracker;
+#[cfg(feature = "test-vectors")]
+pub mod vectors;
+// HTTP client is shared with trackers
+#[cfg(feature = "use-serde")]
+pub mod webseed;
 
 pub mod prelude {
     pub use crate::bencoded::{BInt, BString, FileInfo, Files, Info, Metainfo};
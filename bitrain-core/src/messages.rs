@@ -1,7 +1,17 @@
 //! Type defenitions of various P2P messages.
 //!  
 //! For more info see <https://www.bittorrent.org/beps/bep_0003.html#peer-messages>.
-use std::{mem::size_of, ops::Deref};
+mod error;
+mod limits;
+
+use std::{array::TryFromSliceError, borrow::Cow, cell::RefCell, mem::size_of, ops::Deref};
+
+use bytes::Bytes;
+
+use crate::pool::BufferPool;
+
+pub use error::Error;
+pub use limits::Limits;
 
 /// BitTorrent integer
 pub type BTInt = u32;
@@ -23,6 +33,7 @@ pub trait Standalone {
 ///
 /// To send or recieve `keep-alive` message specifically, use [`Container::<()>`].   
 #[derive(Debug, Clone, PartialEq, Recv, Send)]
+#[cfg_attr(feature = "fuzzing", derive(arbitrary::Arbitrary))]
 #[message(mod_path = "crate::messages")]
 pub enum Message {
     #[standalone(id = 0)]
@@ -62,6 +73,7 @@ message_conversions! {
 pub type Keepalive = ();
 
 #[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "fuzzing", derive(arbitrary::Arbitrary))]
 pub struct Handshake {
     pub reserved: Reserved,
     pub info_hash: Box<[u8; 20]>,
@@ -82,16 +94,68 @@ impl Handshake {
     pub fn peer_id(&self) -> &[u8; 20] {
         &self.peer_id
     }
+
+    /// Starts building handshake without reserved bits.
+    pub fn builder(info_hash: [u8; 20], peer_id: [u8; 20]) -> HandshakeBuilder {
+        HandshakeBuilder {
+            handshake: Self {
+                reserved: Reserved::default(),
+                info_hash: Box::new(info_hash),
+                peer_id: Box::new(peer_id),
+            },
+        }
+    }
+
+    /// Same as [`builder()`](`Handshake::builder`), but fails if `info_hash` or `peer_id` is not 20 bytes long.
+    pub fn try_builder(info_hash: &[u8], peer_id: &[u8]) -> std::result::Result<HandshakeBuilder, TryFromSliceError> {
+        Ok(Self::builder(info_hash.try_into()?, peer_id.try_into()?))
+    }
+}
+
+pub struct HandshakeBuilder {
+    handshake: Handshake,
+}
+
+impl HandshakeBuilder {
+    pub fn reserved(mut self, reserved: Reserved) -> Self {
+        self.handshake.reserved = reserved;
+        self
+    }
+
+    /// Sets bit of `capability`.
+    pub fn capability(mut self, capability: Capability, enabled: bool) -> Self {
+        self.handshake.reserved.set(capability, enabled);
+        self
+    }
+
+    pub fn extensions(self, enabled: bool) -> Self {
+        self.capability(Capability::Extensions, enabled)
+    }
+
+    pub fn dht(self, enabled: bool) -> Self {
+        self.capability(Capability::Dht, enabled)
+    }
+
+    pub fn fast(self, enabled: bool) -> Self {
+        self.capability(Capability::Fast, enabled)
+    }
+
+    pub fn build(self) -> Handshake {
+        self.handshake
+    }
 }
 
 #[repr(transparent)]
 #[derive(Debug, Clone, Default, PartialEq, Encode, Decode)]
-#[message(mod_path = "crate::messages")]
+#[cfg_attr(feature = "fuzzing", derive(arbitrary::Arbitrary))]
+#[message(mod_path = "crate::messages", fixed_size)]
 pub struct Reserved([u8; 8]);
 
 impl Reserved {
     pub const BYTES_COUNT: usize = 8;
     pub const EXTENSION: (usize, u8) = (5, 0x10);
+    pub const DHT: (usize, u8) = (7, 0x01);
+    pub const FAST: (usize, u8) = (7, 0x04);
 
     pub fn inner(&self) -> &[u8] {
         &self.0
@@ -99,7 +163,122 @@ impl Reserved {
 
     ///See <http://www.bittorrent.org/beps/bep_0010.html>
     pub fn supports_extensions(&self) -> bool {
-        self.0[Self::EXTENSION.0] & Self::EXTENSION.1 == Self::EXTENSION.1
+        self.supports(Capability::Extensions)
+    }
+
+    ///See <http://www.bittorrent.org/beps/bep_0005.html>
+    pub fn supports_dht(&self) -> bool {
+        self.supports(Capability::Dht)
+    }
+
+    ///See <http://www.bittorrent.org/beps/bep_0006.html>
+    pub fn supports_fast(&self) -> bool {
+        self.supports(Capability::Fast)
+    }
+
+    pub fn supports(&self, capability: Capability) -> bool {
+        let (byte, mask) = capability.bit();
+        self.0[byte] & mask == mask
+    }
+
+    pub fn set(&mut self, capability: Capability, enabled: bool) {
+        let (byte, mask) = capability.bit();
+
+        if enabled {
+            self.0[byte] |= mask;
+        } else {
+            self.0[byte] &= !mask;
+        }
+    }
+
+    pub fn with(mut self, capability: Capability, enabled: bool) -> Self {
+        self.set(capability, enabled);
+        self
+    }
+
+    pub fn with_extensions(self, enabled: bool) -> Self {
+        self.with(Capability::Extensions, enabled)
+    }
+
+    pub fn with_dht(self, enabled: bool) -> Self {
+        self.with(Capability::Dht, enabled)
+    }
+
+    pub fn with_fast(self, enabled: bool) -> Self {
+        self.with(Capability::Fast, enabled)
+    }
+
+    /// Every capability, advertised with set bits, including ones of unknown meaning.
+    pub fn capabilities(&self) -> Vec<Capability> {
+        (0..Self::BYTES_COUNT)
+            .flat_map(|byte| (0..8).rev().map(move |bit| (byte, 1 << bit)))
+            .filter(|(byte, mask)| self.0[*byte] & mask != 0)
+            .map(|(byte, mask)| Capability::from_bit(byte, mask))
+            .collect()
+    }
+}
+
+impl From<[u8; 8]> for Reserved {
+    fn from(bytes: [u8; 8]) -> Self {
+        Self(bytes)
+    }
+}
+
+/// Extension of protocol, which is advertised with reserved bit of [`Handshake`].
+///
+/// See <https://www.bittorrent.org/beps/bep_0004.html> for allocated bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    AzureusMessaging,
+    LocationAware,
+    /// Extension protocol (BEP 10).
+    Extensions,
+    /// Peer runs DHT node (BEP 5) and may send its port.
+    Dht,
+    /// Peer exchange of XBT.
+    XbtPex,
+    /// Fast extension (BEP 6).
+    Fast,
+    /// NAT traversal of libtorrent.
+    NatTraversal,
+    /// Peer supports BitTorrent v2 (BEP 52).
+    V2Upgrade,
+    /// Bit of unknown meaning.
+    Unknown { byte: usize, mask: u8 },
+}
+
+impl Capability {
+    pub const KNOWN: [Self; 8] = [
+        Self::AzureusMessaging,
+        Self::LocationAware,
+        Self::Extensions,
+        Self::Dht,
+        Self::XbtPex,
+        Self::Fast,
+        Self::NatTraversal,
+        Self::V2Upgrade,
+    ];
+
+    /// Index of byte and mask of bit in [`Reserved`].
+    pub fn bit(&self) -> (usize, u8) {
+        match self {
+            Self::AzureusMessaging => (0, 0x80),
+            Self::LocationAware => (2, 0x08),
+            Self::Extensions => Reserved::EXTENSION,
+            Self::Dht => Reserved::DHT,
+            Self::XbtPex => (7, 0x02),
+            Self::Fast => Reserved::FAST,
+            Self::NatTraversal => (7, 0x08),
+            Self::V2Upgrade => (7, 0x10),
+            Self::Unknown { byte, mask } => (*byte, *mask),
+        }
+    }
+
+    fn from_bit(byte: usize, mask: u8) -> Self {
+        Self::KNOWN
+            .into_iter()
+            .find(|capability| capability.bit() == (byte, mask))
+            .unwrap_or(Self::Unknown { byte, mask })
     }
 }
 
@@ -111,21 +290,28 @@ crate::flag_message! {
 }
 
 #[derive(Debug, Clone, Default, Copy, PartialEq, Encode, Decode, Standalone)]
-#[message(mod_path = "crate::messages")]
+#[cfg_attr(feature = "fuzzing", derive(arbitrary::Arbitrary))]
+#[message(mod_path = "crate::messages", fixed_size)]
 #[standalone(id = 4)]
 pub struct Have {
     pub piece_index: BTInt,
 }
 
 #[derive(Debug, Clone, Default, PartialEq, Encode, Decode, Standalone)]
+#[cfg_attr(feature = "fuzzing", derive(arbitrary::Arbitrary))]
 #[message(mod_path = "crate::messages")]
 #[standalone(id = 5)]
 pub struct Bitfield {
-    pub bits: Vec<u8>,
+    #[cfg_attr(feature = "fuzzing", arbitrary(with = fuzzing::bytes))]
+    pub bits: Bytes,
 }
 
 #[derive(Debug, Clone, Default, Copy, PartialEq, Encode, Decode, Standalone)]
-#[message(mod_path = "crate::messages")]
+#[message(
+    mod_path = "crate::messages",
+    fixed_size,
+    check = "self.data_length <= Self::MAX_DATA_LENGTH"
+)]
 #[standalone(id = 6)]
 pub struct Request {
     pub piece_index: BTInt,
@@ -133,7 +319,13 @@ pub struct Request {
     pub data_length: BTInt,
 }
 
+impl Request {
+    /// Largest block, which can be requested. Peers commonly drop connections, requesting more.
+    pub const MAX_DATA_LENGTH: BTInt = 1 << 17;
+}
+
 #[derive(Debug, Clone, Default, PartialEq, Encode, Decode, Standalone)]
+#[cfg_attr(feature = "fuzzing", derive(arbitrary::Arbitrary))]
 #[message(mod_path = "crate::messages")]
 #[standalone(id = 7)]
 pub struct Piece {
@@ -141,20 +333,114 @@ pub struct Piece {
     pub piece_index: BTInt,
     /// Corresponds to `begin` section of P2P piece message.
     pub offset: BTInt,
-    /// Corresponds to `block` section of P2P piece message.
-    pub data: Vec<u8>,
+    /// Corresponds to `block` section of P2P piece message. Cloning block shares it instead of copying.
+    #[cfg_attr(feature = "fuzzing", arbitrary(with = fuzzing::bytes))]
+    pub data: Bytes,
 }
 
+/// Same as [`Piece`], but borrows block from buffer, which holds the whole message, instead of copying it.
 #[derive(Debug, Clone, Default, Copy, PartialEq, Encode, Decode, Standalone)]
 #[message(mod_path = "crate::messages")]
+#[standalone(id = 7)]
+pub struct PieceRef<'a> {
+    pub piece_index: BTInt,
+    pub offset: BTInt,
+    pub data: &'a [u8],
+}
+
+impl From<PieceRef<'_>> for Piece {
+    fn from(piece: PieceRef<'_>) -> Self {
+        Self {
+            piece_index: piece.piece_index,
+            offset: piece.offset,
+            data: Bytes::copy_from_slice(piece.data),
+        }
+    }
+}
+
+/// Same as [`Piece`], but block is read from `reader` (i.e. file segment) while message is sent,
+/// instead of being loaded into memory first.
+///
+/// Reader is consumed by sending, so message can be sent only once. If reader ends before `length` bytes
+/// are read, sending fails with [`io::ErrorKind::UnexpectedEof`], leaving partial message in stream,
+/// so connection should be closed.
+#[derive(Debug)]
+pub struct PieceReader<R> {
+    pub piece_index: BTInt,
+    pub offset: BTInt,
+    /// Length of block.
+    pub length: BTInt,
+    reader: RefCell<R>,
+}
+
+impl<R: Read> PieceReader<R> {
+    pub fn new(piece_index: BTInt, offset: BTInt, length: BTInt, reader: R) -> Self {
+        Self {
+            piece_index,
+            offset,
+            length,
+            reader: RefCell::new(reader),
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+}
+
+impl<R: Read> Send for PieceReader<R> {
+    fn send_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let header_len = (size_of::<u8>() + 2 * size_of::<BTInt>()) as BTInt;
+
+        (header_len + self.length).encode_to(writer)?;
+        <Piece as Standalone>::ID.encode_to(writer)?;
+        self.piece_index.encode_to(writer)?;
+        self.offset.encode_to(writer)?;
+
+        let mut reader = self.reader.borrow_mut();
+        let copied = io::copy(&mut reader.by_ref().take(self.length as u64), writer)?;
+
+        if copied < self.length as u64 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, Copy, PartialEq, Encode, Decode, Standalone)]
+#[message(
+    mod_path = "crate::messages",
+    fixed_size,
+    check = "self.data_length <= Request::MAX_DATA_LENGTH"
+)]
 #[standalone(id = 8)]
 pub struct Cancel {
     pub piece_index: BTInt,
     pub offset: BTInt,
     pub data_length: BTInt,
 }
+
+/// Message of extension protocol (BEP 10), advertised with [`Capability::Extensions`].
+///
+/// Messages with `id` 0 are extended handshakes, other ids are assigned to extensions by handshake of
+/// recieving side.
+#[derive(Debug, Clone, Default, PartialEq, Encode, Decode, Standalone)]
+#[cfg_attr(feature = "fuzzing", derive(arbitrary::Arbitrary))]
+#[message(mod_path = "crate::messages")]
+#[standalone(id = 20)]
+pub struct Extended {
+    pub id: u8,
+    /// Bencoded dictionary, optionally followed by raw data.
+    #[cfg_attr(feature = "fuzzing", arbitrary(with = fuzzing::bytes))]
+    pub payload: Bytes,
+}
+
+impl Extended {
+    pub const HANDSHAKE_ID: u8 = 0;
+}
 use bitrain_derive::{Decode, Encode, Standalone, Recv, Send};
-use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{LittleEndian, NetworkEndian, ReadBytesExt, WriteBytesExt};
 use std::io::{self, Read, Write};
 
 /// A trait representing a data type, which can be sent in format, specified by
@@ -215,7 +501,7 @@ pub trait Decode: Sized {
     ///
     /// Amount of bytes available for parsing.
     ///
-    /// On successfull return or parsing failure (`recv_from` returns `Ok(None)`) implementors should update this
+    /// On successfull return or parsing failure (`decode_from` returns `Ok(None)`) implementors should update this
     /// argument with `Some(len_hint - bytes_consumed)`.
     ///
     /// If message parsing fails, consumer should not make any assumptions about contents of reader besides fact, that
@@ -225,14 +511,14 @@ pub trait Decode: Sized {
     /// (see [Connection::recv](`crate::peer::Connection::recv()`) for example).  
     ///
     /// In case [`io::Error`] occurs, consumer shouldn't make any asumptions about `len_hint` contents.
-    fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> Result<Self>;
+    fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> DecodeResult<Self>;
 
-    fn decode(mut bytes: &[u8]) -> Result<Self> {
+    fn decode(mut bytes: &[u8]) -> DecodeResult<Self> {
         let mut len = bytes.len();
         Self::decode_from(&mut len, bytes.by_ref())
     }
 
-    fn decode_or_discard_from(len_hint: &mut usize, reader: &mut impl Read) -> Result<Self> {
+    fn decode_or_discard_from(len_hint: &mut usize, reader: &mut impl Read) -> DecodeResult<Self> {
         let result = Self::decode_from(len_hint, reader)?;
 
         if result.is_none() {
@@ -243,7 +529,20 @@ pub trait Decode: Sized {
     }
 }
 
-pub type Result<T> = io::Result<Option<T>>;
+/// Result of decoding, where `Ok(None)` means, that data doesn't match format of type.
+pub type DecodeResult<T> = io::Result<Option<T>>;
+
+/// Result of recieving standalone message.
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Type, which is always encoded into the same amount of bytes.
+///
+/// Allows to size buffers and check frame sizes at compile time, i.e. `const _: () = assert!(Request::SIZE == 12);`.
+/// Can be derived with `#[message(fixed_size)]` along with [`Encode`], if all transmitted fields are `FixedSize`.
+pub trait FixedSize: Encode {
+    /// Amount of bytes `Self` is encoded into. Always equals to [`Encode::size`].
+    const SIZE: usize;
+}
 
 /// Marker trait, that represents standalone P2P message, which can be sent to peer.
 ///
@@ -256,15 +555,24 @@ pub trait Send {
 ///
 /// As any P2P message starts with length, (besides [`Handshake`], which is already implemented),
 /// implementor should always decode length of message in stream from the first four bytes (u32 NetworkEndian).
+///
+/// Keep-alive, which has no payload, is recieved as `Ok(None)`.
 pub trait Recv: Sized {
-    fn recv_from(reader: &mut impl Read) -> Result<Self>;
+    /// Same as [`recv_limited_from()`](`Recv::recv_limited_from`) with [default](`Limits::default`) limits.
+    fn recv_from(reader: &mut impl Read) -> Result<Option<Self>> {
+        Self::recv_limited_from(reader, &Limits::default())
+    }
+
+    /// Recieves message, failing with [`Error::Violation`] before payload is read, if its length exceeds `limits`.
+    fn recv_limited_from(reader: &mut impl Read, limits: &Limits) -> Result<Option<Self>>;
 }
 
 #[macro_export]
 macro_rules! flag_message {
     {$($kind:ident = $id:expr),*} => {$(
         #[derive(Debug, Clone, Copy, Default, PartialEq, Encode, Decode, Standalone)]
-        #[message(mod_path = "crate::messages")]
+        #[cfg_attr(feature = "fuzzing", derive(arbitrary::Arbitrary))]
+        #[message(mod_path = "crate::messages", fixed_size)]
         #[standalone(id = $id)]
         pub struct $kind;
     )*};
@@ -272,6 +580,7 @@ macro_rules! flag_message {
 
 /// Wraps data, which can be exchanged accroding to P2P protocol as [standalone](`Standalone`) message. See [`Recv`] and [`Send`].
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "fuzzing", derive(arbitrary::Arbitrary))]
 #[repr(transparent)]
 pub struct Container<M>(pub M);
 
@@ -292,19 +601,16 @@ impl<M> Container<M> {
 }
 
 impl<R: Decode + Standalone> Recv for Container<R> {
-    fn recv_from(reader: &mut impl Read) -> Result<Self> {
-        let mut len = reader.read_u32::<NetworkEndian>()? as usize;
-        if len == 0 {
-            return Ok(None);
-        }
+    fn recv_limited_from(reader: &mut impl Read, limits: &Limits) -> Result<Option<Self>> {
+        let Some((id, mut len)) = utils::recv_header(reader, limits)? else { return Ok(None) };
 
-        if reader.read_u8()? != <R as Standalone>::ID {
-            return Ok(None);
-        } else {
-            len -= 1;
-
-            <R as Decode>::decode_or_discard_from(&mut len, reader).map(|opt| opt.map(Self))
+        if id != <R as Standalone>::ID {
+            utils::discard_bytes(reader, len)?;
+            return Err(Error::UnknownMessage { id });
         }
+
+        let data = <R as Decode>::decode_or_discard_from(&mut len, reader)?;
+        utils::decoded(data, len, std::any::type_name::<R>()).map(|data| Some(Self(data)))
     }
 }
 
@@ -323,29 +629,23 @@ impl<S: Encode + Standalone> Send for Container<&'_ S> {
 }
 
 impl Recv for Handshake {
-    fn recv_from(reader: &mut impl Read) -> Result<Self> {
-        let mut protocol_name_len =
-            utils::unwrap_or_return!(u8::decode_or_discard_from(&mut 1, reader.by_ref())?) as usize;
-        let protocol = utils::unwrap_or_return!(Vec::decode_or_discard_from(
-            &mut protocol_name_len,
-            reader
-        )?);
+    /// Handshake is bound in size by its format, so `limits` aren't checked. Handshake has no keep-alive
+    /// counterpart, so it's always `Some`.
+    fn recv_limited_from(reader: &mut impl Read, _: &Limits) -> Result<Option<Self>> {
+        let mut protocol = vec![0; reader.read_u8()? as usize];
+        reader.read_exact(&mut protocol)?;
 
         if protocol != Self::BITTORRENT_PROTOCOL {
             // Unknown protocol implies that handshake payload len is unknown
-            return Ok(None);
+            return Err(Error::Violation(format!("unknown protocol {:?}", String::from_utf8_lossy(&protocol))));
         }
 
-        let mut len_hint = 48;
-
-        let reserved = utils::unwrap_or_return!(<[u8; 8]>::decode_or_discard_from(
-            &mut len_hint,
-            reader
-        )?);
-        let info_hash =
-            utils::unwrap_or_return!(Box::decode_or_discard_from(&mut len_hint, reader.by_ref())?);
-        let peer_id =
-            utils::unwrap_or_return!(Box::decode_or_discard_from(&mut len_hint, reader.by_ref())?);
+        let mut reserved = [0; 8];
+        let mut info_hash = Box::new([0; 20]);
+        let mut peer_id = Box::new([0; 20]);
+        reader.read_exact(&mut reserved)?;
+        reader.read_exact(info_hash.as_mut())?;
+        reader.read_exact(peer_id.as_mut())?;
 
         Ok(Some(Self {
             reserved: Reserved(reserved),
@@ -355,6 +655,12 @@ impl Recv for Handshake {
     }
 }
 
+impl Send for Keepalive {
+    fn send_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        (0 as BTInt).encode_to(writer)
+    }
+}
+
 impl Send for Handshake {
     fn send_to(&self, writer: &mut impl Write) -> io::Result<()> {
         (Self::BITTORRENT_PROTOCOL.len() as u8).encode_to(writer)?;
@@ -365,6 +671,61 @@ impl Send for Handshake {
     }
 }
 
+/// Counterpart of [`Decode`] for types, which borrow data from decoded bytes (i.e. [`PieceRef`]).
+/// Derived with `#[derive(Decode)]` for types with lifetime parameter.
+pub trait DecodeBorrowed<'a>: Sized {
+    /// Same as [`Decode::decode_from`], but borrows from `reader`, advancing it past decoded bytes.
+    fn decode_borrowed(len_hint: &mut usize, reader: &mut &'a [u8]) -> DecodeResult<Self>;
+
+    fn decode_ref(mut bytes: &'a [u8]) -> DecodeResult<Self> {
+        let mut len = bytes.len();
+        Self::decode_borrowed(&mut len, &mut bytes)
+    }
+}
+
+/// Counterpart of [`Recv`] for messages, which borrow their payload (i.e. `Container<PieceRef>`)
+/// from buffer of caller. Reusing the same buffer, hot loops recieve messages without allocations.
+pub trait RecvInto<'a>: Sized {
+    /// Same as [`recv_limited_into()`](`RecvInto::recv_limited_into`) with [default](`Limits::default`) limits.
+    fn recv_into(reader: &mut impl Read, buf: &'a mut Vec<u8>) -> Result<Option<Self>> {
+        Self::recv_limited_into(reader, buf, &Limits::default())
+    }
+
+    /// Reads payload of message into `buf`, replacing its contents, and decodes it from there.
+    /// Message is consumed from `reader` entirely, even if it fails to parse, unless it exceeds `limits`.
+    /// Keep-alive is recieved as `Ok(None)`, same as with [`Recv`].
+    fn recv_limited_into(reader: &mut impl Read, buf: &'a mut Vec<u8>, limits: &Limits) -> Result<Option<Self>>;
+}
+
+impl<'a, R: DecodeBorrowed<'a> + Standalone> RecvInto<'a> for Container<R> {
+    fn recv_limited_into(reader: &mut impl Read, buf: &'a mut Vec<u8>, limits: &Limits) -> Result<Option<Self>> {
+        let Some((id, len)) = utils::recv_header(reader, limits)? else { return Ok(None) };
+
+        if id != <R as Standalone>::ID {
+            utils::discard_bytes(reader, len)?;
+            return Err(Error::UnknownMessage { id });
+        }
+
+        buf.clear();
+        if reader.take(len as u64).read_to_end(buf)? < len {
+            return Err(Error::UnexpectedEof);
+        }
+
+        let mut payload: &'a [u8] = buf;
+        let mut len = payload.len();
+        let data = R::decode_borrowed(&mut len, &mut payload)?;
+        utils::decoded(data, len, std::any::type_name::<R>()).map(|data| Some(Self(data)))
+    }
+}
+
+/// Type, which can be encoded in little-endian byte order, used for fields with `#[message(endian = "little")]`.
+///
+/// Only affects integers, byte sequences are encoded the same way regardless of byte order.
+pub trait LittleEndianCodec: Sized {
+    fn encode_le_to(&self, writer: &mut impl Write) -> io::Result<()>;
+    fn decode_le_from(len_hint: &mut usize, reader: &mut impl Read) -> DecodeResult<Self>;
+}
+
 impl Encode for () {
     fn size(&self) -> usize {
         0
@@ -375,8 +736,12 @@ impl Encode for () {
     }
 }
 
+impl FixedSize for () {
+    const SIZE: usize = 0;
+}
+
 impl Decode for () {
-    fn decode_from(_: &mut usize, _: &mut impl Read) -> Result<Self> {
+    fn decode_from(_: &mut usize, _: &mut impl Read) -> DecodeResult<Self> {
         Ok(Some(()))
     }
 }
@@ -393,8 +758,12 @@ macro_rules! impl_sr_for_primitive {
             }
         }
 
+        impl FixedSize for $prim {
+            const SIZE: usize = size_of::<Self>();
+        }
+
         impl Decode for $prim {
-            fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> Result<Self> {
+            fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> DecodeResult<Self> {
                 if *len_hint < size_of::<Self>() {
                     Ok(None)
                 } else {
@@ -403,6 +772,21 @@ macro_rules! impl_sr_for_primitive {
                 }
             }
         }
+
+        impl LittleEndianCodec for $prim {
+            fn encode_le_to(&self, writer: &mut impl Write) -> io::Result<()> {
+                WriteBytesExt::$write::<LittleEndian>(writer, *self)
+            }
+
+            fn decode_le_from(len_hint: &mut usize, reader: &mut impl Read) -> DecodeResult<Self> {
+                if *len_hint < size_of::<Self>() {
+                    Ok(None)
+                } else {
+                    *len_hint -= size_of::<Self>();
+                    ReadBytesExt::$read::<LittleEndian>(reader).map(Option::Some)
+                }
+            }
+        }
     )*};
 }
 
@@ -416,16 +800,38 @@ impl Encode for u8 {
     }
 }
 
+impl FixedSize for u8 {
+    const SIZE: usize = size_of::<Self>();
+}
+
 impl Decode for u8 {
-    fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> Result<Self> {
+    fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> DecodeResult<Self> {
         if *len_hint < size_of::<Self>() {
             Ok(None)
         } else {
+            *len_hint -= size_of::<Self>();
             ReadBytesExt::read_u8(reader).map(Option::Some)
         }
     }
 }
 
+/// Byte sequences have no byte order, so they are encoded the same way as with [`Encode`].
+macro_rules! impl_le_as_be {
+    ($([$($gen:tt)*] $ty:ty),*) => {$(
+        impl<$($gen)*> LittleEndianCodec for $ty {
+            fn encode_le_to(&self, writer: &mut impl Write) -> io::Result<()> {
+                self.deref().encode_to(writer)
+            }
+
+            fn decode_le_from(len_hint: &mut usize, reader: &mut impl Read) -> DecodeResult<Self> {
+                Self::decode_from(len_hint, reader)
+            }
+        }
+    )*};
+}
+
+impl_le_as_be!([] u8, [const D: usize] [u8; D], [] Vec<u8>, [] Bytes);
+
 impl_sr_for_primitive!(
     [u16, write_u16, read_u16],
     [u32, write_u32, read_u32],
@@ -453,8 +859,12 @@ impl<const D: usize> Encode for [u8; D] {
     }
 }
 
+impl<const D: usize> FixedSize for [u8; D] {
+    const SIZE: usize = D;
+}
+
 impl Decode for Vec<u8> {
-    fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> Result<Self> {
+    fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> DecodeResult<Self> {
         let mut buf = vec![0; *len_hint];
         reader.read_exact(&mut buf[..])?;
         *len_hint = 0;
@@ -463,14 +873,25 @@ impl Decode for Vec<u8> {
     }
 }
 
+/// Block is read into buffer from [global pool](`BufferPool::global`), which is then shared by clones without copying.
+impl Decode for Bytes {
+    fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> DecodeResult<Self> {
+        let mut buf = BufferPool::global().take(*len_hint);
+        reader.read_exact(&mut buf[..])?;
+        *len_hint = 0;
+
+        Ok(Some(buf.freeze()))
+    }
+}
+
 impl Decode for Box<[u8]> {
-    fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> Result<Self> {
+    fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> DecodeResult<Self> {
         Vec::<u8>::decode_from(len_hint, reader).map(|opt| opt.map(Into::into))
     }
 }
 
 impl<const D: usize> Decode for [u8; D] {
-    fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> Result<Self> {
+    fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> DecodeResult<Self> {
         if *len_hint < D {
             Ok(None)
         } else {
@@ -484,7 +905,7 @@ impl<const D: usize> Decode for [u8; D] {
 }
 
 impl<const D: usize> Decode for Box<[u8; D]> {
-    fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> Result<Self> {
+    fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> DecodeResult<Self> {
         #![allow(const_item_mutation)]
 
         if *len_hint < D {
@@ -514,7 +935,7 @@ impl Encode for &str {
 }
 
 impl Decode for String {
-    fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> Result<Self> {
+    fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> DecodeResult<Self> {
         //Byte representaions never return Ok(None) so unwrap never falls
         unsafe {
             let bytes = Vec::decode_from(len_hint, reader)?.unwrap_unchecked();
@@ -525,15 +946,245 @@ impl Decode for String {
     }
 }
 
+impl<'a> DecodeBorrowed<'a> for &'a [u8] {
+    fn decode_borrowed(len_hint: &mut usize, reader: &mut &'a [u8]) -> DecodeResult<Self> {
+        if reader.len() < *len_hint {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let (data, rest) = reader.split_at(*len_hint);
+        *reader = rest;
+        *len_hint = 0;
+
+        Ok(Some(data))
+    }
+}
+
+impl<'a> DecodeBorrowed<'a> for Cow<'a, [u8]> {
+    fn decode_borrowed(len_hint: &mut usize, reader: &mut &'a [u8]) -> DecodeResult<Self> {
+        <&[u8]>::decode_borrowed(len_hint, reader).map(|opt| opt.map(Cow::Borrowed))
+    }
+}
+
+/// Owned types are decoded from byte slice as from any other reader.
+macro_rules! impl_borrowed_as_owned {
+    ($([$($gen:tt)*] $ty:ty),*) => {$(
+        impl<'a, $($gen)*> DecodeBorrowed<'a> for $ty {
+            fn decode_borrowed(len_hint: &mut usize, reader: &mut &'a [u8]) -> DecodeResult<Self> {
+                <Self as Decode>::decode_from(len_hint, reader)
+            }
+        }
+    )*};
+}
+
+impl_borrowed_as_owned!(
+    [] (),
+    [] u8,
+    [] u16,
+    [] u32,
+    [] u64,
+    [] u128,
+    [const D: usize] [u8; D],
+    [] Vec<u8>,
+    [] Bytes,
+    [] String
+);
+
+/// Optional trailing field, which is present only if there are bytes left in message.
+///
+/// `None` is not transmitted at all, so only the last fields of message can be optional.
+impl<T: Encode> Encode for Option<T> {
+    fn size(&self) -> usize {
+        self.as_ref().map_or(0, Encode::size)
+    }
+
+    fn encode_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        match self {
+            Some(val) => val.encode_to(writer),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> DecodeResult<Self> {
+        if *len_hint == 0 {
+            Ok(Some(None))
+        } else {
+            T::decode_from(len_hint, reader).map(|opt| opt.map(Some))
+        }
+    }
+}
+
+impl<T: LittleEndianCodec> LittleEndianCodec for Option<T> {
+    fn encode_le_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        match self {
+            Some(val) => val.encode_le_to(writer),
+            None => Ok(()),
+        }
+    }
+
+    fn decode_le_from(len_hint: &mut usize, reader: &mut impl Read) -> DecodeResult<Self> {
+        if *len_hint == 0 {
+            Ok(Some(None))
+        } else {
+            T::decode_le_from(len_hint, reader).map(|opt| opt.map(Some))
+        }
+    }
+}
+
+/// [`Request`] and [`Cancel`] are generated by hand, as derived `Arbitrary` would request oversized blocks,
+/// which are rejected by decoding. [`Bytes`] has no `Arbitrary` implementation, so it's generated from `Vec`.
+#[cfg(feature = "fuzzing")]
+mod fuzzing {
+    use arbitrary::{Arbitrary, Unstructured};
+    use bytes::Bytes;
+
+    use super::{Cancel, Request};
+
+    macro_rules! impl_arbitrary_for_block {
+        ($($kind:ident),*) => {$(
+            impl<'a> Arbitrary<'a> for $kind {
+                fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+                    Ok(Self {
+                        piece_index: u.arbitrary()?,
+                        offset: u.arbitrary()?,
+                        data_length: u.int_in_range(0..=Request::MAX_DATA_LENGTH)?,
+                    })
+                }
+            }
+        )*};
+    }
+
+    impl_arbitrary_for_block!(Request, Cancel);
+
+    pub fn bytes(u: &mut Unstructured<'_>) -> arbitrary::Result<Bytes> {
+        Vec::<u8>::arbitrary(u).map(Into::into)
+    }
+}
+
+impl<'a, T: DecodeBorrowed<'a>> DecodeBorrowed<'a> for Option<T> {
+    fn decode_borrowed(len_hint: &mut usize, reader: &mut &'a [u8]) -> DecodeResult<Self> {
+        if *len_hint == 0 {
+            Ok(Some(None))
+        } else {
+            T::decode_borrowed(len_hint, reader).map(|opt| opt.map(Some))
+        }
+    }
+}
+
 pub mod utils {
     use std::io;
 
+    use byteorder::{NetworkEndian, ReadBytesExt};
+
+    use super::{Decode, DecodeBorrowed, DecodeResult, Encode, Error, FixedSize, Limits, Result};
+
     pub fn discard_bytes(reader: impl io::Read, count: usize) -> io::Result<()> {
         io::copy(&mut reader.take(count as u64), &mut io::sink())?;
 
         Ok(())
     }
 
+    /// Reads length and id of standalone message, returning id and length of payload, if they're within `limits`.
+    /// Keep-alive has neither, so it's `None`.
+    pub fn recv_header(reader: &mut impl io::Read, limits: &Limits) -> Result<Option<(u8, usize)>> {
+        let len = reader.read_u32::<NetworkEndian>()? as usize;
+        if len == 0 {
+            return Ok(None);
+        }
+
+        limits.check_frame(len)?;
+        let id = reader.read_u8()?;
+        limits.check_payload(id, len - 1)?;
+
+        Ok(Some((id, len - 1)))
+    }
+
+    /// Size of `items`, prefixed with their count of type `C` (`#[message(count_prefix = "...")]` fields).
+    pub fn counted_size<C: FixedSize, T: Encode>(items: &[T]) -> usize {
+        C::SIZE + items.iter().map(Encode::size).sum::<usize>()
+    }
+
+    /// Encodes count of `items` as `C`, followed by items one by one.
+    pub fn encode_counted<C, T>(items: &[T], writer: &mut impl io::Write) -> io::Result<()>
+    where
+        C: Encode + TryFrom<usize>,
+        T: Encode,
+    {
+        let count = C::try_from(items.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many items for count prefix"))?;
+
+        count.encode_to(writer)?;
+        items.iter().try_for_each(|item| item.encode_to(writer))
+    }
+
+    /// Decodes items, prefixed with their count of type `C`.
+    pub fn decode_counted<C, T>(len_hint: &mut usize, reader: &mut impl io::Read) -> DecodeResult<Vec<T>>
+    where
+        C: Decode + TryInto<usize>,
+        T: Decode,
+    {
+        let Some(count) = C::decode_from(len_hint, reader)?.and_then(|count| count.try_into().ok()) else {
+            return Ok(None);
+        };
+
+        decode_items(count, len_hint, |len_hint| T::decode_from(len_hint, reader))
+    }
+
+    /// Same as [`decode_counted`], but items borrow from `reader`.
+    pub fn decode_counted_borrowed<'a, C, T>(len_hint: &mut usize, reader: &mut &'a [u8]) -> DecodeResult<Vec<T>>
+    where
+        C: Decode + TryInto<usize>,
+        T: DecodeBorrowed<'a>,
+    {
+        let Some(count) = C::decode_from(len_hint, reader)?.and_then(|count| count.try_into().ok()) else {
+            return Ok(None);
+        };
+
+        decode_items(count, len_hint, |len_hint| T::decode_borrowed(len_hint, reader))
+    }
+
+    /// Upper bound of count of items, which take no bytes on the wire (i.e. empty `Vec<u8>` or items,
+    /// whose fields are all skipped).
+    const MAX_EMPTY_ITEMS: usize = 1 << 16;
+
+    /// Decodes `count` items one by one with `decode`. Count is not trusted, so capacity is bound by bytes left
+    /// and items, which take no bytes, are bound by [`MAX_EMPTY_ITEMS`], so forged count can't make it spin.
+    fn decode_items<T>(
+        count: usize,
+        len_hint: &mut usize,
+        mut decode: impl FnMut(&mut usize) -> DecodeResult<T>,
+    ) -> DecodeResult<Vec<T>> {
+        let mut items = Vec::with_capacity(count.min(*len_hint));
+        let mut empty = 0;
+
+        for _ in 0..count {
+            let left = *len_hint;
+            match decode(len_hint)? {
+                Some(item) => items.push(item),
+                None => return Ok(None),
+            }
+
+            if *len_hint == left {
+                empty += 1;
+                if empty > MAX_EMPTY_ITEMS {
+                    return Ok(None);
+                }
+            }
+        }
+
+        Ok(Some(items))
+    }
+
+    /// Turns failed decoding of `message` payload, which left `discarded` bytes, into [`Error::Malformed`].
+    pub fn decoded<T>(data: Option<T>, discarded: usize, message: &str) -> Result<T> {
+        data.ok_or_else(|| Error::Malformed {
+            reason: format!("invalid payload of {}", message),
+            discarded,
+        })
+    }
+
     #[macro_export]
     macro_rules! unwrap_or_return {
         ($opt:expr) => {
@@ -585,11 +1236,312 @@ mod tests {
         let mut buf = vec![];
 
         Container(&data).send_to(&mut buf).unwrap();
-        let recieved = Container::recv_from((&buf[..]).by_ref())
-            .unwrap()
-            .map(Container::into_inner);
+        let recieved = Container::recv_from((&buf[..]).by_ref()).unwrap().unwrap().into_inner();
 
-        assert_eq!(Some(data), recieved);
+        assert_eq!(data, recieved);
+    }
+
+    #[derive(Debug, Clone, PartialEq, Encode, Decode)]
+    #[message(mod_path = "crate::messages")]
+    enum Nested {
+        Empty,
+        #[message(tag = 7)]
+        Pair(u8, BTInt),
+        Named { have: Have, data: Vec<u8> },
+    }
+
+    #[rstest]
+    #[case::unit(Nested::Empty, &[0])]
+    #[case::tuple(Nested::Pair(1, 2), &[7, 1, 0, 0, 0, 2])]
+    // Implicit tag follows the previous one
+    #[case::named(Nested::Named { have: Have { piece_index: 3 }, data: vec![4, 5] }, &[8, 0, 0, 0, 3, 4, 5])]
+    fn enum_encode_decode(#[case] data: Nested, #[case] bytes: &[u8]) {
+        assert_eq!(data.size(), bytes.len());
+        assert_eq!(data.encode(), bytes);
+        assert_eq!(Nested::decode(bytes).unwrap(), Some(data));
+        assert_eq!(Nested::decode(&[1]).unwrap(), None);
+    }
+
+    #[derive(Debug, Clone, PartialEq, Encode, Decode)]
+    #[message(mod_path = "crate::messages")]
+    struct WithLocalFields {
+        #[message(skip)]
+        cached: Option<u8>,
+        index: BTInt,
+        #[message(default = "BTInt::MAX")]
+        received_at: BTInt,
+    }
+
+    #[test]
+    fn skipped_fields_are_not_transmitted() {
+        let data = WithLocalFields { cached: Some(1), index: 2, received_at: 3 };
+
+        assert_eq!(data.size(), 4);
+        assert_eq!(data.encode(), [0, 0, 0, 2]);
+        assert_eq!(
+            WithLocalFields::decode(&data.encode()).unwrap(),
+            Some(WithLocalFields { cached: None, index: 2, received_at: BTInt::MAX })
+        );
+    }
+
+    mod compact_addr {
+        use std::io::{self, Read, Write};
+        use std::net::{Ipv4Addr, SocketAddrV4};
+
+        pub fn size(_: &SocketAddrV4) -> usize {
+            6
+        }
+
+        pub fn encode_to(addr: &SocketAddrV4, writer: &mut impl Write) -> io::Result<()> {
+            writer.write_all(&addr.ip().octets())?;
+            writer.write_all(&addr.port().to_be_bytes())
+        }
+
+        pub fn decode_from(len_hint: &mut usize, reader: &mut impl Read) -> io::Result<Option<SocketAddrV4>> {
+            if *len_hint < 6 {
+                return Ok(None);
+            }
+
+            let mut buf = [0; 6];
+            reader.read_exact(&mut buf)?;
+            *len_hint -= 6;
+
+            let ip = Ipv4Addr::new(buf[0], buf[1], buf[2], buf[3]);
+            Ok(Some(SocketAddrV4::new(ip, u16::from_be_bytes([buf[4], buf[5]]))))
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Encode, Decode)]
+    #[message(mod_path = "crate::messages")]
+    struct Peer {
+        #[message(with = "compact_addr")]
+        addr: std::net::SocketAddrV4,
+        flags: u8,
+    }
+
+    #[test]
+    fn custom_codec_is_used() {
+        let peer = Peer { addr: "10.0.0.1:6881".parse().unwrap(), flags: 1 };
+
+        assert_eq!(peer.size(), 7);
+        assert_eq!(peer.encode(), [10, 0, 0, 1, 0x1a, 0xe1, 1]);
+        assert_eq!(Peer::decode(&peer.encode()).unwrap(), Some(peer));
+    }
+
+    #[derive(Debug, Clone, PartialEq, Encode, Decode)]
+    #[message(mod_path = "crate::messages")]
+    struct Extended {
+        index: BTInt,
+        port: Option<u16>,
+        flags: Option<u8>,
+    }
+
+    #[rstest]
+    #[case::none(Extended { index: 1, port: None, flags: None }, 4)]
+    #[case::partial(Extended { index: 1, port: Some(6881), flags: None }, 6)]
+    #[case::full(Extended { index: 1, port: Some(6881), flags: Some(2) }, 7)]
+    fn optional_fields_are_trailing(#[case] data: Extended, #[case] size: usize) {
+        assert_eq!(data.size(), size);
+        assert_eq!(data.encode().len(), size);
+        assert_eq!(Extended::decode(&data.encode()).unwrap(), Some(data));
+    }
+
+    #[derive(Debug, Clone, PartialEq, Encode, Decode)]
+    #[message(mod_path = "crate::messages", endian = "little")]
+    struct ResumeEntry {
+        piece_index: BTInt,
+        #[message(endian = "big")]
+        port: u16,
+        hash: [u8; 4],
+        downloaded: Option<u64>,
+    }
+
+    #[test]
+    fn byte_order_is_applied() {
+        let entry = ResumeEntry { piece_index: 1, port: 6881, hash: [1, 2, 3, 4], downloaded: Some(2) };
+        let encoded = entry.encode();
+
+        assert_eq!(encoded, [1, 0, 0, 0, 0x1a, 0xe1, 1, 2, 3, 4, 2, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(ResumeEntry::decode(&encoded).unwrap(), Some(entry));
+    }
+
+    #[derive(Debug, Clone, PartialEq, Encode, Decode)]
+    #[message(mod_path = "crate::messages")]
+    struct HashList {
+        #[message(count_prefix = "u16")]
+        hashes: Vec<[u8; 2]>,
+        #[message(count_prefix = "u8")]
+        haves: Vec<Have>,
+        flags: u8,
+    }
+
+    #[test]
+    fn collections_are_count_prefixed() {
+        let list = HashList { hashes: vec![[1, 2], [3, 4]], haves: vec![Have { piece_index: 5 }], flags: 6 };
+        let encoded = list.encode();
+
+        assert_eq!(list.size(), encoded.len());
+        assert_eq!(encoded, [0, 2, 1, 2, 3, 4, 1, 0, 0, 0, 5, 6]);
+        assert_eq!(HashList::decode(&encoded).unwrap(), Some(list));
+        // Count exceeds items left
+        assert_eq!(HashList::decode(&[0, 3, 1, 2, 3, 4]).unwrap(), None);
+    }
+
+    #[derive(Debug, PartialEq, Decode)]
+    #[message(mod_path = "crate::messages")]
+    struct Chunks {
+        #[message(count_prefix = "u32")]
+        chunks: Vec<Vec<u8>>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Encode, Decode)]
+    #[message(mod_path = "crate::messages")]
+    struct Marker {
+        #[message(skip)]
+        cached: Option<u8>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Encode, Decode)]
+    #[message(mod_path = "crate::messages")]
+    struct Markers {
+        #[message(count_prefix = "u32")]
+        markers: Vec<Marker>,
+        #[message(count_prefix = "u8")]
+        flags: Vec<Option<u8>>,
+    }
+
+    #[test]
+    fn count_of_empty_items_is_bound_by_payload() {
+        assert_eq!(Chunks::decode(&[0, 0, 0, 1, 7]).unwrap(), Some(Chunks { chunks: vec![vec![7]] }));
+        assert_eq!(Chunks::decode(&[0, 0, 0, 1]).unwrap(), Some(Chunks { chunks: vec![vec![]] }));
+        assert_eq!(Chunks::decode(&[0xFF; 4]).unwrap(), None);
+    }
+
+    #[test]
+    fn empty_items_are_count_prefixed() {
+        let markers = Markers { markers: vec![Marker { cached: None }; 2], flags: vec![None, None] };
+        let encoded = markers.encode();
+
+        assert_eq!(encoded, [0, 0, 0, 2, 2]);
+        assert_eq!(Markers::decode(&encoded).unwrap(), Some(markers));
+    }
+
+    #[test]
+    fn piece_is_borrowed() {
+        let piece = Piece { piece_index: 1, offset: 16, data: vec![0xAB; 64].into() };
+        let bytes = piece.encode();
+        let borrowed = PieceRef::decode_ref(&bytes).unwrap().unwrap();
+
+        assert!(bytes.as_ptr_range().contains(&borrowed.data.as_ptr()));
+        assert_eq!(borrowed.encode(), bytes);
+        assert_eq!(Piece::from(borrowed), piece);
+    }
+
+    #[test]
+    fn piece_is_recieved_into_buffer() {
+        let piece = Piece { piece_index: 1, offset: 16, data: vec![0xAB; 64].into() };
+        let mut stream = vec![];
+        Message::Have(Have { piece_index: 3 }).send_to(&mut stream).unwrap();
+        Message::Piece(piece.clone()).send_to(&mut stream).unwrap();
+
+        let mut reader = &stream[..];
+        let mut buf = Vec::with_capacity(128);
+        let ptr = buf.as_ptr();
+
+        assert!(matches!(
+            Container::<PieceRef>::recv_into(&mut reader, &mut buf),
+            Err(Error::UnknownMessage { id: 4 })
+        ));
+        let recieved = Container::<PieceRef>::recv_into(&mut reader, &mut buf).unwrap().unwrap();
+
+        assert_eq!(Piece::from(recieved.into_inner()), piece);
+        assert_eq!(buf.as_ptr(), ptr);
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn piece_is_sent_from_reader() {
+        let piece = Piece { piece_index: 2, offset: 32, data: vec![0xCD; 100].into() };
+        let mut expected = vec![];
+        Message::Piece(piece.clone()).send_to(&mut expected).unwrap();
+
+        let mut file = vec![0xCD; 150];
+        file.extend_from_slice(&[0xEF; 50]);
+        let message = PieceReader::new(2, 32, 100, &file[50..]);
+        let mut sent = vec![];
+        message.send_to(&mut sent).unwrap();
+
+        assert_eq!(sent, expected);
+        assert_eq!(message.into_inner(), &[0xEF; 50][..]);
+        // Reader is too short
+        assert!(PieceReader::new(2, 32, 100, &file[..10]).send_to(&mut vec![]).is_err());
+    }
+
+    #[test]
+    fn decoded_block_is_shared_by_clones() {
+        let piece = Piece::decode(&[0, 0, 0, 1, 0, 0, 0, 16, 0xAB, 0xCD]).unwrap().unwrap();
+        let clone = piece.clone();
+
+        assert_eq!(piece.data, &[0xAB, 0xCD][..]);
+        assert_eq!(clone.data.as_ptr(), piece.data.as_ptr());
+    }
+
+    #[cfg(feature = "fuzzing")]
+    #[test]
+    fn arbitrary_messages_are_valid() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let bytes = (0..4096u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect::<Vec<_>>();
+        let mut u = Unstructured::new(&bytes);
+
+        while !u.is_empty() {
+            let message = Message::arbitrary(&mut u).unwrap();
+            let mut buf = vec![];
+
+            message.send_to(&mut buf).unwrap();
+            assert_eq!(Message::recv_from((&buf[..]).by_ref()).unwrap(), Some(message));
+        }
+    }
+
+    #[rstest]
+    #[case::max(Request::MAX_DATA_LENGTH, true)]
+    #[case::oversized(Request::MAX_DATA_LENGTH + 1, false)]
+    fn checks_are_applied(#[case] data_length: BTInt, #[case] valid: bool) {
+        let request = Request { piece_index: 1, offset: 0, data_length };
+        let decoded = Request::decode(&request.encode());
+
+        match decoded {
+            Ok(decoded) => assert!(valid && decoded == Some(request)),
+            Err(err) => assert!(!valid && err.kind() == io::ErrorKind::InvalidData),
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Standalone)]
+    #[message(mod_path = "crate::messages")]
+    enum ExtensionId {
+        Handshake,
+        #[standalone(id = 3)]
+        Metadata,
+        Pex = 5,
+    }
+
+    #[test]
+    fn enum_ids_are_mapped() {
+        assert_eq!(ExtensionId::Handshake.id(), 0);
+        assert_eq!(u8::from(ExtensionId::Metadata), 3);
+        assert_eq!(ExtensionId::from_id(5), Some(ExtensionId::Pex));
+        assert_eq!(ExtensionId::try_from(1), Err(1));
+    }
+
+    #[rstest]
+    #[case::choke(Choke, Choke::SIZE)]
+    #[case::have(Have::default(), Have::SIZE)]
+    #[case::request(Request::default(), Request::SIZE)]
+    #[case::cancel(Cancel::default(), Cancel::SIZE)]
+    #[case::reserved(Reserved::default(), Reserved::SIZE)]
+    fn fixed_size_matches_encoded<S: Encode>(#[case] data: S, #[case] size: usize) {
+        assert_eq!(data.size(), size);
+        assert_eq!(data.encode().len(), size);
     }
 
     #[rstest]
@@ -607,8 +1559,74 @@ mod tests {
 
         message.send_to(&mut buf).unwrap();
         let recieved = <M as Recv>::recv_from((&buf[..]).by_ref())
+            .unwrap()
             .unwrap();
 
-        assert_eq!(Some(message), recieved);
+        assert_eq!(message, recieved);
+    }
+
+    #[test]
+    fn handshake_is_built() {
+        let handshake = Handshake::try_builder(&[1; 20], b"-BR0100-abcdefghijkl").unwrap().fast(true).build();
+
+        assert_eq!(handshake.info_hash(), &[1; 20]);
+        assert_eq!(handshake.peer_id(), b"-BR0100-abcdefghijkl");
+        assert_eq!(handshake.ext().capabilities(), [Capability::Fast]);
+        assert!(Handshake::try_builder(&[1; 19], &[2; 20]).is_err());
+    }
+
+    #[test]
+    fn capabilities_are_reported() {
+        let reserved = Reserved::default().with_dht(true).with_fast(true).with_extensions(true).with_dht(false);
+
+        assert_eq!(reserved.inner(), [0, 0, 0, 0, 0, 0x10, 0, 0x04]);
+        assert!(reserved.supports_extensions() && reserved.supports_fast() && !reserved.supports_dht());
+
+        let reserved = Reserved::from([0x80, 0, 0, 0x01, 0, 0, 0, 0x11]);
+        assert_eq!(
+            reserved.capabilities(),
+            [
+                Capability::AzureusMessaging,
+                Capability::Unknown { byte: 3, mask: 0x01 },
+                Capability::V2Upgrade,
+                Capability::Dht
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_messages_are_skipped() {
+        // Keep-alive, `Have` with truncated index, unknown message and handshake of other protocol
+        let stream = [&[0, 0, 0, 0][..], &[0, 0, 0, 3, 4, 0, 1], &[0, 0, 0, 2, 42, 0], &[3, b'f', b'o', b'o']].concat();
+        let mut reader = &stream[..];
+
+        assert!(matches!(Message::recv_from(&mut reader), Ok(None)));
+        assert!(matches!(Message::recv_from(&mut reader), Err(Error::Malformed { discarded: 2, .. })));
+        assert!(matches!(Message::recv_from(&mut reader), Err(Error::UnknownMessage { id: 42 })));
+        assert!(matches!(Handshake::recv_from(&mut reader), Err(Error::Violation(_))));
+        assert!(matches!(Message::recv_from(&mut reader), Err(Error::UnexpectedEof)));
+    }
+
+    #[test]
+    fn oversized_messages_are_rejected() {
+        let limits = Limits { max_frame_size: 64, max_bitfield_size: 4, max_block_size: 16 };
+        let piece = Piece { piece_index: 0, offset: 0, data: vec![0; 32].into() };
+        let mut stream = vec![];
+        Message::Bitfield(Bitfield { bits: vec![0xff; 8].into() }).send_to(&mut stream).unwrap();
+        Message::Piece(piece).send_to(&mut stream).unwrap();
+        // Length prefix only, payload is never sent
+        stream.extend_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+
+        let mut reader = &stream[..];
+        assert!(matches!(Message::recv_limited_from(&mut reader, &limits), Err(Error::Violation(_))));
+        assert_eq!(reader.len(), stream.len() - 5);
+
+        let mut reader = &stream[13..];
+        let mut buf = vec![];
+        let recieved = Container::<PieceRef>::recv_limited_into(&mut reader, &mut buf, &limits);
+        assert!(matches!(recieved, Err(Error::Violation(_))));
+
+        let mut reader = &stream[stream.len() - 4..];
+        assert!(matches!(Message::recv_from(&mut reader), Err(Error::Violation(_))));
     }
 }
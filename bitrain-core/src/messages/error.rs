@@ -0,0 +1,76 @@
+use std::{fmt, io};
+
+/// Error of recieving standalone message.
+///
+/// Unlike [`io::Error`], tells whether connection is still usable: after [recoverable](`Error::is_recoverable`)
+/// errors the whole message is consumed and the next one can be recieved.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// Payload doesn't match format of message. `discarded` bytes of it were skipped.
+    Malformed { reason: String, discarded: usize },
+    /// Message with `id` isn't supported or expected, its payload was skipped.
+    UnknownMessage { id: u8 },
+    /// Stream ended in the middle of message.
+    UnexpectedEof,
+    /// Peer broke the protocol in a way, which doesn't allow to continue communication.
+    Violation(String),
+}
+
+impl Error {
+    /// `true` if message was consumed entirely, so connection is still in sync with peer.
+    pub fn is_recoverable(&self) -> bool {
+        matches!(self, Self::Malformed { .. } | Self::UnknownMessage { .. })
+    }
+
+    /// Maps error of underlying stream, including [`Error::UnexpectedEof`], leaving other errors unchanged.
+    pub fn map_io(self, f: impl FnOnce(io::Error) -> io::Error) -> Self {
+        match self {
+            Self::Io(err) => f(err).into(),
+            Self::UnexpectedEof => f(io::ErrorKind::UnexpectedEof.into()).into(),
+            err => err,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::Malformed { reason, discarded } => {
+                write!(f, "malformed message: {} ({} bytes discarded)", reason, discarded)
+            }
+            Self::UnknownMessage { id } => write!(f, "unknown message with id {}", id),
+            Self::UnexpectedEof => f.write_str("stream ended in the middle of message"),
+            Self::Violation(reason) => write!(f, "protocol violation: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => Self::UnexpectedEof,
+            _ => Self::Io(err),
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Io(err) => err,
+            Error::UnexpectedEof => io::ErrorKind::UnexpectedEof.into(),
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
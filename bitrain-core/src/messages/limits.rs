@@ -0,0 +1,63 @@
+use std::mem::size_of;
+
+use super::{BTInt, Bitfield, Error, Piece, Request, Result, Standalone};
+
+/// Maximum sizes of recieved messages, which are checked against length prefix before payload is read,
+/// so peer can't make recieving side allocate arbitrary amounts of memory.
+///
+/// Exceeded limits fail with [`Error::Violation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Size of whole message, except length prefix.
+    pub max_frame_size: usize,
+    /// Size of `bits` of [`Bitfield`].
+    pub max_bitfield_size: usize,
+    /// Size of `data` of [`Piece`].
+    pub max_block_size: usize,
+}
+
+impl Limits {
+    /// Enough for blocks of any size, peers are allowed to request, and bitfields of torrents with
+    /// up to 16M pieces.
+    pub const DEFAULT_MAX_FRAME_SIZE: usize = 1 << 21;
+    pub const DEFAULT_MAX_BITFIELD_SIZE: usize = 1 << 21;
+    pub const DEFAULT_MAX_BLOCK_SIZE: usize = Request::MAX_DATA_LENGTH as usize;
+
+    /// Checks frame of `len` bytes, before its message id is known.
+    pub fn check_frame(&self, len: usize) -> Result<()> {
+        if len > self.max_frame_size {
+            return Err(Error::Violation(format!(
+                "frame of {} bytes exceeds limit of {}",
+                len, self.max_frame_size
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Checks payload of `len` bytes of message with `id`.
+    pub fn check_payload(&self, id: u8, len: usize) -> Result<()> {
+        let (name, size, limit) = match id {
+            Bitfield::ID => ("bitfield", len, self.max_bitfield_size),
+            // Piece index and offset precede block
+            Piece::ID => ("block", len.saturating_sub(2 * size_of::<BTInt>()), self.max_block_size),
+            _ => return Ok(()),
+        };
+
+        if size > limit {
+            return Err(Error::Violation(format!("{} of {} bytes exceeds limit of {}", name, size, limit)));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_frame_size: Self::DEFAULT_MAX_FRAME_SIZE,
+            max_bitfield_size: Self::DEFAULT_MAX_BITFIELD_SIZE,
+            max_block_size: Self::DEFAULT_MAX_BLOCK_SIZE,
+        }
+    }
+}
@@ -0,0 +1,287 @@
+//! Counters, gauges and histograms of client activity.
+//!
+//! [`Metrics`] of [`Session`](crate::session::Session) are updated by its peer connections and torrents.
+//! They can be read directly or exported in Prometheus text format with [`Metrics::encode_prometheus`],
+//! i.e. from handler of `/metrics` endpoint.
+use std::{
+    fmt,
+    sync::atomic::{AtomicI64, AtomicU64, Ordering},
+};
+
+use crate::messages::Message;
+
+/// Value, which only grows.
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.add(1)
+    }
+
+    pub fn add(&self, value: u64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Value, which goes up and down.
+#[derive(Debug, Default)]
+pub struct Gauge(AtomicI64);
+
+impl Gauge {
+    pub fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn dec(&self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn set(&self, value: i64) {
+        self.0.store(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Distribution of observed values over buckets with upper bounds.
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: Vec<u64>,
+    /// Observations per bucket, the last one is for values above all bounds.
+    buckets: Vec<AtomicU64>,
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    pub fn new(bounds: impl Into<Vec<u64>>) -> Self {
+        let mut bounds = bounds.into();
+        bounds.sort_unstable();
+        bounds.dedup();
+
+        Self {
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            bounds,
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, value: u64) {
+        let bucket = self.bounds.partition_point(|bound| *bound < value);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn bounds(&self) -> &[u64] {
+        &self.bounds
+    }
+
+    /// Number of observations less or equal to each bound, followed by total number of observations.
+    pub fn cumulative_counts(&self) -> Vec<u64> {
+        self.buckets
+            .iter()
+            .scan(0, |total, bucket| {
+                *total += bucket.load(Ordering::Relaxed);
+                Some(*total)
+            })
+            .collect()
+    }
+
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().map(|bucket| bucket.load(Ordering::Relaxed)).sum()
+    }
+
+    pub fn sum(&self) -> u64 {
+        self.sum.load(Ordering::Relaxed)
+    }
+}
+
+/// Counters of P2P messages by type.
+#[derive(Debug, Default)]
+pub struct MessageCounters([Counter; MessageCounters::TYPES.len()]);
+
+impl MessageCounters {
+    /// Names of message types, indexed by their ids.
+    pub const TYPES: [&'static str; 9] = [
+        "choke",
+        "unchoke",
+        "interested",
+        "not_interested",
+        "have",
+        "bitfield",
+        "request",
+        "piece",
+        "cancel",
+    ];
+
+    pub fn record(&self, message: &Message) {
+        self.0[utils::message_id(message)].inc()
+    }
+
+    /// Number of recorded messages with `id`, `0` for unknown ids.
+    pub fn get(&self, id: u8) -> u64 {
+        self.0.get(id as usize).map_or(0, Counter::get)
+    }
+}
+
+/// Metrics of session.
+#[derive(Debug)]
+pub struct Metrics {
+    /// Bytes of recieved blocks.
+    pub downloaded: Counter,
+    /// Bytes of sent blocks.
+    pub uploaded: Counter,
+    pub messages_sent: MessageCounters,
+    pub messages_recieved: MessageCounters,
+    /// Peer connections, which completed handshake.
+    pub connections: Gauge,
+    /// Pieces, which failed hash check.
+    pub verification_failures: Counter,
+    /// Time between sending request and recieving its block, in milliseconds.
+    pub request_latency: Histogram,
+}
+
+impl Metrics {
+    pub const LATENCY_BOUNDS: [u64; 9] = [10, 25, 50, 100, 250, 500, 1000, 2500, 5000];
+
+    pub fn new() -> Self {
+        Self {
+            downloaded: Counter::default(),
+            uploaded: Counter::default(),
+            messages_sent: MessageCounters::default(),
+            messages_recieved: MessageCounters::default(),
+            connections: Gauge::default(),
+            verification_failures: Counter::default(),
+            request_latency: Histogram::new(Self::LATENCY_BOUNDS),
+        }
+    }
+
+    /// Writes metrics in [Prometheus text format](https://prometheus.io/docs/instrumenting/exposition_formats/),
+    /// with names prefixed by `bitrain_`.
+    pub fn encode_prometheus(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        utils::write_counter(out, "downloaded_bytes_total", "Bytes of received blocks.", &self.downloaded)?;
+        utils::write_counter(out, "uploaded_bytes_total", "Bytes of sent blocks.", &self.uploaded)?;
+        utils::write_messages(out, "messages_sent_total", "Sent messages by type.", &self.messages_sent)?;
+        utils::write_messages(
+            out,
+            "messages_received_total",
+            "Received messages by type.",
+            &self.messages_recieved,
+        )?;
+
+        writeln!(out, "# HELP bitrain_connections Peer connections, which completed handshake.")?;
+        writeln!(out, "# TYPE bitrain_connections gauge")?;
+        writeln!(out, "bitrain_connections {}", self.connections.get())?;
+
+        utils::write_counter(
+            out,
+            "verification_failures_total",
+            "Pieces, which failed hash check.",
+            &self.verification_failures,
+        )?;
+
+        utils::write_histogram(
+            out,
+            "request_latency_milliseconds",
+            "Time between sending request and receiving its block.",
+            &self.request_latency,
+        )
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+mod utils {
+    use super::*;
+
+    pub fn message_id(message: &Message) -> usize {
+        match message {
+            Message::Choke => 0,
+            Message::Unchoke => 1,
+            Message::Interested => 2,
+            Message::NotInterested => 3,
+            Message::Have(_) => 4,
+            Message::Bitfield(_) => 5,
+            Message::Request(_) => 6,
+            Message::Piece(_) => 7,
+            Message::Cancel(_) => 8,
+        }
+    }
+
+    fn write_header(out: &mut impl fmt::Write, name: &str, help: &str, kind: &str) -> fmt::Result {
+        writeln!(out, "# HELP bitrain_{} {}", name, help)?;
+        writeln!(out, "# TYPE bitrain_{} {}", name, kind)
+    }
+
+    pub fn write_counter(out: &mut impl fmt::Write, name: &str, help: &str, counter: &Counter) -> fmt::Result {
+        write_header(out, name, help, "counter")?;
+        writeln!(out, "bitrain_{} {}", name, counter.get())
+    }
+
+    pub fn write_messages(
+        out: &mut impl fmt::Write,
+        name: &str,
+        help: &str,
+        counters: &MessageCounters,
+    ) -> fmt::Result {
+        write_header(out, name, help, "counter")?;
+
+        for (kind, counter) in MessageCounters::TYPES.iter().zip(&counters.0) {
+            writeln!(out, "bitrain_{}{{type=\"{}\"}} {}", name, kind, counter.get())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_histogram(out: &mut impl fmt::Write, name: &str, help: &str, histogram: &Histogram) -> fmt::Result {
+        write_header(out, name, help, "histogram")?;
+
+        let counts = histogram.cumulative_counts();
+        for (bound, count) in histogram.bounds().iter().zip(&counts) {
+            writeln!(out, "bitrain_{}_bucket{{le=\"{}\"}} {}", name, bound, count)?;
+        }
+
+        writeln!(out, "bitrain_{}_bucket{{le=\"+Inf\"}} {}", name, counts[counts.len() - 1])?;
+        writeln!(out, "bitrain_{}_sum {}", name, histogram.sum())?;
+        writeln!(out, "bitrain_{}_count {}", name, histogram.count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::Have;
+
+    #[test]
+    fn metrics_are_encoded_for_prometheus() {
+        let metrics = Metrics::new();
+        metrics.downloaded.add(16384);
+        metrics.messages_recieved.record(&Message::Have(Have { piece_index: 1 }));
+        metrics.connections.inc();
+        metrics.request_latency.observe(10);
+        metrics.request_latency.observe(70);
+        metrics.request_latency.observe(9000);
+
+        let mut out = String::new();
+        metrics.encode_prometheus(&mut out).unwrap();
+
+        assert_eq!(metrics.messages_recieved.get(4), 1);
+        assert!(out.contains("# TYPE bitrain_downloaded_bytes_total counter\nbitrain_downloaded_bytes_total 16384\n"));
+        assert!(out.contains("bitrain_messages_received_total{type=\"have\"} 1\n"));
+        assert!(out.contains("bitrain_connections 1\n"));
+        assert!(out.contains("bitrain_request_latency_milliseconds_bucket{le=\"10\"} 1\n"));
+        assert!(out.contains("bitrain_request_latency_milliseconds_bucket{le=\"100\"} 2\n"));
+        assert!(out.contains("bitrain_request_latency_milliseconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(out.contains("bitrain_request_latency_milliseconds_sum 9080\n"));
+    }
+}
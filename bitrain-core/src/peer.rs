@@ -1,10 +1,41 @@
+#[cfg(feature = "async")]
+mod async_connection;
+mod ban;
+mod cancel;
+mod capture;
+mod fingerprint;
+mod gather;
+// Info dictionary is parsed with serde
+#[cfg(feature = "use-serde")]
+mod metadata;
+mod mse;
+mod swarm;
+#[cfg(feature = "webrtc")]
+mod webrtc;
+
 use std::{
-    io::{self, Write},
-    net::TcpStream, borrow::Borrow,
+    io::{self, BufRead},
+    net::{SocketAddr, TcpStream, ToSocketAddrs}, borrow::Borrow,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
-use crate::messages::{self, Handshake, Send, Recv};
+use crate::messages::{self, Handshake, Keepalive, Limits, Recv, RecvInto, Send};
 use bufstream::BufStream;
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+
+#[cfg(feature = "async")]
+pub use async_connection::AsyncConnection;
+pub use ban::{Ban, BanList, BanReason};
+pub use cancel::CancelToken;
+pub use capture::{Direction, Frame, Recorder};
+pub use fingerprint::{generate_peer_id, Client, CLIENT_PREFIX};
+#[cfg(feature = "use-serde")]
+pub use metadata::{MetadataError, MetadataFetcher};
+pub use mse::{CryptoMethod, EncryptionPolicy, MseStream};
+pub use swarm::{Candidate, PeerSource, Swarm};
+#[cfg(feature = "webrtc")]
+pub use webrtc::{Accepted, RtcConnection, RtcConnector, RtcPeers};
 
 #[allow(dead_code)]
 pub struct Peer {
@@ -13,6 +44,10 @@ pub struct Peer {
     uploaded: usize,
     downloaded: usize,
     addr: (String, u16),
+    cancel: Option<CancelToken>,
+    encryption: EncryptionPolicy,
+    recorder: Option<Arc<Recorder>>,
+    limits: Limits,
 }
 
 impl Peer {
@@ -31,43 +66,707 @@ impl Peer {
             uploaded: 0,
             downloaded: 0,
             addr,
+            cancel: None,
+            encryption: EncryptionPolicy::default(),
+            recorder: None,
+            limits: Limits::default(),
         }
     }
 
+    /// Makes pending and future connects and handshakes of this peer abort, when `token` is cancelled.
+    ///
+    /// Aborted operations return [`io::ErrorKind::Interrupted`] error.
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Sets whether connections to this peer are encrypted, see [`EncryptionPolicy`].
+    pub fn with_encryption(mut self, policy: EncryptionPolicy) -> Self {
+        self.encryption = policy;
+        self
+    }
+
+    /// Records messages of connections to this peer, starting from handshake, see [`Recorder`].
+    pub fn with_recorder(mut self, recorder: Arc<Recorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Sets limits of messages, recieved over connections to this peer, see [`Connection::set_limits`].
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     /// Attempts to connect to peer and exchange handshakes with it.
+    ///
+    /// Connection is encrypted according to [policy](`Peer::with_encryption`).
     pub fn handshake(&mut self, handshake: impl Borrow<Handshake>) -> messages::Result<(Connection, Handshake)> {
-        let mut connection = self.connect()?;
+        self.handshake_until(handshake.borrow(), None)
+    }
+
+    /// Same as [`handshake()`](`Peer::handshake`), but fails with [`io::ErrorKind::TimedOut`]
+    /// if both connect and handshake exchange are not complete by `deadline`.
+    pub fn handshake_with_deadline(
+        &mut self,
+        handshake: impl Borrow<Handshake>,
+        deadline: Instant,
+    ) -> messages::Result<(Connection, Handshake)> {
+        self.handshake_until(handshake.borrow(), Some(deadline))
+    }
 
-        connection.send(handshake.borrow())?;        
-        let recieved = connection.recv::<Handshake>()?;
-        
-        Ok(recieved.map(|h| (connection, h)))
+    /// Same as [`handshake()`](`Peer::handshake`), but obfuscates connection with [MSE](`MseStream`) first,
+    /// offering `provide` methods of payload encryption, regardless of policy.
+    pub fn handshake_encrypted(
+        &mut self,
+        handshake: impl Borrow<Handshake>,
+        provide: &[CryptoMethod],
+    ) -> messages::Result<(Connection, Handshake)> {
+        self.try_handshake(handshake.borrow(), provide, None)
     }
 
     pub fn connect(&mut self) -> io::Result<Connection> {
-        Ok(Connection::new(TcpStream::connect(&self.addr)?))
+        self.dial(None).map(Connection::new)
+    }
+
+    /// Same as [`connect()`](`Peer::connect`), but fails with [`io::ErrorKind::TimedOut`]
+    /// if connection is not established by `deadline`.
+    pub fn connect_with_deadline(&mut self, deadline: Instant) -> io::Result<Connection> {
+        self.dial(Some(deadline)).map(Connection::new)
+    }
+
+    fn handshake_until(
+        &mut self,
+        handshake: &Handshake,
+        deadline: Option<Instant>,
+    ) -> messages::Result<(Connection, Handshake)> {
+        match self.encryption.provide() {
+            [] => self.try_handshake(handshake, &[], deadline),
+            provide if self.encryption == EncryptionPolicy::Required => self.try_handshake(handshake, provide, deadline),
+            provide => match self.try_handshake(handshake, provide, deadline) {
+                // Peers without MSE support drop connection on unexpected bytes
+                Err(messages::Error::Io(err)) if utils::is_refusal(&err) => {
+                    self.try_handshake(handshake, &[], deadline)
+                }
+                Err(messages::Error::UnexpectedEof) => self.try_handshake(handshake, &[], deadline),
+                result => result,
+            },
+        }
+    }
+
+    /// Connects to peer and exchanges handshakes, running MSE handshake first if any methods are provided.
+    fn try_handshake(
+        &mut self,
+        handshake: &Handshake,
+        provide: &[CryptoMethod],
+        deadline: Option<Instant>,
+    ) -> messages::Result<(Connection, Handshake)> {
+        let tcp = self.dial(deadline)?;
+
+        let mut connection = match provide {
+            [] => Connection::new(tcp),
+            _ => Connection::initiate(tcp, &handshake.info_hash, provide, deadline, self.cancel.as_ref())?,
+        };
+        connection.set_recorder(self.recorder.clone());
+        connection.set_limits(self.limits);
+
+        let recieved = connection.exchange_handshakes(handshake, deadline, self.cancel.as_ref())?;
+
+        Ok((connection, recieved))
+    }
+
+    fn dial(&self, deadline: Option<Instant>) -> io::Result<TcpStream> {
+        if let Some(token) = &self.cancel {
+            token.check()?;
+        }
+
+        connect_until(&self.addr, deadline, self.cancel.as_ref())
     }
 }
 
+/// Connects to the first reachable of `addr`. Socket is registered with `cancel` before connecting,
+/// so cancellation shuts it down and aborts pending connect.
+fn connect_until(
+    addr: impl ToSocketAddrs,
+    deadline: Option<Instant>,
+    cancel: Option<&CancelToken>,
+) -> io::Result<TcpStream> {
+    let mut last_err = None;
+
+    for addr in addr.to_socket_addrs()? {
+        let tcp: TcpStream = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?.into();
+        let _registration = cancel.map(|token| token.register(&tcp)).transpose()?;
+
+        let result = match deadline {
+            Some(deadline) => SockRef::from(&tcp).connect_timeout(&addr.into(), utils::remaining(deadline)?),
+            None => SockRef::from(&tcp).connect(&addr.into()),
+        };
+
+        match result.map_err(|err| utils::map_err(err, cancel)) {
+            Ok(()) => return Ok(tcp),
+            Err(err) if cancel.is_some_and(CancelToken::is_cancelled) => return Err(err),
+            Err(err) => last_err = Some(err),
+        }
+    }
+
+    Err(last_err.unwrap_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "could not resolve to any address")
+    }))
+}
+
 pub struct Connection {
-    inner: BufStream<TcpStream>,
+    inner: BufStream<MseStream<TcpStream>>,
+    head: Vec<u8>,
+    recorder: Option<Arc<Recorder>>,
+    limits: Limits,
+    last_sent: Instant,
+    last_recieved: Instant,
+    keep_alive: Option<Duration>,
+    idle_timeout: Option<Duration>,
 }
 
 impl Connection {
+    /// Interval of keep-alives, which is short enough for peers, that drop connections after two minutes
+    /// of silence.
+    pub const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(90);
+    /// Shorter intervals of keep-alives are raised to this one, so connection isn't flooded with them.
+    pub const MIN_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(1);
+
     fn new(tcp: TcpStream) -> Self {
+        Self::with_stream(MseStream::plaintext(tcp))
+    }
+
+    fn with_stream(stream: MseStream<TcpStream>) -> Self {
         Self {
-            inner: BufStream::new(tcp),
+            inner: BufStream::new(stream),
+            head: Vec::new(),
+            recorder: None,
+            limits: Limits::default(),
+            last_sent: Instant::now(),
+            last_recieved: Instant::now(),
+            keep_alive: None,
+            idle_timeout: None,
         }
     }
 
+    /// Accepts incoming connection for one of torrents with `info_hashes`, if it's allowed by `policy`.
+    ///
+    /// Peers, which start with plaintext handshake, are told from ones, which start with MSE, by first bytes
+    /// of stream. Returns info hash of torrent, peer connected for, if it's already known from MSE handshake.
+    /// Otherwise it's sent in peer's handshake, which should be recieved next.
+    ///
+    /// Fails with [`io::ErrorKind::PermissionDenied`] if peer doesn't respect `policy`.
+    pub fn accept(
+        tcp: TcpStream,
+        info_hashes: &[[u8; 20]],
+        policy: EncryptionPolicy,
+    ) -> io::Result<(Self, Option<[u8; 20]>)> {
+        let plaintext = utils::starts_with_handshake(&tcp)?;
+
+        match (plaintext, policy) {
+            (true, EncryptionPolicy::Required) => Err(utils::denied("peer didn't encrypt connection")),
+            (false, EncryptionPolicy::Disabled) => Err(utils::denied("peer encrypted connection")),
+            (true, _) => Ok((Self::new(tcp), None)),
+            (false, _) => {
+                let (connection, info_hash) = Self::accept_encrypted(tcp, info_hashes, policy.accept())?;
+                Ok((connection, Some(info_hash)))
+            }
+        }
+    }
+
+    /// Accepts incoming connection, obfuscated with [MSE](`MseStream`), for one of torrents with `info_hashes`.
+    /// Returns info hash of torrent, peer connected for.
+    pub fn accept_encrypted(
+        tcp: TcpStream,
+        info_hashes: &[[u8; 20]],
+        accept: &[CryptoMethod],
+    ) -> io::Result<(Self, [u8; 20])> {
+        let (stream, info_hash) = MseStream::respond(tcp, info_hashes, accept)?;
+        Ok((Self::with_stream(stream), info_hash))
+    }
+
+    /// Method, payload is encrypted with. Connections without MSE are plaintext.
+    pub fn crypto_method(&self) -> CryptoMethod {
+        self.inner.get_ref().method()
+    }
+
     /// Attempts to send specified message to peer. See [`P2PSend`]
+    ///
+    /// Large payloads (i.e. `block` of [`Piece`](`messages::Piece`)) are written to socket
+    /// together with message header using vectored I/O, without being copied into send buffer.
     pub fn send<S: Send>(&mut self, message: &S) -> io::Result<()> {
-        message.send_to(&mut self.inner)?;
-        self.inner.flush()
+        let result = match &self.recorder {
+            None => gather::send(&mut self.inner, &mut self.head, message),
+            Some(recorder) => {
+                let recorded = capture::Recorded::new(message);
+                let result = gather::send(&mut self.inner, &mut self.head, &recorded);
+                utils::record(recorder, Direction::Sent, recorded.into_bytes());
+
+                result
+            }
+        };
+
+        if result.is_ok() {
+            self.last_sent = Instant::now();
+        }
+
+        result
     }
 
     ///Attempts to recieve message from peer, discarding residual bytes, if message failed to parse (see [`Recv`]).
-    pub fn recv<R: Recv>(&mut self) -> messages::Result<R> {
-        R::recv_from(&mut self.inner)
+    ///
+    /// Connection can be used further after [recoverable](`messages::Error::is_recoverable`) errors.
+    /// While waiting for message, keep-alives are sent and idle timeout is checked, if they're enabled.
+    /// Keep-alive of peer is recieved as `Ok(None)`.
+    pub fn recv<R: Recv>(&mut self) -> messages::Result<Option<R>> {
+        self.wait_readable()?;
+
+        let result = match &self.recorder {
+            None => R::recv_limited_from(&mut self.inner, &self.limits),
+            Some(recorder) => {
+                let mut frame = vec![];
+                let result = R::recv_limited_from(&mut capture::Tee::new(&mut self.inner, &mut frame), &self.limits);
+                utils::record(recorder, Direction::Recieved, frame);
+
+                result
+            }
+        };
+
+        self.update_recieved(&result);
+        result
+    }
+
+    /// Same as [`recv()`](`Connection::recv`), but reads message into `buf`, which can be reused between calls,
+    /// and borrows payload from there (see [`RecvInto`]).
+    pub fn recv_into<'a, R: RecvInto<'a>>(&mut self, buf: &'a mut Vec<u8>) -> messages::Result<Option<R>> {
+        self.wait_readable()?;
+
+        let result = match &self.recorder {
+            None => R::recv_limited_into(&mut self.inner, buf, &self.limits),
+            Some(recorder) => {
+                let mut frame = vec![];
+                let result =
+                    R::recv_limited_into(&mut capture::Tee::new(&mut self.inner, &mut frame), buf, &self.limits);
+                utils::record(recorder, Direction::Recieved, frame);
+
+                result
+            }
+        };
+
+        self.update_recieved(&result);
+        result
+    }
+
+    /// Recieves handshake of peer, which, unlike other messages, can't be keep-alive.
+    pub fn recv_handshake(&mut self) -> messages::Result<Handshake> {
+        self.recv::<Handshake>().map(|handshake| handshake.expect("handshake is never keep-alive"))
+    }
+
+    /// Makes [`recv()`](`Connection::recv`) send keep-alive, once nothing was sent for `interval`
+    /// (i.e. [`Connection::KEEP_ALIVE_INTERVAL`]), `None` disables keep-alives.
+    ///
+    /// Keep-alives shouldn't be enabled before handshakes are exchanged. `interval` is clamped to
+    /// [`Connection::MIN_KEEP_ALIVE_INTERVAL`].
+    pub fn set_keep_alive(&mut self, interval: Option<Duration>) {
+        self.keep_alive = interval.map(|interval| interval.max(Self::MIN_KEEP_ALIVE_INTERVAL));
+    }
+
+    /// Makes [`recv()`](`Connection::recv`) fail with [`io::ErrorKind::TimedOut`], once nothing was recieved
+    /// for `timeout`, so dead peers can be dropped. `None` lets peer stay silent forever.
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.idle_timeout = timeout;
+    }
+
+    /// Time of last message (including handshake and keep-alives), which was sent successfully.
+    pub fn last_sent(&self) -> Instant {
+        self.last_sent
+    }
+
+    /// Time of last message (including handshake, keep-alives and messages of unknown types),
+    /// which was recieved.
+    pub fn last_recieved(&self) -> Instant {
+        self.last_recieved
+    }
+
+    /// `true` if nothing was recieved for [idle timeout](`Connection::set_idle_timeout`).
+    pub fn is_idle(&self) -> bool {
+        self.idle_timeout.is_some_and(|timeout| self.last_recieved.elapsed() >= timeout)
+    }
+
+    /// Records messages of connection with `recorder`, `None` stops recording.
+    pub fn set_recorder(&mut self, recorder: Option<Arc<Recorder>>) {
+        self.recorder = recorder;
+    }
+
+    /// Limits sizes of recieved messages. Messages, which exceed them, fail with
+    /// [`messages::Error::Violation`] without being read, so connection should be closed.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.tcp().peer_addr()
+    }
+
+    /// Limits duration of every single read and write, `None` lets them block forever.
+    ///
+    /// Expired message exchange leaves connection in unknown state, so it should be closed.
+    pub fn set_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.tcp().set_read_timeout(timeout)?;
+        self.tcp().set_write_timeout(timeout)
+    }
+
+    /// Makes connection shut down, when `token` is cancelled, until returned guard is dropped.
+    #[cfg(feature = "use-serde")]
+    pub(crate) fn register<'a>(&self, token: &'a CancelToken) -> io::Result<cancel::Registration<'a>> {
+        token.register(self.tcp())
+    }
+
+    fn initiate(
+        tcp: TcpStream,
+        info_hash: &[u8; 20],
+        provide: &[CryptoMethod],
+        deadline: Option<Instant>,
+        cancel: Option<&CancelToken>,
+    ) -> io::Result<Self> {
+        let _registration = cancel.map(|token| token.register(&tcp)).transpose()?;
+
+        // Timeouts are reset after handshake exchange, which follows
+        if let Some(deadline) = deadline {
+            let timeout = utils::remaining(deadline)?;
+            tcp.set_read_timeout(Some(timeout))?;
+            tcp.set_write_timeout(Some(timeout))?;
+        }
+
+        MseStream::initiate(tcp, info_hash, provide)
+            .map(Self::with_stream)
+            .map_err(|err| utils::map_err(err, cancel))
+    }
+
+    fn tcp(&self) -> &TcpStream {
+        self.inner.get_ref().get_ref()
+    }
+
+    fn update_recieved<T>(&mut self, result: &messages::Result<T>) {
+        if result.as_ref().map_or_else(messages::Error::is_recoverable, |_| true) {
+            self.last_recieved = Instant::now();
+        }
+    }
+
+    /// Waits until peer sends something, without consuming it, if keep-alives or idle timeout are enabled.
+    /// Read timeout of socket limits the whole wait.
+    fn wait_readable(&mut self) -> io::Result<()> {
+        if self.keep_alive.is_none() && self.idle_timeout.is_none() {
+            return Ok(());
+        }
+
+        let timeout = self.tcp().read_timeout()?;
+        let result = self.wait_readable_until(timeout.map(|timeout| Instant::now() + timeout));
+        self.tcp().set_read_timeout(timeout)?;
+
+        result
+    }
+
+    fn wait_readable_until(&mut self, deadline: Option<Instant>) -> io::Result<()> {
+        loop {
+            if self.is_idle() {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "peer is idle"));
+            }
+            if let Some(deadline) = deadline {
+                utils::remaining(deadline)?;
+            }
+
+            if self.keep_alive.is_some_and(|interval| self.last_sent.elapsed() >= interval) {
+                self.send::<Keepalive>(&())?;
+            }
+
+            let wake = [
+                self.keep_alive.map(|interval| self.last_sent + interval),
+                self.idle_timeout.map(|timeout| self.last_recieved + timeout),
+                deadline,
+            ];
+            let wake = wake.into_iter().flatten().min().expect("either timer is enabled");
+            let Some(wait) = wake.checked_duration_since(Instant::now()).filter(|wait| !wait.is_zero()) else {
+                continue;
+            };
+            self.tcp().set_read_timeout(Some(wait))?;
+
+            // Buffered read doesn't lose anything, if it times out
+            match self.inner.fill_buf() {
+                Ok(_) => return Ok(()),
+                Err(err) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn exchange_handshakes(
+        &mut self,
+        handshake: &Handshake,
+        deadline: Option<Instant>,
+        cancel: Option<&CancelToken>,
+    ) -> messages::Result<Handshake> {
+        let _registration = cancel.map(|token| token.register(self.tcp())).transpose()?;
+
+        if let Some(deadline) = deadline {
+            let timeout = utils::remaining(deadline)?;
+            self.tcp().set_read_timeout(Some(timeout))?;
+            self.tcp().set_write_timeout(Some(timeout))?;
+        }
+
+        let result = self.send(handshake).map_err(messages::Error::from).and_then(|_| self.recv_handshake());
+
+        if deadline.is_some() {
+            self.tcp().set_read_timeout(None)?;
+            self.tcp().set_write_timeout(None)?;
+        }
+
+        result.map_err(|err| err.map_io(|err| utils::map_err(err, cancel)))
+    }
+}
+
+mod utils {
+    use std::{
+        io,
+        net::TcpStream,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::{cancel, CancelToken, Direction, Recorder};
+
+    pub fn remaining(deadline: Instant) -> io::Result<Duration> {
+        deadline
+            .checked_duration_since(Instant::now())
+            .filter(|remaining| !remaining.is_zero())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "deadline has passed"))
+    }
+
+    pub fn map_err(err: io::Error, cancel: Option<&CancelToken>) -> io::Error {
+        match err.kind() {
+            _ if cancel.is_some_and(CancelToken::is_cancelled) => cancel::cancelled(),
+            // Platforms differ in which kind is reported for expired socket timeout
+            io::ErrorKind::WouldBlock => io::Error::new(io::ErrorKind::TimedOut, err),
+            _ => err,
+        }
+    }
+
+    /// Records non-empty `frame`. Failed recording doesn't affect connection.
+    pub fn record(recorder: &Recorder, direction: Direction, frame: Vec<u8>) {
+        if !frame.is_empty() {
+            let _ = recorder.record(direction, frame);
+        }
+    }
+
+    /// `true` if error means, that peer rejected connection attempt, rather than it can't be reached.
+    pub fn is_refusal(err: &io::Error) -> bool {
+        matches!(
+            err.kind(),
+            io::ErrorKind::InvalidData
+                | io::ErrorKind::UnexpectedEof
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+        )
+    }
+
+    /// Peeks into incoming stream to check, whether it starts with plaintext handshake.
+    ///
+    /// Peeking returns the same partial header, even once stream is shut down, so waiting for the rest of it
+    /// is limited by read timeout of `tcp` as a whole.
+    pub fn starts_with_handshake(tcp: &TcpStream) -> io::Result<bool> {
+        const HEADER: &[u8] = b"\x13BitTorrent protocol";
+        let mut buf = [0; HEADER.len()];
+        let deadline = tcp.read_timeout()?.map(|timeout| Instant::now() + timeout);
+
+        loop {
+            let peeked = tcp.peek(&mut buf)?;
+
+            if peeked == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            } else if !HEADER.starts_with(&buf[..peeked]) {
+                return Ok(false);
+            } else if peeked == HEADER.len() {
+                return Ok(true);
+            }
+
+            // Header is split between segments, wait for the rest of it
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    pub fn denied(message: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::PermissionDenied, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, thread, time::Duration};
+
+    fn silent_listener() -> (TcpListener, (String, u16)) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        (listener, ("127.0.0.1".to_owned(), port))
+    }
+
+    #[test]
+    fn handshake_deadline_expires() {
+        let (_listener, addr) = silent_listener();
+        let deadline = Instant::now() + Duration::from_millis(100);
+
+        let err = Peer::new(addr)
+            .handshake_with_deadline(Handshake::default(), deadline)
+            .err()
+            .unwrap();
+
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn cancel_aborts_handshake() {
+        let (_listener, addr) = silent_listener();
+        let token = CancelToken::new();
+        let mut peer = Peer::new(addr).with_cancel_token(token.clone());
+
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            token.cancel();
+        });
+
+        let err = peer.handshake(Handshake::default()).err().unwrap();
+        canceller.join().unwrap();
+
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::Interrupted);
+    }
+
+    /// Listener, which never accepts and whose backlog is full, so further connects to it stay pending.
+    fn full_listener() -> (Socket, Vec<TcpStream>, (String, u16)) {
+        let listener = Socket::new(Domain::IPV4, Type::STREAM, Some(Protocol::TCP)).unwrap();
+        listener.bind(&SocketAddr::from(([127, 0, 0, 1], 0)).into()).unwrap();
+        listener.listen(0).unwrap();
+        let local = listener.local_addr().unwrap().as_socket().unwrap();
+
+        let mut queued = vec![];
+        while let Ok(tcp) = TcpStream::connect_timeout(&local, Duration::from_millis(100)) {
+            queued.push(tcp);
+        }
+
+        (listener, queued, ("127.0.0.1".to_owned(), local.port()))
+    }
+
+    #[test]
+    fn cancel_aborts_connect() {
+        let (_listener, _queued, addr) = full_listener();
+        let token = CancelToken::new();
+        let mut peer = Peer::new(addr).with_cancel_token(token.clone());
+
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            token.cancel();
+        });
+
+        let started = Instant::now();
+        let err = peer.connect().err().unwrap();
+        canceller.join().unwrap();
+
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    fn echo_handshake(listener: TcpListener, info_hashes: &[[u8; 20]], policy: EncryptionPolicy) -> Option<[u8; 20]> {
+        let (tcp, _) = listener.accept().unwrap();
+        let (mut connection, info_hash) = Connection::accept(tcp, info_hashes, policy).unwrap();
+
+        let handshake = connection.recv_handshake().unwrap();
+        connection.send(&handshake).unwrap();
+
+        info_hash
+    }
+
+    #[test]
+    fn handshakes_are_recorded() {
+        let (listener, addr) = silent_listener();
+        let server = thread::spawn(move || echo_handshake(listener, &[], EncryptionPolicy::Disabled));
+        let (recorder, frames) = Recorder::channel();
+
+        let handshake = Handshake::builder([0; 20], [7; 20]).build();
+        Peer::new(addr)
+            .with_encryption(EncryptionPolicy::Disabled)
+            .with_recorder(Arc::new(recorder))
+            .handshake(&handshake)
+            .unwrap();
+        server.join().unwrap();
+
+        let frames = frames.try_iter().collect::<Vec<_>>();
+        assert_eq!(frames.len(), 2);
+        assert_eq!((frames[0].direction, frames[1].direction), (Direction::Sent, Direction::Recieved));
+        assert_eq!(frames[0].bytes, frames[1].bytes);
+        assert_eq!(frames[1].decode::<Handshake>().unwrap(), Some(handshake));
+    }
+
+    #[test]
+    fn encryption_falls_back_to_plaintext() {
+        let (listener, addr) = silent_listener();
+
+        let server = thread::spawn(move || {
+            let (tcp, _) = listener.accept().unwrap();
+            let err = Connection::accept(tcp, &[], EncryptionPolicy::Disabled).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+
+            echo_handshake(listener, &[], EncryptionPolicy::Disabled)
+        });
+
+        let (connection, _) = Peer::new(addr).handshake(Handshake::default()).unwrap();
+
+        assert_eq!(connection.crypto_method(), CryptoMethod::Plaintext);
+        assert_eq!(server.join().unwrap(), None);
+    }
+
+    #[test]
+    fn required_encryption_is_accepted() {
+        let (listener, addr) = silent_listener();
+        let server = thread::spawn(move || echo_handshake(listener, &[[0; 20]], EncryptionPolicy::Enabled));
+
+        let (connection, _) = Peer::new(addr)
+            .with_encryption(EncryptionPolicy::Required)
+            .handshake(Handshake::default())
+            .unwrap();
+
+        assert_eq!(connection.crypto_method(), CryptoMethod::Rc4);
+        assert_eq!(server.join().unwrap(), Some([0; 20]));
+    }
+
+    #[test]
+    fn keep_alives_are_sent_until_peer_is_idle() {
+        let (listener, addr) = silent_listener();
+        let server = thread::spawn(move || {
+            let (mut tcp, _) = listener.accept().unwrap();
+            let mut recieved = vec![];
+            io::Read::read_to_end(&mut tcp, &mut recieved).unwrap();
+
+            recieved
+        });
+
+        let mut connection = Connection::new(TcpStream::connect((addr.0.as_str(), addr.1)).unwrap());
+        // Zero interval is clamped, instead of sending keep-alives back to back
+        connection.set_keep_alive(Some(Duration::ZERO));
+        connection.set_idle_timeout(Some(Duration::from_millis(1500)));
+
+        let err = connection.recv::<messages::Message>().err().unwrap();
+        assert!(connection.is_idle());
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::TimedOut);
+        assert!(connection.last_sent() > connection.last_recieved());
+        drop(connection);
+
+        let recieved = server.join().unwrap();
+        assert_eq!(recieved, [0; 4]);
     }
 }
+
@@ -0,0 +1,273 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{
+    io::{AsyncRead, AsyncWrite},
+    Sink, Stream,
+};
+
+use crate::messages::{Limits, Message, Recv, Send};
+
+/// Connection to peer over any asynchronous transport `T` (i.e. TCP stream of chosen runtime).
+///
+/// Incoming messages are read as [`Stream`] of [`Message`]s, so they can be consumed with combinators
+/// and `select!` loops. Keep-alives and messages of unknown types are skipped, as with
+/// [`Connection::recv`](`super::Connection::recv`). Stream ends, when peer closes connection
+/// between messages.
+///
+/// Outgoing messages are sent through [`Sink`]. Sent messages are buffered and written to transport
+/// only on flush or when buffer exceeds [`Self::SEND_BUFFER_SIZE`], so consumer can batch small messages
+/// and has to flush (i.e. with `SinkExt::send`) to make sure they reach peer.
+#[derive(Debug)]
+pub struct AsyncConnection<T> {
+    inner: T,
+    /// Recieved bytes of incomplete frame.
+    read_buf: Vec<u8>,
+    /// Encoded messages, which are not yet written to transport.
+    write_buf: Vec<u8>,
+    limits: Limits,
+}
+
+impl<T> AsyncConnection<T> {
+    /// Amount of buffered outgoing bytes, after which sink is not ready until they are written.
+    pub const SEND_BUFFER_SIZE: usize = 1 << 16;
+    const READ_CHUNK: usize = 1 << 14;
+
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            read_buf: Vec::new(),
+            write_buf: Vec::new(),
+            limits: Limits::default(),
+        }
+    }
+
+    /// Limits sizes of recieved messages. Frames, which exceed them, fail with [`io::ErrorKind::InvalidData`]
+    /// instead of being buffered, so connection should be closed.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Returns underlying transport. Bytes of partially recieved message and unflushed messages are lost.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncConnection<T> {
+    /// Reads until the whole frame is buffered, returning its length with prefix.
+    /// `None` means transport was closed before the next frame.
+    fn poll_frame(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<Option<usize>>> {
+        loop {
+            if let Some(length) = utils::frame_length(&self.read_buf, &self.limits)? {
+                if self.read_buf.len() >= length {
+                    return Poll::Ready(Ok(Some(length)));
+                }
+            }
+
+            let filled = self.read_buf.len();
+            self.read_buf.resize(filled + Self::READ_CHUNK, 0);
+
+            let result = Pin::new(&mut self.inner).poll_read(cx, &mut self.read_buf[filled..]);
+            let read = match result {
+                Poll::Ready(Ok(read)) => read,
+                Poll::Ready(Err(err)) => {
+                    self.read_buf.truncate(filled);
+                    return Poll::Ready(Err(err));
+                }
+                Poll::Pending => {
+                    self.read_buf.truncate(filled);
+                    return Poll::Pending;
+                }
+            };
+
+            self.read_buf.truncate(filled + read);
+
+            if read == 0 {
+                return Poll::Ready(match filled {
+                    0 => Ok(None),
+                    _ => Err(io::ErrorKind::UnexpectedEof.into()),
+                });
+            }
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> Stream for AsyncConnection<T> {
+    type Item = io::Result<Message>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        loop {
+            let length = match this.poll_frame(cx) {
+                Poll::Ready(Ok(Some(length))) => length,
+                Poll::Ready(Ok(None)) => return Poll::Ready(None),
+                Poll::Ready(Err(err)) => return Poll::Ready(Some(Err(err))),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            let message = Message::recv_limited_from(&mut &this.read_buf[..length], &this.limits);
+            this.read_buf.drain(..length);
+
+            match message {
+                Ok(Some(message)) => return Poll::Ready(Some(Ok(message))),
+                Ok(None) => continue,
+                // Unknown or malformed message, already consumed as a whole
+                Err(err) if err.is_recoverable() => continue,
+                Err(err) => return Poll::Ready(Some(Err(err.into()))),
+            }
+        }
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncConnection<T> {
+    /// Writes out all buffered messages, without flushing transport itself.
+    fn poll_write_buf(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut written = 0;
+
+        let result = loop {
+            if written == self.write_buf.len() {
+                break Poll::Ready(Ok(()));
+            }
+
+            match Pin::new(&mut self.inner).poll_write(cx, &self.write_buf[written..]) {
+                Poll::Ready(Ok(0)) => break Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(n)) => written += n,
+                Poll::Ready(Err(err)) => break Poll::Ready(Err(err)),
+                Poll::Pending => break Poll::Pending,
+            }
+        };
+
+        self.write_buf.drain(..written);
+        result
+    }
+}
+
+impl<T: AsyncWrite + Unpin, M: Send> Sink<M> for AsyncConnection<T> {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if this.write_buf.len() < Self::SEND_BUFFER_SIZE {
+            return Poll::Ready(Ok(()));
+        }
+
+        this.poll_write_buf(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, message: M) -> io::Result<()> {
+        message.send_to(&mut self.get_mut().write_buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        match this.poll_write_buf(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_flush(cx),
+            other => other,
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        match this.poll_write_buf(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut this.inner).poll_close(cx),
+            other => other,
+        }
+    }
+}
+
+mod utils {
+    use super::*;
+
+    /// Length of frame with its 4-byte prefix, if prefix is already recieved.
+    pub fn frame_length(buf: &[u8], limits: &Limits) -> io::Result<Option<usize>> {
+        let Some(prefix) = buf.get(..4) else { return Ok(None) };
+        let length = u32::from_be_bytes(prefix.try_into().unwrap()) as usize;
+        limits.check_frame(length)?;
+
+        Ok(Some(length + 4))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::{Have, Request};
+    use futures::{executor::block_on, io::Cursor, SinkExt, StreamExt};
+
+    #[test]
+    fn messages_are_streamed() {
+        let mut bytes = vec![];
+        Message::Unchoke.send_to(&mut bytes).unwrap();
+        // Keep-alive and message of unknown type
+        bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 2, 20, 0]);
+        Message::Have(Have { piece_index: 7 }).send_to(&mut bytes).unwrap();
+        Message::Request(Request {
+            piece_index: 1,
+            offset: 0,
+            data_length: 1 << 14,
+        })
+        .send_to(&mut bytes)
+        .unwrap();
+
+        let connection = AsyncConnection::new(Cursor::new(bytes));
+        let messages = block_on(connection.collect::<Vec<_>>());
+        let messages = messages.into_iter().collect::<io::Result<Vec<_>>>().unwrap();
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0], Message::Unchoke);
+        assert_eq!(messages[1], Message::Have(Have { piece_index: 7 }));
+    }
+
+    #[test]
+    fn truncated_message_fails() {
+        let mut connection = AsyncConnection::new(Cursor::new(vec![0, 0, 0, 5, 4, 0]));
+        let err = block_on(connection.next()).unwrap().unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn frame_exceeding_limits_fails() {
+        let mut connection = AsyncConnection::new(Cursor::new(vec![0, 0, 1, 0, 5]));
+        connection.set_limits(Limits {
+            max_frame_size: 255,
+            ..Limits::default()
+        });
+        let err = block_on(connection.next()).unwrap().unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn sent_messages_are_buffered_until_flush() {
+        let mut connection = AsyncConnection::new(Cursor::new(vec![]));
+
+        block_on(connection.feed(Message::Interested)).unwrap();
+        block_on(connection.feed(Message::Have(Have { piece_index: 3 }))).unwrap();
+        assert!(connection.get_ref().get_ref().is_empty());
+
+        block_on(SinkExt::<Message>::flush(&mut connection)).unwrap();
+
+        let bytes = connection.into_inner().into_inner();
+        let mut echo = AsyncConnection::new(Cursor::new(bytes));
+        let messages = block_on(echo.by_ref().map(Result::unwrap).collect::<Vec<_>>());
+
+        assert_eq!(messages, [Message::Interested, Message::Have(Have { piece_index: 3 })]);
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, Read, Write},
+    net::IpAddr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::bencoded::encoding::{borrowed, BDictionary, BEncode, Entry};
+use crate::bencoded::{BInt, BString};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BanReason {
+    /// Peer sent data of pieces, which failed hash check, too many times.
+    HashFailures,
+    /// Peer broke P2P protocol, i.e. sent oversized message.
+    ProtocolViolation,
+    /// Peer was banned by user.
+    Manual,
+}
+
+impl BanReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::HashFailures => "hash failures",
+            Self::ProtocolViolation => "protocol violation",
+            Self::Manual => "manual",
+        }
+    }
+
+    fn from_str(reason: &str) -> Option<Self> {
+        [Self::HashFailures, Self::ProtocolViolation, Self::Manual]
+            .into_iter()
+            .find(|known| known.as_str() == reason)
+    }
+}
+
+impl fmt::Display for BanReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ban {
+    pub reason: BanReason,
+    /// Time ban expires at, `None` for permanent ban.
+    pub until: Option<SystemTime>,
+}
+
+/// IP addresses of peers, which aren't connected to or accepted.
+///
+/// Peers are banned by IP rather than by port, so they can't evade ban by reconnecting. Peers, which sent
+/// data of failed pieces, are banned once they get [`Self::MAX_HASH_FAILURES`] strikes, as failed piece
+/// can have blocks of honest peers as well.
+#[derive(Debug, Clone, Default)]
+pub struct BanList {
+    bans: HashMap<IpAddr, Ban>,
+    strikes: HashMap<IpAddr, u32>,
+}
+
+impl BanList {
+    /// Duration of automatic bans.
+    pub const DEFAULT_DURATION: Duration = Duration::from_secs(60 * 60);
+    pub const MAX_HASH_FAILURES: u32 = 3;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bans `ip` until given time, or permanently. Replaces previous ban of `ip`.
+    pub fn ban(&mut self, ip: IpAddr, reason: BanReason, until: Option<SystemTime>) {
+        self.bans.insert(ip, Ban { reason, until });
+    }
+
+    /// Lifts ban of `ip` and clears its strikes, returning lifted ban.
+    pub fn unban(&mut self, ip: &IpAddr) -> Option<Ban> {
+        self.strikes.remove(ip);
+        self.bans.remove(ip)
+    }
+
+    /// Ban of `ip`, unless it's expired by `now`.
+    pub fn get(&self, ip: &IpAddr, now: SystemTime) -> Option<&Ban> {
+        self.bans.get(ip).filter(|ban| ban.until.is_none_or(|until| until > now))
+    }
+
+    pub fn is_banned(&self, ip: &IpAddr, now: SystemTime) -> bool {
+        self.get(ip, now).is_some()
+    }
+
+    /// Gives `ip` a strike for data of failed piece, banning it for [`Self::DEFAULT_DURATION`] once it has
+    /// [`Self::MAX_HASH_FAILURES`] strikes. Returns `true` if `ip` was banned.
+    pub fn record_hash_failure(&mut self, ip: IpAddr, now: SystemTime) -> bool {
+        let strikes = self.strikes.entry(ip).or_default();
+        *strikes += 1;
+
+        if *strikes < Self::MAX_HASH_FAILURES {
+            return false;
+        }
+
+        self.strikes.remove(&ip);
+        self.ban(ip, BanReason::HashFailures, Some(now + Self::DEFAULT_DURATION));
+        true
+    }
+
+    /// Removes bans, which are expired by `now`.
+    pub fn expire(&mut self, now: SystemTime) {
+        self.bans.retain(|_, ban| ban.until.is_none_or(|until| until > now));
+    }
+
+    /// All bans, including expired ones, which weren't removed yet.
+    pub fn iter(&self) -> impl Iterator<Item = (&IpAddr, &Ban)> {
+        self.bans.iter()
+    }
+
+    /// Loads bans, saved by [`save()`](`BanList::save`). Fails with [`io::ErrorKind::InvalidData`],
+    /// if data is malformed.
+    pub fn load(mut source: impl Read) -> io::Result<Self> {
+        let mut bytes = vec![];
+        source.read_to_end(&mut bytes)?;
+
+        let entry = borrowed::Entry::from_bytes(&bytes)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?
+            .to_owned_entry();
+        let entries: Vec<Entry> = entry.parse_or_err(utils::invalid("ban list"))?;
+
+        let bans = entries.into_iter().map(utils::parse).collect::<io::Result<_>>()?;
+
+        Ok(Self {
+            bans,
+            strikes: HashMap::new(),
+        })
+    }
+
+    /// Saves bans as bencoded list of dictionaries with `ip`, `reason` and `until` in seconds since UNIX epoch.
+    /// Strikes aren't saved.
+    pub fn save(&self, mut target: impl Write) -> io::Result<()> {
+        let entries = self.bans.iter().map(|(ip, ban)| utils::to_entry(ip, ban)).collect();
+        target.write_all(&Entry::List(entries).encode())
+    }
+}
+
+mod utils {
+    use super::*;
+
+    pub fn invalid(field: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, format!("invalid field `{}`", field))
+    }
+
+    pub fn parse(entry: Entry) -> io::Result<(IpAddr, Ban)> {
+        let mut ban: BDictionary = entry.parse_or_err(invalid("ban list"))?;
+        let mut take = |key: &str| ban.remove(key.as_bytes()).ok_or_else(|| invalid(key));
+
+        let ip: String = take("ip")?.parse_or_err(invalid("ip"))?;
+        let reason: String = take("reason")?.parse_or_err(invalid("reason"))?;
+        let until = match take("until") {
+            Ok(until) => Some(UNIX_EPOCH + Duration::from_secs(until.parse_or_err::<BInt, _>(invalid("until"))?)),
+            Err(_) => None,
+        };
+
+        Ok((
+            ip.parse().map_err(|_| invalid("ip"))?,
+            Ban {
+                reason: BanReason::from_str(&reason).ok_or_else(|| invalid("reason"))?,
+                until,
+            },
+        ))
+    }
+
+    pub fn to_entry(ip: &IpAddr, ban: &Ban) -> Entry {
+        let mut entry = BDictionary::new();
+        entry.insert(BString(b"ip".to_vec()), Entry::String(BString(ip.to_string().into_bytes())));
+        entry.insert(
+            BString(b"reason".to_vec()),
+            Entry::String(BString(ban.reason.as_str().as_bytes().to_vec())),
+        );
+
+        if let Some(until) = ban.until {
+            let until = until.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            entry.insert(BString(b"until".to_vec()), Entry::Integer(until));
+        }
+
+        Entry::Dictionary(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn bans_expire_and_persist() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let peer = IpAddr::from(Ipv4Addr::new(10, 0, 0, 1));
+        let manual = IpAddr::from(Ipv4Addr::new(10, 0, 0, 2));
+        let mut bans = BanList::new();
+
+        assert!(!bans.record_hash_failure(peer, now));
+        assert!(!bans.record_hash_failure(peer, now));
+        assert!(!bans.is_banned(&peer, now));
+        assert!(bans.record_hash_failure(peer, now));
+        bans.ban(manual, BanReason::Manual, None);
+
+        let mut saved = vec![];
+        bans.save(&mut saved).unwrap();
+        let loaded = BanList::load(&saved[..]).unwrap();
+
+        assert_eq!(loaded.get(&peer, now).unwrap().reason, BanReason::HashFailures);
+        assert!(!loaded.is_banned(&peer, now + BanList::DEFAULT_DURATION));
+        assert!(loaded.is_banned(&manual, now + BanList::DEFAULT_DURATION));
+
+        bans.expire(now + BanList::DEFAULT_DURATION);
+        assert_eq!(bans.iter().count(), 1);
+        assert!(bans.unban(&manual).is_some());
+        assert!(BanList::load(&b"l4:spame"[..]).is_err());
+    }
+}
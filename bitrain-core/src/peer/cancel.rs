@@ -0,0 +1,102 @@
+use std::{
+    io,
+    net::{Shutdown, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// Shared flag, which aborts pending connects and handshakes of every [`Peer`](`super::Peer`) it was given to.
+///
+/// Token is cheap to clone, all clones refer to the same state. Once cancelled, token stays cancelled
+/// forever, so stopped torrent should create a new token before restarting.
+///
+/// ### Note
+///
+/// Connects and handshakes are aborted by shutting down their sockets, which are registered with token
+/// for the time of operation, so no helper threads are needed. Platforms, on which shutdown doesn't interrupt
+/// pending connect, still release caller once connect times out.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    next_id: AtomicU64,
+    streams: Mutex<Vec<(u64, TcpStream)>>,
+}
+
+impl CancelToken {
+    /// How often cancellation is checked by loops, which can't be woken up by shutdown (i.e. of DHT node).
+    pub const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels all operations, that are currently pending or will be started with this token.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+
+        let streams = std::mem::take(&mut *self.inner.streams.lock().unwrap());
+        for (_, stream) in streams {
+            // Stream may be already closed by peer, nothing to do about it
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns [`io::ErrorKind::Interrupted`] error if token is cancelled.
+    pub fn check(&self) -> io::Result<()> {
+        if self.is_cancelled() {
+            Err(cancelled())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Makes `stream` shut down on cancellation until returned guard is dropped.
+    pub(crate) fn register(&self, stream: &TcpStream) -> io::Result<Registration<'_>> {
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
+        self.inner
+            .streams
+            .lock()
+            .unwrap()
+            .push((id, stream.try_clone()?));
+
+        // Token could be cancelled between check and registration
+        if let Err(err) = self.check() {
+            let _ = stream.shutdown(Shutdown::Both);
+            return Err(err);
+        }
+
+        Ok(Registration { token: self, id })
+    }
+}
+
+pub(crate) struct Registration<'a> {
+    token: &'a CancelToken,
+    id: u64,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        self.token
+            .inner
+            .streams
+            .lock()
+            .unwrap()
+            .retain(|(id, _)| *id != self.id);
+    }
+}
+
+pub(crate) fn cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "operation cancelled")
+}
@@ -0,0 +1,219 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    io::{self, Read, Write},
+    sync::{mpsc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use byteorder::{NetworkEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::messages::{self, Recv, Send};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Recieved,
+}
+
+/// Message, which was sent or recieved over connection, as it's transmitted (after decryption of MSE).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub direction: Direction,
+    /// Time since UNIX epoch, with microsecond precision.
+    pub timestamp: Duration,
+    /// Bytes of message, including length prefix.
+    pub bytes: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(direction: Direction, bytes: Vec<u8>) -> Self {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+
+        Self {
+            direction,
+            timestamp: Duration::from_micros(timestamp.as_micros() as u64),
+            bytes,
+        }
+    }
+
+    /// Decodes frame as message of type `R`, i.e. [`Handshake`](`crate::messages::Handshake`) or
+    /// [`Message`](`crate::messages::Message`). Keep-alive is decoded as `Ok(None)`.
+    pub fn decode<R: Recv>(&self) -> messages::Result<Option<R>> {
+        R::recv_from(&mut &self.bytes[..])
+    }
+
+    /// Writes frame in capture format: direction (`0` for sent, `1` for recieved), timestamp in microseconds
+    /// as `u64`, length of frame as `u32` and frame itself. Integers are in network byte order.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_u8(self.direction as u8)?;
+        writer.write_u64::<NetworkEndian>(self.timestamp.as_micros() as u64)?;
+        writer.write_u32::<NetworkEndian>(self.bytes.len() as u32)?;
+        writer.write_all(&self.bytes)
+    }
+
+    /// Reads frame, written by [`write_to()`](`Frame::write_to`). Returns `None` at the end of capture.
+    pub fn read_from(reader: &mut impl Read) -> io::Result<Option<Self>> {
+        let direction = match reader.read_u8() {
+            Ok(0) => Direction::Sent,
+            Ok(1) => Direction::Recieved,
+            Ok(_) => return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid direction of frame")),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let timestamp = Duration::from_micros(reader.read_u64::<NetworkEndian>()?);
+        let mut bytes = vec![0; reader.read_u32::<NetworkEndian>()? as usize];
+        reader.read_exact(&mut bytes)?;
+
+        Ok(Some(Self {
+            direction,
+            timestamp,
+            bytes,
+        }))
+    }
+}
+
+enum Sink {
+    Writer(Box<dyn Write + std::marker::Send>),
+    Channel(mpsc::Sender<Frame>),
+}
+
+/// Recorder of every message, which is sent or recieved over connections it's given to
+/// (see [`Peer::with_recorder`](`super::Peer::with_recorder`)), for debugging of interoperability with other
+/// clients. Recorder is shared with `Arc`, frames of all connections are recorded in the order they occur.
+pub struct Recorder {
+    sink: Mutex<Sink>,
+}
+
+impl Recorder {
+    /// Writes frames to `writer` (i.e. file) in capture format, see [`Frame::write_to`].
+    /// Capture can be replayed with [`Frame::read_from`].
+    pub fn to_writer(writer: impl Write + std::marker::Send + 'static) -> Self {
+        Self {
+            sink: Mutex::new(Sink::Writer(Box::new(writer))),
+        }
+    }
+
+    /// Sends frames to returned channel.
+    pub fn channel() -> (Self, mpsc::Receiver<Frame>) {
+        let (sender, reciever) = mpsc::channel();
+        let recorder = Self {
+            sink: Mutex::new(Sink::Channel(sender)),
+        };
+
+        (recorder, reciever)
+    }
+
+    /// Records frame of `bytes`. Frames aren't recorded anymore, once channel reciever is dropped.
+    pub fn record(&self, direction: Direction, bytes: Vec<u8>) -> io::Result<()> {
+        let frame = Frame::new(direction, bytes);
+
+        match &mut *self.sink.lock().unwrap() {
+            Sink::Writer(writer) => {
+                frame.write_to(writer)?;
+                writer.flush()
+            }
+            Sink::Channel(sender) => {
+                let _ = sender.send(frame);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recorder").finish_non_exhaustive()
+    }
+}
+
+/// Stream, which copies bytes, read from or written to `inner`, into `copy`.
+pub struct Tee<'a, T> {
+    inner: &'a mut T,
+    copy: &'a mut Vec<u8>,
+}
+
+impl<'a, T> Tee<'a, T> {
+    pub fn new(inner: &'a mut T, copy: &'a mut Vec<u8>) -> Self {
+        Self { inner, copy }
+    }
+}
+
+impl<T: Read> Read for Tee<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.copy.extend_from_slice(&buf[..read]);
+
+        Ok(read)
+    }
+}
+
+impl<T: Write> Write for Tee<'_, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.copy.extend_from_slice(&buf[..written]);
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Message, which copies its bytes into `copy`, while being sent.
+pub struct Recorded<'a, S> {
+    message: &'a S,
+    copy: RefCell<Vec<u8>>,
+}
+
+impl<'a, S> Recorded<'a, S> {
+    pub fn new(message: &'a S) -> Self {
+        Self {
+            message,
+            copy: RefCell::default(),
+        }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.copy.into_inner()
+    }
+}
+
+impl<S: Send> Send for Recorded<'_, S> {
+    fn send_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.message.send_to(&mut Tee::new(writer, &mut self.copy.borrow_mut()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::{Have, Message};
+
+    #[test]
+    fn capture_is_replayed() {
+        let (recorder, frames) = Recorder::channel();
+        let mut bytes = vec![];
+        Message::Have(Have { piece_index: 5 }).send_to(&mut bytes).unwrap();
+        recorder.record(Direction::Recieved, bytes).unwrap();
+        recorder.record(Direction::Sent, vec![0, 0, 0, 0]).unwrap();
+
+        let mut capture = vec![];
+        for frame in frames.try_iter() {
+            frame.write_to(&mut capture).unwrap();
+        }
+
+        let mut reader = &capture[..];
+        let first = Frame::read_from(&mut reader).unwrap().unwrap();
+        let second = Frame::read_from(&mut reader).unwrap().unwrap();
+
+        assert_eq!(first.direction, Direction::Recieved);
+        assert_eq!(first.decode::<Message>().unwrap(), Some(Message::Have(Have { piece_index: 5 })));
+        assert_eq!(second.direction, Direction::Sent);
+        assert!(matches!(second.decode::<Message>(), Ok(None)));
+        assert!(second.timestamp >= first.timestamp);
+        assert!(Frame::read_from(&mut reader).unwrap().is_none());
+    }
+}
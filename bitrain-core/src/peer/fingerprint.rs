@@ -0,0 +1,185 @@
+use std::{borrow::Cow, fmt};
+
+use rand::{distributions::Alphanumeric, Rng};
+
+/// Prefix of peer ids of this crate in Azureus style: client `BR`, version `0.1.0`.
+pub const CLIENT_PREFIX: &[u8; 8] = b"-BR0100-";
+
+/// Generates peer id, which starts with Azureus-style `prefix` (i.e. [`CLIENT_PREFIX`]),
+/// followed by 12 random alphanumeric characters.
+pub fn generate_peer_id(prefix: &[u8; 8]) -> [u8; 20] {
+    let mut peer_id = [0; 20];
+    peer_id[..8].copy_from_slice(prefix);
+
+    for (byte, random) in peer_id[8..].iter_mut().zip(rand::thread_rng().sample_iter(Alphanumeric)) {
+        *byte = random;
+    }
+
+    peer_id
+}
+
+/// Client software of peer, identified by its peer id.
+///
+/// Peer ids are not verified in any way, so client can be trusted only as much as peer itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Client {
+    /// Name of client, or its two-letter code, if client is unknown.
+    pub name: Cow<'static, str>,
+    pub version: String,
+}
+
+impl Client {
+    /// Parses client from peer id in Azureus (`-qB4250-...`), Shadow (`T03I--...`) or Mainline (`M4-3-6--...`)
+    /// style. Returns `None`, if peer id follows none of them.
+    pub fn from_peer_id(peer_id: &[u8; 20]) -> Option<Self> {
+        utils::azureus(peer_id)
+            .or_else(|| utils::mainline(peer_id))
+            .or_else(|| utils::shadow(peer_id))
+    }
+}
+
+impl fmt::Display for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.name, self.version)
+    }
+}
+
+mod utils {
+    use super::*;
+
+    static AZUREUS_CLIENTS: &[(&str, &str)] = &[
+        ("AG", "Ares"),
+        ("AZ", "Vuze"),
+        ("BC", "BitComet"),
+        ("BI", "BiglyBT"),
+        ("BR", "Bitrain"),
+        ("BT", "BitTorrent"),
+        ("DE", "Deluge"),
+        ("FD", "Free Download Manager"),
+        ("KT", "KTorrent"),
+        ("LT", "libtorrent"),
+        ("lt", "rTorrent"),
+        ("qB", "qBittorrent"),
+        ("SD", "Thunder"),
+        ("TR", "Transmission"),
+        ("UM", "\u{b5}Torrent Mac"),
+        ("UT", "\u{b5}Torrent"),
+        ("UW", "\u{b5}Torrent Web"),
+        ("WW", "WebTorrent"),
+        ("XL", "Xunlei"),
+    ];
+
+    static SHADOW_CLIENTS: &[(u8, &str)] = &[
+        (b'A', "ABC"),
+        (b'O', "Osprey Permaseed"),
+        (b'Q', "BTQueue"),
+        (b'R', "Tribler"),
+        (b'S', "Shadow"),
+        (b'T', "BitTornado"),
+        (b'U', "UPnP NAT Bit Torrent"),
+    ];
+
+    /// `-XXVVVV-`, where `XX` is client code and `VVVV` is version.
+    pub fn azureus(peer_id: &[u8; 20]) -> Option<Client> {
+        let [b'-', code @ .., b'-'] = &peer_id[..8] else { return None };
+        let (code, version) = code.split_at(2);
+
+        if !code.iter().all(u8::is_ascii_alphabetic) || !version.iter().all(u8::is_ascii_alphanumeric) {
+            return None;
+        }
+
+        let code = std::str::from_utf8(code).ok()?;
+        let version = match (code, version) {
+            // Transmission puts minor version in two digits: `-TR2940-` is 2.94
+            ("TR", [major, minor @ .., b'0']) if version.iter().all(u8::is_ascii_digit) => {
+                format!("{}.{}", *major as char, std::str::from_utf8(minor).ok()?)
+            }
+            (_, [major, minor, patch, b'0']) => format!("{}.{}.{}", *major as char, *minor as char, *patch as char),
+            _ => version.iter().map(|c| (*c as char).to_string()).collect::<Vec<_>>().join("."),
+        };
+
+        let name = AZUREUS_CLIENTS
+            .iter()
+            .find(|(known, _)| *known == code)
+            .map_or_else(|| Cow::Owned(code.to_owned()), |(_, name)| Cow::Borrowed(*name));
+
+        Some(Client { name, version })
+    }
+
+    /// `M4-3-6--`: `M` followed by version numbers, separated and terminated with dashes.
+    pub fn mainline(peer_id: &[u8; 20]) -> Option<Client> {
+        let [b'M', version @ ..] = &peer_id[..8] else { return None };
+        let version = std::str::from_utf8(version).ok()?.trim_end_matches('-');
+        let numbers = version.split('-').collect::<Vec<_>>();
+
+        if numbers.len() != 3 || !numbers.iter().all(|n| !n.is_empty() && n.bytes().all(|c| c.is_ascii_digit())) {
+            return None;
+        }
+
+        Some(Client { name: Cow::Borrowed("BitTorrent"), version: numbers.join(".") })
+    }
+
+    /// `T03I--`: client letter, followed by version characters and dashes up to sixth byte.
+    pub fn shadow(peer_id: &[u8; 20]) -> Option<Client> {
+        let (_, name) = SHADOW_CLIENTS.iter().find(|(code, _)| *code == peer_id[0])?;
+        let version = &peer_id[1..6];
+        let len = version.iter().position(|c| *c == b'-').unwrap_or(version.len());
+
+        // Version is terminated with at least one dash
+        if len == 0 || len == version.len() || version[len..].iter().any(|c| *c != b'-') {
+            return None;
+        }
+
+        let version = version[..len]
+            .iter()
+            .map(|c| shadow_digit(*c).map(|digit| digit.to_string()))
+            .collect::<Option<Vec<_>>>()?
+            .join(".");
+
+        Some(Client { name: Cow::Borrowed(name), version })
+    }
+
+    fn shadow_digit(c: u8) -> Option<u8> {
+        match c {
+            b'0'..=b'9' => Some(c - b'0'),
+            b'A'..=b'Z' => Some(c - b'A' + 10),
+            b'a'..=b'z' => Some(c - b'a' + 36),
+            b'.' => Some(62),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn peer_id(prefix: &[u8]) -> [u8; 20] {
+        let mut peer_id = [b'x'; 20];
+        peer_id[..prefix.len()].copy_from_slice(prefix);
+        peer_id
+    }
+
+    #[rstest]
+    #[case::qbittorrent(b"-qB4250-", "qBittorrent 4.2.5")]
+    #[case::transmission(b"-TR2940-", "Transmission 2.94")]
+    #[case::utorrent(b"-UT355W-", "\u{b5}Torrent 3.5.5.W")]
+    #[case::unknown_azureus(b"-ZZ1234-", "ZZ 1.2.3.4")]
+    #[case::mainline(b"M4-20-8-", "BitTorrent 4.20.8")]
+    #[case::shadow(b"T03I--", "BitTornado 0.3.18")]
+    fn clients_are_recognized(#[case] prefix: &[u8], #[case] expected: &str) {
+        let client = Client::from_peer_id(&peer_id(prefix)).unwrap();
+
+        assert_eq!(client.to_string(), expected);
+    }
+
+    #[test]
+    fn generated_peer_id_is_recognized() {
+        let peer_id = generate_peer_id(CLIENT_PREFIX);
+
+        assert!(peer_id[8..].iter().all(u8::is_ascii_alphanumeric));
+        assert_eq!(Client::from_peer_id(&peer_id).unwrap().to_string(), "Bitrain 0.1.0");
+        assert_eq!(Client::from_peer_id(&[0xff; 20]), None);
+    }
+}
@@ -0,0 +1,127 @@
+use std::io::{self, IoSlice, Read, Write};
+
+use bufstream::BufStream;
+
+use crate::messages::Send;
+
+/// Writer, that collects small writes into `head` and passes large ones to `direct` writer
+/// together with collected bytes via single [`Write::write_vectored`] call.
+///
+/// This way big payloads (i.e. `block` of [`Piece`](`crate::messages::Piece`)) are never copied
+/// into intermediate buffers, while message header is still sent with the same syscall.
+///
+/// Caller is responsible for submitting residual `head` bytes with [`GatherWriter::finish`].
+pub struct GatherWriter<'a, W: Write> {
+    head: &'a mut Vec<u8>,
+    direct: &'a mut W,
+}
+
+impl<'a, W: Write> GatherWriter<'a, W> {
+    /// Writes larger than this are sent directly, without copying.
+    pub const DIRECT_WRITE_THRESHOLD: usize = 4 * 1024;
+
+    pub fn new(head: &'a mut Vec<u8>, direct: &'a mut W) -> Self {
+        head.clear();
+        Self { head, direct }
+    }
+
+    /// Writes all collected bytes to `direct` writer.
+    pub fn finish(self) -> io::Result<()> {
+        self.direct.write_all(self.head)?;
+        self.head.clear();
+
+        Ok(())
+    }
+}
+
+/// Sends `message` past buffer of `stream`, so payload of message is written to underlying stream
+/// in the same pass as its header, without being copied into buffer. `head` is reused between calls.
+pub fn send<S: Send, W: Read + Write>(stream: &mut BufStream<W>, head: &mut Vec<u8>, message: &S) -> io::Result<()> {
+    // Every send flushes, so nothing should be buffered, but ordering must be preserved anyway
+    stream.flush()?;
+
+    let mut writer = GatherWriter::new(head, stream.get_mut());
+    message.send_to(&mut writer)?;
+    writer.finish()
+}
+
+impl<W: Write> Write for GatherWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() < Self::DIRECT_WRITE_THRESHOLD {
+            self.head.extend_from_slice(buf);
+            return Ok(buf.len());
+        }
+
+        loop {
+            let written = self
+                .direct
+                .write_vectored(&[IoSlice::new(self.head), IoSlice::new(buf)])?;
+
+            if written == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            } else if written < self.head.len() {
+                self.head.drain(..written);
+            } else {
+                let from_buf = written - self.head.len();
+                self.head.clear();
+
+                if from_buf > 0 {
+                    return Ok(from_buf);
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::{Message, Piece, Send};
+
+    #[derive(Default)]
+    struct CountingWriter {
+        bytes: Vec<u8>,
+        calls: usize,
+    }
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.calls += 1;
+            self.bytes.write(buf)
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+            self.calls += 1;
+            self.bytes.write_vectored(bufs)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn piece_is_sent_with_single_write() {
+        let message = Message::Piece(Piece {
+            piece_index: 1,
+            offset: 16384,
+            data: vec![0xAB; 16384].into(),
+        });
+
+        let mut expected = vec![];
+        message.send_to(&mut expected).unwrap();
+
+        let mut head = vec![];
+        let mut direct = CountingWriter::default();
+        let mut writer = GatherWriter::new(&mut head, &mut direct);
+        message.send_to(&mut writer).unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(direct.bytes, expected);
+        assert_eq!(direct.calls, 1);
+    }
+}
@@ -0,0 +1,349 @@
+use std::{fmt, io, time::Duration};
+
+use bytes::Bytes;
+use sha1::{Digest, Sha1};
+
+use super::{Connection, EncryptionPolicy, Peer};
+use crate::bencoded::encoding::{borrowed, BDictionary, BEncode, Entry};
+use crate::bencoded::{BInt, BString, Info, Metainfo, Parser, Serde};
+use crate::messages::{self, Container, Extended, Handshake};
+
+/// Fetches metainfo of torrent, known only by its info-hash (i.e. from magnet link), from peers
+/// with `ut_metadata` extension (BEP 9).
+///
+/// Peers are tried one by one, until one of them serves `info` dictionary, which matches info-hash.
+/// Fetched metainfo has no trackers, consumer adds ones of magnet link, if any.
+///
+/// See <https://www.bittorrent.org/beps/bep_0009.html> for more info.
+#[derive(Debug, Clone)]
+pub struct MetadataFetcher {
+    info_hash: [u8; 20],
+    peer_id: [u8; 20],
+    timeout: Duration,
+    encryption: EncryptionPolicy,
+    max_size: usize,
+}
+
+type PeerAddr = (String, u16);
+
+#[derive(Debug)]
+pub enum MetadataError {
+    Io(io::Error),
+    Message(messages::Error),
+    /// Peer doesn't support extension protocol or `ut_metadata`, or doesn't have metadata itself.
+    Unsupported,
+    /// Peer refused to send piece of metadata.
+    Rejected { piece: usize },
+    /// Metadata size, announced by peer, exceeds limit.
+    TooLarge(usize),
+    /// Message of peer doesn't match BEP 9.
+    Malformed(&'static str),
+    /// Assembled metadata doesn't match info-hash.
+    HashMismatch,
+}
+
+impl MetadataFetcher {
+    /// Time, peer has to answer every message.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+    pub const DEFAULT_MAX_SIZE: usize = 1 << 24;
+    /// Size of metadata pieces, except the last one.
+    pub const PIECE_SIZE: usize = 1 << 14;
+    /// Id of `ut_metadata` messages, sent to us, which is advertised with our extended handshake.
+    pub const UT_METADATA_ID: u8 = 1;
+
+    pub fn new(info_hash: [u8; 20], peer_id: [u8; 20]) -> Self {
+        Self {
+            info_hash,
+            peer_id,
+            timeout: Self::DEFAULT_TIMEOUT,
+            encryption: EncryptionPolicy::default(),
+            max_size: Self::DEFAULT_MAX_SIZE,
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn encryption(mut self, encryption: EncryptionPolicy) -> Self {
+        self.encryption = encryption;
+        self
+    }
+
+    /// Sets limit of metadata size, peers may announce.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Tries `peers` in order, until one of them serves metadata.
+    ///
+    /// ## Errors
+    ///
+    /// If no peer succeeded, returns errors of all peers paired with their addresses.
+    pub fn fetch(
+        &self,
+        peers: impl IntoIterator<Item = PeerAddr>,
+    ) -> Result<Metainfo, Vec<(PeerAddr, MetadataError)>> {
+        let mut errors = vec![];
+
+        for addr in peers {
+            match self.fetch_from(addr.clone()) {
+                Ok(metainfo) => return Ok(metainfo),
+                Err(err) => errors.push((addr, err)),
+            }
+        }
+
+        Err(errors)
+    }
+
+    /// Connects to peer at `addr` and fetches metadata from it.
+    pub fn fetch_from(&self, addr: PeerAddr) -> Result<Metainfo, MetadataError> {
+        let handshake = Handshake::builder(self.info_hash, self.peer_id).extensions(true).build();
+        let (mut connection, recieved) = Peer::new(addr).with_encryption(self.encryption).handshake(handshake)?;
+
+        if !recieved.reserved.supports_extensions() {
+            return Err(MetadataError::Unsupported);
+        }
+
+        self.fetch_over(&mut connection)
+    }
+
+    /// Fetches metadata over `connection`, which completed handshake with extension protocol enabled.
+    pub fn fetch_over(&self, connection: &mut Connection) -> Result<Metainfo, MetadataError> {
+        connection.set_timeout(Some(self.timeout))?;
+        connection.send(&Container(&utils::handshake()))?;
+
+        let (peer_id, size) = loop {
+            let message = utils::recv(connection)?;
+            if message.id == Extended::HANDSHAKE_ID {
+                break utils::parse_handshake(&message.payload)?;
+            }
+        };
+
+        if size > self.max_size {
+            return Err(MetadataError::TooLarge(size));
+        }
+
+        let mut metadata = Vec::with_capacity(size);
+        for piece in 0..size.div_ceil(Self::PIECE_SIZE) {
+            connection.send(&Container(&utils::message(peer_id, 0, piece)))?;
+            let expected = Self::PIECE_SIZE.min(size - metadata.len());
+
+            let data = loop {
+                let message = utils::recv(connection)?;
+                if message.id != Self::UT_METADATA_ID {
+                    continue;
+                }
+
+                let (header, len) = borrowed::Entry::decode_prefix(&message.payload)
+                    .map_err(|_| MetadataError::Malformed("ut_metadata message"))?;
+                let header = header.to_owned_entry();
+                match header.get("msg_type").and_then(Entry::as_int) {
+                    // We have no metadata to share
+                    Some(0) => {
+                        let requested = header.get("piece").and_then(Entry::as_int).unwrap_or_default();
+                        connection.send(&Container(&utils::message(peer_id, 2, requested as usize)))?;
+                    }
+                    Some(1) if header.get("piece").and_then(Entry::as_int) == Some(piece as BInt) => {
+                        break message.payload.slice(len..);
+                    }
+                    Some(2) => return Err(MetadataError::Rejected { piece }),
+                    _ => {}
+                }
+            };
+
+            if data.len() != expected {
+                return Err(MetadataError::Malformed("size of metadata piece"));
+            }
+            metadata.extend_from_slice(&data);
+        }
+
+        if <[u8; 20]>::from(Sha1::digest(&metadata)) != self.info_hash {
+            return Err(MetadataError::HashMismatch);
+        }
+
+        let info: Info = Serde.parse(&metadata[..]).map_err(|_| MetadataError::Malformed("info"))?;
+        Ok(Metainfo::from_raw_info(info, BString(metadata)))
+    }
+}
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::Message(err) => write!(f, "{}", err),
+            Self::Unsupported => f.write_str("peer doesn't serve metadata"),
+            Self::Rejected { piece } => write!(f, "peer rejected metadata piece {}", piece),
+            Self::TooLarge(size) => write!(f, "metadata of {} bytes exceeds limit", size),
+            Self::Malformed(what) => write!(f, "malformed {}", what),
+            Self::HashMismatch => f.write_str("metadata doesn't match info-hash"),
+        }
+    }
+}
+
+impl std::error::Error for MetadataError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Message(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MetadataError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<messages::Error> for MetadataError {
+    fn from(err: messages::Error) -> Self {
+        Self::Message(err)
+    }
+}
+
+mod utils {
+    use super::*;
+
+    fn key(key: &str) -> BString {
+        BString(key.as_bytes().to_vec())
+    }
+
+    /// Extended handshake, which advertises `ut_metadata` support.
+    pub fn handshake() -> Extended {
+        let mut extensions = BDictionary::new();
+        extensions.insert(key("ut_metadata"), Entry::Integer(MetadataFetcher::UT_METADATA_ID as BInt));
+        let mut handshake = BDictionary::new();
+        handshake.insert(key("m"), Entry::Dictionary(extensions));
+
+        Extended {
+            id: Extended::HANDSHAKE_ID,
+            payload: Bytes::from(Entry::Dictionary(handshake).encode().into_vec()),
+        }
+    }
+
+    /// `ut_metadata` message without data of `msg_type` (0 for request, 2 for reject) for `piece`.
+    pub fn message(id: u8, msg_type: BInt, piece: usize) -> Extended {
+        let mut message = BDictionary::new();
+        message.insert(key("msg_type"), Entry::Integer(msg_type));
+        message.insert(key("piece"), Entry::Integer(piece as BInt));
+
+        Extended {
+            id,
+            payload: Bytes::from(Entry::Dictionary(message).encode().into_vec()),
+        }
+    }
+
+    /// Id of `ut_metadata` messages of peer and size of metadata, announced in its extended handshake.
+    pub fn parse_handshake(payload: &[u8]) -> Result<(u8, usize), MetadataError> {
+        let handshake = borrowed::Entry::from_bytes(payload)
+            .map_err(|_| MetadataError::Malformed("extended handshake"))?
+            .to_owned_entry();
+
+        let id = handshake.get_path("m.ut_metadata").and_then(Entry::as_int);
+        let size = handshake.get("metadata_size").and_then(Entry::as_int);
+
+        match (id, size) {
+            // Id 0 disables extension
+            (Some(id @ 1..=255), Some(size @ 1..)) => Ok((id as u8, size as usize)),
+            _ => Err(MetadataError::Unsupported),
+        }
+    }
+
+    /// Recieves next extended message, skipping other messages.
+    pub fn recv(connection: &mut Connection) -> Result<Extended, MetadataError> {
+        loop {
+            match connection.recv::<Container<Extended>>() {
+                Ok(Some(message)) => return Ok(message.into_inner()),
+                // Keep-alives and other messages
+                Ok(None) => continue,
+                Err(err) if err.is_recoverable() => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bencoded::Files;
+    use crate::test_utils;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Peer, which serves `metadata` over connection, accepted by `listener`.
+    fn serve_metadata(listener: TcpListener, info_hash: [u8; 20], metadata: Vec<u8>) {
+        let (tcp, _) = listener.accept().unwrap();
+        let (mut connection, _) = Connection::accept(tcp, &[info_hash], EncryptionPolicy::Enabled).unwrap();
+
+        let handshake = connection.recv_handshake().unwrap();
+        assert!(handshake.reserved.supports_extensions());
+        connection.send(&handshake).unwrap();
+
+        let ours = format!("d1:md11:ut_metadatai3ee13:metadata_sizei{}ee", metadata.len());
+        let payload = Bytes::from(ours.into_bytes());
+        connection.send(&Container(&Extended { id: 0, payload })).unwrap();
+
+        while let Ok(message) = connection.recv::<Container<Extended>>() {
+            let Some(message) = message.map(Container::into_inner) else { continue };
+            if message.id != 3 {
+                continue;
+            }
+
+            let request = borrowed::Entry::from_bytes(&message.payload).unwrap().to_owned_entry();
+            let piece = request.get("piece").and_then(Entry::as_int).unwrap() as usize;
+            let start = piece * MetadataFetcher::PIECE_SIZE;
+            let data = &metadata[start..metadata.len().min(start + MetadataFetcher::PIECE_SIZE)];
+
+            let mut payload = format!("d8:msg_typei1e5:piecei{}e10:total_sizei{}ee", piece, metadata.len()).into_bytes();
+            payload.extend_from_slice(data);
+            let reply = Extended {
+                id: MetadataFetcher::UT_METADATA_ID,
+                payload: payload.into(),
+            };
+            connection.send(&Container(&reply)).unwrap();
+        }
+    }
+
+    #[test]
+    fn metadata_is_fetched_and_verified() {
+        let files = Files::Single {
+            length: 2000 << 14,
+            md5sum: None,
+        };
+        let info = Info {
+            pieces: BString((0..20 * 2000).map(|i| i as u8).collect()),
+            ..test_utils::info(1 << 14, files)
+        };
+        let metadata = info.to_entry().encode().into_vec();
+        let info_hash = info.info_hash();
+        // Three metadata pieces, the last one is partial
+        assert_eq!(metadata.len().div_ceil(MetadataFetcher::PIECE_SIZE), 3);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || serve_metadata(listener, info_hash, metadata));
+
+        let fetcher = MetadataFetcher::new(info_hash, *b"-BR0001-metadatapeer");
+        // The first peer is unreachable
+        let metainfo = fetcher
+            .fetch([("127.0.0.1".to_owned(), 1), ("127.0.0.1".to_owned(), port)])
+            .unwrap();
+
+        assert_eq!(metainfo.info, info);
+        assert_eq!(metainfo.info_hash(), info_hash);
+        assert!(metainfo.announce.is_empty());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let forged = Info { private: Some(true), ..info };
+        thread::spawn(move || serve_metadata(listener, info_hash, forged.to_entry().encode().into_vec()));
+
+        let err = fetcher.fetch_from(("127.0.0.1".to_owned(), port)).unwrap_err();
+        assert!(matches!(err, MetadataError::HashMismatch), "{}", err);
+    }
+}
@@ -0,0 +1,518 @@
+use std::io::{self, IoSlice, Read, Write};
+
+use num_bigint::BigUint;
+use rand::Rng;
+use sha1::{Digest, Sha1};
+
+/// Size of Diffie-Hellman public key in bytes.
+const KEY_SIZE: usize = 96;
+/// Maximal length of random padding.
+const MAX_PAD: usize = 512;
+
+/// Method of encrypting payload stream after [MSE](`MseStream`) handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CryptoMethod {
+    /// Only handshake is obfuscated, payload is sent as is.
+    Plaintext,
+    /// Payload is encrypted with RC4.
+    Rc4,
+}
+
+impl CryptoMethod {
+    /// Bit of method in `crypto_provide` and `crypto_select` fields.
+    pub fn bit(self) -> u32 {
+        match self {
+            Self::Plaintext => 0x01,
+            Self::Rc4 => 0x02,
+        }
+    }
+}
+
+/// Whether connections are obfuscated with [MSE](`MseStream`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EncryptionPolicy {
+    /// Connections are plaintext, encrypted incoming connections are refused.
+    Disabled,
+    /// Outgoing connections try MSE first and fall back to plaintext, if peer doesn't support it.
+    /// Both plaintext and encrypted incoming connections are accepted.
+    #[default]
+    Enabled,
+    /// Only encrypted connections are made and accepted, payload is always encrypted with RC4.
+    Required,
+}
+
+impl EncryptionPolicy {
+    /// Methods, offered to peer on outgoing connections, in order of preference.
+    pub fn provide(self) -> &'static [CryptoMethod] {
+        match self {
+            Self::Disabled => &[],
+            Self::Enabled => &[CryptoMethod::Rc4, CryptoMethod::Plaintext],
+            Self::Required => &[CryptoMethod::Rc4],
+        }
+    }
+
+    /// Methods, accepted from peer on incoming connections, in order of preference.
+    pub fn accept(self) -> &'static [CryptoMethod] {
+        self.provide()
+    }
+}
+
+/// Stream, obfuscated with Message Stream Encryption (also known as Protocol Encryption).
+///
+/// Peers exchange Diffie-Hellman keys and derive RC4 keys from shared secret and info hash of torrent,
+/// after which they agree on [`CryptoMethod`] of payload stream. Encryption hides BitTorrent handshake
+/// from traffic shaping, which is why many peers refuse plaintext connections.
+///
+/// Stream with plaintext method or without handshake at all (see [`MseStream::plaintext`]) passes
+/// data as is. For more info see <https://wiki.vuze.com/w/Message_Stream_Encryption>.
+pub struct MseStream<S> {
+    inner: S,
+    method: CryptoMethod,
+    ciphers: Option<Ciphers>,
+    /// Decrypted initial payload of initiator, which is read before the rest of stream.
+    initial: Vec<u8>,
+    write_buf: Vec<u8>,
+}
+
+struct Ciphers {
+    encrypt: Rc4,
+    decrypt: Rc4,
+}
+
+impl<S> MseStream<S> {
+    /// Wraps `inner` stream without any encryption.
+    pub fn plaintext(inner: S) -> Self {
+        Self {
+            inner,
+            method: CryptoMethod::Plaintext,
+            ciphers: None,
+            initial: Vec::new(),
+            write_buf: Vec::new(),
+        }
+    }
+
+    pub fn method(&self) -> CryptoMethod {
+        self.method
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+}
+
+impl<S: Read + Write> MseStream<S> {
+    /// Performs handshake as connecting side, offering `provide` methods to peer, which seeds `info_hash`.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] if peer doesn't follow protocol or selects method, which
+    /// wasn't provided.
+    pub fn initiate(mut inner: S, info_hash: &[u8; 20], provide: &[CryptoMethod]) -> io::Result<Self> {
+        let keys = KeyPair::generate();
+
+        inner.write_all(&keys.public)?;
+        inner.write_all(&utils::pad())?;
+        inner.flush()?;
+
+        let mut remote = [0; KEY_SIZE];
+        inner.read_exact(&mut remote)?;
+        let secret = keys.shared_secret(&remote)?;
+
+        let mut encrypt = utils::cipher(&utils::hash(&[b"keyA", &secret, info_hash]));
+        let mut decrypt = utils::cipher(&utils::hash(&[b"keyB", &secret, info_hash]));
+
+        let mut header = vec![0; 8];
+        header.extend_from_slice(&utils::provide_bits(provide).to_be_bytes());
+        // Neither padding, nor initial payload are sent, handshake follows in payload stream
+        header.extend_from_slice(&[0, 0, 0, 0]);
+        encrypt.apply(&mut header);
+
+        inner.write_all(&utils::hash(&[b"req1", &secret]))?;
+        inner.write_all(&utils::xor(
+            utils::hash(&[b"req2", info_hash]),
+            utils::hash(&[b"req3", &secret]),
+        ))?;
+        inner.write_all(&header)?;
+        inner.flush()?;
+
+        // Verification constant is found by its encrypted form after peer's padding
+        let mut vc = [0; 8];
+        decrypt.apply(&mut vc);
+        utils::sync(&mut inner, &vc)?;
+
+        let mut select = [0; 6];
+        inner.read_exact(&mut select)?;
+        decrypt.apply(&mut select);
+
+        let bit = u32::from_be_bytes(select[..4].try_into().unwrap());
+        let method = provide
+            .iter()
+            .copied()
+            .find(|method| method.bit() == bit)
+            .ok_or_else(|| utils::invalid("peer selected crypto method, which wasn't provided"))?;
+
+        utils::skip(&mut inner, &mut decrypt, u16::from_be_bytes([select[4], select[5]]) as usize)?;
+
+        Ok(Self::finish(inner, method, encrypt, decrypt, Vec::new()))
+    }
+
+    /// Performs handshake as accepting side for torrents with `info_hashes`, selecting the first
+    /// of `accept` methods, which is provided by peer. Returns info hash of torrent, peer connected for.
+    pub fn respond(mut inner: S, info_hashes: &[[u8; 20]], accept: &[CryptoMethod]) -> io::Result<(Self, [u8; 20])> {
+        let mut remote = [0; KEY_SIZE];
+        inner.read_exact(&mut remote)?;
+
+        let keys = KeyPair::generate();
+        inner.write_all(&keys.public)?;
+        inner.write_all(&utils::pad())?;
+        inner.flush()?;
+
+        let secret = keys.shared_secret(&remote)?;
+        utils::sync(&mut inner, &utils::hash(&[b"req1", &secret]))?;
+
+        let mut obfuscated = [0; 20];
+        inner.read_exact(&mut obfuscated)?;
+        let req2 = utils::xor(obfuscated, utils::hash(&[b"req3", &secret]));
+
+        let info_hash = *info_hashes
+            .iter()
+            .find(|info_hash| utils::hash(&[b"req2", &info_hash[..]]) == req2)
+            .ok_or_else(|| utils::invalid("peer requested unknown torrent"))?;
+
+        let mut decrypt = utils::cipher(&utils::hash(&[b"keyA", &secret, &info_hash]));
+        let mut encrypt = utils::cipher(&utils::hash(&[b"keyB", &secret, &info_hash]));
+
+        let mut header = [0; 14];
+        inner.read_exact(&mut header)?;
+        decrypt.apply(&mut header);
+
+        if header[..8] != [0; 8] {
+            return Err(utils::invalid("invalid verification constant"));
+        }
+
+        let provide = u32::from_be_bytes(header[8..12].try_into().unwrap());
+        let method = accept
+            .iter()
+            .copied()
+            .find(|method| provide & method.bit() != 0)
+            .ok_or_else(|| utils::invalid("peer provided no acceptable crypto method"))?;
+
+        let pad_length = u16::from_be_bytes([header[12], header[13]]) as usize;
+        utils::skip(&mut inner, &mut decrypt, pad_length)?;
+
+        let mut length = [0; 2];
+        inner.read_exact(&mut length)?;
+        decrypt.apply(&mut length);
+
+        let mut initial = vec![0; u16::from_be_bytes(length) as usize];
+        inner.read_exact(&mut initial)?;
+        decrypt.apply(&mut initial);
+
+        let mut reply = vec![0; 8];
+        reply.extend_from_slice(&method.bit().to_be_bytes());
+        reply.extend_from_slice(&[0, 0]);
+        encrypt.apply(&mut reply);
+
+        inner.write_all(&reply)?;
+        inner.flush()?;
+
+        Ok((Self::finish(inner, method, encrypt, decrypt, initial), info_hash))
+    }
+
+    fn finish(inner: S, method: CryptoMethod, encrypt: Rc4, decrypt: Rc4, initial: Vec<u8>) -> Self {
+        let ciphers = match method {
+            CryptoMethod::Plaintext => None,
+            CryptoMethod::Rc4 => Some(Ciphers { encrypt, decrypt }),
+        };
+
+        Self {
+            inner,
+            method,
+            ciphers,
+            initial,
+            write_buf: Vec::new(),
+        }
+    }
+}
+
+impl<S: Read> Read for MseStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.initial.is_empty() {
+            let read = buf.len().min(self.initial.len());
+            buf[..read].copy_from_slice(&self.initial[..read]);
+            self.initial.drain(..read);
+
+            return Ok(read);
+        }
+
+        let read = self.inner.read(buf)?;
+
+        if let Some(ciphers) = &mut self.ciphers {
+            ciphers.decrypt.apply(&mut buf[..read]);
+        }
+
+        Ok(read)
+    }
+}
+
+impl<S: Write> Write for MseStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(ciphers) = &mut self.ciphers else { return self.inner.write(buf) };
+
+        // Keystream is advanced right away, so encrypted bytes have to be written as a whole
+        self.write_buf.clear();
+        self.write_buf.extend_from_slice(buf);
+        ciphers.encrypt.apply(&mut self.write_buf);
+        self.inner.write_all(&self.write_buf)?;
+
+        Ok(buf.len())
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        match self.ciphers {
+            None => self.inner.write_vectored(bufs),
+            Some(_) => {
+                let buf = bufs.iter().find(|buf| !buf.is_empty()).map_or(&[][..], |buf| &buf[..]);
+                self.write(buf)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<S> std::fmt::Debug for MseStream<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MseStream").field("method", &self.method).finish_non_exhaustive()
+    }
+}
+
+/// Diffie-Hellman key pair over 768-bit MSE prime with generator 2.
+struct KeyPair {
+    private: BigUint,
+    public: [u8; KEY_SIZE],
+}
+
+impl KeyPair {
+    const PRIME: [u8; KEY_SIZE] = utils::from_hex(
+        b"FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F14374FE1356D6D51C245E485B576625E7EC6F44C42E9A63A36210000000000090563",
+    );
+
+    fn generate() -> Self {
+        let private = BigUint::from_bytes_be(&rand::thread_rng().gen::<[u8; 20]>());
+        let public = BigUint::from(2u8).modpow(&private, &BigUint::from_bytes_be(&Self::PRIME));
+
+        Self {
+            private,
+            public: utils::to_key(&public),
+        }
+    }
+
+    fn shared_secret(&self, remote: &[u8; KEY_SIZE]) -> io::Result<[u8; KEY_SIZE]> {
+        let prime = BigUint::from_bytes_be(&Self::PRIME);
+        let remote = BigUint::from_bytes_be(remote);
+
+        // Trivial keys would make secret predictable
+        if remote <= BigUint::from(1u8) || remote >= &prime - 1u8 {
+            return Err(utils::invalid("invalid public key"));
+        }
+
+        Ok(utils::to_key(&remote.modpow(&self.private, &prime)))
+    }
+}
+
+/// RC4 keystream.
+#[derive(Clone)]
+struct Rc4 {
+    state: [u8; 256],
+    i: u8,
+    j: u8,
+}
+
+impl Rc4 {
+    fn new(key: &[u8]) -> Self {
+        let mut state = [0; 256];
+        for (i, byte) in state.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        let mut j = 0u8;
+        for i in 0..256 {
+            j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+            state.swap(i, j as usize);
+        }
+
+        Self { state, i: 0, j: 0 }
+    }
+
+    fn apply(&mut self, data: &mut [u8]) {
+        for byte in data {
+            self.i = self.i.wrapping_add(1);
+            self.j = self.j.wrapping_add(self.state[self.i as usize]);
+            self.state.swap(self.i as usize, self.j as usize);
+
+            let k = self.state[self.i as usize].wrapping_add(self.state[self.j as usize]);
+            *byte ^= self.state[k as usize];
+        }
+    }
+}
+
+mod utils {
+    use super::*;
+
+    pub fn hash(parts: &[&[u8]]) -> [u8; 20] {
+        let mut hasher = Sha1::new();
+        for part in parts {
+            hasher.update(part);
+        }
+
+        hasher.finalize().into()
+    }
+
+    pub fn xor(mut a: [u8; 20], b: [u8; 20]) -> [u8; 20] {
+        a.iter_mut().zip(b).for_each(|(a, b)| *a ^= b);
+        a
+    }
+
+    /// Cipher with the first 1024 bytes of keystream discarded, as required by MSE.
+    pub fn cipher(key: &[u8; 20]) -> Rc4 {
+        let mut rc4 = Rc4::new(key);
+        rc4.apply(&mut [0; 1024]);
+        rc4
+    }
+
+    pub fn provide_bits(methods: &[CryptoMethod]) -> u32 {
+        methods.iter().fold(0, |bits, method| bits | method.bit())
+    }
+
+    /// Random padding of random length.
+    pub fn pad() -> Vec<u8> {
+        let mut rng = rand::thread_rng();
+        (0..rng.gen_range(0..=MAX_PAD)).map(|_| rng.gen()).collect()
+    }
+
+    /// Big-endian key, padded with leading zeros.
+    pub fn to_key(value: &BigUint) -> [u8; KEY_SIZE] {
+        let bytes = value.to_bytes_be();
+        let mut key = [0; KEY_SIZE];
+        key[KEY_SIZE - bytes.len()..].copy_from_slice(&bytes);
+
+        key
+    }
+
+    /// Reads stream until `pattern`, which follows at most `MAX_PAD` bytes of padding.
+    ///
+    /// Bytes are read one by one, as nothing after pattern may be consumed.
+    pub fn sync(reader: &mut impl Read, pattern: &[u8]) -> io::Result<()> {
+        let mut window = Vec::with_capacity(MAX_PAD + pattern.len());
+
+        while !window.ends_with(pattern) {
+            if window.len() == MAX_PAD + pattern.len() {
+                return Err(invalid("handshake synchronization failed"));
+            }
+
+            let mut byte = [0];
+            reader.read_exact(&mut byte)?;
+            window.push(byte[0]);
+        }
+
+        Ok(())
+    }
+
+    /// Reads and decrypts `length` bytes of padding, checking its length.
+    pub fn skip(reader: &mut impl Read, decrypt: &mut Rc4, length: usize) -> io::Result<()> {
+        if length > MAX_PAD {
+            return Err(invalid("padding is too long"));
+        }
+
+        let mut pad = vec![0; length];
+        reader.read_exact(&mut pad)?;
+        decrypt.apply(&mut pad);
+
+        Ok(())
+    }
+
+    pub const fn from_hex<const N: usize>(hex: &[u8]) -> [u8; N] {
+        const fn digit(c: u8) -> u8 {
+            match c {
+                b'0'..=b'9' => c - b'0',
+                _ => c - b'A' + 10,
+            }
+        }
+
+        let mut bytes = [0; N];
+        let mut i = 0;
+
+        while i < N {
+            bytes[i] = (digit(hex[2 * i]) << 4) | digit(hex[2 * i + 1]);
+            i += 1;
+        }
+
+        bytes
+    }
+
+    pub fn invalid(message: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    fn pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+
+        (client, listener.accept().unwrap().0)
+    }
+
+    #[test]
+    fn rc4_matches_reference() {
+        let mut rc4 = Rc4::new(b"Key");
+        let mut data = *b"Plaintext";
+        rc4.apply(&mut data);
+
+        assert_eq!(data, [0xbb, 0xf3, 0x16, 0xe8, 0xd9, 0x40, 0xaf, 0x0a, 0xd3]);
+    }
+
+    #[rstest::rstest]
+    #[case(&[CryptoMethod::Rc4, CryptoMethod::Plaintext], &[CryptoMethod::Rc4], CryptoMethod::Rc4)]
+    #[case(&[CryptoMethod::Plaintext, CryptoMethod::Rc4], &[CryptoMethod::Plaintext, CryptoMethod::Rc4], CryptoMethod::Plaintext)]
+    fn handshake_negotiates_method(
+        #[case] provide: &'static [CryptoMethod],
+        #[case] accept: &'static [CryptoMethod],
+        #[case] expected: CryptoMethod,
+    ) {
+        let (client, server) = pair();
+        let info_hash = [7; 20];
+
+        let responder = thread::spawn(move || {
+            let (mut stream, info_hash) = MseStream::respond(server, &[[1; 20], [7; 20]], accept).unwrap();
+
+            let mut request = [0; 4];
+            stream.read_exact(&mut request).unwrap();
+            stream.write_all(&request.map(|byte| byte + 1)).unwrap();
+
+            (stream.method(), info_hash)
+        });
+
+        let mut stream = MseStream::initiate(client, &info_hash, provide).unwrap();
+        stream.write_all(&[1, 2, 3, 4]).unwrap();
+
+        let mut responce = [0; 4];
+        stream.read_exact(&mut responce).unwrap();
+
+        assert_eq!(responce, [2, 3, 4, 5]);
+        assert_eq!(stream.method(), expected);
+        assert_eq!(responder.join().unwrap(), (expected, info_hash));
+    }
+}
@@ -0,0 +1,245 @@
+use std::time::{Duration, Instant};
+
+/// Where address of peer was learned from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PeerSource {
+    Tracker,
+    Dht,
+    /// Peer exchange with other peers.
+    Pex,
+    /// Local service discovery.
+    Lsd,
+    /// Peer connected to us.
+    Incoming,
+    /// Address was added by user.
+    Manual,
+}
+
+impl PeerSource {
+    /// Returns `false` for sources, which private torrents must not use (BEP 27): DHT, peer exchange and
+    /// local service discovery.
+    pub fn is_allowed(self, private: bool) -> bool {
+        !private || !matches!(self, Self::Dht | Self::Pex | Self::Lsd)
+    }
+}
+
+/// Known peer of swarm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate<A> {
+    pub addr: A,
+    /// Sources, which reported peer, in order they did.
+    pub sources: Vec<PeerSource>,
+    pub attempts: u32,
+    /// Failed connection attempts since the last successful one.
+    pub failures: u32,
+    /// `true` while connection is being established or is established.
+    pub active: bool,
+    /// Time, peer can be tried again at, if it was tried before.
+    pub retry_at: Option<Instant>,
+}
+
+/// Candidate peers of torrent with addresses of type `A` (i.e. `(host, port)` pairs), collected from
+/// all sources.
+///
+/// Peers, reported by several sources, are kept once. Candidates, which were never tried, are handed out
+/// first, then the ones with the fewest failures. Failed peers are retried with exponential backoff,
+/// until they fail [`Self::MAX_FAILURES`] times in a row and are forgotten.
+///
+/// Swarm of private torrent ignores peers of sources, which aren't [allowed](PeerSource::is_allowed) for it,
+/// so DHT, PEX and LSD components can report peers unconditionally.
+#[derive(Debug, Clone)]
+pub struct Swarm<A> {
+    candidates: Vec<Candidate<A>>,
+    private: bool,
+}
+
+impl<A: Clone + PartialEq> Swarm<A> {
+    /// Time, peer is retried after its first failure or disconnect. Each next failure doubles it.
+    pub const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+    pub const MAX_FAILURES: u32 = 5;
+
+    pub fn new() -> Self {
+        Self {
+            candidates: vec![],
+            private: false,
+        }
+    }
+
+    /// Marks swarm as one of private torrent (see [`Info::is_private`](crate::bencoded::Info::is_private)).
+    pub fn private(mut self, private: bool) -> Self {
+        self.private = private;
+        self
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.private
+    }
+
+    /// Adds peer at `addr`, reported by `source`. Returns `true` if peer wasn't known yet.
+    ///
+    /// Peers of sources, which aren't allowed for private torrent, are ignored.
+    pub fn add(&mut self, addr: A, source: PeerSource) -> bool {
+        if !source.is_allowed(self.private) {
+            return false;
+        }
+
+        if let Some(candidate) = self.get_mut(&addr) {
+            if !candidate.sources.contains(&source) {
+                candidate.sources.push(source);
+            }
+
+            return false;
+        }
+
+        self.candidates.push(Candidate {
+            addr,
+            sources: vec![source],
+            attempts: 0,
+            failures: 0,
+            active: false,
+            retry_at: None,
+        });
+
+        true
+    }
+
+    /// Adds peers of `source`, returning number of new ones.
+    pub fn extend(&mut self, addrs: impl IntoIterator<Item = A>, source: PeerSource) -> usize {
+        addrs.into_iter().filter(|addr| self.add(addr.clone(), source)).count()
+    }
+
+    pub fn get(&self, addr: &A) -> Option<&Candidate<A>> {
+        self.candidates.iter().find(|candidate| candidate.addr == *addr)
+    }
+
+    pub fn remove(&mut self, addr: &A) -> Option<Candidate<A>> {
+        let index = self.candidates.iter().position(|candidate| candidate.addr == *addr)?;
+        Some(self.candidates.remove(index))
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Candidate<A>> {
+        self.candidates.iter()
+    }
+
+    /// Addresses of peers, which can be connected to by `now`, from the best one.
+    pub fn candidates(&self, now: Instant) -> Vec<A> {
+        let mut available = self
+            .candidates
+            .iter()
+            .filter(|candidate| !candidate.active && candidate.retry_at.is_none_or(|retry_at| retry_at <= now))
+            .collect::<Vec<_>>();
+        // Stable sort keeps order of discovery among equals
+        available.sort_by_key(|candidate| (candidate.attempts > 0, candidate.failures));
+
+        available.into_iter().map(|candidate| candidate.addr.clone()).collect()
+    }
+
+    /// Reports, that connection to peer is being established.
+    pub fn on_attempt(&mut self, addr: &A) {
+        if let Some(candidate) = self.get_mut(addr) {
+            candidate.attempts += 1;
+            candidate.active = true;
+        }
+    }
+
+    /// Reports, that handshake with peer succeeded.
+    pub fn on_connected(&mut self, addr: &A) {
+        if let Some(candidate) = self.get_mut(addr) {
+            candidate.failures = 0;
+            candidate.active = true;
+        }
+    }
+
+    /// Reports failed connection attempt, forgetting peer after [`Self::MAX_FAILURES`] failures in a row.
+    pub fn on_failed(&mut self, addr: &A, now: Instant) {
+        let Some(candidate) = self.get_mut(addr) else { return };
+        candidate.failures += 1;
+        candidate.active = false;
+
+        if candidate.failures >= Self::MAX_FAILURES {
+            self.remove(addr);
+            return;
+        }
+
+        candidate.retry_at = Some(now + Self::RETRY_INTERVAL * (1 << (candidate.failures - 1)));
+    }
+
+    /// Reports, that connection to peer was closed, so it can be tried again after [`Self::RETRY_INTERVAL`].
+    pub fn on_disconnected(&mut self, addr: &A, now: Instant) {
+        // Failed attempts keep their backoff
+        if let Some(candidate) = self.get_mut(addr).filter(|candidate| candidate.active) {
+            candidate.active = false;
+            candidate.retry_at = Some(now + Self::RETRY_INTERVAL);
+        }
+    }
+
+    fn get_mut(&mut self, addr: &A) -> Option<&mut Candidate<A>> {
+        self.candidates.iter_mut().find(|candidate| candidate.addr == *addr)
+    }
+}
+
+impl<A: Clone + PartialEq> Default for Swarm<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn candidates_are_deduplicated_and_ranked() {
+        let now = Instant::now();
+        let mut swarm = Swarm::new();
+
+        assert_eq!(swarm.extend(["a", "b", "c"], PeerSource::Tracker), 3);
+        assert_eq!(swarm.extend(["b", "d"], PeerSource::Dht), 1);
+        assert_eq!(swarm.get(&"b").unwrap().sources, [PeerSource::Tracker, PeerSource::Dht]);
+
+        swarm.on_attempt(&"a");
+        swarm.on_attempt(&"b");
+        swarm.on_attempt(&"c");
+        swarm.on_failed(&"a", now);
+        swarm.on_failed(&"b", now);
+        swarm.on_failed(&"b", now + Swarm::<&str>::RETRY_INTERVAL);
+        assert_eq!(swarm.candidates(now), ["d"]);
+
+        // Backoff of `b` is twice as long after its second failure
+        let later = now + 3 * Swarm::<&str>::RETRY_INTERVAL;
+        swarm.on_disconnected(&"c", now);
+        assert_eq!(swarm.candidates(later), ["d", "c", "a", "b"]);
+
+        swarm.on_attempt(&"d");
+        swarm.on_connected(&"d");
+        assert!(swarm.get(&"d").unwrap().active);
+        assert!(!swarm.candidates(later).contains(&"d"));
+
+        for _ in 0..Swarm::<&str>::MAX_FAILURES {
+            swarm.on_failed(&"a", later);
+        }
+        assert!(swarm.get(&"a").is_none());
+        assert_eq!(swarm.len(), 3);
+    }
+
+    #[test]
+    fn private_swarm_ignores_decentralized_sources() {
+        let mut swarm = Swarm::new().private(true);
+
+        assert!(!swarm.add("a", PeerSource::Dht));
+        assert_eq!(swarm.extend(["a", "b"], PeerSource::Pex), 0);
+        assert!(swarm.add("a", PeerSource::Tracker));
+        assert!(!swarm.add("a", PeerSource::Lsd));
+        assert!(swarm.add("c", PeerSource::Incoming));
+        assert_eq!(swarm.get(&"a").unwrap().sources, [PeerSource::Tracker]);
+        assert_eq!(swarm.len(), 2);
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+};
+
+use bufstream::BufStream;
+use rand::Rng;
+
+use crate::messages::{self, Handshake, Recv, Send};
+use crate::tracker::{SessionDescription, Signal};
+
+/// WebRTC implementation, which establishes peer connections with data channels.
+///
+/// No implementation is bundled: WebRTC stacks are big and usually tied to specific async runtime,
+/// so consumer plugs in one of their choice. Data channel is used as byte stream, the same way WebTorrent
+/// clients do, so [`Channel`](`RtcConnector::Channel`) reads should block until channel is open.
+pub trait RtcConnector {
+    type Channel: Read + Write;
+    /// Peer connection, which waits for answer to its offer.
+    type Pending;
+
+    /// Creates peer connection with data channel and returns its offer.
+    fn offer(&mut self) -> io::Result<(Self::Pending, SessionDescription)>;
+    /// Completes `pending` connection with `answer` of remote peer.
+    fn connect(&mut self, pending: Self::Pending, answer: SessionDescription) -> io::Result<Self::Channel>;
+    /// Creates peer connection in responce to `offer` of remote peer, returning answer and data channel.
+    fn answer(&mut self, offer: SessionDescription) -> io::Result<(SessionDescription, Self::Channel)>;
+}
+
+/// Connections to WebTorrent peers of one torrent, which are signalled via WebSocket tracker.
+///
+/// Offers, created with [`RtcPeers::offers`], are announced with
+/// [`WebSocketTracker::announce_with_offers`](`crate::tracker::WebSocketTracker::announce_with_offers`),
+/// and signals, recieved from tracker, are passed to [`RtcPeers::on_signal`].
+pub struct RtcPeers<C: RtcConnector> {
+    connector: C,
+    info_hash: [u8; 20],
+    /// Pending connections by offer ids.
+    pending: HashMap<[u8; 20], C::Pending>,
+}
+
+/// Connection, established in responce to signal.
+pub struct Accepted<C: Read + Write> {
+    pub peer_id: [u8; 20],
+    pub connection: RtcConnection<C>,
+    /// Answer to offer with id, which should be sent back with
+    /// [`WebSocketTracker::answer`](`crate::tracker::WebSocketTracker::answer`), if peer offered connection.
+    pub answer: Option<([u8; 20], SessionDescription)>,
+}
+
+impl<C: RtcConnector> RtcPeers<C> {
+    pub fn new(connector: C, info_hash: [u8; 20]) -> Self {
+        Self {
+            connector,
+            info_hash,
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn info_hash(&self) -> &[u8; 20] {
+        &self.info_hash
+    }
+
+    /// Number of offers, which weren't answered yet.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Creates `count` offers with random ids.
+    pub fn offers(&mut self, count: usize) -> io::Result<Vec<([u8; 20], SessionDescription)>> {
+        (0..count)
+            .map(|_| {
+                let (pending, offer) = self.connector.offer()?;
+                let offer_id = rand::thread_rng().gen();
+                self.pending.insert(offer_id, pending);
+
+                Ok((offer_id, offer))
+            })
+            .collect()
+    }
+
+    /// Drops offers, which weren't answered, i.e. before announcing new ones.
+    pub fn clear_offers(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Answers offer or completes connection, which was answered. Returns `None` for signals of other
+    /// torrents and answers to unknown offers.
+    pub fn on_signal(&mut self, signal: Signal) -> io::Result<Option<Accepted<C::Channel>>> {
+        match signal {
+            Signal::Offer {
+                info_hash,
+                peer_id,
+                offer_id,
+                offer,
+            } if info_hash == self.info_hash => {
+                let (answer, channel) = self.connector.answer(offer)?;
+
+                Ok(Some(Accepted {
+                    peer_id,
+                    connection: RtcConnection::new(channel),
+                    answer: Some((offer_id, answer)),
+                }))
+            }
+            Signal::Answer {
+                info_hash,
+                peer_id,
+                offer_id,
+                answer,
+            } if info_hash == self.info_hash => {
+                let Some(pending) = self.pending.remove(&offer_id) else { return Ok(None) };
+                let channel = self.connector.connect(pending, answer)?;
+
+                Ok(Some(Accepted {
+                    peer_id,
+                    connection: RtcConnection::new(channel),
+                    answer: None,
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Connection to WebTorrent peer over WebRTC data channel. Messages are the same as over TCP.
+pub struct RtcConnection<C: Read + Write> {
+    inner: BufStream<C>,
+    /// Reusable buffer of message headers, see [`RtcConnection::send`].
+    head: Vec<u8>,
+}
+
+impl<C: Read + Write> RtcConnection<C> {
+    pub fn new(channel: C) -> Self {
+        Self {
+            inner: BufStream::new(channel),
+            head: Vec::new(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &C {
+        self.inner.get_ref()
+    }
+
+    /// Exchanges handshakes with peer.
+    pub fn handshake(&mut self, handshake: &Handshake) -> messages::Result<Handshake> {
+        self.send(handshake)?;
+        self.recv::<Handshake>().map(|handshake| handshake.expect("handshake is never keep-alive"))
+    }
+
+    /// Attempts to send specified message to peer. Large payloads are written to channel directly,
+    /// as with [`Connection::send`](`super::Connection::send`).
+    pub fn send<S: Send>(&mut self, message: &S) -> io::Result<()> {
+        super::gather::send(&mut self.inner, &mut self.head, message)
+    }
+
+    /// Attempts to recieve message from peer, discarding residual bytes, if message failed to parse (see [`Recv`]).
+    /// Keep-alive is recieved as `Ok(None)`.
+    pub fn recv<R: Recv>(&mut self) -> messages::Result<Option<R>> {
+        R::recv_from(&mut self.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    /// Connector, which "signals" TCP connections by sending listener address as SDP.
+    struct TcpConnector;
+
+    impl RtcConnector for TcpConnector {
+        type Channel = TcpStream;
+        type Pending = TcpListener;
+
+        fn offer(&mut self) -> io::Result<(TcpListener, SessionDescription)> {
+            let listener = TcpListener::bind("127.0.0.1:0")?;
+            let offer = SessionDescription {
+                kind: "offer".to_owned(),
+                sdp: listener.local_addr()?.to_string(),
+            };
+
+            Ok((listener, offer))
+        }
+
+        fn connect(&mut self, pending: TcpListener, _: SessionDescription) -> io::Result<TcpStream> {
+            Ok(pending.accept()?.0)
+        }
+
+        fn answer(&mut self, offer: SessionDescription) -> io::Result<(SessionDescription, TcpStream)> {
+            let answer = SessionDescription {
+                kind: "answer".to_owned(),
+                sdp: String::new(),
+            };
+
+            Ok((answer, TcpStream::connect(offer.sdp)?))
+        }
+    }
+
+    #[test]
+    fn signalled_peers_exchange_handshakes() {
+        let info_hash = [3; 20];
+        let mut offering = RtcPeers::new(TcpConnector, info_hash);
+        let mut answering = RtcPeers::new(TcpConnector, info_hash);
+
+        let (offer_id, offer) = offering.offers(1).unwrap().pop().unwrap();
+        let other_torrent = Signal::Offer {
+            info_hash: [4; 20],
+            peer_id: [1; 20],
+            offer_id,
+            offer: offer.clone(),
+        };
+        assert!(answering.on_signal(other_torrent).unwrap().is_none());
+
+        let signal = Signal::Offer {
+            info_hash,
+            peer_id: [1; 20],
+            offer_id,
+            offer,
+        };
+        let accepted = answering.on_signal(signal).unwrap().unwrap();
+        let (answered_id, answer) = accepted.answer.unwrap();
+        assert_eq!((accepted.peer_id, answered_id), ([1; 20], offer_id));
+
+        let mut answering = accepted.connection;
+        let handshake = Handshake::builder(info_hash, [0; 20]).build();
+        let sent = handshake.clone();
+        let answering = thread::spawn(move || answering.handshake(&sent).unwrap());
+
+        let signal = Signal::Answer {
+            info_hash,
+            peer_id: [2; 20],
+            offer_id,
+            answer,
+        };
+        let mut accepted = offering.on_signal(signal).unwrap().unwrap();
+
+        assert_eq!(accepted.peer_id, [2; 20]);
+        assert_eq!(offering.pending(), 0);
+        assert_eq!(accepted.connection.handshake(&handshake).unwrap(), handshake);
+        assert_eq!(answering.join().unwrap(), handshake);
+    }
+}
@@ -0,0 +1,122 @@
+//! Choice of pieces and blocks to download next.
+mod queue;
+mod requests;
+mod strategies;
+
+pub use queue::QueueDepth;
+pub use requests::{Recieved, RequestTracker};
+pub use strategies::{PriorityWindow, RandomFirst, RarestFirst, Sequential};
+
+/// Strategy of choosing next piece to download from peer.
+///
+/// Pickers are selected per torrent and share download state through [`Pieces`], so strategy can be
+/// switched at any time (i.e. to [`Sequential`] when user starts streaming media).
+pub trait PiecePicker: std::fmt::Debug + Send {
+    /// Picks piece, which is wanted (see [`Pieces::is_wanted`]) and which `peer` has. Returns `None` if peer
+    /// has no wanted pieces.
+    fn pick(&mut self, pieces: &Pieces, peer: &[bool]) -> Option<usize>;
+}
+
+/// Download state of pieces and their availability among connected peers.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Pieces {
+    have: Vec<bool>,
+    in_progress: Vec<bool>,
+    /// Number of connected peers, which have each piece.
+    availability: Vec<u32>,
+}
+
+impl Pieces {
+    /// State of torrent with `count` pieces, none of which are downloaded.
+    pub fn new(count: usize) -> Self {
+        Self::with_have(vec![false; count])
+    }
+
+    /// State of torrent with pieces, which are already downloaded (i.e. from resume data).
+    pub fn with_have(have: Vec<bool>) -> Self {
+        Self {
+            in_progress: vec![false; have.len()],
+            availability: vec![0; have.len()],
+            have,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.have.len()
+    }
+
+    pub fn have(&self, piece: usize) -> bool {
+        self.have.get(piece).copied().unwrap_or(false)
+    }
+
+    /// Number of downloaded pieces.
+    pub fn have_count(&self) -> usize {
+        self.have.iter().filter(|have| **have).count()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.have.iter().all(|have| *have)
+    }
+
+    pub fn in_progress(&self, piece: usize) -> bool {
+        self.in_progress.get(piece).copied().unwrap_or(false)
+    }
+
+    /// Returns `true` if piece is neither downloaded nor in progress.
+    pub fn is_wanted(&self, piece: usize) -> bool {
+        piece < self.count() && !self.have[piece] && !self.in_progress[piece]
+    }
+
+    /// Number of connected peers, which have `piece`.
+    pub fn availability(&self, piece: usize) -> u32 {
+        self.availability.get(piece).copied().unwrap_or(0)
+    }
+
+    /// Wanted pieces, which `peer` has.
+    pub fn candidates<'a>(&'a self, peer: &'a [bool]) -> impl Iterator<Item = usize> + 'a {
+        (0..self.count()).filter(move |piece| peer.get(*piece).copied().unwrap_or(false) && self.is_wanted(*piece))
+    }
+
+    /// Reports peer, which connected with `bitfield` of its pieces.
+    pub fn add_peer(&mut self, bitfield: &[bool]) {
+        for (availability, has) in self.availability.iter_mut().zip(bitfield) {
+            *availability += *has as u32;
+        }
+    }
+
+    /// Reports peer, which disconnected, with pieces it had.
+    pub fn remove_peer(&mut self, bitfield: &[bool]) {
+        for (availability, has) in self.availability.iter_mut().zip(bitfield) {
+            *availability = availability.saturating_sub(*has as u32);
+        }
+    }
+
+    /// Reports `Have` message of peer.
+    pub fn on_peer_have(&mut self, piece: usize) {
+        if let Some(availability) = self.availability.get_mut(piece) {
+            *availability += 1;
+        }
+    }
+
+    /// Marks picked piece, which is being downloaded.
+    pub fn start(&mut self, piece: usize) {
+        if let Some(in_progress) = self.in_progress.get_mut(piece) {
+            *in_progress = true;
+        }
+    }
+
+    /// Marks piece, which was downloaded and passed verification.
+    pub fn complete(&mut self, piece: usize) {
+        if piece < self.count() {
+            self.have[piece] = true;
+            self.in_progress[piece] = false;
+        }
+    }
+
+    /// Returns piece, which failed verification or was abandoned, to wanted ones.
+    pub fn abort(&mut self, piece: usize) {
+        if let Some(in_progress) = self.in_progress.get_mut(piece) {
+            *in_progress = false;
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::time::{Duration, Instant};
+
+use super::RequestTracker;
+
+/// Number of requests, which are kept pending on single peer, tuned to bandwidth-delay product of connection.
+///
+/// Too shallow queue leaves fast peer idle between requests, while too deep one commits blocks to slow peer,
+/// which others could deliver sooner. Depth covers twice the blocks, peer delivers over its lowest observed
+/// latency: when queue is what limits the peer, latency stays close to the lowest one and depth grows,
+/// once peer is saturated, requests wait in its queue and depth stops growing.
+///
+/// Depth starts from [`Self::MIN_DEPTH`], so the lowest latency is observed before requests start to queue up.
+#[derive(Debug, Clone)]
+pub struct QueueDepth {
+    min: usize,
+    max: usize,
+    depth: usize,
+    /// Smoothed throughput in bytes per second, once it's measured.
+    rate: Option<f64>,
+    min_latency: Option<Duration>,
+    window_start: Option<Instant>,
+    window_bytes: u64,
+}
+
+impl QueueDepth {
+    pub const MIN_DEPTH: usize = 2;
+    pub const DEFAULT_MAX_DEPTH: usize = 250;
+    /// Period, throughput is measured over.
+    pub const RATE_WINDOW: Duration = Duration::from_secs(1);
+    /// Weight of the latest measurement in smoothed throughput.
+    pub const SMOOTHING: f64 = 0.25;
+
+    /// Depth, which never exceeds `max` one, at least one.
+    pub fn new(max: usize) -> Self {
+        let max = max.max(1);
+        let min = Self::MIN_DEPTH.min(max);
+
+        Self {
+            min,
+            max,
+            depth: min,
+            rate: None,
+            min_latency: None,
+            window_start: None,
+            window_bytes: 0,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Throughput in bytes per second.
+    pub fn rate(&self) -> Option<u64> {
+        self.rate.map(|rate| rate as u64)
+    }
+
+    /// The lowest time between request and its block.
+    pub fn min_latency(&self) -> Option<Duration> {
+        self.min_latency
+    }
+
+    /// Reports block of `length` bytes, recieved `latency` after it was requested. Depth is updated once
+    /// per [`Self::RATE_WINDOW`].
+    pub fn on_block(&mut self, length: u64, latency: Duration, now: Instant) {
+        self.min_latency = Some(self.min_latency.map_or(latency, |min| min.min(latency)));
+        self.window_bytes += length;
+
+        let window_start = *self.window_start.get_or_insert(now);
+        let elapsed = now.saturating_duration_since(window_start);
+        if elapsed < Self::RATE_WINDOW {
+            return;
+        }
+
+        let sample = self.window_bytes as f64 / elapsed.as_secs_f64();
+        let rate = self.rate.map_or(sample, |rate| rate + Self::SMOOTHING * (sample - rate));
+        self.rate = Some(rate);
+        self.window_start = Some(now);
+        self.window_bytes = 0;
+
+        let latency = self.min_latency.unwrap_or_default().as_secs_f64();
+        let blocks = 2.0 * rate * latency / RequestTracker::<()>::BLOCK_SIZE as f64;
+        self.depth = (blocks.ceil() as usize).clamp(self.min, self.max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK: u64 = RequestTracker::<()>::BLOCK_SIZE as u64;
+
+    /// Feeds depth with blocks of peer, which delivers up to `capacity` blocks per second
+    /// over link with `rtt`, for `seconds`.
+    fn simulate(queue: &mut QueueDepth, capacity: u64, rtt: Duration, seconds: u64) {
+        let start = Instant::now();
+
+        for second in 0..seconds {
+            // Queue is drained once per round trip, unless peer can't deliver that fast
+            let per_second = ((queue.depth() as f64 / rtt.as_secs_f64()) as u64).min(capacity);
+            let latency = rtt.max(Duration::from_secs_f64(queue.depth() as f64 / capacity as f64));
+
+            for block in 0..per_second {
+                let now = start + Duration::from_secs_f64(second as f64 + block as f64 / per_second as f64);
+                queue.on_block(BLOCK, latency, now);
+            }
+        }
+    }
+
+    #[test]
+    fn depth_follows_bandwidth_delay_product() {
+        let rtt = Duration::from_millis(100);
+        let mut fast = QueueDepth::new(QueueDepth::DEFAULT_MAX_DEPTH);
+        let mut slow = QueueDepth::new(QueueDepth::DEFAULT_MAX_DEPTH);
+        assert_eq!(fast.rate(), None);
+
+        // 6.25 MiB/s of fast peer needs 40 blocks in flight over 100ms
+        simulate(&mut fast, 400, rtt, 30);
+        // 160 KiB/s of slow peer is delivered by single block, which takes 100ms to serve
+        simulate(&mut slow, 10, rtt, 30);
+
+        assert!((40..=100).contains(&fast.depth()), "{}", fast.depth());
+        assert_eq!(fast.min_latency(), Some(rtt));
+        assert!(slow.depth() <= 6, "{}", slow.depth());
+        assert!((8 * BLOCK..=12 * BLOCK).contains(&slow.rate().unwrap()));
+
+        let mut capped = QueueDepth::new(20);
+        simulate(&mut capped, 400, rtt, 30);
+        assert_eq!(capped.depth(), 20);
+    }
+}
@@ -0,0 +1,416 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use super::Pieces;
+use crate::bencoded::{BInt, Layout};
+use crate::messages::{BTInt, Cancel, Request};
+
+/// Tracks requests of blocks of pieces, which are being downloaded, from peers of type `P`
+/// (i.e. their addresses).
+///
+/// Picked pieces are split into blocks of [`Self::BLOCK_SIZE`], which are handed out as [`Request`]s.
+/// Requests, which weren't answered in time or belong to disconnected peers, are returned to
+/// missing blocks and requested again.
+///
+/// Near completion, when every remaining block is already requested, tracker enters endgame mode:
+/// blocks are requested from several peers at once, so the last pieces don't wait for the slowest
+/// peer, and duplicates are cancelled once block is recieved.
+///
+/// Peers, which leave requests unanswered for [snub timeout](`Self::DEFAULT_SNUB_TIMEOUT`), are snubbed:
+/// they're given only one request at a time and no endgame duplicates, until they deliver a block.
+#[derive(Debug, Clone)]
+pub struct RequestTracker<P> {
+    layout: Layout,
+    timeout: Duration,
+    snub_timeout: Duration,
+    pieces: BTreeMap<BTInt, Vec<Block<P>>>,
+    snubbed: Vec<P>,
+    endgame: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Block<P> {
+    Missing,
+    Requested(Vec<Pending<P>>),
+    Recieved,
+}
+
+#[derive(Debug, Clone)]
+struct Pending<P> {
+    peer: P,
+    sent: Instant,
+    deadline: Instant,
+}
+
+/// Outcome of recieved block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recieved<P> {
+    /// `true` if all blocks of piece are recieved, so it can be verified. Piece is no longer tracked.
+    pub piece_complete: bool,
+    /// Duplicate requests of block to other peers, which should be cancelled.
+    pub cancels: Vec<(P, Cancel)>,
+}
+
+impl<P: Clone + PartialEq> RequestTracker<P> {
+    /// Size of requested blocks, all but the last block of piece have this size.
+    pub const BLOCK_SIZE: BTInt = 1 << 14;
+    /// Time peer has to answer request.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+    /// Time peer may leave its requests unanswered, before it's snubbed.
+    pub const DEFAULT_SNUB_TIMEOUT: Duration = Duration::from_secs(30);
+
+    pub fn new(layout: Layout) -> Self {
+        Self {
+            layout,
+            timeout: Self::DEFAULT_TIMEOUT,
+            snub_timeout: Self::DEFAULT_SNUB_TIMEOUT,
+            pieces: BTreeMap::new(),
+            snubbed: vec![],
+            endgame: false,
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn snub_timeout(mut self, snub_timeout: Duration) -> Self {
+        self.snub_timeout = snub_timeout;
+        self
+    }
+
+    pub fn is_endgame(&self) -> bool {
+        self.endgame
+    }
+
+    /// Pieces, which are being downloaded.
+    pub fn pieces(&self) -> impl Iterator<Item = BTInt> + '_ {
+        self.pieces.keys().copied()
+    }
+
+    /// Starts tracking blocks of picked `piece`. Returns `false` if piece is out of range or already tracked.
+    pub fn add_piece(&mut self, piece: BTInt) -> bool {
+        let Some(size) = self.layout.piece_size(piece as BInt) else { return false };
+
+        if self.pieces.contains_key(&piece) {
+            return false;
+        }
+
+        let blocks = size.div_ceil(Self::BLOCK_SIZE as BInt) as usize;
+        self.pieces.insert(piece, vec![Block::Missing; blocks]);
+
+        true
+    }
+
+    /// Stops tracking `piece` (i.e. it was abandoned), returning requests, which should be cancelled.
+    pub fn remove_piece(&mut self, piece: BTInt) -> Vec<(P, Cancel)> {
+        let Some(blocks) = self.pieces.remove(&piece) else { return vec![] };
+
+        blocks
+            .into_iter()
+            .enumerate()
+            .flat_map(|(i, block)| match block {
+                Block::Requested(pending) => pending
+                    .into_iter()
+                    .map(|pending| (pending.peer, self.cancel(piece, i)))
+                    .collect(),
+                _ => vec![],
+            })
+            .collect()
+    }
+
+    /// Hands out up to `count` requests for `peer`, which has pieces marked in `peer_has`. Missing blocks
+    /// of earlier pieces go first; in endgame mode blocks, which are requested from other peers, are
+    /// requested again. Snubbed peer is handed out request only if it has none pending.
+    pub fn request(&mut self, peer: &P, peer_has: &[bool], count: usize, now: Instant) -> Vec<Request> {
+        let mut requests = vec![];
+        let deadline = now + self.timeout;
+        let snubbed = self.is_snubbed(peer);
+        let count = match snubbed {
+            true => count.min(1usize.saturating_sub(self.pending_count(peer))),
+            false => count,
+        };
+
+        for (piece, blocks) in &mut self.pieces {
+            if !peer_has.get(*piece as usize).copied().unwrap_or(false) {
+                continue;
+            }
+
+            for (i, block) in blocks.iter_mut().enumerate() {
+                if requests.len() == count {
+                    return requests;
+                }
+
+                let pending = Pending {
+                    peer: peer.clone(),
+                    sent: now,
+                    deadline,
+                };
+
+                match block {
+                    Block::Missing => *block = Block::Requested(vec![pending]),
+                    Block::Requested(others)
+                        if self.endgame && !snubbed && others.iter().all(|other| other.peer != *peer) =>
+                    {
+                        others.push(pending)
+                    }
+                    _ => continue,
+                }
+
+                requests.push(utils::request(&self.layout, *piece, i));
+            }
+        }
+
+        requests
+    }
+
+    /// Reports block at `offset` of `piece`, recieved from `peer`. Returns `None` if block wasn't requested
+    /// or was already recieved from other peer.
+    pub fn on_block(&mut self, peer: &P, piece: BTInt, offset: BTInt) -> Option<Recieved<P>> {
+        self.snubbed.retain(|snubbed| snubbed != peer);
+        let blocks = self.pieces.get_mut(&piece)?;
+
+        if !offset.is_multiple_of(Self::BLOCK_SIZE) {
+            return None;
+        }
+
+        let block = blocks.get_mut((offset / Self::BLOCK_SIZE) as usize)?;
+
+        // Late responce to expired request is still useful
+        let pending = match std::mem::replace(block, Block::Recieved) {
+            Block::Requested(pending) => pending,
+            Block::Missing => vec![],
+            Block::Recieved => return None,
+        };
+
+        let piece_complete = blocks.iter().all(|block| matches!(block, Block::Recieved));
+
+        if piece_complete {
+            self.pieces.remove(&piece);
+        }
+
+        let cancels = pending
+            .into_iter()
+            .filter(|pending| pending.peer != *peer)
+            .map(|pending| (pending.peer, self.cancel(piece, (offset / Self::BLOCK_SIZE) as usize)))
+            .collect();
+
+        Some(Recieved {
+            piece_complete,
+            cancels,
+        })
+    }
+
+    /// Drops requests of `peer`, which disconnected or choked us, so their blocks can be requested from others.
+    pub fn on_peer_gone(&mut self, peer: &P) {
+        self.retain_requests(|pending| pending.peer != *peer);
+        self.snubbed.retain(|snubbed| snubbed != peer);
+    }
+
+    /// Snubs peers, which have requests older than snub timeout by `now`, returning newly snubbed ones.
+    /// Should be called before [`expire()`](`Self::expire`), which drops such requests eventually.
+    pub fn snub(&mut self, now: Instant) -> Vec<P> {
+        let mut snubbed = vec![];
+
+        for pending in self.pieces.values().flatten().flat_map(Block::pending) {
+            let is_stale = now.saturating_duration_since(pending.sent) >= self.snub_timeout;
+
+            if is_stale && !self.snubbed.contains(&pending.peer) && !snubbed.contains(&pending.peer) {
+                snubbed.push(pending.peer.clone());
+            }
+        }
+
+        self.snubbed.extend(snubbed.iter().cloned());
+        snubbed
+    }
+
+    /// `true` if `peer` didn't deliver any block since it was snubbed.
+    pub fn is_snubbed(&self, peer: &P) -> bool {
+        self.snubbed.contains(peer)
+    }
+
+    /// Number of requests, which are pending on `peer`.
+    pub fn pending_count(&self, peer: &P) -> usize {
+        self.pieces
+            .values()
+            .flatten()
+            .flat_map(Block::pending)
+            .filter(|pending| pending.peer == *peer)
+            .count()
+    }
+
+    /// Drops requests, which weren't answered by `now`, returning them, so they can be cancelled.
+    pub fn expire(&mut self, now: Instant) -> Vec<(P, Request)> {
+        let mut expired = vec![];
+
+        for (piece, blocks) in &self.pieces {
+            for (i, block) in blocks.iter().enumerate() {
+                if let Block::Requested(pending) = block {
+                    expired.extend(
+                        pending
+                            .iter()
+                            .filter(|pending| pending.deadline <= now)
+                            .map(|pending| (pending.peer.clone(), utils::request(&self.layout, *piece, i))),
+                    );
+                }
+            }
+        }
+
+        self.retain_requests(|pending| pending.deadline > now);
+        expired
+    }
+
+    /// Enters endgame mode, when no pieces are left to pick and every block of tracked pieces is requested,
+    /// or leaves it, i.e. when piece failed verification. Returns `true` if tracker is in endgame mode.
+    pub fn update_endgame(&mut self, pieces: &Pieces) -> bool {
+        let nothing_to_pick = (0..pieces.count()).all(|piece| !pieces.is_wanted(piece));
+        let all_requested = self.pieces.values().flatten().all(|block| !matches!(block, Block::Missing));
+
+        self.endgame = nothing_to_pick && all_requested && !self.pieces.is_empty();
+        self.endgame
+    }
+
+    fn retain_requests(&mut self, keep: impl Fn(&Pending<P>) -> bool) {
+        for block in self.pieces.values_mut().flatten() {
+            if let Block::Requested(pending) = block {
+                pending.retain(&keep);
+
+                if pending.is_empty() {
+                    *block = Block::Missing;
+                }
+            }
+        }
+    }
+
+    fn cancel(&self, piece: BTInt, block: usize) -> Cancel {
+        let request = utils::request(&self.layout, piece, block);
+
+        Cancel {
+            piece_index: request.piece_index,
+            offset: request.offset,
+            data_length: request.data_length,
+        }
+    }
+}
+
+impl<P> Block<P> {
+    fn pending(&self) -> &[Pending<P>] {
+        match self {
+            Block::Requested(pending) => pending,
+            _ => &[],
+        }
+    }
+}
+
+mod utils {
+    use super::*;
+
+    /// Request of `block` of `piece`. The last block of piece may be shorter.
+    pub fn request(layout: &Layout, piece: BTInt, block: usize) -> Request {
+        let block_size = RequestTracker::<()>::BLOCK_SIZE;
+        let piece_size = layout.piece_size(piece as BInt).expect("tracked piece is in range");
+        let offset = block as BTInt * block_size;
+
+        Request {
+            piece_index: piece,
+            offset,
+            data_length: (piece_size - offset as BInt).min(block_size as BInt) as BTInt,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bencoded::Files;
+    use crate::test_utils;
+
+    fn layout() -> Layout {
+        let files = Files::Single {
+            length: (3 << 14) + 100,
+            md5sum: None,
+        };
+
+        test_utils::info(2 << 14, files).layout()
+    }
+
+    #[test]
+    fn blocks_are_requested_and_expired() {
+        let now = Instant::now();
+        let mut tracker = RequestTracker::new(layout());
+
+        assert!(tracker.add_piece(0));
+        assert!(tracker.add_piece(1));
+        assert!(!tracker.add_piece(2));
+
+        let requests = tracker.request(&"a", &[true, true], 3, now);
+        let lengths = requests.iter().map(|request| request.data_length).collect::<Vec<_>>();
+
+        assert_eq!(lengths, [1 << 14, 1 << 14, 1 << 14]);
+        assert_eq!(tracker.request(&"b", &[true, false], 3, now), []);
+
+        tracker.on_peer_gone(&"a");
+        let requests = tracker.request(&"b", &[true, true], 4, now);
+        assert_eq!(requests.last().unwrap().data_length, 100);
+
+        let expired = tracker.expire(now + RequestTracker::<&str>::DEFAULT_TIMEOUT);
+        assert_eq!(expired.len(), 4);
+        assert_eq!(tracker.request(&"c", &[true, true], 4, now), requests);
+    }
+
+    #[test]
+    fn endgame_duplicates_requests() {
+        let now = Instant::now();
+        let mut tracker = RequestTracker::new(layout());
+        let mut pieces = Pieces::new(2);
+
+        pieces.complete(0);
+        pieces.start(1);
+        tracker.add_piece(1);
+
+        assert!(!tracker.update_endgame(&pieces));
+        assert_eq!(tracker.request(&"a", &[true, true], 4, now).len(), 2);
+        assert!(tracker.update_endgame(&pieces));
+
+        let duplicates = tracker.request(&"b", &[true, true], 4, now);
+        assert_eq!(duplicates.len(), 2);
+        assert_eq!(tracker.request(&"a", &[true, true], 4, now), []);
+
+        let recieved = tracker.on_block(&"b", 1, 0).unwrap();
+        assert!(!recieved.piece_complete);
+        assert_eq!(recieved.cancels, [("a", Cancel { piece_index: 1, offset: 0, data_length: 1 << 14 })]);
+        assert_eq!(tracker.on_block(&"a", 1, 0), None);
+
+        let recieved = tracker.on_block(&"a", 1, 1 << 14).unwrap();
+        assert!(recieved.piece_complete);
+        assert_eq!(recieved.cancels, [("b", Cancel { piece_index: 1, offset: 1 << 14, data_length: 100 })]);
+        assert_eq!(tracker.pieces().count(), 0);
+    }
+
+    #[test]
+    fn snubbed_peer_gets_single_request() {
+        let now = Instant::now();
+        let mut tracker = RequestTracker::new(layout()).snub_timeout(Duration::from_secs(10));
+        tracker.add_piece(0);
+        tracker.add_piece(1);
+
+        assert_eq!(tracker.request(&"a", &[true, true], 2, now).len(), 2);
+        assert_eq!(tracker.snub(now + Duration::from_secs(5)), Vec::<&str>::new());
+        assert_eq!(tracker.snub(now + Duration::from_secs(10)), ["a"]);
+        assert_eq!(tracker.snub(now + Duration::from_secs(15)), Vec::<&str>::new());
+        assert!(tracker.is_snubbed(&"a"));
+        assert_eq!(tracker.request(&"a", &[true, true], 2, now), []);
+
+        tracker.expire(now + RequestTracker::<&str>::DEFAULT_TIMEOUT);
+        assert_eq!(tracker.request(&"a", &[true, true], 2, now).len(), 1);
+
+        // Delivered block lifts snub
+        tracker.on_block(&"a", 0, 0);
+        assert!(!tracker.is_snubbed(&"a"));
+        assert_eq!(tracker.pending_count(&"a"), 0);
+        assert_eq!(tracker.request(&"a", &[true, true], 2, now).len(), 2);
+    }
+}
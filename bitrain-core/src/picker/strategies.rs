@@ -0,0 +1,145 @@
+use std::ops::Range;
+
+use rand::seq::IteratorRandom;
+
+use super::{PiecePicker, Pieces};
+
+/// Picks pieces, which the fewest peers have, so rare pieces spread before their owners leave.
+/// Ties are broken randomly, so peers don't all request the same piece.
+///
+/// Default strategy of BitTorrent clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RarestFirst;
+
+impl PiecePicker for RarestFirst {
+    fn pick(&mut self, pieces: &Pieces, peer: &[bool]) -> Option<usize> {
+        let rarest = pieces.candidates(peer).map(|piece| pieces.availability(piece)).min()?;
+
+        pieces
+            .candidates(peer)
+            .filter(|piece| pieces.availability(*piece) == rarest)
+            .choose(&mut rand::thread_rng())
+    }
+}
+
+/// Picks pieces in order, so content can be consumed while it downloads (i.e. played by media player).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Sequential;
+
+impl PiecePicker for Sequential {
+    fn pick(&mut self, pieces: &Pieces, peer: &[bool]) -> Option<usize> {
+        pieces.candidates(peer).next()
+    }
+}
+
+/// Picks random pieces until `count` pieces are downloaded, then the rarest ones.
+///
+/// New peer has nothing to upload, so it's better to complete any pieces quickly, than to wait
+/// for rare ones, which are slow to download.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RandomFirst {
+    pub count: usize,
+}
+
+impl RandomFirst {
+    /// Common number of random first pieces.
+    pub const DEFAULT_COUNT: usize = 4;
+
+    pub fn new(count: usize) -> Self {
+        Self { count }
+    }
+}
+
+impl Default for RandomFirst {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_COUNT)
+    }
+}
+
+impl PiecePicker for RandomFirst {
+    fn pick(&mut self, pieces: &Pieces, peer: &[bool]) -> Option<usize> {
+        if pieces.have_count() < self.count {
+            pieces.candidates(peer).choose(&mut rand::thread_rng())
+        } else {
+            RarestFirst.pick(pieces, peer)
+        }
+    }
+}
+
+/// Picks pieces of window in order, falling back to other strategy outside of it.
+///
+/// Window follows playback position of streamed media: pieces, which are about to be played, are
+/// downloaded first, while the rest of content is still downloaded with `fallback`.
+#[derive(Debug)]
+pub struct PriorityWindow {
+    window: Range<usize>,
+    fallback: Box<dyn PiecePicker>,
+}
+
+impl PriorityWindow {
+    pub fn new(window: Range<usize>, fallback: Box<dyn PiecePicker>) -> Self {
+        Self { window, fallback }
+    }
+
+    pub fn window(&self) -> Range<usize> {
+        self.window.clone()
+    }
+
+    /// Moves window, i.e. when user seeks media.
+    pub fn set_window(&mut self, window: Range<usize>) {
+        self.window = window;
+    }
+}
+
+impl PiecePicker for PriorityWindow {
+    fn pick(&mut self, pieces: &Pieces, peer: &[bool]) -> Option<usize> {
+        let in_window = self.window.clone().find(|piece| {
+            peer.get(*piece).copied().unwrap_or(false) && pieces.is_wanted(*piece)
+        });
+
+        in_window.or_else(|| self.fallback.pick(pieces, peer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pieces() -> Pieces {
+        let mut pieces = Pieces::new(6);
+
+        pieces.add_peer(&[true, true, true, true, true, true]);
+        pieces.add_peer(&[true, true, false, true, true, false]);
+        pieces.add_peer(&[true, false, false, true, true, true]);
+        pieces.complete(0);
+        pieces.start(1);
+
+        pieces
+    }
+
+    #[test]
+    fn strategies_pick_wanted_pieces() {
+        let mut pieces = pieces();
+        let peer = [true; 6];
+
+        assert_eq!(RarestFirst.pick(&pieces, &peer), Some(2));
+        assert_eq!(Sequential.pick(&pieces, &peer), Some(2));
+
+        let mut window = PriorityWindow::new(4..6, Box::new(Sequential));
+        assert_eq!(window.pick(&pieces, &peer), Some(4));
+
+        window.set_window(0..2);
+        assert_eq!(window.pick(&pieces, &peer), Some(2));
+
+        // Random pieces are picked until enough are downloaded
+        let picked = RandomFirst::new(2).pick(&pieces, &peer).unwrap();
+        assert!(pieces.is_wanted(picked));
+
+        pieces.complete(2);
+        pieces.complete(3);
+        pieces.remove_peer(&[true, true, true, true, true, true]);
+
+        assert_eq!(RandomFirst::new(2).pick(&pieces, &peer), Some(5));
+        assert_eq!(Sequential.pick(&pieces, &[true, true, false, false, false, false]), None);
+    }
+}
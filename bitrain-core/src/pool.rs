@@ -0,0 +1,115 @@
+//! Reusable buffers for blocks, which are recieved from peers and written to disk.
+//!
+//! Fast downloads decode thousands of blocks per second. Instead of allocating buffer for each of them,
+//! [`Bytes`] of decoded blocks are [returned](`BufferPool::put`) to pool, once they are written, and reused
+//! by the next decoded blocks.
+use std::sync::{Mutex, OnceLock};
+
+use bytes::{Bytes, BytesMut};
+
+/// Pool of equally sized buffers.
+#[derive(Debug)]
+pub struct BufferPool {
+    buffers: Mutex<Vec<BytesMut>>,
+    buffer_size: usize,
+    /// Number of buffers, pool keeps at most.
+    capacity: usize,
+}
+
+impl BufferPool {
+    /// Size of blocks, requested by virtually all clients.
+    pub const BLOCK_SIZE: usize = 1 << 14;
+    /// 4 MiB of blocks.
+    pub const DEFAULT_CAPACITY: usize = 256;
+
+    pub fn new(buffer_size: usize, capacity: usize) -> Self {
+        Self {
+            buffers: Mutex::default(),
+            buffer_size,
+            capacity,
+        }
+    }
+
+    /// Pool of [block](`BufferPool::BLOCK_SIZE`) buffers, which is used by decoding of [`Bytes`].
+    pub fn global() -> &'static Self {
+        static GLOBAL: OnceLock<BufferPool> = OnceLock::new();
+        GLOBAL.get_or_init(|| Self::new(Self::BLOCK_SIZE, Self::DEFAULT_CAPACITY))
+    }
+
+    /// Takes zeroed buffer of `len` bytes. Only buffers of sizes close to pool ones are pooled: larger ones
+    /// don't fit and smaller ones (i.e. of bitfields) would hold much more memory, than they need.
+    pub fn take(&self, len: usize) -> BytesMut {
+        if len > self.buffer_size || len <= self.buffer_size / 2 {
+            return BytesMut::zeroed(len);
+        }
+
+        let pooled = self.buffers.lock().unwrap().pop();
+
+        match pooled {
+            Some(mut buffer) => {
+                buffer.resize(len, 0);
+                buffer
+            }
+            None => {
+                let mut buffer = BytesMut::with_capacity(self.buffer_size);
+                buffer.resize(len, 0);
+                buffer
+            }
+        }
+    }
+
+    /// Returns buffer of `bytes` for reuse. Buffer is dropped, if it's still shared by other clones,
+    /// its capacity differs from pool ones (so large buffers aren't kept alive) or pool is full.
+    pub fn put(&self, bytes: Bytes) {
+        let Ok(mut buffer) = bytes.try_into_mut() else { return };
+
+        if buffer.capacity() != self.buffer_size {
+            return;
+        }
+
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.len() < self.capacity {
+            buffer.clear();
+            buffers.push(buffer);
+        }
+    }
+
+    /// Number of buffers, which are ready for reuse.
+    pub fn available(&self) -> usize {
+        self.buffers.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn returned_buffers_are_reused() {
+        let pool = BufferPool::new(16, 1);
+
+        let mut buffer = pool.take(12);
+        buffer.copy_from_slice(&[1; 12]);
+        let bytes = buffer.freeze();
+        let ptr = bytes.as_ptr();
+
+        // Shared buffer can't be reused yet
+        let clone = bytes.clone();
+        pool.put(bytes);
+        assert_eq!(pool.available(), 0);
+
+        // Large buffer would hold more memory, than pool needs
+        pool.put(Bytes::from(vec![0; 1024]));
+        assert_eq!(pool.available(), 0);
+
+        pool.put(clone);
+        pool.put(pool.take(4).freeze());
+        // Pool is full
+        pool.put(Bytes::from(vec![0; 16]));
+        assert_eq!(pool.available(), 1);
+
+        let buffer = pool.take(16);
+        assert_eq!(buffer.as_ptr(), ptr);
+        assert_eq!(&buffer[..], &[0; 16]);
+    }
+}
@@ -0,0 +1,50 @@
+//! Mapping of external ports on home routers, so peers outside of NAT can connect to listener.
+//!
+//! Routers are asked with NAT-PMP (<https://www.rfc-editor.org/rfc/rfc6886>) or UPnP IGD, mappings are
+//! kept alive by [`PortForwarding`].
+mod forwarding;
+mod natpmp;
+mod upnp;
+
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+pub use forwarding::PortForwarding;
+pub use natpmp::NatPmp;
+pub use upnp::Upnp;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// Port mapping, granted by router.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mapping {
+    pub protocol: Protocol,
+    pub internal_port: u16,
+    /// Port, which is open on external address of router. May differ from requested one.
+    pub external_port: u16,
+    /// Time, for which mapping is granted.
+    pub lifetime: Duration,
+    pub obtained: Instant,
+}
+
+impl Mapping {
+    /// Time, when mapping should be renewed: half of its lifetime, as recommended by NAT-PMP.
+    pub fn renew_at(&self) -> Instant {
+        self.obtained + self.lifetime / 2
+    }
+}
+
+/// Protocol of asking router for port mappings.
+pub trait PortMapper: std::fmt::Debug + Send {
+    /// Requests mapping of external `port` to the same `port` of this host for `lifetime`. Requesting
+    /// existing mapping again renews it.
+    fn map(&mut self, protocol: Protocol, port: u16, lifetime: Duration) -> io::Result<Mapping>;
+    /// Removes `mapping` before its lifetime ends.
+    fn unmap(&mut self, mapping: &Mapping) -> io::Result<()>;
+}
@@ -0,0 +1,167 @@
+use std::{
+    io,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use super::{Mapping, PortMapper, Protocol};
+
+/// Port mappings, which are renewed in background thread until dropped.
+///
+/// Mappings are renewed at half of their lifetime and removed from router on drop. Failed renewals
+/// are retried in a minute, as router may be restarted meanwhile.
+#[derive(Debug)]
+pub struct PortForwarding {
+    mappings: Arc<Mutex<Vec<Mapping>>>,
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PortForwarding {
+    /// Lifetime of mappings, recommended by NAT-PMP.
+    pub const DEFAULT_LIFETIME: Duration = Duration::from_secs(7200);
+    /// Delay of retrying failed renewal.
+    const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+    /// Maps `ports` with `mapper` for `lifetime`, i.e. TCP and UDP port of listener, and starts renewing them.
+    /// Fails, if any of the ports can't be mapped.
+    pub fn start(
+        mut mapper: impl PortMapper + 'static,
+        ports: &[(Protocol, u16)],
+        lifetime: Duration,
+    ) -> io::Result<Self> {
+        let mut mappings = Vec::with_capacity(ports.len());
+
+        for &(protocol, port) in ports {
+            match mapper.map(protocol, port, lifetime) {
+                Ok(mapping) => mappings.push(mapping),
+                Err(err) => {
+                    utils::unmap_all(&mut mapper, &mappings);
+                    return Err(err);
+                }
+            }
+        }
+
+        let mappings = Arc::new(Mutex::new(mappings));
+        let (stop, stopped) = mpsc::channel();
+        let shared = mappings.clone();
+        let thread = thread::spawn(move || utils::renew(mapper, shared, lifetime, stopped));
+
+        Ok(Self {
+            mappings,
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    /// Current mappings. External ports may change after renewal.
+    pub fn mappings(&self) -> Vec<Mapping> {
+        self.mappings.lock().unwrap().clone()
+    }
+}
+
+impl Drop for PortForwarding {
+    fn drop(&mut self) {
+        // Disconnected channel stops renewal
+        self.stop.take();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+mod utils {
+    use super::*;
+
+    pub fn renew(
+        mut mapper: impl PortMapper,
+        mappings: Arc<Mutex<Vec<Mapping>>>,
+        lifetime: Duration,
+        stopped: mpsc::Receiver<()>,
+    ) {
+        loop {
+            let renew_at = mappings.lock().unwrap().iter().map(Mapping::renew_at).min();
+            let Some(renew_at) = renew_at else { return };
+            let timeout = renew_at.saturating_duration_since(Instant::now());
+
+            match stopped.recv_timeout(timeout) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => break,
+            }
+
+            let mut mappings = mappings.lock().unwrap();
+
+            for mapping in mappings.iter_mut().filter(|mapping| mapping.renew_at() <= Instant::now()) {
+                match mapper.map(mapping.protocol, mapping.internal_port, lifetime) {
+                    Ok(renewed) => *mapping = renewed,
+                    Err(_) => {
+                        // Postpone retry, keeping mapping the same
+                        mapping.lifetime = PortForwarding::RETRY_DELAY * 2;
+                        mapping.obtained = Instant::now();
+                    }
+                }
+            }
+        }
+
+        unmap_all(&mut mapper, &mappings.lock().unwrap());
+    }
+
+    /// Removes `mappings`, ignoring errors, as they expire anyway.
+    pub fn unmap_all(mapper: &mut impl PortMapper, mappings: &[Mapping]) {
+        for mapping in mappings {
+            let _ = mapper.unmap(mapping);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mapper, which grants every request and records calls.
+    #[derive(Debug, Default, Clone)]
+    struct Recording {
+        calls: Arc<Mutex<Vec<(&'static str, u16)>>>,
+    }
+
+    impl PortMapper for Recording {
+        fn map(&mut self, protocol: Protocol, port: u16, lifetime: Duration) -> io::Result<Mapping> {
+            self.calls.lock().unwrap().push(("map", port));
+
+            Ok(Mapping {
+                protocol,
+                internal_port: port,
+                external_port: port + 1,
+                lifetime,
+                obtained: Instant::now(),
+            })
+        }
+
+        fn unmap(&mut self, mapping: &Mapping) -> io::Result<()> {
+            self.calls.lock().unwrap().push(("unmap", mapping.internal_port));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn mappings_are_renewed_and_removed() {
+        let mapper = Recording::default();
+        let calls = mapper.calls.clone();
+        let ports = [(Protocol::Tcp, 6881), (Protocol::Udp, 6881)];
+
+        let forwarding = PortForwarding::start(mapper, &ports, Duration::from_millis(200)).unwrap();
+        assert_eq!(forwarding.mappings()[0].external_port, 6882);
+
+        thread::sleep(Duration::from_millis(150));
+        drop(forwarding);
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.iter().filter(|(call, _)| *call == "map").count(), 4);
+        assert_eq!(calls[4..], [("unmap", 6881), ("unmap", 6881)]);
+    }
+}
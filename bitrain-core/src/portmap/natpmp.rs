@@ -0,0 +1,206 @@
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use super::{Mapping, PortMapper, Protocol};
+
+/// Client of NAT-PMP gateway.
+///
+/// Requests are retransmitted with doubling timeout, starting with 250ms, as gateway is reached over UDP.
+#[derive(Debug)]
+pub struct NatPmp {
+    gateway: SocketAddr,
+    socket: UdpSocket,
+    attempts: u32,
+}
+
+impl NatPmp {
+    /// Port gateway listens for requests on.
+    pub const PORT: u16 = 5351;
+    /// Number of request transmissions, before gateway is considered unreachable (about 4 seconds).
+    pub const DEFAULT_ATTEMPTS: u32 = 4;
+    const INITIAL_TIMEOUT: Duration = Duration::from_millis(250);
+
+    /// Creates client of gateway, listening on `gateway` address.
+    pub fn new(gateway: SocketAddr) -> io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.connect(gateway)?;
+
+        Ok(Self {
+            gateway,
+            socket,
+            attempts: Self::DEFAULT_ATTEMPTS,
+        })
+    }
+
+    /// Creates client of default gateway of this host. Gateway can be found only on Linux so far.
+    pub fn with_default_gateway() -> io::Result<Self> {
+        let gateway = utils::default_gateway()?;
+        Self::new(SocketAddrV4::new(gateway, Self::PORT).into())
+    }
+
+    pub fn attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    pub fn gateway(&self) -> SocketAddr {
+        self.gateway
+    }
+
+    /// Asks gateway for its external address.
+    pub fn external_address(&mut self) -> io::Result<Ipv4Addr> {
+        let responce = self.request(&[0, 0], 12)?;
+        Ok(Ipv4Addr::new(responce[8], responce[9], responce[10], responce[11]))
+    }
+
+    /// Sends `request` until responce of at least `size` bytes with matching opcode and success code arrives.
+    fn request(&mut self, request: &[u8], size: usize) -> io::Result<Vec<u8>> {
+        let mut timeout = Self::INITIAL_TIMEOUT;
+        let mut buf = [0; 16];
+
+        for _ in 0..self.attempts {
+            self.socket.send(request)?;
+            let deadline = Instant::now() + timeout;
+
+            while let Some(remaining) = deadline.checked_duration_since(Instant::now()).filter(|r| !r.is_zero()) {
+                self.socket.set_read_timeout(Some(remaining))?;
+
+                let read = match self.socket.recv(&mut buf) {
+                    Ok(read) => read,
+                    Err(err) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => break,
+                    Err(err) => return Err(err),
+                };
+
+                // Responces to other requests (i.e. retransmitted ones) are skipped
+                if read < 4 || buf[0] != 0 || buf[1] != request[1] | 0x80 {
+                    continue;
+                }
+
+                utils::check_result(u16::from_be_bytes([buf[2], buf[3]]))?;
+
+                if read < size {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "truncated NAT-PMP responce"));
+                }
+
+                return Ok(buf[..read].to_vec());
+            }
+
+            timeout *= 2;
+        }
+
+        Err(io::Error::new(io::ErrorKind::TimedOut, "NAT-PMP gateway didn't respond"))
+    }
+}
+
+impl PortMapper for NatPmp {
+    fn map(&mut self, protocol: Protocol, port: u16, lifetime: Duration) -> io::Result<Mapping> {
+        let request = utils::mapping_request(protocol, port, port, lifetime.as_secs() as u32);
+        let responce = self.request(&request, 16)?;
+
+        Ok(Mapping {
+            protocol,
+            internal_port: u16::from_be_bytes([responce[8], responce[9]]),
+            external_port: u16::from_be_bytes([responce[10], responce[11]]),
+            lifetime: Duration::from_secs(u32::from_be_bytes(responce[12..16].try_into().unwrap()) as u64),
+            obtained: Instant::now(),
+        })
+    }
+
+    fn unmap(&mut self, mapping: &Mapping) -> io::Result<()> {
+        // Zero lifetime and external port delete mapping
+        let request = utils::mapping_request(mapping.protocol, mapping.internal_port, 0, 0);
+        self.request(&request, 16).map(|_| ())
+    }
+}
+
+mod utils {
+    use super::*;
+
+    pub fn mapping_request(protocol: Protocol, internal: u16, external: u16, lifetime: u32) -> [u8; 12] {
+        let opcode = match protocol {
+            Protocol::Udp => 1,
+            Protocol::Tcp => 2,
+        };
+
+        let mut request = [0; 12];
+        request[1] = opcode;
+        request[4..6].copy_from_slice(&internal.to_be_bytes());
+        request[6..8].copy_from_slice(&external.to_be_bytes());
+        request[8..12].copy_from_slice(&lifetime.to_be_bytes());
+
+        request
+    }
+
+    pub fn check_result(code: u16) -> io::Result<()> {
+        let (kind, message) = match code {
+            0 => return Ok(()),
+            1 => (io::ErrorKind::Unsupported, "unsupported NAT-PMP version"),
+            2 => (io::ErrorKind::PermissionDenied, "port mapping is not authorized"),
+            3 => (io::ErrorKind::NotConnected, "gateway has no external address"),
+            4 => (io::ErrorKind::OutOfMemory, "gateway is out of resources"),
+            _ => (io::ErrorKind::Unsupported, "unsupported NAT-PMP request"),
+        };
+
+        Err(io::Error::new(kind, message))
+    }
+
+    /// Reads default gateway from routing table.
+    #[cfg(target_os = "linux")]
+    pub fn default_gateway() -> io::Result<Ipv4Addr> {
+        let routes = std::fs::read_to_string("/proc/net/route")?;
+
+        // Columns are interface, destination and gateway in little-endian hex
+        routes
+            .lines()
+            .skip(1)
+            .map(|line| line.split_whitespace().collect::<Vec<_>>())
+            .find(|columns| columns.get(1) == Some(&"00000000"))
+            .and_then(|columns| u32::from_str_radix(columns.get(2)?, 16).ok())
+            .map(|gateway| Ipv4Addr::from(gateway.swap_bytes()))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no default gateway"))
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn default_gateway() -> io::Result<Ipv4Addr> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "default gateway lookup is not supported"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn mapping_is_requested() {
+        let gateway = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = gateway.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let mut request = [0; 12];
+            let (_, client) = gateway.recv_from(&mut request).unwrap();
+
+            // Mapped to another external port for an hour
+            let mut responce = vec![0, 0x82, 0, 0, 0, 0, 0, 1];
+            responce.extend_from_slice(&request[4..6]);
+            responce.extend_from_slice(&6882u16.to_be_bytes());
+            responce.extend_from_slice(&3600u32.to_be_bytes());
+            gateway.send_to(&responce, client).unwrap();
+
+            request
+        });
+
+        let mapping = NatPmp::new(addr)
+            .unwrap()
+            .map(Protocol::Tcp, 6881, Duration::from_secs(7200))
+            .unwrap();
+        let request = server.join().unwrap();
+
+        assert_eq!(request, [0, 2, 0, 0, 0x1A, 0xE1, 0x1A, 0xE1, 0, 0, 0x1C, 0x20]);
+        assert_eq!((mapping.internal_port, mapping.external_port), (6881, 6882));
+        assert_eq!(mapping.lifetime, Duration::from_secs(3600));
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    io::{self, Read, Write},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use super::{Mapping, PortMapper, Protocol};
+
+/// Client of UPnP Internet Gateway Device, which maps ports with `WANIPConnection` (or `WANPPPConnection`)
+/// service.
+#[derive(Debug, Clone)]
+pub struct Upnp {
+    control: SocketAddr,
+    /// Path of service control URL.
+    control_path: String,
+    service_type: String,
+    /// Address of this host in gateway network.
+    local_ip: IpAddr,
+    timeout: Duration,
+}
+
+impl Upnp {
+    /// Multicast address of SSDP discovery.
+    pub const SSDP_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(239, 255, 255, 250)), 1900);
+    /// Timeout of requests to gateway.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Searches local network for gateway device for `timeout`, using the first one, which responds.
+    pub fn discover(timeout: Duration) -> io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        let search = format!(
+            "M-SEARCH * HTTP/1.1\r\nHOST: {}\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: {}\r\n\r\n",
+            Self::SSDP_ADDR,
+            "urn:schemas-upnp-org:device:InternetGatewayDevice:1"
+        );
+        socket.send_to(search.as_bytes(), Self::SSDP_ADDR)?;
+
+        let deadline = Instant::now() + timeout;
+        let mut buf = [0; 2048];
+
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()).filter(|r| !r.is_zero()) {
+            socket.set_read_timeout(Some(remaining))?;
+
+            let read = match socket.recv(&mut buf) {
+                Ok(read) => read,
+                Err(err) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => break,
+                Err(err) => return Err(err),
+            };
+
+            let responce = String::from_utf8_lossy(&buf[..read]);
+            let location = responce.lines().find_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                name.trim().eq_ignore_ascii_case("location").then(|| value.trim().to_owned())
+            });
+
+            // Devices without suitable service may respond too
+            if let Some(Ok(upnp)) = location.map(|location| Self::from_description(&location)) {
+                return Ok(upnp);
+            }
+        }
+
+        Err(io::Error::new(io::ErrorKind::NotFound, "no UPnP gateway found"))
+    }
+
+    /// Creates client of gateway with device description at `location` URL.
+    pub fn from_description(location: &str) -> io::Result<Self> {
+        let (addr, path) = utils::parse_url(location)?;
+        let head = format!("GET {} HTTP/1.0\r\n", path);
+        let (status, description, local_ip) = utils::request(addr, &head, "", Self::DEFAULT_TIMEOUT)?;
+
+        if status != 200 {
+            return Err(io::Error::other(format!("gateway responded with status {}", status)));
+        }
+
+        let (service_type, control_url) = utils::connection_service(&description)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "gateway has no WAN connection service"))?;
+
+        let (control, control_path) = match control_url.split_once("://") {
+            Some(_) => utils::parse_url(&control_url)?,
+            None if control_url.starts_with('/') => (addr, control_url),
+            None => (addr, format!("/{}", control_url)),
+        };
+
+        Ok(Self {
+            control,
+            control_path,
+            service_type,
+            local_ip,
+            timeout: Self::DEFAULT_TIMEOUT,
+        })
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Address of this host, which ports are mapped to.
+    pub fn local_ip(&self) -> IpAddr {
+        self.local_ip
+    }
+
+    /// Invokes `action` of connection service with `arguments`, returning status and body of responce.
+    fn call(&self, action: &str, arguments: &[(&str, String)]) -> io::Result<(u16, String)> {
+        let arguments = arguments
+            .iter()
+            .map(|(name, value)| format!("<{0}>{1}</{0}>", name, value))
+            .collect::<String>();
+
+        let body = format!(
+            "<?xml version=\"1.0\"?>\
+            <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+            s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+            <s:Body><u:{0} xmlns:u=\"{1}\">{2}</u:{0}></s:Body></s:Envelope>",
+            action, self.service_type, arguments
+        );
+        let head = format!(
+            "POST {} HTTP/1.0\r\nContent-Type: text/xml; charset=\"utf-8\"\r\nSOAPAction: \"{}#{}\"\r\n",
+            self.control_path, self.service_type, action
+        );
+
+        let (status, responce, _) = utils::request(self.control, &head, &body, self.timeout)?;
+        Ok((status, responce))
+    }
+}
+
+impl PortMapper for Upnp {
+    fn map(&mut self, protocol: Protocol, port: u16, lifetime: Duration) -> io::Result<Mapping> {
+        let mut arguments = vec![
+            ("NewRemoteHost", String::new()),
+            ("NewExternalPort", port.to_string()),
+            ("NewProtocol", utils::protocol(protocol).to_owned()),
+            ("NewInternalPort", port.to_string()),
+            ("NewInternalClient", self.local_ip.to_string()),
+            ("NewEnabled", "1".to_owned()),
+            ("NewPortMappingDescription", "bitrain".to_owned()),
+            ("NewLeaseDuration", lifetime.as_secs().to_string()),
+        ];
+
+        let (mut status, mut responce) = self.call("AddPortMapping", &arguments)?;
+
+        // Older gateways support only permanent mappings, renewing them is harmless
+        if utils::tag(&responce, "errorCode") == Some("725") {
+            arguments.last_mut().unwrap().1 = "0".to_owned();
+            (status, responce) = self.call("AddPortMapping", &arguments)?;
+        }
+
+        utils::check("AddPortMapping", status, &responce)?;
+
+        Ok(Mapping {
+            protocol,
+            internal_port: port,
+            external_port: port,
+            lifetime,
+            obtained: Instant::now(),
+        })
+    }
+
+    fn unmap(&mut self, mapping: &Mapping) -> io::Result<()> {
+        let arguments = [
+            ("NewRemoteHost", String::new()),
+            ("NewExternalPort", mapping.external_port.to_string()),
+            ("NewProtocol", utils::protocol(mapping.protocol).to_owned()),
+        ];
+        let (status, responce) = self.call("DeletePortMapping", &arguments)?;
+
+        utils::check("DeletePortMapping", status, &responce)
+    }
+}
+
+mod utils {
+    use super::*;
+
+    pub fn protocol(protocol: Protocol) -> &'static str {
+        match protocol {
+            Protocol::Tcp => "TCP",
+            Protocol::Udp => "UDP",
+        }
+    }
+
+    /// Turns failed action into error with UPnP error code, if gateway reported one.
+    pub fn check(action: &str, status: u16, responce: &str) -> io::Result<()> {
+        match (status, tag(responce, "errorCode")) {
+            (200, _) => Ok(()),
+            (_, Some(code)) => Err(io::Error::other(format!("{} failed with UPnP error {}", action, code))),
+            (_, None) => Err(io::Error::other(format!("{} failed with status {}", action, status))),
+        }
+    }
+
+    /// Splits `http://` URL into address and path.
+    pub fn parse_url(url: &str) -> io::Result<(SocketAddr, String)> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, "invalid gateway URL");
+
+        let rest = url.strip_prefix("http://").ok_or_else(invalid)?;
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        let authority = if authority.contains(':') {
+            authority.to_owned()
+        } else {
+            format!("{}:80", authority)
+        };
+
+        let addr = authority.to_socket_addrs()?.next().ok_or_else(invalid)?;
+        let path = if path.is_empty() { "/" } else { path };
+
+        Ok((addr, path.to_owned()))
+    }
+
+    /// Performs HTTP/1.0 request with `head` (request line and headers) and `body`. Returns status, body of
+    /// responce and local address of connection.
+    pub fn request(addr: SocketAddr, head: &str, body: &str, timeout: Duration) -> io::Result<(u16, String, IpAddr)> {
+        let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        let request = format!("{}Host: {}\r\nContent-Length: {}\r\n\r\n{}", head, addr, body.len(), body);
+        stream.write_all(request.as_bytes())?;
+
+        // Connection is closed after responce, so no need to deal with chunks
+        let mut responce = vec![];
+        stream.read_to_end(&mut responce)?;
+        let responce = String::from_utf8_lossy(&responce);
+
+        let (head, body) = responce.split_once("\r\n\r\n").unwrap_or((&responce, ""));
+        let status = head
+            .split(' ')
+            .nth(1)
+            .and_then(|status| status.parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed http responce"))?;
+
+        Ok((status, body.to_owned(), stream.local_addr()?.ip()))
+    }
+
+    /// Text of the first `name` element.
+    pub fn tag<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+        let start = xml.find(&format!("<{}>", name))? + name.len() + 2;
+        let end = start + xml[start..].find(&format!("</{}>", name))?;
+
+        Some(xml[start..end].trim())
+    }
+
+    /// Type and control URL of WAN connection service from device description.
+    pub fn connection_service(description: &str) -> Option<(String, String)> {
+        description.split("<service>").skip(1).find_map(|service| {
+            let service_type = tag(service, "serviceType")?;
+
+            if !service_type.contains(":WANIPConnection:") && !service_type.contains(":WANPPPConnection:") {
+                return None;
+            }
+
+            Some((service_type.to_owned(), tag(service, "controlURL")?.to_owned()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, thread};
+
+    const DESCRIPTION: &str = "<?xml version=\"1.0\"?><root><device><serviceList>\
+        <service><serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1</serviceType>\
+        <controlURL>/l3f</controlURL></service>\
+        <service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>\
+        <controlURL>/ctl/IPConn</controlURL></service>\
+        </serviceList></device></root>";
+
+    fn respond(listener: &TcpListener, responce: &str) -> String {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = String::new();
+
+        while !request.ends_with("\r\n\r\n") {
+            let mut byte = [0];
+            stream.read_exact(&mut byte).unwrap();
+            request.push(char::from(byte[0]));
+        }
+
+        let length = request
+            .lines()
+            .find_map(|line| line.strip_prefix("Content-Length: "))
+            .and_then(|length| length.parse().ok())
+            .unwrap();
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).unwrap();
+
+        write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", responce.len(), responce).unwrap();
+        request + &String::from_utf8(body).unwrap()
+    }
+
+    #[test]
+    fn port_is_mapped_with_soap_action() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let location = format!("http://{}/rootDesc.xml", listener.local_addr().unwrap());
+
+        let server = thread::spawn(move || {
+            respond(&listener, DESCRIPTION);
+            respond(&listener, "")
+        });
+
+        let mut upnp = Upnp::from_description(&location).unwrap();
+        let mapping = upnp.map(Protocol::Udp, 6881, Duration::from_secs(3600)).unwrap();
+        let request = server.join().unwrap();
+
+        assert_eq!(mapping.external_port, 6881);
+        assert!(request.starts_with("POST /ctl/IPConn HTTP/1.0\r\n"));
+        assert!(request.contains("SOAPAction: \"urn:schemas-upnp-org:service:WANIPConnection:1#AddPortMapping\""));
+        assert!(request.contains("<NewProtocol>UDP</NewProtocol>"));
+        assert!(request.contains("<NewInternalClient>127.0.0.1</NewInternalClient>"));
+    }
+}
@@ -0,0 +1,201 @@
+//! Running downloads, which tie metainfo, trackers, peer connections, picker and storage together.
+//!
+//! [`Session`] is entry point for using crate as client library: every added torrent is downloaded
+//! in background threads, while [`Torrent`] handle reports its progress and stops it. Torrents of session
+//! share its peer id, connection budget, listener and DHT node.
+//!
+//! Download is leech-only so far: requests of other peers are not served.
+mod config;
+mod events;
+mod listener;
+mod throttle;
+mod torrent;
+mod worker;
+
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU16, AtomicUsize, Ordering},
+        mpsc, Arc, Mutex, MutexGuard,
+    },
+    thread,
+    time::{Instant, SystemTime},
+};
+
+use crate::bencoded::Metainfo;
+use crate::dht::DhtNode;
+use crate::metrics::Metrics;
+use crate::peer::{generate_peer_id, BanList, CLIENT_PREFIX};
+
+pub use config::{SessionConfig, TorrentConfig};
+pub use events::{EventKind, TorrentEvent};
+pub use torrent::{Torrent, TorrentState, TorrentStats};
+
+use events::EventBus;
+use throttle::RateLimiter;
+use torrent::Shared;
+
+/// Client, which downloads torrents under one peer id.
+///
+/// Session is cheap to clone, all clones manage the same torrents. Progress of torrents can be followed
+/// with [`Session::subscribe`] instead of polling their stats.
+#[derive(Debug, Clone)]
+pub struct Session {
+    inner: Arc<Inner>,
+}
+
+/// State of session, shared by its torrents and listener.
+#[derive(Debug)]
+struct Inner {
+    peer_id: [u8; 20],
+    config: SessionConfig,
+    events: EventBus,
+    /// Running torrents by info hashes.
+    torrents: Mutex<HashMap<[u8; 20], Arc<Shared>>>,
+    /// Number of peer connections of all torrents.
+    connections: AtomicUsize,
+    /// Port, reported to trackers: the one of listener, once it's bound.
+    port: AtomicU16,
+    dht: Mutex<Option<DhtNode>>,
+    /// Budget of download rate, which torrents draw from.
+    download_limiter: RateLimiter,
+    metrics: Metrics,
+    bans: Mutex<BanList>,
+}
+
+impl Session {
+    /// Prefix of generated peer ids, see [`CLIENT_PREFIX`].
+    pub const PEER_ID_PREFIX: &'static [u8; 8] = CLIENT_PREFIX;
+
+    /// Creates session with default settings, which stores torrents in `download_dir`.
+    pub fn new(download_dir: impl Into<PathBuf>) -> Self {
+        Self::with_config(SessionConfig::new(download_dir))
+    }
+
+    /// Creates session with random peer id.
+    pub fn with_config(config: SessionConfig) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                peer_id: generate_peer_id(Self::PEER_ID_PREFIX),
+                port: AtomicU16::new(config.listen_port),
+                download_limiter: RateLimiter::default().child(config.download_rate_limit, Instant::now()),
+                config,
+                events: EventBus::default(),
+                torrents: Mutex::default(),
+                connections: AtomicUsize::new(0),
+                dht: Mutex::default(),
+                metrics: Metrics::new(),
+                bans: Mutex::default(),
+            }),
+        }
+    }
+
+    pub fn peer_id(&self) -> &[u8; 20] {
+        &self.inner.peer_id
+    }
+
+    pub fn config(&self) -> &SessionConfig {
+        &self.inner.config
+    }
+
+    /// Starts accepting peers of all torrents on [`listen_port`](`SessionConfig::listen_port`), returning
+    /// address of listener. Port 0 binds to any free port, which is reported to trackers instead.
+    ///
+    /// Listener stops, once all clones of session and handles of its torrents are dropped.
+    pub fn listen(&self) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, self.inner.config.listen_port))?;
+        listener.set_nonblocking(true)?;
+
+        let addr = listener.local_addr()?;
+        self.inner.port.store(addr.port(), Ordering::Relaxed);
+
+        let session = Arc::downgrade(&self.inner);
+        thread::spawn(move || listener::run(listener, session));
+
+        Ok(addr)
+    }
+
+    /// Looks for peers of public torrents in DHT with `node`, which should be already bootstrapped.
+    pub fn set_dht(&self, node: DhtNode) {
+        *self.inner.dht.lock().unwrap() = Some(node);
+    }
+
+    /// Starts downloading torrent of `metainfo` with [default](`SessionConfig::torrent`) settings.
+    /// Download runs until returned handle is stopped or dropped.
+    ///
+    /// Fails if files of torrent can't be created or torrent is already added.
+    pub fn add_torrent(&self, metainfo: &Metainfo) -> io::Result<Torrent> {
+        self.add_torrent_with(metainfo, self.inner.config.torrent.clone())
+    }
+
+    /// Same as [`add_torrent()`](`Session::add_torrent`) with settings of torrent.
+    pub fn add_torrent_with(&self, metainfo: &Metainfo, config: TorrentConfig) -> io::Result<Torrent> {
+        Torrent::start(metainfo, self.inner.clone(), config)
+    }
+
+    /// Info hashes of running torrents.
+    pub fn torrents(&self) -> Vec<[u8; 20]> {
+        self.inner.torrents.lock().unwrap().keys().copied().collect()
+    }
+
+    /// Stats of running torrent with `info_hash`.
+    pub fn stats(&self, info_hash: &[u8; 20]) -> Option<TorrentStats> {
+        self.inner.torrent(info_hash).map(|shared| shared.stats())
+    }
+
+    /// Number of peer connections of all torrents.
+    pub fn connections(&self) -> usize {
+        self.inner.connections.load(Ordering::SeqCst)
+    }
+
+    /// Metrics of all torrents of session.
+    pub fn metrics(&self) -> &Metrics {
+        &self.inner.metrics
+    }
+
+    /// Peers, which aren't connected to or accepted by torrents of session. Peers are banned automatically
+    /// for protocol violations and data of failed pieces, list can be also edited, saved and loaded.
+    pub fn bans(&self) -> MutexGuard<'_, BanList> {
+        self.inner.bans.lock().unwrap()
+    }
+
+    /// Returns channel, which recieves events of all torrents of session, starting from now.
+    ///
+    /// Events are buffered until recieved, so channel should be drained or dropped.
+    pub fn subscribe(&self) -> mpsc::Receiver<TorrentEvent> {
+        self.inner.events.subscribe()
+    }
+}
+
+impl Inner {
+    /// Takes slot of connection budget, returns `false` if budget is exhausted.
+    fn try_connect(&self) -> bool {
+        self.connections
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |connections| {
+                (connections < self.config.max_connections).then_some(connections + 1)
+            })
+            .is_ok()
+    }
+
+    /// Returns slot, taken by [`try_connect()`](`Inner::try_connect`).
+    fn disconnected(&self) {
+        self.connections.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    fn port(&self) -> u16 {
+        self.port.load(Ordering::Relaxed)
+    }
+
+    /// `true` if peer at `host` is banned. Host names can't be checked before connection.
+    fn is_banned(&self, host: &str) -> bool {
+        host.parse::<IpAddr>()
+            .is_ok_and(|ip| self.bans.lock().unwrap().is_banned(&ip, SystemTime::now()))
+    }
+
+    fn torrent(&self, info_hash: &[u8; 20]) -> Option<Arc<Shared>> {
+        self.torrents.lock().unwrap().get(info_hash).cloned()
+    }
+}
@@ -0,0 +1,183 @@
+use std::{path::PathBuf, time::Duration};
+
+use crate::peer::EncryptionPolicy;
+use crate::picker::{QueueDepth, RequestTracker};
+use crate::storage::Allocation;
+use crate::tracker::AnnounceRequest;
+
+/// Settings of [`Session`](`super::Session`), shared by all of its torrents.
+///
+/// Created with defaults by [`SessionConfig::new`] and adjusted with setters of the same names as fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionConfig {
+    /// Directory, torrents are downloaded into, unless [`TorrentConfig::download_dir`] is set.
+    pub download_dir: PathBuf,
+    /// Port of [listener](`super::Session::listen`), which is reported to trackers.
+    pub listen_port: u16,
+    /// Number of peer connections of all torrents together.
+    pub max_connections: usize,
+    /// Whether peer connections are encrypted.
+    pub encryption: EncryptionPolicy,
+    /// Time peer has to accept connection and answer handshake.
+    pub connect_timeout: Duration,
+    /// Limit of every single read and write of tracker announces.
+    pub tracker_timeout: Duration,
+    /// Limit of total download rate of all torrents in bytes per second, unlimited if `None`.
+    pub download_rate_limit: Option<u64>,
+    /// Settings of torrents, which are added without their own ones.
+    pub torrent: TorrentConfig,
+}
+
+/// Settings of single torrent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TorrentConfig {
+    /// Directory, torrent is downloaded into instead of session one.
+    pub download_dir: Option<PathBuf>,
+    /// Number of peers, torrent is connected to, within connection budget of session.
+    pub max_peers: usize,
+    /// Limit of requests, which are kept pending on every unchoked peer. Actual number is tuned
+    /// to throughput and latency of peer, see [`QueueDepth`].
+    pub queue_depth: usize,
+    /// Time peer has to answer request, before block is requested again.
+    pub request_timeout: Duration,
+    /// Time peer may leave requests unanswered, before it's given only one request at a time.
+    pub snub_timeout: Duration,
+    /// Limit of download rate in bytes per second, unlimited if `None`. Rate is limited by session one as well.
+    pub download_rate_limit: Option<u64>,
+    /// Limit of download rate of every single peer in bytes per second, unlimited if `None`.
+    pub peer_download_rate_limit: Option<u64>,
+    pub allocation: Allocation,
+    /// `key`, sent with announces, so trackers recognize torrent after IP change. Random if `None`, restored
+    /// key (i.e. [`ResumeData::key`](`crate::storage::ResumeData::key`)) keeps it across restarts.
+    pub announce_key: Option<u32>,
+    /// Number of peers, asked from trackers with every announce, except the `stopped` one.
+    pub numwant: u32,
+}
+
+impl SessionConfig {
+    pub const DEFAULT_LISTEN_PORT: u16 = 6881;
+    pub const DEFAULT_MAX_CONNECTIONS: usize = 200;
+    pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+    pub const DEFAULT_TRACKER_TIMEOUT: Duration = Duration::from_secs(30);
+
+    pub fn new(download_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            download_dir: download_dir.into(),
+            listen_port: Self::DEFAULT_LISTEN_PORT,
+            max_connections: Self::DEFAULT_MAX_CONNECTIONS,
+            encryption: EncryptionPolicy::default(),
+            connect_timeout: Self::DEFAULT_CONNECT_TIMEOUT,
+            tracker_timeout: Self::DEFAULT_TRACKER_TIMEOUT,
+            download_rate_limit: None,
+            torrent: TorrentConfig::default(),
+        }
+    }
+
+    pub fn listen_port(mut self, listen_port: u16) -> Self {
+        self.listen_port = listen_port;
+        self
+    }
+
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+
+    pub fn encryption(mut self, encryption: EncryptionPolicy) -> Self {
+        self.encryption = encryption;
+        self
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    pub fn tracker_timeout(mut self, tracker_timeout: Duration) -> Self {
+        self.tracker_timeout = tracker_timeout;
+        self
+    }
+
+    pub fn download_rate_limit(mut self, bytes_per_second: u64) -> Self {
+        self.download_rate_limit = Some(bytes_per_second);
+        self
+    }
+
+    pub fn torrent(mut self, torrent: TorrentConfig) -> Self {
+        self.torrent = torrent;
+        self
+    }
+}
+
+impl TorrentConfig {
+    pub const DEFAULT_MAX_PEERS: usize = 30;
+    pub const DEFAULT_QUEUE_DEPTH: usize = QueueDepth::DEFAULT_MAX_DEPTH;
+
+    pub fn download_dir(mut self, download_dir: impl Into<PathBuf>) -> Self {
+        self.download_dir = Some(download_dir.into());
+        self
+    }
+
+    pub fn max_peers(mut self, max_peers: usize) -> Self {
+        self.max_peers = max_peers;
+        self
+    }
+
+    /// Sets limit of pending requests per peer, at least one.
+    pub fn queue_depth(mut self, queue_depth: usize) -> Self {
+        self.queue_depth = queue_depth.max(1);
+        self
+    }
+
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    pub fn snub_timeout(mut self, snub_timeout: Duration) -> Self {
+        self.snub_timeout = snub_timeout;
+        self
+    }
+
+    pub fn download_rate_limit(mut self, bytes_per_second: u64) -> Self {
+        self.download_rate_limit = Some(bytes_per_second);
+        self
+    }
+
+    pub fn peer_download_rate_limit(mut self, bytes_per_second: u64) -> Self {
+        self.peer_download_rate_limit = Some(bytes_per_second);
+        self
+    }
+
+    pub fn allocation(mut self, allocation: Allocation) -> Self {
+        self.allocation = allocation;
+        self
+    }
+
+    pub fn announce_key(mut self, announce_key: u32) -> Self {
+        self.announce_key = Some(announce_key);
+        self
+    }
+
+    pub fn numwant(mut self, numwant: u32) -> Self {
+        self.numwant = numwant;
+        self
+    }
+}
+
+impl Default for TorrentConfig {
+    fn default() -> Self {
+        Self {
+            download_dir: None,
+            max_peers: Self::DEFAULT_MAX_PEERS,
+            queue_depth: Self::DEFAULT_QUEUE_DEPTH,
+            request_timeout: RequestTracker::<()>::DEFAULT_TIMEOUT,
+            snub_timeout: RequestTracker::<()>::DEFAULT_SNUB_TIMEOUT,
+            download_rate_limit: None,
+            peer_download_rate_limit: None,
+            allocation: Allocation::default(),
+            announce_key: None,
+            numwant: AnnounceRequest::DEFAULT_NUMWANT,
+        }
+    }
+}
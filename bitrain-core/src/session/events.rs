@@ -0,0 +1,76 @@
+use std::{
+    net::SocketAddr,
+    sync::{mpsc, Mutex},
+};
+
+/// Notable change of torrent state, which is emitted by session threads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TorrentEvent {
+    pub info_hash: [u8; 20],
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventKind {
+    /// Piece passed verification and is announced to peers.
+    PieceVerified(usize),
+    /// Piece didn't match its hash and will be downloaded again.
+    PieceFailed(usize),
+    PeerConnected(SocketAddr),
+    PeerDisconnected(SocketAddr),
+    /// Announce to tracker at `url` failed.
+    TrackerError { url: String, message: String },
+    /// All pieces are downloaded and verified.
+    Complete,
+    /// Download was aborted, see [`TorrentState::Failed`](`super::TorrentState::Failed`).
+    Failed(String),
+}
+
+/// Broadcasts events to every subscribed channel.
+#[derive(Debug, Default)]
+pub(super) struct EventBus {
+    subscribers: Mutex<Vec<mpsc::Sender<TorrentEvent>>>,
+}
+
+impl EventBus {
+    pub fn subscribe(&self) -> mpsc::Receiver<TorrentEvent> {
+        let (sender, reciever) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(sender);
+
+        reciever
+    }
+
+    /// Sends event to subscribers, forgetting ones, which dropped their recievers.
+    pub fn emit(&self, info_hash: [u8; 20], kind: EventKind) {
+        let event = TorrentEvent { info_hash, kind };
+
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_broadcast_to_subscribers() {
+        let bus = EventBus::default();
+        let first = bus.subscribe();
+        let second = bus.subscribe();
+        drop(second);
+
+        bus.emit([1; 20], EventKind::PieceVerified(3));
+
+        assert_eq!(
+            first.try_recv(),
+            Ok(TorrentEvent {
+                info_hash: [1; 20],
+                kind: EventKind::PieceVerified(3)
+            })
+        );
+        assert_eq!(bus.subscribers.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,96 @@
+use std::{
+    io,
+    net::{TcpListener, TcpStream},
+    sync::Weak,
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::worker;
+use super::Inner;
+use crate::peer::{CancelToken, Connection};
+
+/// How often listener checks for incoming connections and whether session is still alive.
+const TICK: Duration = Duration::from_millis(100);
+
+/// Accepts peers of torrents of `session` on non-blocking `listener`, until session is dropped.
+///
+/// Every accepted peer takes slot of connection budget before its handshake is started, and handshakes,
+/// which aren't finished within [`connect_timeout`](`super::SessionConfig::connect_timeout`), are aborted.
+pub fn run(listener: TcpListener, session: Weak<Inner>) {
+    let mut pending = vec![];
+
+    loop {
+        let Some(session) = session.upgrade() else { return };
+        utils::abort_expired(&mut pending, Instant::now());
+
+        match listener.accept() {
+            Ok((tcp, peer_addr)) => {
+                if session.is_banned(&peer_addr.ip().to_string()) || !session.try_connect() {
+                    continue;
+                }
+
+                let token = CancelToken::new();
+                pending.push((Instant::now() + session.config.connect_timeout, token.clone()));
+                thread::spawn(move || {
+                    if !accept(&session, tcp, &token).unwrap_or(false) {
+                        session.disconnected();
+                    }
+                });
+            }
+            // Session isn't kept alive while listener waits
+            Err(_) => {
+                drop(session);
+                thread::sleep(TICK);
+            }
+        }
+    }
+}
+
+/// Exchanges handshakes with peer and hands it over to its torrent, if torrent is running and has room for peer.
+///
+/// Returns `true` if peer was handed over, so its slot of connection budget was released on disconnect.
+/// Handshake is aborted once `cancel` is cancelled.
+fn accept(session: &Inner, tcp: TcpStream, cancel: &CancelToken) -> io::Result<bool> {
+    tcp.set_nonblocking(false)?;
+    tcp.set_read_timeout(Some(session.config.connect_timeout))?;
+    tcp.set_write_timeout(Some(session.config.connect_timeout))?;
+    let peer_addr = tcp.peer_addr()?;
+    let registration = cancel.register(&tcp)?;
+
+    let info_hashes = session.torrents.lock().unwrap().keys().copied().collect::<Vec<_>>();
+    let (mut connection, info_hash) = Connection::accept(tcp, &info_hashes, session.config.encryption)?;
+    let recieved = connection.recv_handshake()?;
+    drop(registration);
+
+    // Torrent of MSE handshake should match the one of plaintext one
+    if info_hash.is_some_and(|info_hash| info_hash != *recieved.info_hash) || *recieved.peer_id == session.peer_id {
+        return Ok(false);
+    }
+
+    let Some(shared) = session.torrent(&recieved.info_hash) else { return Ok(false) };
+    let addr = (peer_addr.ip().to_string(), peer_addr.port());
+
+    if !shared.admit_connected(&addr) {
+        return Ok(false);
+    }
+
+    worker::run_incoming(shared, connection, addr);
+    Ok(true)
+}
+
+mod utils {
+    use super::*;
+
+    /// Aborts handshakes, which weren't finished by their deadline. Finished ones are just forgotten.
+    pub fn abort_expired(pending: &mut Vec<(Instant, CancelToken)>, now: Instant) {
+        pending.retain(|(deadline, token)| {
+            if *deadline > now {
+                return true;
+            }
+
+            token.cancel();
+            false
+        });
+    }
+}
@@ -0,0 +1,97 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Token bucket, which limits transfer rate. Up to one second worth of transfer may be done in a burst.
+#[derive(Debug, Clone)]
+pub(super) struct Throttle {
+    /// Bytes per second.
+    rate: u64,
+    /// Bytes, which can be transferred right away. Negative, when transfer is ahead of rate.
+    available: f64,
+    last: Instant,
+}
+
+impl Throttle {
+    pub fn new(rate: u64, now: Instant) -> Self {
+        Self {
+            rate: rate.max(1),
+            available: rate as f64,
+            last: now,
+        }
+    }
+
+    /// Accounts `amount` of transferred bytes, returning how long transfer should pause to keep the rate.
+    pub fn consume(&mut self, amount: u64, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = self.last.max(now);
+
+        let rate = self.rate as f64;
+        self.available = (self.available + elapsed * rate).min(rate) - amount as f64;
+
+        if self.available >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.available / rate)
+        }
+    }
+}
+
+/// Hierarchy of throttles, i.e. of connection, its torrent and session. Every transfer is accounted by all levels
+/// and pauses for the longest of their pauses, so limit of level holds regardless of number of its children.
+#[derive(Debug, Clone, Default)]
+pub(super) struct RateLimiter {
+    /// Throttles from the top level down, shared with other children of the same parents.
+    levels: Vec<Arc<Mutex<Throttle>>>,
+}
+
+impl RateLimiter {
+    /// Creates limiter, which draws from budget of this one and additionally limits rate to `rate`, if it's set.
+    pub fn child(&self, rate: Option<u64>, now: Instant) -> Self {
+        let mut levels = self.levels.clone();
+        levels.extend(rate.map(|rate| Arc::new(Mutex::new(Throttle::new(rate, now)))));
+
+        Self { levels }
+    }
+
+    /// Accounts `amount` of transferred bytes on every level, returning how long transfer should pause.
+    pub fn consume(&self, amount: u64, now: Instant) -> Duration {
+        self.levels
+            .iter()
+            .map(|throttle| throttle.lock().unwrap().consume(amount, now))
+            .max()
+            .unwrap_or(Duration::ZERO)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transfer_ahead_of_rate_is_paused() {
+        let now = Instant::now();
+        let mut throttle = Throttle::new(1000, now);
+
+        assert_eq!(throttle.consume(1000, now), Duration::ZERO);
+        assert_eq!(throttle.consume(500, now), Duration::from_millis(500));
+        assert_eq!(throttle.consume(500, now + Duration::from_millis(500)), Duration::from_millis(500));
+        // Idle time doesn't accumulate beyond burst
+        assert_eq!(throttle.consume(1000, now + Duration::from_secs(10)), Duration::ZERO);
+    }
+
+    #[test]
+    fn children_draw_from_budget_of_parent() {
+        let now = Instant::now();
+        let session = RateLimiter::default().child(Some(1000), now);
+        let first = session.child(None, now);
+        let second = session.child(Some(100), now);
+
+        assert_eq!(first.consume(900, now), Duration::ZERO);
+        // Both limit of peer and the rest of session budget are exceeded, the longer pause wins
+        assert_eq!(second.consume(200, now), Duration::from_secs(1));
+        assert_eq!(first.consume(100, now), Duration::from_millis(200));
+        assert_eq!(RateLimiter::default().consume(1 << 20, now), Duration::ZERO);
+    }
+}
@@ -0,0 +1,697 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    net::IpAddr,
+    sync::{Arc, Mutex, MutexGuard},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::bencoded::{BInt, BString, Layout, Metainfo, TrackerResponce};
+use crate::dht::NodeId;
+use crate::messages::BTInt;
+use crate::peer::{CancelToken, PeerSource, Swarm};
+use crate::picker::{PiecePicker, Pieces, RarestFirst, RequestTracker};
+use crate::storage::{FileStorage, PieceVerifier};
+use crate::tracker::{AnnounceRequest, Announcer, Event, HttpTracker, TrackerList};
+
+use super::config::TorrentConfig;
+use super::events::EventKind;
+use super::throttle::RateLimiter;
+use super::worker;
+use super::Inner;
+
+/// Handle of running download, which is stopped when handle is dropped.
+#[derive(Debug)]
+pub struct Torrent {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TorrentState {
+    Downloading,
+    /// All pieces are downloaded and verified.
+    Complete,
+    Stopped,
+    /// Download was aborted, i.e. because data couldn't be written to disk.
+    Failed(String),
+}
+
+/// Snapshot of download progress.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TorrentStats {
+    pub state: TorrentState,
+    pub pieces: usize,
+    /// Number of pieces, which passed verification.
+    pub have: usize,
+    /// Amount of recieved block data, in bytes.
+    pub downloaded: u64,
+    pub uploaded: u64,
+    /// Number of connected peers.
+    pub peers: usize,
+}
+
+/// State of torrent, shared by its threads.
+#[derive(Debug)]
+pub(super) struct Shared {
+    pub info_hash: [u8; 20],
+    /// `key` of announces.
+    pub announce_key: u32,
+    pub config: TorrentConfig,
+    pub session: Arc<Inner>,
+    pub cancel: CancelToken,
+    /// Budget of download rate of torrent, which its peers draw from.
+    pub download_limiter: RateLimiter,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+pub(super) struct State {
+    pub pieces: Pieces,
+    pub picker: Box<dyn PiecePicker>,
+    /// Requests of connected peers by their ids.
+    pub requests: RequestTracker<usize>,
+    pub storage: FileStorage,
+    pub verifier: PieceVerifier,
+    /// Verified pieces in order of verification, which are announced to peers with `Have`.
+    pub verified: Vec<BTInt>,
+    pub status: TorrentState,
+    pub downloaded: u64,
+    pub uploaded: u64,
+    /// Addresses of connected peers.
+    pub peers: HashSet<(String, u16)>,
+    /// IPs of peers, which sent blocks of pieces, which aren't verified yet.
+    pub contributors: HashMap<BTInt, HashSet<IpAddr>>,
+    /// Peers from trackers and DHT, which are tried by coordinator and reported on by workers.
+    pub swarm: Swarm<(String, u16)>,
+    next_peer_id: usize,
+}
+
+impl Shared {
+    pub fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    pub fn emit(&self, kind: EventKind) {
+        self.session.events.emit(self.info_hash, kind)
+    }
+
+    /// Reserves connection to peer at `addr`, if torrent is downloading, has room for it within its own limit
+    /// and connection budget of session and isn't connected to it yet.
+    ///
+    /// Reservation is released by [`worker`] on disconnect.
+    pub fn admit(&self, addr: &(String, u16)) -> bool {
+        if !self.session.try_connect() {
+            return false;
+        }
+
+        let admitted = self.admit_connected(addr);
+        if !admitted {
+            self.session.disconnected();
+        }
+
+        admitted
+    }
+
+    /// Same as [`admit()`](`Shared::admit`), but for peer, which holds slot of connection budget already
+    /// (i.e. accepted by listener). Slot isn't released, if peer isn't admitted.
+    pub fn admit_connected(&self, addr: &(String, u16)) -> bool {
+        let mut state = self.lock();
+
+        if state.status != TorrentState::Downloading
+            || state.peers.len() >= self.config.max_peers
+            || state.peers.contains(addr)
+        {
+            return false;
+        }
+
+        state.peers.insert(addr.clone())
+    }
+
+    pub fn stats(&self) -> TorrentStats {
+        let state = self.lock();
+
+        TorrentStats {
+            state: state.status.clone(),
+            pieces: state.pieces.count(),
+            have: state.pieces.have_count(),
+            downloaded: state.downloaded,
+            uploaded: state.uploaded,
+            peers: state.peers.len(),
+        }
+    }
+}
+
+impl State {
+    /// Allocates id of newly connected peer.
+    pub fn next_peer_id(&mut self) -> usize {
+        self.next_peer_id += 1;
+        self.next_peer_id
+    }
+}
+
+impl Torrent {
+    /// How often background thread checks verified pieces and due announces.
+    const TICK: Duration = Duration::from_millis(100);
+
+    /// Starts download and registers it in `session`.
+    pub(super) fn start(metainfo: &Metainfo, session: Arc<Inner>, config: TorrentConfig) -> io::Result<Self> {
+        let info_hash = metainfo.info_hash();
+        let mut torrents = session.torrents.lock().unwrap();
+        if torrents.contains_key(&info_hash) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, "torrent is already added"));
+        }
+
+        let download_dir = config.download_dir.as_ref().unwrap_or(&session.config.download_dir);
+        let mut storage = FileStorage::new(&metainfo.info, download_dir).allocation(config.allocation);
+        storage.allocate()?;
+
+        let pieces = metainfo.info.piece_count();
+        let shared = Arc::new(Shared {
+            info_hash,
+            announce_key: config.announce_key.unwrap_or_else(rand::random),
+            cancel: CancelToken::new(),
+            download_limiter: session.download_limiter.child(config.download_rate_limit, Instant::now()),
+            session: session.clone(),
+            state: Mutex::new(State {
+                pieces: Pieces::new(pieces),
+                picker: Box::new(RarestFirst),
+                requests: RequestTracker::new(metainfo.info.layout())
+                    .timeout(config.request_timeout)
+                    .snub_timeout(config.snub_timeout),
+                storage,
+                verifier: PieceVerifier::with_available_parallelism(&metainfo.info),
+                verified: vec![],
+                status: TorrentState::Downloading,
+                downloaded: 0,
+                uploaded: 0,
+                peers: HashSet::new(),
+                contributors: HashMap::new(),
+                swarm: Swarm::new().private(metainfo.info.is_private()),
+                next_peer_id: 0,
+            }),
+            config,
+        });
+        torrents.insert(info_hash, shared.clone());
+        drop(torrents);
+
+        let coordinator = Coordinator {
+            shared: shared.clone(),
+            trackers: TrackerList::from_metainfo(metainfo),
+            announcer: Announcer::new(Instant::now()),
+            tracker_ids: HashMap::new(),
+            layout: metainfo.info.layout(),
+            event: Some(Event::Started),
+            // Peers of private torrents come only from their trackers (BEP 27)
+            next_dht_lookup: (!metainfo.info.is_private()).then(Instant::now),
+        };
+        let thread = thread::spawn(move || coordinator.run());
+
+        Ok(Self {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn info_hash(&self) -> &[u8; 20] {
+        &self.shared.info_hash
+    }
+
+    pub fn stats(&self) -> TorrentStats {
+        self.shared.stats()
+    }
+
+    /// `key`, sent with announces, which should be saved to be reused after restart.
+    pub fn announce_key(&self) -> u32 {
+        self.shared.announce_key
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.shared.lock().status == TorrentState::Complete
+    }
+
+    /// Switches strategy of picking pieces (i.e. to [`Sequential`](`crate::picker::Sequential`) for streaming).
+    pub fn set_picker(&self, picker: Box<dyn PiecePicker>) {
+        self.shared.lock().picker = picker;
+    }
+
+    /// Disconnects peers, tells trackers, that download is stopped, and flushes files to disk.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.shared.cancel.cancel();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+            self.shared.session.torrents.lock().unwrap().remove(&self.shared.info_hash);
+        }
+    }
+}
+
+impl Drop for Torrent {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Background thread of torrent, which announces to trackers, connects to peers and handles verified pieces.
+struct Coordinator {
+    shared: Arc<Shared>,
+    trackers: TrackerList,
+    announcer: Announcer,
+    /// `tracker id`s, returned by trackers, by their URLs.
+    tracker_ids: HashMap<String, BString>,
+    layout: Layout,
+    /// Event of the next announce.
+    event: Option<Event>,
+    /// Time of the next lookup of peers in DHT of session, `None` for private torrents.
+    next_dht_lookup: Option<Instant>,
+}
+
+impl Coordinator {
+    /// Interval between lookups of peers in DHT.
+    const DHT_INTERVAL: Duration = Duration::from_secs(15 * 60);
+    /// Time, single DHT lookup may take.
+    const DHT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn run(mut self) {
+        while !self.shared.cancel.is_cancelled() {
+            let now = Instant::now();
+
+            if self.announcer.is_due(now) {
+                self.announce(now);
+            }
+            if self.next_dht_lookup.is_some_and(|next| next <= now) {
+                self.lookup_dht(now);
+            }
+
+            self.connect_peers(now);
+            self.handle_verified(now);
+
+            thread::sleep(Torrent::TICK);
+        }
+
+        let mut state = self.shared.lock();
+        if matches!(state.status, TorrentState::Downloading | TorrentState::Complete) {
+            state.status = TorrentState::Stopped;
+        }
+        let _ = state.storage.sync();
+        drop(state);
+
+        // Trackers are told about stop only if they heard about start
+        if self.event.is_none() {
+            self.event = Some(Event::Stopped);
+            self.announce(Instant::now());
+        }
+    }
+
+    fn announce(&mut self, now: Instant) {
+        let request = {
+            let state = self.shared.lock();
+            let left = (0..state.pieces.count())
+                .filter(|piece| !state.pieces.have(*piece))
+                .filter_map(|piece| self.layout.piece_size(piece as BInt))
+                .sum::<BInt>();
+
+            let session = &self.shared.session;
+            let mut builder = AnnounceRequest::builder(self.shared.info_hash, session.peer_id, session.port())
+                .downloaded(state.downloaded)
+                .uploaded(state.uploaded)
+                .left(left)
+                .key(self.shared.announce_key)
+                // Stopping client needs no peers
+                .numwant(match self.event {
+                    Some(Event::Stopped) => 0,
+                    _ => self.shared.config.numwant,
+                });
+            if let Some(event) = self.event {
+                builder = builder.event(event);
+            }
+
+            builder.build()
+        };
+
+        let responce = self.trackers.announce_with(|url| {
+            let tracker = HttpTracker::new(url).with_timeout(self.shared.session.config.tracker_timeout);
+            let mut request = request.clone();
+            request.tracker_id = self.tracker_ids.get(url).cloned();
+
+            match tracker.announce(&request) {
+                Ok(TrackerResponce::Success(responce)) => {
+                    if let Some(tracker_id) = responce.info.tracker_id() {
+                        self.tracker_ids.insert(url.to_owned(), tracker_id.clone());
+                    }
+
+                    Ok(responce)
+                }
+                Ok(TrackerResponce::Error(failure)) => Err(failure.to_string()),
+                Err(err) => Err(err.to_string()),
+            }
+        });
+
+        match responce {
+            Ok(responce) => {
+                self.announcer.on_success(&responce.info, now);
+                self.event = None;
+
+                self.add_peers(responce.peers().map(|addr| addr.into_host_port()), PeerSource::Tracker);
+            }
+            Err(errors) => {
+                self.announcer.on_failure(now);
+
+                for (url, message) in errors {
+                    self.shared.emit(EventKind::TrackerError { url, message });
+                }
+            }
+        }
+    }
+
+    /// Looks for peers in DHT of session. Lookup is skipped, if DHT is busy with lookup of other torrent.
+    fn lookup_dht(&mut self, now: Instant) {
+        self.next_dht_lookup = Some(now + Self::DHT_INTERVAL);
+
+        let Ok(mut dht) = self.shared.session.dht.try_lock() else { return };
+        let Some(node) = dht.as_mut() else { return };
+
+        if let Ok(result) = node.get_peers(NodeId::from(self.shared.info_hash), now + Self::DHT_TIMEOUT) {
+            drop(dht);
+            let peers = result.peers.iter().map(|addr| (addr.ip().to_string(), addr.port()));
+            self.add_peers(peers, PeerSource::Dht);
+        }
+    }
+
+    fn add_peers(&mut self, peers: impl Iterator<Item = (String, u16)>, source: PeerSource) {
+        self.shared.lock().swarm.extend(peers, source);
+    }
+
+    fn connect_peers(&mut self, now: Instant) {
+        let candidates = self.shared.lock().swarm.candidates(now);
+
+        for addr in candidates {
+            if self.shared.session.is_banned(&addr.0) {
+                self.shared.lock().swarm.remove(&addr);
+                continue;
+            }
+            // Peer connected to us from the same address
+            if self.shared.lock().peers.contains(&addr) {
+                continue;
+            }
+
+            // Torrent or the whole session is out of connections, peers are tried later
+            if !self.shared.admit(&addr) {
+                return;
+            }
+
+            self.shared.lock().swarm.on_attempt(&addr);
+            let shared = self.shared.clone();
+            thread::spawn(move || worker::run(shared, addr));
+        }
+    }
+
+    fn handle_verified(&mut self, now: Instant) {
+        let mut state = self.shared.lock();
+
+        while let Some(verification) = state.verifier.try_recv() {
+            let contributors = state.contributors.remove(&(verification.piece as BTInt)).unwrap_or_default();
+
+            if verification.passed {
+                state.pieces.complete(verification.piece);
+                state.verified.push(verification.piece as BTInt);
+                self.shared.emit(EventKind::PieceVerified(verification.piece));
+            } else {
+                state.pieces.abort(verification.piece);
+                self.shared.session.metrics.verification_failures.inc();
+
+                let mut bans = self.shared.session.bans.lock().unwrap();
+                for ip in contributors {
+                    bans.record_hash_failure(ip, SystemTime::now());
+                }
+                self.shared.emit(EventKind::PieceFailed(verification.piece));
+            }
+        }
+
+        if state.status == TorrentState::Downloading && state.pieces.is_complete() {
+            state.status = TorrentState::Complete;
+            self.shared.emit(EventKind::Complete);
+
+            // Trackers, which haven't heard about start yet, learn about completion from `left`
+            if self.event.is_none() {
+                self.event = Some(Event::Completed);
+                drop(state);
+                self.announce(now);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bencoded::PeerList;
+    use bytes::Bytes;
+    use crate::messages::{Bitfield, Handshake, Message, Piece, Standalone};
+    use crate::peer::{BanReason, Connection, EncryptionPolicy, Peer};
+    use crate::session::{Session, SessionConfig};
+    use crate::test_utils::temp_dir;
+    use std::{
+        fs,
+        io::{Read, Write},
+        net::{Ipv4Addr, SocketAddrV4, TcpListener, TcpStream},
+        sync::mpsc,
+    };
+
+    /// Tracker, which returns single `peer` and `tracker id` to every announce and reports request lines.
+    fn serve_tracker(listener: TcpListener, peer: SocketAddrV4, requests: mpsc::Sender<String>) {
+        let PeerList::Compact(peers) = PeerList::from_compact([peer]) else { unreachable!() };
+        let mut body = b"d8:completei1e10:incompletei0e8:intervali1800e5:peers6:".to_vec();
+        body.extend_from_slice(peers.as_bytes());
+        body.extend_from_slice(b"10:tracker id2:ide");
+
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut request = vec![];
+            while !request.ends_with(b"\r\n\r\n") {
+                let mut byte = [0];
+                stream.read_exact(&mut byte).unwrap();
+                request.push(byte[0]);
+            }
+            let line = String::from_utf8_lossy(&request).lines().next().unwrap_or_default().to_owned();
+            let _ = requests.send(line);
+
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len()).unwrap();
+            stream.write_all(&body).unwrap();
+        }
+    }
+
+    const SEED_ID: &[u8; 20] = b"-XX0001-seedseedseed";
+
+    /// Peer, which accepts connection and seeds `data`.
+    fn serve_seed(listener: TcpListener, info_hash: [u8; 20], data: Vec<u8>, piece_length: usize) {
+        let (tcp, _) = listener.accept().unwrap();
+        let (mut connection, _) = Connection::accept(tcp, &[info_hash], EncryptionPolicy::Enabled).unwrap();
+
+        let handshake = connection.recv_handshake().unwrap();
+        let handshake = Handshake {
+            peer_id: Box::new(*SEED_ID),
+            ..handshake
+        };
+        connection.send(&handshake).unwrap();
+
+        seed(connection, data, piece_length);
+    }
+
+    /// Tells peer, that every piece of `data` is available, and answers all requests.
+    fn seed(mut connection: Connection, data: Vec<u8>, piece_length: usize) {
+        let data = Bytes::from(data);
+        let pieces = data.len().div_ceil(piece_length);
+        let bits = (0..pieces.div_ceil(8))
+            .map(|byte| (0..8).filter(|bit| byte * 8 + bit < pieces).map(|bit| 0x80 >> bit).sum())
+            .collect();
+        connection.send(&Message::Bitfield(Bitfield { bits })).unwrap();
+        connection.send(&Message::Unchoke).unwrap();
+
+        while let Ok(message) = connection.recv::<Message>() {
+            if let Some(Message::Request(request)) = message {
+                let start = request.piece_index as usize * piece_length + request.offset as usize;
+                let piece = Piece {
+                    piece_index: request.piece_index,
+                    offset: request.offset,
+                    data: data.slice(start..start + request.data_length as usize),
+                };
+
+                connection.send(&Message::Piece(piece)).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn torrent_is_downloaded_from_seed() {
+        let dir = temp_dir("session-download");
+        let data = (0..40000u32).map(|i| (i * 7 % 251) as u8).collect::<Vec<_>>();
+        fs::write(dir.join("content.bin"), &data).unwrap();
+
+        let tracker = TcpListener::bind("127.0.0.1:0").unwrap();
+        let seed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let seed_addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, seed.local_addr().unwrap().port());
+        let announce = format!("http://{}/announce", tracker.local_addr().unwrap());
+
+        let metainfo = Metainfo::builder(dir.join("content.bin"), announce)
+            .piece_length(1 << 14)
+            .build()
+            .unwrap();
+        let info_hash = metainfo.info_hash();
+
+        let (requests, announces) = mpsc::channel();
+        thread::spawn(move || serve_tracker(tracker, seed_addr, requests));
+        thread::spawn(move || serve_seed(seed, info_hash, data, 1 << 14));
+
+        let session = Session::new(dir.join("downloads"));
+        let events = session.subscribe();
+        let torrent = session.add_torrent(&metainfo).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !torrent.is_complete() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        let stats = torrent.stats();
+        let key = format!("key={:08X}", torrent.announce_key());
+        torrent.stop();
+
+        // Tracker id of the first announce is sent back with `completed` and `stopped` ones
+        let announces = announces.try_iter().collect::<Vec<_>>();
+        assert_eq!(announces.len(), 3);
+        assert!(announces.iter().all(|announce| announce.contains(&key)));
+        assert!(announces[0].contains("numwant=50") && announces[2].contains("numwant=0"));
+        assert!(!announces[0].contains("trackerid"));
+        assert!(announces[1..].iter().all(|announce| announce.contains("trackerid=id")));
+
+        assert_eq!((stats.state, stats.have, stats.pieces), (TorrentState::Complete, 3, 3));
+        assert_eq!(stats.downloaded, 40000);
+        assert_eq!(session.metrics().downloaded.get(), 40000);
+        assert_eq!(session.metrics().messages_recieved.get(Piece::ID), 3);
+
+        let events = events.try_iter().map(|event| event.kind).collect::<Vec<_>>();
+        assert_eq!(events[0], EventKind::PeerConnected(seed_addr.into()));
+        assert_eq!(events.iter().filter(|kind| matches!(kind, EventKind::PieceVerified(_))).count(), 3);
+        assert!(events.contains(&EventKind::Complete));
+        assert_eq!(
+            fs::read(dir.join("downloads/content.bin")).unwrap(),
+            fs::read(dir.join("content.bin")).unwrap()
+        );
+    }
+
+    #[test]
+    fn torrent_is_downloaded_from_incoming_peer() {
+        let dir = temp_dir("session-incoming");
+        let data = (0..20000u32).map(|i| (i * 13 % 251) as u8).collect::<Vec<_>>();
+        fs::write(dir.join("content.bin"), &data).unwrap();
+
+        // Tracker is unreachable, so peer is known only from its connection
+        let metainfo = Metainfo::builder(dir.join("content.bin"), "http://127.0.0.1:1/announce")
+            .piece_length(1 << 14)
+            .build()
+            .unwrap();
+        let info_hash = metainfo.info_hash();
+
+        let session = Session::with_config(SessionConfig::new(dir.join("downloads")).listen_port(0));
+        let addr = session.listen().unwrap();
+        let torrent = session.add_torrent(&metainfo).unwrap();
+        assert_eq!(session.add_torrent(&metainfo).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(session.torrents(), vec![info_hash]);
+
+        thread::spawn(move || {
+            let handshake = Handshake::builder(info_hash, *SEED_ID).build();
+            let (connection, _) = Peer::new(("127.0.0.1".into(), addr.port())).handshake(handshake).unwrap();
+            seed(connection, data, 1 << 14);
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !torrent.is_complete() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        assert_eq!(session.stats(&info_hash).map(|stats| stats.have), Some(2));
+        torrent.stop();
+        assert!(session.torrents().is_empty());
+    }
+
+    #[test]
+    fn banned_peer_is_not_accepted() {
+        let dir = temp_dir("session-banned");
+        fs::write(dir.join("content.bin"), [7; 100]).unwrap();
+        let metainfo = Metainfo::builder(dir.join("content.bin"), "http://127.0.0.1:1/announce")
+            .build()
+            .unwrap();
+
+        let session = Session::with_config(SessionConfig::new(dir.join("downloads")).listen_port(0));
+        let addr = session.listen().unwrap();
+        let _torrent = session.add_torrent(&metainfo).unwrap();
+        session.bans().ban(Ipv4Addr::LOCALHOST.into(), BanReason::Manual, None);
+
+        let handshake = Handshake::builder(metainfo.info_hash(), *SEED_ID).build();
+        let result = Peer::new(("127.0.0.1".into(), addr.port()))
+            .with_encryption(EncryptionPolicy::Disabled)
+            .handshake(handshake);
+
+        assert!(result.is_err());
+        assert_eq!(session.connections(), 0);
+    }
+
+    /// Waits up to `timeout` for `session` to have `connections`, returns `true` if it does.
+    fn wait_connections(session: &Session, connections: usize, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while session.connections() != connections && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        session.connections() == connections
+    }
+
+    #[test]
+    fn pending_handshakes_take_connection_budget() {
+        let dir = temp_dir("session-pending");
+        let config = SessionConfig::new(dir.join("downloads")).listen_port(0).max_connections(1);
+        let session = Session::with_config(config);
+        let addr = session.listen().unwrap();
+
+        let silent = TcpStream::connect(addr).unwrap();
+        assert!(wait_connections(&session, 1, Duration::from_secs(2)));
+
+        // Budget is exhausted, so connection is closed without handshake
+        let mut rejected = TcpStream::connect(addr).unwrap();
+        rejected.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        assert!(matches!(rejected.read(&mut [0]), Ok(0) | Err(_)));
+        assert_eq!(session.connections(), 1);
+
+        drop(silent);
+        assert!(wait_connections(&session, 0, Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn stalled_handshake_is_aborted() {
+        let dir = temp_dir("session-stalled");
+        let config = SessionConfig::new(dir.join("downloads"))
+            .listen_port(0)
+            .connect_timeout(Duration::from_millis(300));
+        let session = Session::with_config(config);
+        let addr = session.listen().unwrap();
+
+        // Every byte after header arrives within read timeout, but whole handshake would take seconds
+        let mut tcp = TcpStream::connect(addr).unwrap();
+        tcp.write_all(b"\x13BitTorrent protocol").unwrap();
+        thread::spawn(move || {
+            for byte in [0; 48] {
+                if tcp.write_all(&[byte]).is_err() {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
+        });
+
+        assert!(wait_connections(&session, 1, Duration::from_secs(2)));
+        assert!(wait_connections(&session, 0, Duration::from_secs(2)));
+    }
+}
@@ -0,0 +1,333 @@
+use std::{
+    io,
+    net::SocketAddr,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use super::events::EventKind;
+use super::throttle::RateLimiter;
+use super::torrent::{Shared, State, TorrentState};
+use crate::messages::{self, BTInt, Bitfield, Handshake, Have, Message, Piece, Request};
+use crate::peer::{BanList, BanReason, Connection, Peer};
+use crate::picker::QueueDepth;
+use crate::pool::BufferPool;
+
+/// Time peer may stay silent. Peers send keep-alives every two minutes.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(150);
+
+/// Connects to peer at `addr` and downloads from it until either side disconnects, torrent is stopped
+/// or complete. Connection should be [admitted](`Shared::admit`) first.
+pub fn run(shared: Arc<Shared>, addr: (String, u16)) {
+    let mut worker = Worker::new(shared);
+
+    match worker.connect(addr.clone()) {
+        Ok(Some(connection)) => {
+            worker.shared.lock().swarm.on_connected(&addr);
+            let _ = worker.download(connection);
+        }
+        // Peer of other torrent or ourselves, which is never worth retrying
+        Ok(None) => {
+            worker.shared.lock().swarm.remove(&addr);
+        }
+        Err(_) => worker.shared.lock().swarm.on_failed(&addr, Instant::now()),
+    }
+    worker.disconnect(&addr);
+}
+
+/// Same as [`run()`], but for admitted peer, which connected to listener and sent its handshake already.
+pub fn run_incoming(shared: Arc<Shared>, mut connection: Connection, addr: (String, u16)) {
+    let mut worker = Worker::new(shared);
+
+    if connection.send(&worker.handshake()).is_ok() {
+        let _ = worker.download(connection);
+    }
+    worker.disconnect(&addr);
+}
+
+/// Download state of single peer connection.
+struct Worker {
+    shared: Arc<Shared>,
+    /// Id of peer in request tracker.
+    id: usize,
+    peer_has: Vec<bool>,
+    choked: bool,
+    /// Requests, which were sent and not answered yet, with time of sending.
+    pending: Vec<(Request, Instant)>,
+    /// Number of verified pieces, which were announced to peer.
+    announced: usize,
+    /// Address of peer, once handshake is complete.
+    connected: Option<SocketAddr>,
+    download_limiter: RateLimiter,
+    /// Number of requests, which are kept pending.
+    queue: QueueDepth,
+}
+
+impl Worker {
+    fn new(shared: Arc<Shared>) -> Self {
+        let id = shared.lock().next_peer_id();
+        let download_limiter = shared
+            .download_limiter
+            .child(shared.config.peer_download_rate_limit, Instant::now());
+
+        Self {
+            download_limiter,
+            queue: QueueDepth::new(shared.config.queue_depth),
+            shared,
+            id,
+            peer_has: vec![],
+            choked: true,
+            pending: vec![],
+            announced: 0,
+            connected: None,
+        }
+    }
+
+    fn handshake(&self) -> Handshake {
+        Handshake::builder(self.shared.info_hash, self.shared.session.peer_id).build()
+    }
+
+    /// Connects and exchanges handshakes, returns `None` if peer is of other torrent or is session itself.
+    fn connect(&self, addr: (String, u16)) -> io::Result<Option<Connection>> {
+        let session = &self.shared.session;
+        let mut peer = Peer::new(addr)
+            .with_cancel_token(self.shared.cancel.clone())
+            .with_encryption(session.config.encryption);
+        let deadline = Instant::now() + session.config.connect_timeout;
+        let (connection, recieved) = peer.handshake_with_deadline(self.handshake(), deadline)?;
+
+        if *recieved.info_hash != self.shared.info_hash || *recieved.peer_id == session.peer_id {
+            return Ok(None);
+        }
+
+        Ok(Some(connection))
+    }
+
+    fn download(&mut self, mut connection: Connection) -> io::Result<()> {
+        // Guards and locks outlive mutable borrows of worker
+        let shared = self.shared.clone();
+        let _registration = connection.register(&shared.cancel)?;
+        connection.set_timeout(Some(IDLE_TIMEOUT))?;
+        connection.set_keep_alive(Some(Connection::KEEP_ALIVE_INTERVAL));
+        connection.set_idle_timeout(Some(IDLE_TIMEOUT));
+
+        let peer_addr = connection.peer_addr()?;
+        self.connected = Some(peer_addr);
+        shared.session.metrics.connections.inc();
+        shared.emit(EventKind::PeerConnected(peer_addr));
+
+        let bitfield = {
+            let state = self.shared.lock();
+            self.peer_has = vec![false; state.pieces.count()];
+            self.announced = state.verified.len();
+
+            (state.pieces.have_count() > 0).then(|| utils::bitfield(&state))
+        };
+
+        if let Some(bitfield) = bitfield {
+            self.send(&mut connection, &Message::Bitfield(bitfield))?;
+        }
+        self.send(&mut connection, &Message::Interested)?;
+
+        loop {
+            let message = match connection.recv::<Message>() {
+                Ok(Some(message)) => message,
+                // Keep-alive
+                Ok(None) => continue,
+                // Messages of unsupported extensions
+                Err(err) if err.is_recoverable() => continue,
+                Err(err) => {
+                    if let messages::Error::Violation(_) = err {
+                        self.ban(BanReason::ProtocolViolation);
+                    }
+
+                    return Err(err.into());
+                }
+            };
+            shared.session.metrics.messages_recieved.record(&message);
+
+            let (outgoing, pause) = {
+                let mut state = shared.lock();
+
+                if state.status != TorrentState::Downloading {
+                    return Ok(());
+                }
+
+                let pause = self.on_message(&mut state, message);
+                (self.outgoing(&mut state), pause)
+            };
+
+            for message in outgoing {
+                self.send(&mut connection, &message)?;
+            }
+
+            // Peer is not read from meanwhile, so it's slowed down by TCP flow control
+            if !pause.is_zero() {
+                thread::sleep(pause);
+            }
+        }
+    }
+
+    /// Handles `message`, returning time downloading should pause for to respect rate limits.
+    fn on_message(&mut self, state: &mut State, message: Message) -> Duration {
+        match message {
+            Message::Choke => {
+                self.choked = true;
+                self.pending.clear();
+                state.requests.on_peer_gone(&self.id);
+            }
+            Message::Unchoke => self.choked = false,
+            Message::Bitfield(bitfield) => {
+                state.pieces.remove_peer(&self.peer_has);
+                self.peer_has = utils::bits(&bitfield, self.peer_has.len());
+                state.pieces.add_peer(&self.peer_has);
+            }
+            Message::Have(Have { piece_index }) => {
+                if let Some(has) = self.peer_has.get_mut(piece_index as usize).filter(|has| !**has) {
+                    *has = true;
+                    state.pieces.on_peer_have(piece_index as usize);
+                }
+            }
+            Message::Piece(piece) => {
+                let length = piece.data.len() as u64;
+                self.on_piece(state, piece);
+
+                return self.download_limiter.consume(length, Instant::now());
+            }
+            // Requests are not served yet
+            _ => {}
+        }
+
+        Duration::ZERO
+    }
+
+    fn on_piece(&mut self, state: &mut State, piece: Piece) {
+        let metrics = &self.shared.session.metrics;
+        let sent = self
+            .pending
+            .iter()
+            .find(|(request, _)| (request.piece_index, request.offset) == (piece.piece_index, piece.offset));
+        if let Some((_, sent)) = sent {
+            let latency = sent.elapsed();
+            metrics.request_latency.observe(latency.as_millis() as u64);
+            self.queue.on_block(piece.data.len() as u64, latency, Instant::now());
+        }
+        self.pending
+            .retain(|(request, _)| (request.piece_index, request.offset) != (piece.piece_index, piece.offset));
+
+        let Some(recieved) = state.requests.on_block(&self.id, piece.piece_index, piece.offset) else { return };
+
+        if let Err(err) = state.storage.write(piece.piece_index as _, piece.offset as _, &piece.data) {
+            return self.fail(state, err);
+        }
+        state.downloaded += piece.data.len() as u64;
+        metrics.downloaded.add(piece.data.len() as u64);
+        if let Some(peer_addr) = self.connected {
+            state.contributors.entry(piece.piece_index).or_default().insert(peer_addr.ip());
+        }
+        BufferPool::global().put(piece.data);
+
+        if recieved.piece_complete {
+            match state.storage.read_piece(piece.piece_index as _) {
+                Ok(data) => state.verifier.submit(piece.piece_index as usize, data),
+                Err(err) => self.fail(state, err),
+            }
+        }
+    }
+
+    /// Messages, which should be sent after state change: `Have`s of newly verified pieces and new requests.
+    fn outgoing(&mut self, state: &mut State) -> Vec<Message> {
+        let mut outgoing = state.verified[self.announced..]
+            .iter()
+            .map(|piece_index| Message::Have(Have { piece_index: *piece_index }))
+            .collect::<Vec<_>>();
+        self.announced = state.verified.len();
+
+        if self.choked {
+            return outgoing;
+        }
+
+        // Blocks, which weren't recieved in time, are requested again, possibly from other peers
+        let now = Instant::now();
+        state.requests.snub(now);
+        state.requests.expire(now);
+        self.pending
+            .retain(|(_, sent)| now.duration_since(*sent) < self.shared.config.request_timeout);
+
+        let count = self.queue.depth().saturating_sub(self.pending.len());
+        let mut requests = state.requests.request(&self.id, &self.peer_has, count, now);
+
+        while requests.len() < count {
+            let Some(piece) = state.picker.pick(&state.pieces, &self.peer_has) else { break };
+
+            state.pieces.start(piece);
+            state.requests.add_piece(piece as BTInt);
+            requests.extend(state.requests.request(&self.id, &self.peer_has, count - requests.len(), now));
+        }
+
+        state.requests.update_endgame(&state.pieces);
+
+        self.pending.extend(requests.iter().map(|request| (*request, now)));
+        outgoing.extend(requests.into_iter().map(Message::Request));
+        outgoing
+    }
+
+    fn send(&self, connection: &mut Connection, message: &Message) -> io::Result<()> {
+        connection.send(message)?;
+        self.shared.session.metrics.messages_sent.record(message);
+
+        Ok(())
+    }
+
+    /// Bans connected peer for [default](`BanList::DEFAULT_DURATION`) duration.
+    fn ban(&self, reason: BanReason) {
+        if let Some(peer_addr) = self.connected {
+            let until = SystemTime::now() + BanList::DEFAULT_DURATION;
+            self.shared.session.bans.lock().unwrap().ban(peer_addr.ip(), reason, Some(until));
+        }
+    }
+
+    /// Stops download after failure of disk I/O.
+    fn fail(&self, state: &mut State, err: io::Error) {
+        state.status = TorrentState::Failed(err.to_string());
+        self.shared.emit(EventKind::Failed(err.to_string()));
+    }
+
+    fn disconnect(&mut self, addr: &(String, u16)) {
+        let mut state = self.shared.lock();
+
+        state.pieces.remove_peer(&self.peer_has);
+        state.requests.on_peer_gone(&self.id);
+        state.peers.remove(addr);
+        state.swarm.on_disconnected(addr, Instant::now());
+        self.shared.session.disconnected();
+
+        if let Some(peer_addr) = self.connected {
+            self.shared.session.metrics.connections.dec();
+            self.shared.emit(EventKind::PeerDisconnected(peer_addr));
+        }
+    }
+}
+
+mod utils {
+    use super::*;
+
+    /// Bitfield of verified pieces.
+    pub fn bitfield(state: &State) -> Bitfield {
+        let mut bits = vec![0; state.pieces.count().div_ceil(8)];
+
+        for piece in (0..state.pieces.count()).filter(|piece| state.pieces.have(*piece)) {
+            bits[piece / 8] |= 0x80 >> (piece % 8);
+        }
+
+        Bitfield { bits: bits.into() }
+    }
+
+    /// Pieces, marked in `bitfield`, out of `count` ones. Spare bits are ignored.
+    pub fn bits(bitfield: &Bitfield, count: usize) -> Vec<bool> {
+        (0..count)
+            .map(|piece| bitfield.bits.get(piece / 8).is_some_and(|byte| byte & (0x80 >> (piece % 8)) != 0))
+            .collect()
+    }
+}
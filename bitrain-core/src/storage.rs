@@ -0,0 +1,12 @@
+//! Storage of torrent data on disk.
+mod cache;
+mod files;
+mod hash;
+mod resume;
+mod verify;
+
+pub use cache::{BlockCache, CacheStats};
+pub use files::{Allocation, FileStorage, Relocation};
+pub use hash::PieceHasher;
+pub use resume::{FileState, ResumeData, ResumeError, TrackerState};
+pub use verify::{PieceVerifier, Verification};
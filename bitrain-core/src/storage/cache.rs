@@ -0,0 +1,251 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    sync::Arc,
+};
+
+use super::FileStorage;
+use crate::bencoded::BInt;
+
+/// LRU cache of blocks, read from [`FileStorage`].
+///
+/// Blocks are cached by exact range, they were requested with (piece, offset and length of `Request`),
+/// so hot blocks, uploaded to many peers, are read from disk once. Least recently used blocks are evicted,
+/// when total size of cached data exceeds memory budget.
+#[derive(Debug, Clone)]
+pub struct BlockCache {
+    budget: usize,
+    used: usize,
+    tick: u64,
+    blocks: HashMap<BlockKey, Cached>,
+    /// Keys of blocks by their last access.
+    recent: BTreeMap<u64, BlockKey>,
+    stats: CacheStats,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct BlockKey {
+    piece: BInt,
+    offset: BInt,
+    length: BInt,
+}
+
+#[derive(Debug, Clone)]
+struct Cached {
+    data: Arc<[u8]>,
+    tick: u64,
+}
+
+/// Counters of cache lookups.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Number of blocks, evicted to fit into budget.
+    pub evictions: u64,
+}
+
+impl CacheStats {
+    /// Share of lookups, which were hits, `0.0` if there were none.
+    pub fn hit_ratio(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+impl BlockCache {
+    /// Default memory budget, 32 MiB.
+    pub const DEFAULT_BUDGET: usize = 32 << 20;
+
+    /// Creates cache, which holds at most `budget` bytes of data.
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            used: 0,
+            tick: 0,
+            blocks: HashMap::new(),
+            recent: BTreeMap::new(),
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Changes memory budget, evicting blocks, which don't fit into the new one.
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict(0);
+    }
+
+    /// Total size of cached data in bytes.
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// Number of cached blocks.
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Returns cached block, counting hit or miss.
+    pub fn get(&mut self, piece: BInt, offset: BInt, length: BInt) -> Option<Arc<[u8]>> {
+        let key = BlockKey { piece, offset, length };
+        let tick = self.next_tick();
+
+        let Some(cached) = self.blocks.get_mut(&key) else {
+            self.stats.misses += 1;
+            return None;
+        };
+
+        self.stats.hits += 1;
+        self.recent.remove(&cached.tick);
+        self.recent.insert(tick, key);
+        cached.tick = tick;
+
+        Some(cached.data.clone())
+    }
+
+    /// Caches block at `offset` inside `piece`. Blocks, larger than the whole budget, are not cached.
+    pub fn insert(&mut self, piece: BInt, offset: BInt, data: Arc<[u8]>) {
+        let key = BlockKey {
+            piece,
+            offset,
+            length: data.len() as BInt,
+        };
+
+        self.remove(&key);
+
+        if data.len() > self.budget {
+            return;
+        }
+
+        self.evict(data.len());
+
+        let tick = self.next_tick();
+        self.used += data.len();
+        self.recent.insert(tick, key);
+        self.blocks.insert(key, Cached { data, tick });
+    }
+
+    /// Reads block from cache or, on miss, from `storage`, caching it.
+    pub fn read(&mut self, storage: &mut FileStorage, piece: BInt, offset: BInt, length: BInt) -> io::Result<Arc<[u8]>> {
+        if let Some(data) = self.get(piece, offset, length) {
+            return Ok(data);
+        }
+
+        let mut data = vec![0; length as usize];
+        storage.read(piece, offset, &mut data)?;
+
+        let data: Arc<[u8]> = data.into();
+        self.insert(piece, offset, data.clone());
+
+        Ok(data)
+    }
+
+    /// Writes block to `storage`, dropping cached blocks of `piece`, which would be outdated.
+    pub fn write(&mut self, storage: &mut FileStorage, piece: BInt, offset: BInt, data: &[u8]) -> io::Result<()> {
+        self.invalidate_piece(piece);
+        storage.write(piece, offset, data)
+    }
+
+    /// Drops cached blocks of `piece`, i.e. when it failed verification or was rewritten.
+    pub fn invalidate_piece(&mut self, piece: BInt) {
+        let keys = self
+            .blocks
+            .keys()
+            .filter(|key| key.piece == piece)
+            .copied()
+            .collect::<Vec<_>>();
+
+        for key in keys {
+            self.remove(&key);
+        }
+    }
+
+    /// Drops all cached blocks. Stats are kept.
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+        self.recent.clear();
+        self.used = 0;
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn remove(&mut self, key: &BlockKey) {
+        if let Some(cached) = self.blocks.remove(key) {
+            self.recent.remove(&cached.tick);
+            self.used -= cached.data.len();
+        }
+    }
+
+    /// Evicts least recently used blocks, until `additional` bytes fit into budget.
+    fn evict(&mut self, additional: usize) {
+        while self.used + additional > self.budget {
+            let Some((_, key)) = self.recent.pop_first() else { break };
+
+            if let Some(cached) = self.blocks.remove(&key) {
+                self.used -= cached.data.len();
+                self.stats.evictions += 1;
+            }
+        }
+    }
+}
+
+impl Default for BlockCache {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_BUDGET)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(byte: u8, length: usize) -> Arc<[u8]> {
+        vec![byte; length].into()
+    }
+
+    #[test]
+    fn least_recently_used_blocks_are_evicted() {
+        let mut cache = BlockCache::new(8);
+
+        cache.insert(0, 0, block(0, 4));
+        cache.insert(0, 4, block(1, 4));
+        assert_eq!(cache.get(0, 0, 4).as_deref(), Some(&[0; 4][..]));
+
+        // The second block wasn't used since the first one was
+        cache.insert(1, 0, block(2, 4));
+
+        assert_eq!(cache.get(0, 4, 4), None);
+        assert!(cache.get(0, 0, 4).is_some());
+        assert_eq!(cache.get(0, 0, 2), None);
+
+        cache.insert(2, 0, block(3, 16));
+        cache.invalidate_piece(1);
+
+        assert_eq!((cache.len(), cache.used()), (1, 4));
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 2,
+                misses: 2,
+                evictions: 1
+            }
+        );
+    }
+}
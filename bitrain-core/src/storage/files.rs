@@ -0,0 +1,451 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    ops::Range,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::bencoded::{BInt, FileSegment, Info, Layout};
+
+/// Strategy of disk space allocation for torrent files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Allocation {
+    /// Files are created with their full length, but without writing data, so on most file systems
+    /// they are sparse and take space only as pieces are written.
+    #[default]
+    Sparse,
+    /// Disk space of files is reserved up front (`fallocate` where available), which prevents fragmentation
+    /// and running out of space mid-download at the cost of slow first write.
+    Full,
+    /// Files are created empty and grow as pieces are written.
+    OnDemand,
+}
+
+/// How files are moved to new root directory by [`FileStorage::relocate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Relocation {
+    /// Files are renamed, which is instant on the same file system. Files, which can't be renamed
+    /// (i.e. new root is on another disk), are copied instead.
+    #[default]
+    Rename,
+    /// Files are copied, storage switches to copies and only then originals are removed.
+    Copy,
+}
+
+/// Torrent content, stored in files under root directory.
+///
+/// Pieces are read and written by their index, storage maps them to files according to [`Layout`].
+/// Files are opened (and allocated, if they are new) on first write and kept open until
+/// [`close()`](`FileStorage::close`). Padding files are never created, their bytes read as zeros.
+#[derive(Debug)]
+pub struct FileStorage {
+    root: PathBuf,
+    /// Paths of files, relative to root.
+    paths: Vec<PathBuf>,
+    layout: Layout,
+    allocation: Allocation,
+    handles: Vec<Option<Handle>>,
+}
+
+#[derive(Debug)]
+struct Handle {
+    file: File,
+    writable: bool,
+}
+
+impl FileStorage {
+    /// Creates storage for content of `info` in `root` directory. Single file is stored as `root/name`,
+    /// files of multi-file torrent under `root/name/` directory.
+    pub fn new(info: &Info, root: impl Into<PathBuf>) -> Self {
+        let paths = info.iter_files().map(|(path, _, _)| path).collect::<Vec<_>>();
+
+        Self {
+            root: root.into(),
+            handles: paths.iter().map(|_| None).collect(),
+            paths,
+            layout: info.layout(),
+            allocation: Allocation::default(),
+        }
+    }
+
+    pub fn allocation(mut self, allocation: Allocation) -> Self {
+        self.allocation = allocation;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    /// Full path of `file`, `None` if file doesn't exist.
+    pub fn file_path(&self, file: usize) -> Option<PathBuf> {
+        self.paths.get(file).map(|path| self.root.join(path))
+    }
+
+    /// Creates and allocates all files up front, instead of on first write.
+    pub fn allocate(&mut self) -> io::Result<()> {
+        for file in 0..self.paths.len() {
+            if !self.layout.is_padding(file) {
+                self.open(file, true)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads `buffer.len()` bytes at `offset` inside `piece`.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if range doesn't fit into piece and with
+    /// [`io::ErrorKind::UnexpectedEof`] if file is shorter, than range (i.e. it grows on demand and
+    /// range wasn't written yet).
+    pub fn read(&mut self, piece: BInt, offset: BInt, buffer: &mut [u8]) -> io::Result<()> {
+        buffer.fill(0);
+
+        for (segment, range) in self.segments(piece, offset, buffer.len())? {
+            let handle = self.open(segment.file, false)?;
+
+            handle.seek(SeekFrom::Start(segment.offset))?;
+            handle.read_exact(&mut buffer[range])?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes `data` at `offset` inside `piece`. Fails with [`io::ErrorKind::InvalidInput`] if range doesn't fit
+    /// into piece.
+    pub fn write(&mut self, piece: BInt, offset: BInt, data: &[u8]) -> io::Result<()> {
+        for (segment, range) in self.segments(piece, offset, data.len())? {
+            let handle = self.open(segment.file, true)?;
+
+            handle.seek(SeekFrom::Start(segment.offset))?;
+            handle.write_all(&data[range])?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads the whole `piece`, i.e. to verify it.
+    pub fn read_piece(&mut self, piece: BInt) -> io::Result<Vec<u8>> {
+        let size = self.layout.piece_size(piece).ok_or_else(utils::out_of_range)?;
+        let mut buffer = vec![0; size as usize];
+
+        self.read(piece, 0, &mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Flushes written data to disk.
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.handles.iter().flatten().try_for_each(|handle| handle.file.sync_all())
+    }
+
+    /// Closes open files. They are reopened on next access.
+    pub fn close(&mut self) {
+        self.handles.iter_mut().for_each(|handle| *handle = None);
+    }
+
+    /// Moves files to `root` directory and switches storage to it. Missing files (i.e. not downloaded yet)
+    /// are skipped.
+    ///
+    /// Storage can't be read or written while files are moved, so data, that is written concurrently,
+    /// can't be lost: consumers, which share storage between threads, wait for relocation on its lock.
+    /// If moving any file fails, files that were already moved are returned back and storage stays
+    /// at the old root. Existing files at new root are never overwritten, [`io::ErrorKind::AlreadyExists`]
+    /// is returned instead.
+    pub fn relocate(&mut self, root: impl Into<PathBuf>, relocation: Relocation) -> io::Result<()> {
+        let root = root.into();
+        let mut moved = vec![];
+
+        self.close();
+
+        for (file, path) in self.paths.iter().enumerate() {
+            let source = self.root.join(path);
+
+            if self.layout.is_padding(file) || !source.is_file() {
+                continue;
+            }
+
+            let target = root.join(path);
+
+            match utils::move_file(&source, &target, relocation) {
+                Ok(renamed) => moved.push((source, target, renamed)),
+                Err(err) => {
+                    utils::move_back(moved);
+                    return Err(err);
+                }
+            }
+        }
+
+        let old_root = std::mem::replace(&mut self.root, root);
+
+        for (source, _, renamed) in moved {
+            if !renamed {
+                // Copy is already in use, leftover original only wastes space
+                let _ = fs::remove_file(source);
+            }
+        }
+
+        utils::remove_empty_dirs(&old_root, &self.paths);
+        Ok(())
+    }
+
+    /// File segments of range, together with their ranges in piece buffer.
+    fn segments(
+        &self,
+        piece: BInt,
+        offset: BInt,
+        length: usize,
+    ) -> io::Result<Vec<(FileSegment, Range<usize>)>> {
+        let segments = self
+            .layout
+            .file_segments(piece, offset, length as BInt)
+            .ok_or_else(utils::out_of_range)?;
+        let start = piece * self.layout.piece_length() + offset;
+
+        Ok(segments
+            .into_iter()
+            .map(|segment| {
+                let position = (self.layout.file_offset(segment.file).unwrap() + segment.offset - start) as usize;
+                (segment, position..position + segment.length as usize)
+            })
+            .collect())
+    }
+
+    /// Opens `file`, creating and allocating it if `write` is set. Files, which were only read so far,
+    /// are opened read-only and reopened for the first write.
+    fn open(&mut self, file: usize, write: bool) -> io::Result<&mut File> {
+        if self.handles[file].as_ref().is_some_and(|handle| handle.writable || !write) {
+            return Ok(&mut self.handles[file].as_mut().unwrap().file);
+        }
+
+        let path = &self.paths[file];
+
+        if !path.components().all(|component| matches!(component, Component::Normal(_))) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "file path leaves storage root"));
+        }
+
+        let path = self.root.join(path);
+
+        let handle = if write {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            let handle = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+            utils::allocate(&handle, self.layout.file_length(file).unwrap(), self.allocation)?;
+            handle
+        } else {
+            File::open(&path)?
+        };
+
+        let handle = self.handles[file].insert(Handle {
+            file: handle,
+            writable: write,
+        });
+
+        Ok(&mut handle.file)
+    }
+}
+
+mod utils {
+    use super::*;
+
+    pub fn out_of_range() -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, "range doesn't fit into piece")
+    }
+
+    /// Moves `source` file to `target`, returning `true` if it was renamed and `false` if copied.
+    pub fn move_file(source: &Path, target: &Path, relocation: Relocation) -> io::Result<bool> {
+        if target.exists() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", target.display())));
+        }
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        if relocation == Relocation::Rename && fs::rename(source, target).is_ok() {
+            return Ok(true);
+        }
+
+        if let Err(err) = fs::copy(source, target) {
+            let _ = fs::remove_file(target);
+            return Err(err);
+        }
+
+        Ok(false)
+    }
+
+    /// Undoes moves of failed relocation.
+    pub fn move_back(moved: Vec<(PathBuf, PathBuf, bool)>) {
+        for (source, target, renamed) in moved {
+            // Nothing else can be done, if undo fails, originals or copies are still in place
+            let _ = if renamed {
+                fs::rename(target, source)
+            } else {
+                fs::remove_file(target)
+            };
+        }
+    }
+
+    /// Removes directories of `paths` under `root`, which became empty, deepest first.
+    pub fn remove_empty_dirs(root: &Path, paths: &[PathBuf]) {
+        let mut dirs = paths
+            .iter()
+            .flat_map(|path| path.ancestors().skip(1))
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .collect::<Vec<_>>();
+
+        dirs.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+        dirs.dedup();
+
+        for dir in dirs {
+            // Fails for directories, which hold other files, and those are kept
+            let _ = fs::remove_dir(root.join(dir));
+        }
+    }
+
+    /// Allocates `length` bytes of `file` according to `allocation`. Files, which are already longer,
+    /// are never truncated.
+    pub fn allocate(file: &File, length: u64, allocation: Allocation) -> io::Result<()> {
+        let current = file.metadata()?.len();
+
+        if current >= length {
+            return Ok(());
+        }
+
+        match allocation {
+            Allocation::Sparse => file.set_len(length),
+            Allocation::Full => preallocate(file, current, length),
+            Allocation::OnDemand => Ok(()),
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    fn preallocate(file: &File, current: u64, length: u64) -> io::Result<()> {
+        use std::os::unix::io::AsRawFd;
+
+        let offset = current as libc::off_t;
+        let length = (length - current) as libc::off_t;
+
+        // SAFETY: descriptor is owned by `file`, which outlives the call
+        match unsafe { libc::posix_fallocate(file.as_raw_fd(), offset, length) } {
+            0 => Ok(()),
+            // File system doesn't support allocation without writing
+            libc::EOPNOTSUPP | libc::EINVAL => fill_zeros(file, current, current + length as u64),
+            err => Err(io::Error::from_raw_os_error(err)),
+        }
+    }
+
+    /// Extending file on Windows allocates its clusters (unless file is marked sparse), while
+    /// `SetFileValidData` would additionally require privileges, which client usually doesn't have.
+    #[cfg(windows)]
+    fn preallocate(file: &File, _current: u64, length: u64) -> io::Result<()> {
+        file.set_len(length)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd", windows)))]
+    fn preallocate(file: &File, current: u64, length: u64) -> io::Result<()> {
+        fill_zeros(file, current, length)
+    }
+
+    #[cfg_attr(windows, allow(dead_code))]
+    fn fill_zeros(mut file: &File, current: u64, length: u64) -> io::Result<()> {
+        file.seek(SeekFrom::Start(current))?;
+        io::copy(&mut io::repeat(0).take(length - current), &mut file)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::bencoded::{FileInfo, Files};
+    use crate::test_utils::{self, temp_dir};
+
+    fn file(path: &str, length: BInt, attr: Option<&str>) -> FileInfo {
+        FileInfo {
+            length,
+            md5sum: None,
+            path: path.split('/').map(ToOwned::to_owned).collect(),
+            attr: attr.map(ToOwned::to_owned),
+        }
+    }
+
+    fn info() -> Info {
+        let files = vec![
+            file("a.txt", 3, None),
+            file(".pad/1", 1, Some("p")),
+            file("sub/b.txt", 6, None),
+        ];
+
+        Info {
+            name: "content".to_owned(),
+            ..test_utils::info(4, Files::Multiple { files })
+        }
+    }
+
+    #[rstest]
+    #[case::sparse(Allocation::Sparse, 6)]
+    #[case::full(Allocation::Full, 6)]
+    #[case::on_demand(Allocation::OnDemand, 4)]
+    fn pieces_are_stored_in_files(#[case] allocation: Allocation, #[case] expected_length: u64) {
+        let dir = temp_dir(&format!("storage-{:?}", allocation));
+        let mut storage = FileStorage::new(&info(), &dir).allocation(allocation);
+
+        storage.write(0, 0, b"abc\0").unwrap();
+        storage.write(1, 0, b"defg").unwrap();
+
+        assert_eq!(storage.read_piece(0).unwrap(), b"abc\0");
+        assert_eq!(storage.read_piece(1).unwrap(), b"defg");
+        assert_eq!(fs::read(dir.join("content").join("a.txt")).unwrap(), b"abc");
+        assert!(!dir.join("content").join(".pad").exists());
+
+        let b = fs::metadata(storage.file_path(2).unwrap()).unwrap();
+        assert_eq!(b.len(), expected_length);
+
+        let mut buffer = [0; 2];
+        let err = storage.read(2, 0, &mut buffer).map(drop);
+
+        match allocation {
+            Allocation::OnDemand => assert_eq!(err.unwrap_err().kind(), io::ErrorKind::UnexpectedEof),
+            _ => assert!(err.is_ok()),
+        }
+
+        assert_eq!(storage.write(2, 1, b"xy").unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[rstest]
+    #[case::rename(Relocation::Rename)]
+    #[case::copy(Relocation::Copy)]
+    fn files_are_relocated(#[case] relocation: Relocation) {
+        let dir = temp_dir(&format!("storage-relocate-{:?}", relocation));
+        let mut storage = FileStorage::new(&info(), dir.join("ssd"));
+
+        storage.write(0, 0, b"abc\0").unwrap();
+        storage.relocate(dir.join("hdd"), relocation).unwrap();
+
+        assert_eq!(storage.root(), dir.join("hdd"));
+        assert_eq!(storage.read_piece(0).unwrap(), b"abc\0");
+        assert!(!dir.join("ssd").join("content").exists());
+
+        // Second file wasn't written, so it's created at new root
+        storage.write(1, 0, b"defg").unwrap();
+        assert!(dir.join("hdd").join("content").join("sub").join("b.txt").is_file());
+
+        fs::create_dir_all(dir.join("ssd").join("content")).unwrap();
+        fs::write(dir.join("ssd").join("content").join("a.txt"), b"old").unwrap();
+
+        let err = storage.relocate(dir.join("ssd"), relocation).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(storage.root(), dir.join("hdd"));
+        assert_eq!(storage.read_piece(1).unwrap(), b"defg");
+    }
+}
@@ -0,0 +1,105 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use sha1::{Digest, Sha1};
+
+/// SHA-1 hashing of pieces, which is the most CPU-heavy part of download and torrent creation.
+///
+/// Single piece is hashed with SHA extensions of CPU, if they are available (see
+/// [`is_accelerated()`](`PieceHasher::is_accelerated`)), and batches of pieces are spread over threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PieceHasher {
+    threads: usize,
+}
+
+impl PieceHasher {
+    /// Hasher, which uses up to `threads` threads (at least one) per batch.
+    pub fn new(threads: usize) -> Self {
+        Self { threads: threads.max(1) }
+    }
+
+    /// Same as [`new()`](`PieceHasher::new`) with one thread per available CPU.
+    pub fn with_available_parallelism() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, |threads| threads.get()))
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Whether CPU supports SHA extensions. They are detected at runtime by SHA-1 implementation, so hashing
+    /// is accelerated without any configuration.
+    pub fn is_accelerated() -> bool {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            is_x86_feature_detected!("sha")
+                && is_x86_feature_detected!("sse2")
+                && is_x86_feature_detected!("ssse3")
+                && is_x86_feature_detected!("sse4.1")
+        }
+        #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+        {
+            false
+        }
+    }
+
+    pub fn hash(data: &[u8]) -> [u8; 20] {
+        Sha1::digest(data).into()
+    }
+
+    /// Hashes `pieces` in parallel, returning hashes in the same order.
+    pub fn hash_all<P: AsRef<[u8]> + Sync>(&self, pieces: &[P]) -> Vec<[u8; 20]> {
+        let threads = self.threads.min(pieces.len());
+        if threads <= 1 {
+            return pieces.iter().map(|piece| Self::hash(piece.as_ref())).collect();
+        }
+
+        // Pieces are taken one by one, so threads stay busy even if pieces differ in size
+        let next = AtomicUsize::new(0);
+        let mut hashes = thread::scope(|scope| {
+            let workers = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut hashes = vec![];
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(piece) = pieces.get(index) else { return hashes };
+                            hashes.push((index, Self::hash(piece.as_ref())));
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("hashing doesn't panic"))
+                .collect::<Vec<_>>()
+        });
+
+        hashes.sort_unstable_by_key(|(index, _)| *index);
+        hashes.into_iter().map(|(_, hash)| hash).collect()
+    }
+}
+
+impl Default for PieceHasher {
+    fn default() -> Self {
+        Self::with_available_parallelism()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parallel_hashes_are_ordered() {
+        let pieces = (0..50u8).map(|i| vec![i; 100 + i as usize]).collect::<Vec<_>>();
+        let sequential = pieces.iter().map(|piece| PieceHasher::hash(piece)).collect::<Vec<_>>();
+
+        assert_eq!(PieceHasher::new(4).hash_all(&pieces), sequential);
+        assert_eq!(PieceHasher::new(1).hash_all(&pieces), sequential);
+        assert_eq!(PieceHasher::hash(b"abc")[..4], [0xa9, 0x99, 0x3e, 0x36]);
+    }
+}
@@ -0,0 +1,305 @@
+use std::{
+    fmt, fs,
+    io::{self, Read, Write},
+    time::UNIX_EPOCH,
+};
+
+use crate::bencoded::encoding::{self, borrowed, BDictionary, BEncode, Entry};
+use crate::bencoded::{BInt, BString};
+
+/// State of torrent, saved on shutdown, so restarted client can skip re-hashing of the whole download.
+///
+/// Data is trusted only while files stay the same: consumer compares [`files`](`ResumeData::files`) with
+/// [`FileState`]s of files on disk and re-verifies pieces of files, which were changed meanwhile.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ResumeData {
+    pub info_hash: [u8; 20],
+    /// Pieces, which passed verification, by index.
+    pub pieces: Vec<bool>,
+    /// States of torrent files in order of [`Info`](`crate::bencoded::Info`) files.
+    pub files: Vec<FileState>,
+    /// Total amount of downloaded data, in bytes.
+    pub downloaded: u64,
+    /// Total amount of uploaded data, in bytes.
+    pub uploaded: u64,
+    pub trackers: Vec<TrackerState>,
+    /// `key`, sent with announces, to be reused after restart.
+    pub key: Option<u32>,
+}
+
+/// Size and modification time of file, when resume data was saved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FileState {
+    pub length: u64,
+    /// Modification time in seconds since UNIX epoch, if platform reports one.
+    pub mtime: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TrackerState {
+    pub url: String,
+    /// Tier of tracker in announce list.
+    pub tier: usize,
+    /// `tracker id`, returned by tracker, to be sent with next announces.
+    pub tracker_id: Option<BString>,
+    /// `true` if `completed` event was already reported to tracker.
+    pub completed_sent: bool,
+}
+
+#[derive(Debug)]
+pub enum ResumeError {
+    IO(io::Error),
+    Bencode(encoding::Error),
+    MissingField(&'static str),
+    /// Field is present, but has unexpected type or value.
+    InvalidField(&'static str),
+    /// Data was saved in newer format version.
+    UnsupportedVersion(BInt),
+}
+
+impl fmt::Display for ResumeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IO(err) => write!(f, "failed to read resume data: {}", err),
+            Self::Bencode(err) => write!(f, "malformed bencode: {}", err),
+            Self::MissingField(field) => write!(f, "missing field `{}`", field),
+            Self::InvalidField(field) => write!(f, "invalid field `{}`", field),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported resume data version {}", version),
+        }
+    }
+}
+
+impl std::error::Error for ResumeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IO(err) => Some(err),
+            Self::Bencode(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ResumeError {
+    fn from(err: io::Error) -> Self {
+        Self::IO(err)
+    }
+}
+
+impl From<encoding::Error> for ResumeError {
+    fn from(err: encoding::Error) -> Self {
+        Self::Bencode(err)
+    }
+}
+
+type Result<T> = std::result::Result<T, ResumeError>;
+
+impl ResumeData {
+    /// Value of `file-format` key, which tells resume data from other bencoded files.
+    pub const FORMAT: &'static str = "bitrain resume data";
+    /// Current version of format.
+    pub const VERSION: BInt = 1;
+
+    /// Number of verified pieces.
+    pub fn verified_count(&self) -> usize {
+        self.pieces.iter().filter(|verified| **verified).count()
+    }
+
+    pub fn load(mut source: impl Read) -> Result<Self> {
+        let mut bytes = vec![];
+        source.read_to_end(&mut bytes)?;
+
+        let entry = borrowed::Entry::from_bytes(&bytes)?.to_owned_entry();
+        let mut data: BDictionary = entry.parse_or_err(ResumeError::InvalidField("resume data"))?;
+
+        let format: BString = utils::take(&mut data, "file-format")?;
+        if format.as_bytes() != Self::FORMAT.as_bytes() {
+            return Err(ResumeError::InvalidField("file-format"));
+        }
+
+        let version: BInt = utils::take(&mut data, "file-version")?;
+        if version > Self::VERSION {
+            return Err(ResumeError::UnsupportedVersion(version));
+        }
+
+        let info_hash: BString = utils::take(&mut data, "info-hash")?;
+        let pieces: BString = utils::take(&mut data, "pieces")?;
+        let files: Vec<Entry> = utils::take(&mut data, "files")?;
+        let trackers: Vec<Entry> = utils::take_optional(&mut data, "trackers")?.unwrap_or_default();
+        let key: Option<BInt> = utils::take_optional(&mut data, "key")?;
+
+        Ok(Self {
+            info_hash: info_hash
+                .0
+                .try_into()
+                .map_err(|_| ResumeError::InvalidField("info-hash"))?,
+            pieces: pieces.0.iter().map(|piece| *piece & 1 == 1).collect(),
+            files: files.into_iter().map(FileState::parse).collect::<Result<_>>()?,
+            downloaded: utils::take_optional(&mut data, "downloaded")?.unwrap_or_default(),
+            uploaded: utils::take_optional(&mut data, "uploaded")?.unwrap_or_default(),
+            trackers: trackers.into_iter().map(TrackerState::parse).collect::<Result<_>>()?,
+            key: key
+                .map(u32::try_from)
+                .transpose()
+                .map_err(|_| ResumeError::InvalidField("key"))?,
+        })
+    }
+
+    pub fn save(&self, mut target: impl Write) -> io::Result<()> {
+        target.write_all(&self.to_entry().encode())
+    }
+
+    fn to_entry(&self) -> Entry {
+        let mut data = BDictionary::new();
+
+        utils::insert(&mut data, "file-format", utils::string(Self::FORMAT));
+        utils::insert(&mut data, "file-version", Entry::Integer(Self::VERSION));
+        utils::insert(&mut data, "info-hash", Entry::String(BString(self.info_hash.to_vec())));
+        // One byte per piece, so that piece count is kept exactly
+        utils::insert(
+            &mut data,
+            "pieces",
+            Entry::String(BString(self.pieces.iter().map(|verified| *verified as u8).collect())),
+        );
+        utils::insert(&mut data, "files", Entry::List(self.files.iter().map(|file| file.to_entry()).collect()));
+        utils::insert(&mut data, "downloaded", Entry::Integer(self.downloaded));
+        utils::insert(&mut data, "uploaded", Entry::Integer(self.uploaded));
+        utils::insert(&mut data, "trackers", Entry::List(self.trackers.iter().map(TrackerState::to_entry).collect()));
+
+        if let Some(key) = self.key {
+            utils::insert(&mut data, "key", Entry::Integer(key as BInt));
+        }
+
+        Entry::Dictionary(data)
+    }
+}
+
+impl FileState {
+    /// State of file with `metadata`.
+    pub fn from_metadata(metadata: &fs::Metadata) -> Self {
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|mtime| mtime.as_secs());
+
+        Self {
+            length: metadata.len(),
+            mtime,
+        }
+    }
+
+    fn parse(entry: Entry) -> Result<Self> {
+        let mut file: BDictionary = entry.parse_or_err(ResumeError::InvalidField("files"))?;
+
+        Ok(Self {
+            length: utils::take(&mut file, "length")?,
+            mtime: utils::take_optional(&mut file, "mtime")?,
+        })
+    }
+
+    fn to_entry(self) -> Entry {
+        let mut file = BDictionary::new();
+        utils::insert(&mut file, "length", Entry::Integer(self.length));
+
+        if let Some(mtime) = self.mtime {
+            utils::insert(&mut file, "mtime", Entry::Integer(mtime));
+        }
+
+        Entry::Dictionary(file)
+    }
+}
+
+impl TrackerState {
+    fn parse(entry: Entry) -> Result<Self> {
+        let mut tracker: BDictionary = entry.parse_or_err(ResumeError::InvalidField("trackers"))?;
+        let tier: BInt = utils::take_optional(&mut tracker, "tier")?.unwrap_or_default();
+
+        Ok(Self {
+            url: utils::take(&mut tracker, "url")?,
+            tier: usize::try_from(tier).map_err(|_| ResumeError::InvalidField("tier"))?,
+            tracker_id: utils::take_optional(&mut tracker, "tracker id")?,
+            completed_sent: utils::take_optional::<BInt>(&mut tracker, "completed sent")? == Some(1),
+        })
+    }
+
+    fn to_entry(&self) -> Entry {
+        let mut tracker = BDictionary::new();
+
+        utils::insert(&mut tracker, "url", utils::string(&self.url));
+        utils::insert(&mut tracker, "tier", Entry::Integer(self.tier as BInt));
+        utils::insert(&mut tracker, "completed sent", Entry::Integer(self.completed_sent as BInt));
+
+        if let Some(tracker_id) = &self.tracker_id {
+            utils::insert(&mut tracker, "tracker id", Entry::String(tracker_id.clone()));
+        }
+
+        Entry::Dictionary(tracker)
+    }
+}
+
+mod utils {
+    use super::*;
+
+    pub fn insert(dictionary: &mut BDictionary, key: &str, value: Entry) {
+        dictionary.insert(BString(key.as_bytes().to_vec()), value);
+    }
+
+    pub fn string(value: &str) -> Entry {
+        Entry::String(BString(value.as_bytes().to_vec()))
+    }
+
+    pub fn take_optional<T: TryFrom<Entry>>(dictionary: &mut BDictionary, key: &'static str) -> Result<Option<T>> {
+        dictionary
+            .remove(key.as_bytes())
+            .map(|entry| entry.parse_or_err(ResumeError::InvalidField(key)))
+            .transpose()
+    }
+
+    pub fn take<T: TryFrom<Entry>>(dictionary: &mut BDictionary, key: &'static str) -> Result<T> {
+        take_optional(dictionary, key)?.ok_or(ResumeError::MissingField(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resume_data_roundtrips() {
+        let data = ResumeData {
+            info_hash: [7; 20],
+            pieces: vec![true, false, true],
+            files: vec![
+                FileState {
+                    length: 10,
+                    mtime: Some(1_700_000_000),
+                },
+                FileState { length: 0, mtime: None },
+            ],
+            downloaded: 1 << 33,
+            uploaded: 42,
+            trackers: vec![TrackerState {
+                url: "http://tracker.example/announce".to_owned(),
+                tier: 1,
+                tracker_id: Some(BString(b"id".to_vec())),
+                completed_sent: true,
+            }],
+            key: Some(0xDEADBEEF),
+        };
+
+        let mut bytes = vec![];
+        data.save(&mut bytes).unwrap();
+
+        assert_eq!(ResumeData::load(&bytes[..]).unwrap(), data);
+        assert_eq!(data.verified_count(), 2);
+    }
+
+    #[test]
+    fn foreign_files_are_rejected() {
+        let err = ResumeData::load(&b"d11:file-format7:unknowne"[..]).unwrap_err();
+        assert!(matches!(err, ResumeError::InvalidField("file-format")));
+
+        let err = ResumeData::load(&b"d11:file-format19:bitrain resume data12:file-versioni2ee"[..]).unwrap_err();
+        assert!(matches!(err, ResumeError::UnsupportedVersion(2)));
+    }
+}
@@ -0,0 +1,185 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+use super::PieceHasher;
+use crate::bencoded::Info;
+
+/// Outcome of piece verification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Verification {
+    pub piece: usize,
+    /// `true` if piece data matches its hash in [`Info`].
+    pub passed: bool,
+}
+
+/// Pool of worker threads, which check completed pieces against SHA-1 hashes of [`Info`].
+///
+/// Pieces are [`submit`](`PieceVerifier::submit`)ted with their data and verified in background, so
+/// hashing doesn't stall peer connections. Results come in order of completion, not of submission.
+/// Piece should be announced with `Have` only after it passed verification.
+#[derive(Debug)]
+pub struct PieceVerifier {
+    hashes: Arc<[[u8; 20]]>,
+    jobs: Option<mpsc::Sender<Job>>,
+    results: mpsc::Receiver<Verification>,
+    pending: Arc<AtomicUsize>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+#[derive(Debug)]
+struct Job {
+    piece: usize,
+    data: Vec<u8>,
+}
+
+impl PieceVerifier {
+    /// Starts `threads` workers (at least one) for pieces of `info`.
+    pub fn new(info: &Info, threads: usize) -> Self {
+        let hashes: Arc<[[u8; 20]]> = info.piece_hashes().copied().collect();
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let (results_tx, results) = mpsc::channel();
+        let queue = Arc::new(Mutex::new(queue));
+
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                let hashes = hashes.clone();
+                let queue = queue.clone();
+                let results = results_tx.clone();
+
+                thread::spawn(move || utils::work(&hashes, &queue, &results))
+            })
+            .collect();
+
+        Self {
+            hashes,
+            jobs: Some(jobs),
+            results,
+            pending: Arc::new(AtomicUsize::new(0)),
+            workers,
+        }
+    }
+
+    /// Same as [`new()`](`PieceVerifier::new`) with one worker per available CPU.
+    pub fn with_available_parallelism(info: &Info) -> Self {
+        let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+        Self::new(info, threads)
+    }
+
+    pub fn piece_count(&self) -> usize {
+        self.hashes.len()
+    }
+
+    /// Queues `data` of `piece` for verification. Piece, which is out of range, fails verification.
+    pub fn submit(&self, piece: usize, data: Vec<u8>) {
+        self.pending.fetch_add(1, Ordering::SeqCst);
+
+        let jobs = self.jobs.as_ref().expect("jobs are open until verifier is dropped");
+        jobs.send(Job { piece, data }).expect("workers live as long as verifier");
+    }
+
+    /// Number of submitted pieces, which results weren't recieved yet.
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::SeqCst)
+    }
+
+    /// Returns result of verification, if one is ready.
+    pub fn try_recv(&self) -> Option<Verification> {
+        let verification = self.results.try_recv().ok()?;
+        self.pending.fetch_sub(1, Ordering::SeqCst);
+
+        Some(verification)
+    }
+
+    /// Waits for result of verification until `deadline`. Returns `None` if deadline passes first.
+    pub fn recv_until(&self, deadline: Instant) -> Option<Verification> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        let verification = self.results.recv_timeout(timeout).ok()?;
+        self.pending.fetch_sub(1, Ordering::SeqCst);
+
+        Some(verification)
+    }
+}
+
+impl Drop for PieceVerifier {
+    /// Stops workers after pieces, which are already queued, are verified.
+    fn drop(&mut self) {
+        drop(self.jobs.take());
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+mod utils {
+    use super::*;
+
+    pub fn work(hashes: &[[u8; 20]], queue: &Mutex<mpsc::Receiver<Job>>, results: &mpsc::Sender<Verification>) {
+        loop {
+            // Lock is released before hashing, so other workers can take next job meanwhile
+            let job = queue.lock().unwrap().recv();
+            let Ok(Job { piece, data }) = job else { return };
+
+            let passed = hashes
+                .get(piece)
+                .is_some_and(|hash| PieceHasher::hash(&data) == *hash);
+
+            if results.send(Verification { piece, passed }).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use sha1::{Digest, Sha1};
+    use crate::bencoded::{BString, Files};
+    use crate::test_utils;
+
+    #[test]
+    fn pieces_are_verified() {
+        let pieces = [Sha1::digest(b"hello"), Sha1::digest(b"world")].concat();
+        let info = Info {
+            pieces: BString(pieces),
+            ..test_utils::info(
+                5,
+                Files::Single {
+                    length: 10,
+                    md5sum: None,
+                },
+            )
+        };
+
+        let verifier = PieceVerifier::new(&info, 2);
+
+        verifier.submit(0, b"hello".to_vec());
+        verifier.submit(1, b"wrong".to_vec());
+        verifier.submit(2, b"extra".to_vec());
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut verifications = (0..3)
+            .map(|_| verifier.recv_until(deadline).unwrap())
+            .collect::<Vec<_>>();
+        verifications.sort_by_key(|verification| verification.piece);
+
+        assert_eq!(
+            verifications,
+            [
+                Verification { piece: 0, passed: true },
+                Verification { piece: 1, passed: false },
+                Verification { piece: 2, passed: false },
+            ]
+        );
+        assert_eq!(verifier.pending(), 0);
+    }
+}
@@ -0,0 +1,30 @@
+//! Helpers, shared by tests of different modules.
+use std::fs;
+use std::path::PathBuf;
+
+use crate::bencoded::encoding::BDictionary;
+use crate::bencoded::{BInt, BString, Files, Info};
+
+/// Creates empty directory `name` in system temporary directory, unique to test process.
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("bitrain-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
+
+/// Builds [`Info`] of `files` named `test`, with zeroed hashes of all its pieces.
+pub(crate) fn info(piece_length: BInt, files: Files) -> Info {
+    let mut info = Info {
+        piece_length,
+        pieces: BString(vec![]),
+        private: None,
+        name: "test".to_owned(),
+        files,
+        extra: BDictionary::new(),
+    };
+    info.pieces = BString(vec![0; 20 * info.total_size().div_ceil(piece_length) as usize]);
+
+    info
+}
@@ -0,0 +1,22 @@
+//! Tracker communication utilities.
+//!
+//! For more info see <https://www.bittorrent.org/beps/bep_0003.html#trackers>.
+mod announce;
+mod announcer;
+#[cfg(feature = "use-serde")]
+pub(crate) mod http;
+mod list;
+#[cfg(feature = "webtorrent")]
+mod websocket;
+
+pub use announce::{AnnounceRequest, AnnounceRequestBuilder, Event};
+pub use announcer::Announcer;
+#[cfg(feature = "use-serde")]
+pub use http::{HttpError, HttpTracker, TlsConnector, TlsStream};
+#[cfg(feature = "native-tls")]
+pub use http::NativeTlsConnector;
+#[cfg(feature = "rustls-tls")]
+pub use http::RustlsConnector;
+pub use list::TrackerList;
+#[cfg(feature = "webtorrent")]
+pub use websocket::{SessionDescription, Signal, WebSocketError, WebSocketResponce, WebSocketTracker};
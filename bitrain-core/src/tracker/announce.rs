@@ -0,0 +1,287 @@
+use std::{borrow::Cow, net::IpAddr};
+
+use crate::bencoded::BString;
+
+/// Event, reported to tracker with announce.
+///
+/// Regular (periodical) announces carry no event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Event {
+    /// First announce of download.
+    Started,
+    /// Client is shutting down gracefully.
+    Stopped,
+    /// Download is complete. Shouldn't be sent, if download was already complete when client started.
+    Completed,
+}
+
+impl Event {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Started => "started",
+            Self::Stopped => "stopped",
+            Self::Completed => "completed",
+        }
+    }
+}
+
+/// Parameters of announce request to tracker.
+///
+/// Use [`AnnounceRequest::builder`] to construct one. See <https://www.bittorrent.org/beps/bep_0003.html#trackers>
+/// and <https://wiki.theory.org/BitTorrentSpecification#Tracker_Request_Parameters> for description of parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnounceRequest {
+    pub info_hash: [u8; 20],
+    pub peer_id: [u8; 20],
+    pub port: u16,
+    pub uploaded: u64,
+    pub downloaded: u64,
+    pub left: u64,
+    pub event: Option<Event>,
+    /// Number of peers client would like to receive, [`Self::DEFAULT_NUMWANT`] for most trackers if `None`.
+    pub numwant: Option<u32>,
+    /// Identification of client, which is not shared with other peers. Allows tracker to prove client identity
+    /// if its IP address changes.
+    pub key: Option<u32>,
+    /// `tracker id`, returned by tracker in previous responce.
+    pub tracker_id: Option<BString>,
+    /// Asks tracker for compact peer list (BEP 23), which takes 6 bytes per peer. Set by default.
+    pub compact: bool,
+    /// Asks tracker to omit peer ids from canonical peer list, if it's not compact.
+    pub no_peer_id: bool,
+    /// Actual IP address of client, if differs from one tracker sees request from.
+    pub ip: Option<IpAddr>,
+}
+
+impl AnnounceRequest {
+    /// Number of peers, trackers usually return, if `numwant` isn't sent.
+    pub const DEFAULT_NUMWANT: u32 = 50;
+
+    pub fn builder(info_hash: [u8; 20], peer_id: [u8; 20], port: u16) -> AnnounceRequestBuilder {
+        AnnounceRequestBuilder {
+            request: Self {
+                info_hash,
+                peer_id,
+                port,
+                uploaded: 0,
+                downloaded: 0,
+                left: 0,
+                event: None,
+                numwant: None,
+                key: None,
+                tracker_id: None,
+                compact: true,
+                no_peer_id: false,
+                ip: None,
+            },
+        }
+    }
+
+    /// Returns request parameters as raw (not yet URL-encoded) key-value pairs in canonical order.
+    ///
+    /// Optional parameters are included only if set, except for `compact`, which is always sent, as trackers
+    /// disagree on its default.
+    pub fn params(&self) -> Vec<(&'static str, Cow<'_, [u8]>)> {
+        fn text(value: impl ToString) -> Cow<'static, [u8]> {
+            Cow::Owned(value.to_string().into_bytes())
+        }
+
+        let mut params = vec![
+            ("info_hash", Cow::Borrowed(&self.info_hash[..])),
+            ("peer_id", Cow::Borrowed(&self.peer_id[..])),
+            ("port", text(self.port)),
+            ("uploaded", text(self.uploaded)),
+            ("downloaded", text(self.downloaded)),
+            ("left", text(self.left)),
+        ];
+
+        if let Some(event) = self.event {
+            params.push(("event", Cow::Borrowed(event.as_str().as_bytes())));
+        }
+        if let Some(numwant) = self.numwant {
+            params.push(("numwant", text(numwant)));
+        }
+        if let Some(key) = self.key {
+            params.push(("key", text(format!("{:08X}", key))));
+        }
+        if let Some(tracker_id) = &self.tracker_id {
+            params.push(("trackerid", Cow::Borrowed(&tracker_id.0[..])));
+        }
+        params.push(("compact", text(self.compact as u8)));
+        if self.no_peer_id {
+            params.push(("no_peer_id", text(1)));
+        }
+        if let Some(ip) = self.ip {
+            params.push(("ip", text(ip)));
+        }
+
+        params
+    }
+
+    /// Returns URL-encoded query string of request (without leading `?`).
+    ///
+    /// Binary parameters (`info_hash`, `peer_id`) are encoded bytewise: every byte, except unreserved
+    /// characters of RFC 3986, is written as `%XX`, as trackers expect.
+    pub fn query(&self) -> String {
+        let mut query = String::new();
+
+        for (key, value) in self.params() {
+            if !query.is_empty() {
+                query.push('&');
+            }
+
+            query.push_str(key);
+            query.push('=');
+            utils::percent_encode(&value, &mut query);
+        }
+
+        query
+    }
+
+    /// Appends query of request to `announce` URL, preserving query already present in it (i.e. passkey).
+    pub fn url(&self, announce: &str) -> String {
+        // Fragment is never sent to server
+        let announce = announce.split('#').next().unwrap_or_default();
+
+        let separator = match announce.find('?') {
+            None => "?",
+            Some(_) if announce.ends_with(['?', '&']) => "",
+            Some(_) => "&",
+        };
+
+        format!("{}{}{}", announce, separator, self.query())
+    }
+}
+
+/// Builder of [`AnnounceRequest`]. All transfer counters default to zero, compact peer list is asked for
+/// and other optional parameters are unset.
+#[derive(Debug, Clone)]
+pub struct AnnounceRequestBuilder {
+    request: AnnounceRequest,
+}
+
+impl AnnounceRequestBuilder {
+    pub fn uploaded(mut self, uploaded: u64) -> Self {
+        self.request.uploaded = uploaded;
+        self
+    }
+
+    pub fn downloaded(mut self, downloaded: u64) -> Self {
+        self.request.downloaded = downloaded;
+        self
+    }
+
+    pub fn left(mut self, left: u64) -> Self {
+        self.request.left = left;
+        self
+    }
+
+    pub fn event(mut self, event: Event) -> Self {
+        self.request.event = Some(event);
+        self
+    }
+
+    pub fn numwant(mut self, numwant: u32) -> Self {
+        self.request.numwant = Some(numwant);
+        self
+    }
+
+    pub fn key(mut self, key: u32) -> Self {
+        self.request.key = Some(key);
+        self
+    }
+
+    pub fn tracker_id(mut self, tracker_id: BString) -> Self {
+        self.request.tracker_id = Some(tracker_id);
+        self
+    }
+
+    pub fn compact(mut self, compact: bool) -> Self {
+        self.request.compact = compact;
+        self
+    }
+
+    pub fn no_peer_id(mut self, no_peer_id: bool) -> Self {
+        self.request.no_peer_id = no_peer_id;
+        self
+    }
+
+    pub fn ip(mut self, ip: IpAddr) -> Self {
+        self.request.ip = Some(ip);
+        self
+    }
+
+    pub fn build(self) -> AnnounceRequest {
+        self.request
+    }
+}
+
+mod utils {
+    /// Encodes every byte except unreserved characters of RFC 3986.
+    pub fn percent_encode(bytes: &[u8], target: &mut String) {
+        for &byte in bytes {
+            if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+                target.push(char::from(byte));
+            } else {
+                target.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hex_literal::hex;
+    use rstest::rstest;
+
+    #[test]
+    fn optional_params_are_included_only_if_set() {
+        let bare = AnnounceRequest::builder([1; 20], [2; 20], 6881).left(100).build();
+        let full = AnnounceRequest::builder([1; 20], [2; 20], 6881)
+            .event(Event::Started)
+            .numwant(50)
+            .key(0xDEADBEEF)
+            .tracker_id(BString(b"id".to_vec()))
+            .compact(false)
+            .no_peer_id(true)
+            .ip("10.0.0.1".parse().unwrap())
+            .build();
+
+        let keys = |request: &AnnounceRequest| {
+            request.params().into_iter().map(|(key, _)| key).collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            keys(&bare),
+            ["info_hash", "peer_id", "port", "uploaded", "downloaded", "left", "compact"]
+        );
+        assert_eq!(keys(&full).len(), 13);
+        assert!(bare.params().contains(&("compact", Cow::Borrowed(&b"1"[..]))));
+        assert!(full.params().contains(&("compact", Cow::Borrowed(&b"0"[..]))));
+        assert!(full.params().contains(&("key", Cow::Borrowed(&b"DEADBEEF"[..]))));
+        assert!(full.params().contains(&("event", Cow::Borrowed(&b"started"[..]))));
+    }
+
+    #[test]
+    fn binary_params_are_encoded_bytewise() {
+        let info_hash = hex!("123456789abcdef123456789abcdef123456789a");
+        let request = AnnounceRequest::builder(info_hash, *b"-BR0001-a b+c/d\xe9~_.!", 6881).build();
+
+        // Example from https://wiki.theory.org/BitTorrentSpecification#Tracker_Request_Parameters
+        assert!(request
+            .query()
+            .starts_with("info_hash=%124Vx%9A%BC%DE%F1%23Eg%89%AB%CD%EF%124Vx%9A&peer_id=-BR0001-a%20b%2Bc%2Fd%E9~_.%21&"));
+    }
+
+    #[rstest]
+    #[case::bare("http://tracker.example/announce", "http://tracker.example/announce?")]
+    #[case::passkey("http://tracker.example/announce?passkey=abc", "http://tracker.example/announce?passkey=abc&")]
+    #[case::trailing_separator("http://tracker.example/announce?passkey=abc&", "http://tracker.example/announce?passkey=abc&")]
+    #[case::fragment("http://tracker.example/announce#top", "http://tracker.example/announce?")]
+    fn query_is_appended_to_announce_url(#[case] announce: &str, #[case] prefix: &str) {
+        let request = AnnounceRequest::builder([0xFF; 20], [b'0'; 20], 6881).build();
+
+        assert_eq!(request.url(announce), format!("{}{}", prefix, request.query()));
+    }
+}
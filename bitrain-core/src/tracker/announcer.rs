@@ -0,0 +1,158 @@
+use std::{
+    cmp,
+    time::{Duration, Instant},
+};
+
+use crate::bencoded::{TrackerInfo, TrackerResponce};
+
+/// Scheduler of announces to single tracker.
+///
+/// Announcer does no I/O itself: consumer asks it, when next announce is due, performs announce and
+/// reports outcome back, so schedule can be adjusted according to `interval` and `min interval`,
+/// returned by tracker, or failed announce can be retried with exponential backoff.
+#[derive(Debug, Clone)]
+pub struct Announcer {
+    next: Instant,
+    last_success: Option<Instant>,
+    interval: Duration,
+    min_interval: Option<Duration>,
+    failures: u32,
+}
+
+impl Announcer {
+    /// Interval used until tracker reports its own.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(30 * 60);
+    /// Delay before the first retry of failed announce. Doubled with each consecutive failure.
+    pub const RETRY_DELAY: Duration = Duration::from_secs(15);
+    /// Upper bound of retry delay.
+    pub const MAX_RETRY_DELAY: Duration = Duration::from_secs(30 * 60);
+    /// Lower bound of interval between regular announces, so misbehaving tracker can't make us flood it.
+    pub const MIN_INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Creates announcer, which schedules the first announce at `now`.
+    pub fn new(now: Instant) -> Self {
+        Self {
+            next: now,
+            last_success: None,
+            interval: Self::DEFAULT_INTERVAL,
+            min_interval: None,
+            failures: 0,
+        }
+    }
+
+    /// Time, when next announce should be performed.
+    pub fn next_announce(&self) -> Instant {
+        self.next
+    }
+
+    pub fn is_due(&self, now: Instant) -> bool {
+        now >= self.next
+    }
+
+    /// Number of consecutive failed announces.
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Reports outcome of announce, performed at `now`. Error responces count as failures.
+    pub fn on_responce(&mut self, responce: &TrackerResponce, now: Instant) {
+        match responce {
+            TrackerResponce::Success(responce) => self.on_success(&responce.info, now),
+            TrackerResponce::Error(_) => self.on_failure(now),
+        }
+    }
+
+    /// Reports successful announce, performed at `now`.
+    pub fn on_success(&mut self, info: &TrackerInfo, now: Instant) {
+        self.schedule(info.interval(), info.min_interval(), now)
+    }
+
+    /// Same as [`on_success()`](`Announcer::on_success`) for trackers, which report intervals by other means
+    /// (i.e. UDP trackers).
+    ///
+    /// `interval` is clamped to be no less than `min_interval` and [`Announcer::MIN_INTERVAL`].
+    pub fn schedule(&mut self, interval: Duration, min_interval: Option<Duration>, now: Instant) {
+        let floor = cmp::max(min_interval.unwrap_or_default(), Self::MIN_INTERVAL);
+        let interval = cmp::max(interval, floor);
+
+        self.interval = interval;
+        self.min_interval = min_interval;
+        self.failures = 0;
+        self.last_success = Some(now);
+        self.next = now + interval;
+    }
+
+    /// Reports failed announce, performed at `now`, and schedules retry.
+    pub fn on_failure(&mut self, now: Instant) {
+        self.failures = self.failures.saturating_add(1);
+
+        let backoff = Self::RETRY_DELAY
+            .checked_mul(1 << cmp::min(self.failures - 1, 16))
+            .unwrap_or(Self::MAX_RETRY_DELAY);
+
+        self.next = now + cmp::min(backoff, Self::MAX_RETRY_DELAY);
+    }
+
+    /// Asks for announce ahead of schedule (i.e. to get more peers), respecting `min interval`.
+    ///
+    /// Returns time, when the announce is scheduled.
+    pub fn request_announce(&mut self, now: Instant) -> Instant {
+        let earliest = match (self.last_success, self.min_interval) {
+            (Some(last), Some(min_interval)) => cmp::max(now, last + min_interval),
+            _ => now,
+        };
+
+        self.next = cmp::min(self.next, earliest);
+        self.next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_back_off_exponentially() {
+        let now = Instant::now();
+        let mut announcer = Announcer::new(now);
+
+        announcer.on_failure(now);
+        assert_eq!(announcer.next_announce(), now + Announcer::RETRY_DELAY);
+        announcer.on_failure(now);
+        assert_eq!(announcer.next_announce(), now + Announcer::RETRY_DELAY * 2);
+
+        for _ in 0..40 {
+            announcer.on_failure(now);
+        }
+        assert_eq!(announcer.next_announce(), now + Announcer::MAX_RETRY_DELAY);
+
+        announcer.schedule(Duration::from_secs(1800), None, now);
+        assert_eq!(announcer.failures(), 0);
+        assert_eq!(announcer.next_announce(), now + Duration::from_secs(1800));
+    }
+
+    #[test]
+    fn early_announce_respects_min_interval() {
+        let now = Instant::now();
+        let mut announcer = Announcer::new(now);
+        announcer.schedule(Duration::from_secs(1800), Some(Duration::from_secs(60)), now);
+
+        let scheduled = announcer.request_announce(now + Duration::from_secs(10));
+
+        assert_eq!(scheduled, now + Duration::from_secs(60));
+        assert!(!announcer.is_due(now + Duration::from_secs(59)));
+    }
+
+    #[test]
+    fn zero_interval_is_clamped() {
+        let now = Instant::now();
+        let mut announcer = Announcer::new(now);
+
+        announcer.schedule(Duration::ZERO, None, now);
+        assert_eq!(announcer.next_announce(), now + Announcer::MIN_INTERVAL);
+        assert!(!announcer.is_due(now));
+
+        announcer.schedule(Duration::ZERO, Some(Duration::from_secs(300)), now);
+        assert_eq!(announcer.next_announce(), now + Duration::from_secs(300));
+    }
+}
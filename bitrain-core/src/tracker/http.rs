@@ -0,0 +1,402 @@
+mod tls;
+
+use std::{
+    fmt,
+    io::{self, BufReader, Write},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::bencoded::{ParseError, Parser, Serde, TrackerResponce};
+
+use super::AnnounceRequest;
+
+#[cfg(feature = "native-tls")]
+pub use tls::NativeTlsConnector;
+#[cfg(feature = "rustls-tls")]
+pub use tls::RustlsConnector;
+pub(crate) use tls::default_connector;
+pub use tls::{TlsConnector, TlsStream};
+
+/// Client of HTTP (`http://` and `https://`) trackers.
+///
+/// Every announce is performed over a new connection. For `https://` trackers connector of enabled TLS
+/// backend (`rustls-tls` or `native-tls` feature) is used by default. Custom one can be supplied with
+/// [`HttpTracker::with_tls_connector`].
+#[derive(Clone)]
+pub struct HttpTracker {
+    url: String,
+    tls: Option<Arc<dyn TlsConnector>>,
+    timeout: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub enum HttpError {
+    IO(io::Error),
+    /// Announce URL is not a valid `http://` or `https://` URL.
+    InvalidUrl,
+    /// Tracker uses `https://`, but no TLS backend is enabled.
+    TlsUnavailable,
+    /// Tracker responded with non-success status and no bencoded responce.
+    Status(u16),
+    Parse(ParseError),
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IO(err) => write!(f, "io error: {}", err),
+            Self::InvalidUrl => f.write_str("invalid tracker url"),
+            Self::TlsUnavailable => f.write_str("https trackers require TLS backend to be enabled"),
+            Self::Status(status) => write!(f, "tracker responded with status {}", status),
+            Self::Parse(err) => write!(f, "malformed tracker responce: {:?}", err),
+        }
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+impl From<io::Error> for HttpError {
+    fn from(err: io::Error) -> Self {
+        Self::IO(err)
+    }
+}
+
+impl HttpTracker {
+    /// Maximum size of responce body. Responces of trackers are at most tens of kilobytes, so larger ones
+    /// are rejected before they are read.
+    pub const MAX_RESPONCE_SIZE: usize = 4 << 20;
+
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            tls: tls::default_connector(),
+            timeout: None,
+        }
+    }
+
+    /// Uses `connector` for `https://` trackers instead of default one.
+    pub fn with_tls_connector(mut self, connector: impl TlsConnector + 'static) -> Self {
+        self.tls = Some(Arc::new(connector));
+        self
+    }
+
+    /// Limits duration of connect and of every single read and write.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Announces to tracker and parses its responce.
+    ///
+    /// Rejected announce is not an error on this level and is returned as [`TrackerResponce::Error`].
+    pub fn announce(&self, request: &AnnounceRequest) -> Result<TrackerResponce, HttpError> {
+        let url = request.url(&self.url);
+        let (status, body) = get(&url, &[], self.tls.as_deref(), self.timeout, Self::MAX_RESPONCE_SIZE)?;
+
+        match Serde.parse(&body[..]) {
+            Ok(responce) => Ok(responce),
+            Err(_) if !(200..300).contains(&status) => Err(HttpError::Status(status)),
+            Err(err) => Err(HttpError::Parse(err)),
+        }
+    }
+}
+
+/// Performs `GET` request of `url` over a new connection, returning status and body of responce.
+///
+/// Every one of `headers` is sent as `name: value` line after mandatory ones. Body of more than `max_size` bytes
+/// is rejected as [`io::ErrorKind::InvalidData`].
+pub(crate) fn get(
+    url: &str,
+    headers: &[(&str, &str)],
+    tls: Option<&dyn TlsConnector>,
+    timeout: Option<Duration>,
+    max_size: usize,
+) -> Result<(u16, Vec<u8>), HttpError> {
+    let url = utils::Url::parse(url).ok_or(HttpError::InvalidUrl)?;
+
+    let tcp = utils::connect(url.host, url.port, timeout)?;
+    tcp.set_read_timeout(timeout)?;
+    tcp.set_write_timeout(timeout)?;
+
+    let mut stream: Box<dyn TlsStream> = if url.secure {
+        let tls = tls.ok_or(HttpError::TlsUnavailable)?;
+        tls.connect(url.host, tcp)?
+    } else {
+        Box::new(tcp)
+    };
+
+    let mut request = format!(
+        "GET {}{} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nAccept-Encoding: identity\r\n",
+        // Path can be omitted in URL, but not in request
+        if url.target.starts_with('/') { "" } else { "/" },
+        url.target,
+        url.authority
+    );
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+
+    stream.write_all(request.as_bytes())?;
+    stream.flush()?;
+
+    Ok(utils::read_responce(BufReader::new(stream), max_size)?)
+}
+
+mod utils {
+    use std::{
+        io::{self, BufRead, Read},
+        net::{TcpStream, ToSocketAddrs},
+        time::Duration,
+    };
+
+    /// Parts of announce URL, relevant for making request.
+    pub struct Url<'a> {
+        pub secure: bool,
+        /// Host without brackets (for IPv6).
+        pub host: &'a str,
+        pub port: u16,
+        /// Host and optional port, as specified in URL.
+        pub authority: &'a str,
+        /// Path with optional query.
+        pub target: &'a str,
+    }
+
+    impl<'a> Url<'a> {
+        pub fn parse(url: &'a str) -> Option<Self> {
+            let (secure, rest) = match url.split_once("://")? {
+                (scheme, rest) if scheme.eq_ignore_ascii_case("http") => (false, rest),
+                (scheme, rest) if scheme.eq_ignore_ascii_case("https") => (true, rest),
+                _ => return None,
+            };
+            let rest = rest.split('#').next()?;
+            let (authority, target) = rest.split_at(rest.find(['/', '?']).unwrap_or(rest.len()));
+            let default_port = if secure { 443 } else { 80 };
+
+            let (host, port) = match authority.strip_prefix('[') {
+                Some(bracketed) => {
+                    let (host, port) = bracketed.split_once(']')?;
+                    (host, port.strip_prefix(':'))
+                }
+                None => match authority.split_once(':') {
+                    Some((host, port)) => (host, Some(port)),
+                    None => (authority, None),
+                },
+            };
+            let port = match port {
+                Some(port) => port.parse().ok()?,
+                None => default_port,
+            };
+
+            if host.is_empty() {
+                return None;
+            }
+
+            Some(Self {
+                secure,
+                host,
+                port,
+                authority,
+                target,
+            })
+        }
+    }
+
+    pub fn connect(host: &str, port: u16, timeout: Option<Duration>) -> io::Result<TcpStream> {
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => return TcpStream::connect((host, port)),
+        };
+
+        let mut last_err = None;
+
+        for addr in (host, port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, timeout) {
+                Ok(stream) => return Ok(stream),
+                Err(err) => last_err = Some(err),
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "could not resolve to any address")
+        }))
+    }
+
+    /// Reads status and body of HTTP/1.1 responce.
+    pub fn read_responce(mut reader: impl BufRead, max_size: usize) -> io::Result<(u16, Vec<u8>)> {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+
+        let status = line
+            .split(' ')
+            .nth(1)
+            .and_then(|status| status.parse().ok())
+            .ok_or_else(|| malformed("invalid status line"))?;
+
+        let mut content_length = None;
+        let mut chunked = false;
+
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(malformed("unexpected end of headers"));
+            }
+
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+
+            if let Some((name, value)) = header.split_once(':') {
+                let value = value.trim();
+
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = Some(value.parse().map_err(|_| malformed("invalid content length"))?);
+                } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                    chunked = value.eq_ignore_ascii_case("chunked");
+                }
+            }
+        }
+
+        let body = if chunked {
+            read_chunked(reader, max_size)?
+        } else if let Some(len) = content_length {
+            if len > max_size {
+                return Err(too_large());
+            }
+
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body)?;
+            body
+        } else {
+            // Body is read up to one byte past limit to detect exceeding it
+            let mut body = vec![];
+            match reader.take(max_size as u64 + 1).read_to_end(&mut body) {
+                Ok(_) => {}
+                // Some servers close connection without TLS `close_notify`
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {}
+                Err(err) => return Err(err),
+            }
+            body
+        };
+
+        if body.len() > max_size {
+            return Err(too_large());
+        }
+
+        Ok((status, body))
+    }
+
+    fn read_chunked(mut reader: impl BufRead, max_size: usize) -> io::Result<Vec<u8>> {
+        let mut body = vec![];
+        let mut line = String::new();
+
+        loop {
+            line.clear();
+            reader.read_line(&mut line)?;
+
+            let size = line.trim_end().split(';').next().unwrap_or_default();
+            let size = usize::from_str_radix(size.trim(), 16).map_err(|_| malformed("invalid chunk size"))?;
+
+            // Trailers are of no interest
+            if size == 0 {
+                return Ok(body);
+            }
+
+            let start = body.len();
+            if size > max_size - start {
+                return Err(too_large());
+            }
+
+            body.resize(start + size, 0);
+            reader.read_exact(&mut body[start..])?;
+
+            line.clear();
+            reader.read_line(&mut line)?;
+        }
+    }
+
+    fn too_large() -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, "http responce exceeds size limit")
+    }
+
+    fn malformed(msg: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, format!("malformed http responce: {}", msg))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bencoded::{BString, TrackerFailure};
+    use std::{io::Read, net::TcpListener, thread};
+
+    #[test]
+    fn announce_over_plain_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/announce?passkey=abc", listener.local_addr().unwrap());
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![];
+            while !request.ends_with(b"\r\n\r\n") {
+                let mut byte = [0];
+                stream.read_exact(&mut byte).unwrap();
+                request.push(byte[0]);
+            }
+
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                    5\r\nd14:f\r\n13\r\nailure reason4:test\r\n1\r\ne\r\n0\r\n\r\n")
+                .unwrap();
+
+            String::from_utf8(request).unwrap()
+        });
+
+        let request = AnnounceRequest::builder([0xAB; 20], *b"-BR0001-abcdefghijkl", 6881).build();
+        let responce = HttpTracker::new(url).announce(&request).unwrap();
+        let request = server.join().unwrap();
+
+        assert_eq!(
+            responce,
+            TrackerResponce::Error(TrackerFailure {
+                failure_reason: BString(b"test".to_vec())
+            })
+        );
+        assert!(request.starts_with(&format!(
+            "GET /announce?passkey=abc&info_hash={}&peer_id=-BR0001-abcdefghijkl&port=6881&",
+            "%AB".repeat(20)
+        )));
+    }
+
+    #[test]
+    fn oversized_responce_is_rejected() {
+        let declared = b"HTTP/1.1 200 OK\r\nContent-Length: 1000000000000\r\n\r\n";
+        let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n8\r\n01234567\r\n0\r\n\r\n";
+        let unknown = b"HTTP/1.1 200 OK\r\n\r\n01234567";
+
+        for responce in [&declared[..], chunked, unknown] {
+            let err = utils::read_responce(responce, 4).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        assert_eq!(utils::read_responce(&chunked[..], 8).unwrap(), (200, b"01234567".to_vec()));
+    }
+
+    #[test]
+    fn urls_are_parsed() {
+        let url = utils::Url::parse("https://[::1]/announce").unwrap();
+        assert!(url.secure);
+        assert_eq!((url.host, url.port, url.target), ("::1", 443, "/announce"));
+
+        let url = utils::Url::parse("HTTP://tracker.example:8080?key=1#frag").unwrap();
+        assert!(!url.secure);
+        assert_eq!((url.host, url.port, url.target), ("tracker.example", 8080, "?key=1"));
+
+        assert!(utils::Url::parse("udp://tracker.example:6969").is_none());
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    sync::Arc,
+};
+
+/// Encrypted connection to tracker.
+pub trait TlsStream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> TlsStream for T {}
+
+/// Wraps TCP connection into TLS session for `https://` trackers.
+///
+/// Implementations for `rustls` and `native-tls` are available under `rustls-tls` and `native-tls`
+/// features respectively. Embedders can supply their own with
+/// [`HttpTracker::with_tls_connector`](`super::HttpTracker::with_tls_connector`).
+pub trait TlsConnector: Send + Sync {
+    /// Performs TLS handshake over `stream`, verifying certificate against `domain`.
+    fn connect(&self, domain: &str, stream: TcpStream) -> io::Result<Box<dyn TlsStream>>;
+}
+
+/// [`TlsConnector`], backed by `rustls` with Mozilla root certificates.
+#[cfg(feature = "rustls-tls")]
+#[derive(Clone)]
+pub struct RustlsConnector {
+    config: Arc<rustls::ClientConfig>,
+}
+
+#[cfg(feature = "rustls-tls")]
+impl RustlsConnector {
+    pub fn new() -> Self {
+        let roots = rustls::RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        let config = rustls::ClientConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .expect("default protocol versions are supported by ring provider")
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+        Self::with_config(Arc::new(config))
+    }
+
+    /// Uses custom client config (i.e. with private CA of tracker).
+    pub fn with_config(config: Arc<rustls::ClientConfig>) -> Self {
+        Self { config }
+    }
+}
+
+#[cfg(feature = "rustls-tls")]
+impl Default for RustlsConnector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "rustls-tls")]
+impl TlsConnector for RustlsConnector {
+    fn connect(&self, domain: &str, stream: TcpStream) -> io::Result<Box<dyn TlsStream>> {
+        let name = rustls::pki_types::ServerName::try_from(domain.to_owned())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let connection =
+            rustls::ClientConnection::new(self.config.clone(), name).map_err(io::Error::other)?;
+
+        Ok(Box::new(rustls::StreamOwned::new(connection, stream)))
+    }
+}
+
+/// [`TlsConnector`], backed by platform TLS implementation.
+#[cfg(feature = "native-tls")]
+#[derive(Clone)]
+pub struct NativeTlsConnector {
+    inner: native_tls::TlsConnector,
+}
+
+#[cfg(feature = "native-tls")]
+impl NativeTlsConnector {
+    pub fn new() -> io::Result<Self> {
+        native_tls::TlsConnector::new()
+            .map(Self::from)
+            .map_err(io::Error::other)
+    }
+}
+
+#[cfg(feature = "native-tls")]
+impl From<native_tls::TlsConnector> for NativeTlsConnector {
+    fn from(inner: native_tls::TlsConnector) -> Self {
+        Self { inner }
+    }
+}
+
+#[cfg(feature = "native-tls")]
+impl TlsConnector for NativeTlsConnector {
+    fn connect(&self, domain: &str, stream: TcpStream) -> io::Result<Box<dyn TlsStream>> {
+        self.inner
+            .connect(domain, stream)
+            .map(|stream| Box::new(stream) as Box<dyn TlsStream>)
+            // Handshake error holds the stream, which doesn't have to be `Sync`
+            .map_err(|err| io::Error::other(err.to_string()))
+    }
+}
+
+/// Returns connector of enabled TLS backend, if any.
+#[allow(unreachable_code)]
+pub(crate) fn default_connector() -> Option<Arc<dyn TlsConnector>> {
+    #[cfg(feature = "rustls-tls")]
+    return Some(Arc::new(RustlsConnector::new()));
+
+    #[cfg(feature = "native-tls")]
+    return NativeTlsConnector::new()
+        .ok()
+        .map(|connector| Arc::new(connector) as Arc<dyn TlsConnector>);
+
+    None
+}
@@ -0,0 +1,130 @@
+use rand::seq::SliceRandom;
+
+use crate::bencoded::Metainfo;
+
+/// Tiered list of tracker URLs, which are tried according to BEP 12.
+///
+/// Trackers are tried tier by tier, in order in which they are placed in tier. Order inside each tier is
+/// shuffled on creation, and tracker, which responded successfully, is moved to the front of its tier.
+///
+/// See <http://bittorrent.org/beps/bep_0012.html> for more info.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackerList {
+    tiers: Vec<Vec<String>>,
+}
+
+impl TrackerList {
+    /// Creates list from tracker tiers, shuffling trackers within each tier. Empty tiers are dropped.
+    pub fn new(tiers: Vec<Vec<String>>) -> Self {
+        let mut rng = rand::thread_rng();
+
+        let tiers = tiers
+            .into_iter()
+            .filter(|tier| !tier.is_empty())
+            .map(|mut tier| {
+                tier.shuffle(&mut rng);
+                tier
+            })
+            .collect();
+
+        Self { tiers }
+    }
+
+    /// Creates list from `announce-list` of metainfo, falling back to single `announce` URL
+    /// if `announce-list` is absent or contains no trackers.
+    pub fn from_metainfo(metainfo: &Metainfo) -> Self {
+        let list = Self::new(metainfo.announce_list.clone().unwrap_or_default());
+
+        if list.is_empty() && !metainfo.announce.is_empty() {
+            Self::new(vec![vec![metainfo.announce.clone()]])
+        } else {
+            list
+        }
+    }
+
+    pub fn tiers(&self) -> &[Vec<String>] {
+        &self.tiers
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiers.is_empty()
+    }
+
+    /// Iterates over all trackers in order in which they should be tried.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.tiers.iter().flatten().map(String::as_str)
+    }
+
+    /// Moves `url` to the front of its tier. Does nothing if `url` is not in the list.
+    pub fn promote(&mut self, url: &str) {
+        for tier in &mut self.tiers {
+            if let Some(pos) = tier.iter().position(|tracker| tracker == url) {
+                tier[..=pos].rotate_right(1);
+                return;
+            }
+        }
+    }
+
+    /// Tries `announce` on trackers one by one until it succeeds, promoting successful tracker.
+    ///
+    /// ## Errors
+    ///
+    /// If no tracker succeeded, returns errors of all trackers paired with their URLs
+    /// (empty, if there are no trackers at all).
+    pub fn announce_with<T, E>(
+        &mut self,
+        mut announce: impl FnMut(&str) -> Result<T, E>,
+    ) -> Result<T, Vec<(String, E)>> {
+        let mut errors = vec![];
+
+        for tier in &mut self.tiers {
+            for pos in 0..tier.len() {
+                match announce(&tier[pos]) {
+                    Ok(response) => {
+                        tier[..=pos].rotate_right(1);
+                        return Ok(response);
+                    }
+                    Err(err) => errors.push((tier[pos].clone(), err)),
+                }
+            }
+        }
+
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list() -> TrackerList {
+        TrackerList::new(vec![
+            vec!["a".to_owned(), "b".to_owned(), "c".to_owned()],
+            vec![],
+            vec!["d".to_owned()],
+        ])
+    }
+
+    #[test]
+    fn working_tracker_is_promoted() {
+        let mut list = list();
+        let result = list.announce_with(|url| if url == "c" { Ok(url.to_owned()) } else { Err(()) });
+
+        assert_eq!(result, Ok("c".to_owned()));
+        assert_eq!(list.tiers().len(), 2);
+        assert_eq!(list.tiers()[0][0], "c");
+    }
+
+    #[test]
+    fn tiers_are_tried_in_order() {
+        let mut list = list();
+        let mut tried = vec![];
+        let result = list.announce_with(|url| {
+            tried.push(url.to_owned());
+            Err::<(), _>(url.to_owned())
+        });
+
+        assert_eq!(result.unwrap_err().len(), 4);
+        assert_eq!(tried.last().map(String::as_str), Some("d"));
+    }
+}
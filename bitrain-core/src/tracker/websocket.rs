@@ -0,0 +1,382 @@
+use std::{collections::VecDeque, fmt, net::TcpStream, time::Duration};
+
+use serde_derive::{Deserialize, Serialize};
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+use super::{AnnounceRequest, Event};
+
+/// Client of WebSocket (`ws://` and `wss://`) trackers, used by WebTorrent swarms.
+///
+/// Besides announces, tracker relays WebRTC offers and answers between peers of the same swarm. Offers are
+/// sent along with announce (see [`WebSocketTracker::announce_with_offers`]) and signals of other peers
+/// are recieved with [`WebSocketTracker::next_signal`]. Connecting to WebTorrent peers with them is up to
+/// WebRTC implementation.
+///
+/// See <https://github.com/webtorrent/bittorrent-tracker> for protocol reference.
+pub struct WebSocketTracker {
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    /// Signals, recieved while waiting for announce responce.
+    signals: VecDeque<Signal>,
+}
+
+/// Responce to announce of WebSocket tracker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebSocketResponce {
+    pub interval: Duration,
+    /// Number of seeders, if reported.
+    pub complete: Option<u64>,
+    /// Number of leechers, if reported.
+    pub incomplete: Option<u64>,
+    pub warning_message: Option<String>,
+}
+
+/// Session description of WebRTC peer connection, which is relayed by tracker.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionDescription {
+    /// `offer` or `answer`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub sdp: String,
+}
+
+/// WebRTC signal of other peer, relayed by tracker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Signal {
+    /// Peer offers to connect, offer should be answered with [`WebSocketTracker::answer`].
+    Offer {
+        info_hash: [u8; 20],
+        peer_id: [u8; 20],
+        offer_id: [u8; 20],
+        offer: SessionDescription,
+    },
+    /// Peer answered our offer with `offer_id`.
+    Answer {
+        info_hash: [u8; 20],
+        peer_id: [u8; 20],
+        offer_id: [u8; 20],
+        answer: SessionDescription,
+    },
+}
+
+#[derive(Debug)]
+pub enum WebSocketError {
+    WebSocket(Box<tungstenite::Error>),
+    Json(serde_json::Error),
+    /// Tracker responded with `failure reason`.
+    Failure(String),
+    /// Tracker closed connection before responding.
+    Closed,
+}
+
+impl fmt::Display for WebSocketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WebSocket(err) => write!(f, "websocket error: {}", err),
+            Self::Json(err) => write!(f, "malformed tracker message: {}", err),
+            Self::Failure(reason) => write!(f, "tracker failure: {}", reason),
+            Self::Closed => f.write_str("tracker closed connection"),
+        }
+    }
+}
+
+impl std::error::Error for WebSocketError {}
+
+impl From<tungstenite::Error> for WebSocketError {
+    fn from(err: tungstenite::Error) -> Self {
+        Self::WebSocket(Box::new(err))
+    }
+}
+
+impl From<serde_json::Error> for WebSocketError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+#[derive(Serialize)]
+struct OutgoingAnnounce<'a> {
+    action: &'static str,
+    info_hash: String,
+    peer_id: String,
+    uploaded: u64,
+    downloaded: u64,
+    left: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event: Option<&'a str>,
+    numwant: u32,
+    offers: Vec<OutgoingOffer<'a>>,
+}
+
+#[derive(Serialize)]
+struct OutgoingOffer<'a> {
+    offer: &'a SessionDescription,
+    offer_id: String,
+}
+
+#[derive(Serialize)]
+struct OutgoingAnswer<'a> {
+    action: &'static str,
+    info_hash: String,
+    peer_id: String,
+    to_peer_id: String,
+    offer_id: String,
+    answer: &'a SessionDescription,
+}
+
+#[derive(Deserialize)]
+struct Incoming {
+    action: Option<String>,
+    info_hash: Option<String>,
+    interval: Option<u64>,
+    complete: Option<u64>,
+    incomplete: Option<u64>,
+    #[serde(rename = "failure reason")]
+    failure_reason: Option<String>,
+    #[serde(rename = "warning message")]
+    warning_message: Option<String>,
+    peer_id: Option<String>,
+    offer_id: Option<String>,
+    offer: Option<SessionDescription>,
+    answer: Option<SessionDescription>,
+}
+
+impl Incoming {
+    /// Relayed offer or answer, `None` if message is not a valid signal.
+    fn into_signal(self) -> Option<Signal> {
+        let info_hash = utils::binary_bytes(self.info_hash.as_deref()?)?;
+        let peer_id = utils::binary_bytes(self.peer_id.as_deref()?)?;
+        let offer_id = utils::binary_bytes(self.offer_id.as_deref()?)?;
+
+        match (self.offer, self.answer) {
+            (Some(offer), _) => Some(Signal::Offer {
+                info_hash,
+                peer_id,
+                offer_id,
+                offer,
+            }),
+            (None, Some(answer)) => Some(Signal::Answer {
+                info_hash,
+                peer_id,
+                offer_id,
+                answer,
+            }),
+            (None, None) => None,
+        }
+    }
+}
+
+impl WebSocketTracker {
+    /// Interval used if tracker doesn't report one.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(120);
+
+    /// Connects to tracker at `url` (`ws://` or `wss://`).
+    pub fn connect(url: &str) -> Result<Self, WebSocketError> {
+        let (socket, _) = tungstenite::connect(url)?;
+
+        Ok(Self {
+            socket,
+            signals: VecDeque::new(),
+        })
+    }
+
+    /// Announces to tracker and waits for responce for the same torrent.
+    ///
+    /// `numwant` of request is ignored, as peers can be received only via WebRTC offers.
+    pub fn announce(&mut self, request: &AnnounceRequest) -> Result<WebSocketResponce, WebSocketError> {
+        self.announce_with_offers(request, &[])
+    }
+
+    /// Same as [`announce()`](`WebSocketTracker::announce`), but also sends WebRTC `offers` with their ids,
+    /// which tracker relays to other peers. Their answers are recieved as [`Signal::Answer`].
+    pub fn announce_with_offers(
+        &mut self,
+        request: &AnnounceRequest,
+        offers: &[([u8; 20], SessionDescription)],
+    ) -> Result<WebSocketResponce, WebSocketError> {
+        let info_hash = utils::binary_string(&request.info_hash);
+
+        let announce = OutgoingAnnounce {
+            action: "announce",
+            info_hash: info_hash.clone(),
+            peer_id: utils::binary_string(&request.peer_id),
+            uploaded: request.uploaded,
+            downloaded: request.downloaded,
+            left: request.left,
+            event: request.event.as_ref().map(Event::as_str),
+            numwant: offers.len() as u32,
+            offers: offers
+                .iter()
+                .map(|(offer_id, offer)| OutgoingOffer {
+                    offer,
+                    offer_id: utils::binary_string(offer_id),
+                })
+                .collect(),
+        };
+
+        self.socket
+            .send(Message::Text(serde_json::to_string(&announce)?))?;
+
+        loop {
+            let text = match self.socket.read()? {
+                Message::Text(text) => text,
+                Message::Close(_) => return Err(WebSocketError::Closed),
+                // Pings are answered by tungstenite itself
+                _ => continue,
+            };
+
+            let incoming: Incoming = serde_json::from_str(&text)?;
+
+            if let Some(reason) = incoming.failure_reason {
+                return Err(WebSocketError::Failure(reason));
+            }
+
+            if incoming.offer.is_some() || incoming.answer.is_some() {
+                self.signals.extend(incoming.into_signal());
+                continue;
+            }
+
+            let is_announce_responce = incoming.action.as_deref() == Some("announce")
+                && incoming.info_hash.as_deref() == Some(info_hash.as_str())
+                && (incoming.interval.is_some() || incoming.complete.is_some());
+
+            if is_announce_responce {
+                return Ok(WebSocketResponce {
+                    interval: incoming
+                        .interval
+                        .map_or(Self::DEFAULT_INTERVAL, Duration::from_secs),
+                    complete: incoming.complete,
+                    incomplete: incoming.incomplete,
+                    warning_message: incoming.warning_message,
+                });
+            }
+        }
+    }
+
+    /// Returns next relayed offer or answer, waiting for one if none was recieved yet.
+    pub fn next_signal(&mut self) -> Result<Signal, WebSocketError> {
+        if let Some(signal) = self.signals.pop_front() {
+            return Ok(signal);
+        }
+
+        loop {
+            let text = match self.socket.read()? {
+                Message::Text(text) => text,
+                Message::Close(_) => return Err(WebSocketError::Closed),
+                _ => continue,
+            };
+
+            let incoming: Incoming = serde_json::from_str(&text)?;
+
+            // Late announce responces and malformed signals are of no interest
+            if let Some(signal) = incoming.into_signal() {
+                return Ok(signal);
+            }
+        }
+    }
+
+    /// Sends `answer` to offer with `offer_id` of peer with `to_peer_id`, as peer with `peer_id`.
+    pub fn answer(
+        &mut self,
+        info_hash: &[u8; 20],
+        peer_id: &[u8; 20],
+        to_peer_id: &[u8; 20],
+        offer_id: &[u8; 20],
+        answer: &SessionDescription,
+    ) -> Result<(), WebSocketError> {
+        let answer = OutgoingAnswer {
+            action: "announce",
+            info_hash: utils::binary_string(info_hash),
+            peer_id: utils::binary_string(peer_id),
+            to_peer_id: utils::binary_string(to_peer_id),
+            offer_id: utils::binary_string(offer_id),
+            answer,
+        };
+
+        self.socket
+            .send(Message::Text(serde_json::to_string(&answer)?))?;
+
+        Ok(())
+    }
+
+    /// Closes connection to tracker.
+    pub fn close(mut self) -> Result<(), WebSocketError> {
+        self.socket.close(None)?;
+        Ok(())
+    }
+}
+
+mod utils {
+    /// WebTorrent trackers transfer binary ids as JSON strings, mapping each byte to character with the same code.
+    pub fn binary_string(bytes: &[u8]) -> String {
+        bytes.iter().map(|&b| char::from(b)).collect()
+    }
+
+    /// Reverse of [`binary_string`], `None` if string has wrong length or non-byte characters.
+    pub fn binary_bytes<const N: usize>(string: &str) -> Option<[u8; N]> {
+        let bytes = string
+            .chars()
+            .map(|c| u8::try_from(c).ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        bytes.try_into().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, thread};
+
+    #[test]
+    fn binary_ids_are_mapped_bytewise() {
+        let encoded = utils::binary_string(&[0x00, 0x41, 0xE9, 0xFF]);
+
+        assert_eq!(encoded, "\u{0}A\u{e9}\u{ff}");
+        assert_eq!(encoded.chars().count(), 4);
+        assert_eq!(utils::binary_bytes(&encoded), Some([0x00, 0x41, 0xE9, 0xFF]));
+    }
+
+    #[test]
+    fn offers_and_answers_are_relayed() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+
+        let server = thread::spawn(move || {
+            let mut socket = tungstenite::accept(listener.accept().unwrap().0).unwrap();
+            let announce = socket.read().unwrap().into_text().unwrap();
+
+            let answer = r#"{"action":"announce","info_hash":"AAAAAAAAAAAAAAAAAAAA","peer_id":"BBBBBBBBBBBBBBBBBBBB","offer_id":"CCCCCCCCCCCCCCCCCCCC","answer":{"type":"answer","sdp":"v=0"}}"#;
+            socket.send(Message::Text(answer.to_owned())).unwrap();
+            let responce = r#"{"action":"announce","info_hash":"AAAAAAAAAAAAAAAAAAAA","interval":60}"#;
+            socket.send(Message::Text(responce.to_owned())).unwrap();
+
+            announce
+        });
+
+        let offer = SessionDescription {
+            kind: "offer".to_owned(),
+            sdp: "v=0".to_owned(),
+        };
+        let request = AnnounceRequest::builder(*b"AAAAAAAAAAAAAAAAAAAA", [1; 20], 0).build();
+
+        let mut tracker = WebSocketTracker::connect(&url).unwrap();
+        let responce = tracker.announce_with_offers(&request, &[(*b"CCCCCCCCCCCCCCCCCCCC", offer)]).unwrap();
+        let signal = tracker.next_signal().unwrap();
+        let announce = server.join().unwrap();
+
+        assert_eq!(responce.interval, Duration::from_secs(60));
+        assert!(announce.contains(r#""numwant":1,"offers":[{"offer":{"type":"offer","sdp":"v=0"},"offer_id":"CCCCCCCCCCCCCCCCCCCC"}]"#));
+        assert_eq!(
+            signal,
+            Signal::Answer {
+                info_hash: *b"AAAAAAAAAAAAAAAAAAAA",
+                peer_id: *b"BBBBBBBBBBBBBBBBBBBB",
+                offer_id: *b"CCCCCCCCCCCCCCCCCCCC",
+                answer: SessionDescription {
+                    kind: "answer".to_owned(),
+                    sdp: "v=0".to_owned(),
+                },
+            }
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! Known-good encodings of protocol structures, to verify conformance against fixed bytes.
+//!
+//! Vectors pair values with their exact encodings. [`assert_message_round_trip`] and
+//! [`assert_bencode_round_trip`] check, that encoding gives the same bytes and decoding gives back the same value,
+//! so implementations of dependents (i.e. custom messages or transports) can be checked the same way as this crate.
+use std::fmt::Debug;
+
+use bytes::Bytes;
+
+use crate::messages::{Bitfield, Cancel, Handshake, Have, Message, Piece, Recv, Request, Send};
+#[cfg(feature = "use-serde")]
+use crate::bencoded::{Parser, Saver, Serde};
+
+/// Keep-alive message, which is only length prefix of zero.
+pub const KEEP_ALIVE: &[u8] = &[0, 0, 0, 0];
+
+/// Handshake of [`handshake()`].
+pub const HANDSHAKE: &[u8] = b"\x13BitTorrent protocol\x00\x00\x00\x00\x00\x10\x00\x05\
+    \x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\x01\
+    -BR0001-abcdefghijkl";
+
+/// `.torrent` of single file `sample.txt` of 20 bytes, with private flag.
+pub const SAMPLE_TORRENT: &[u8] = include_bytes!("bencoded/sample.torrent");
+pub const SAMPLE_TORRENT_INFO_HASH: [u8; 20] = [
+    0xd0, 0xd1, 0x4c, 0x92, 0x6e, 0x6e, 0x99, 0x76, 0x1a, 0x2f, 0xdc, 0xff, 0x27, 0xb4, 0x03, 0xd9, 0x63, 0x76, 0xef,
+    0xf6,
+];
+
+/// Successful announce responce with interval of 30 minutes, compact IPv4 peer `10.0.0.1:6881` and
+/// IPv6 peer `[::1]:6881`.
+pub const TRACKER_RESPONCE: &[u8] = b"d8:completei1e10:incompletei0e8:intervali1800e\
+    5:peers6:\x0a\x00\x00\x01\x1a\xe1\
+    6:peers618:\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\x1a\xe1e";
+/// Announce responce with failure reason `unregistered torrent`.
+pub const TRACKER_FAILURE: &[u8] = b"d14:failure reason20:unregistered torrente";
+
+/// Handshake with extension protocol, fast extension and DHT bits set, info hash of `0x01` bytes
+/// and peer id `-BR0001-abcdefghijkl`.
+pub fn handshake() -> Handshake {
+    Handshake::builder([1; 20], *b"-BR0001-abcdefghijkl")
+        .extensions(true)
+        .fast(true)
+        .dht(true)
+        .build()
+}
+
+/// Every type of [`Message`] with its encoding.
+pub fn messages() -> Vec<(Message, &'static [u8])> {
+    vec![
+        (Message::Choke, &[0, 0, 0, 1, 0]),
+        (Message::Unchoke, &[0, 0, 0, 1, 1]),
+        (Message::Interested, &[0, 0, 0, 1, 2]),
+        (Message::NotInterested, &[0, 0, 0, 1, 3]),
+        (Message::Have(Have { piece_index: 0x1234 }), &[0, 0, 0, 5, 4, 0, 0, 0x12, 0x34]),
+        (
+            Message::Bitfield(Bitfield { bits: Bytes::from_static(&[0xff, 0x80]) }),
+            &[0, 0, 0, 3, 5, 0xff, 0x80],
+        ),
+        (
+            Message::Request(Request { piece_index: 1, offset: 0x4000, data_length: 0x4000 }),
+            &[0, 0, 0, 13, 6, 0, 0, 0, 1, 0, 0, 0x40, 0, 0, 0, 0x40, 0],
+        ),
+        (
+            Message::Piece(Piece { piece_index: 1, offset: 0x4000, data: Bytes::from_static(b"abc") }),
+            &[0, 0, 0, 12, 7, 0, 0, 0, 1, 0, 0, 0x40, 0, b'a', b'b', b'c'],
+        ),
+        (
+            Message::Cancel(Cancel { piece_index: 1, offset: 0x4000, data_length: 0x4000 }),
+            &[0, 0, 0, 13, 8, 0, 0, 0, 1, 0, 0, 0x40, 0, 0, 0, 0x40, 0],
+        ),
+    ]
+}
+
+/// Asserts, that `message` is sent as `bytes` and `bytes` are recieved as `message`.
+pub fn assert_message_round_trip<M: Send + Recv + PartialEq + Debug>(message: &M, bytes: &[u8]) {
+    let mut encoded = vec![];
+    message.send_to(&mut encoded).expect("message should be encoded");
+    assert_eq!(encoded, bytes, "encoding of {:?} doesn't match vector", message);
+
+    let mut reader = bytes;
+    let decoded = M::recv_from(&mut reader).expect("vector should be decoded");
+    assert_eq!(decoded.as_ref(), Some(message));
+    assert!(reader.is_empty(), "vector of {:?} isn't consumed entirely", message);
+}
+
+/// Asserts, that `bytes` are parsed as `T` and saved back as the same bytes, returning parsed value.
+#[cfg(feature = "use-serde")]
+pub fn assert_bencode_round_trip<T>(bytes: &[u8]) -> T
+where
+    Serde: Parser<T> + Saver<T>,
+    <Serde as Parser<T>>::Err: Debug,
+    <Serde as Saver<T>>::Err: Debug,
+{
+    let decoded = Serde.parse(bytes).expect("vector should be parsed");
+
+    let mut encoded = vec![];
+    Serde.save(&decoded, &mut encoded).expect("value should be saved");
+    assert_eq!(encoded, bytes, "saved value doesn't match vector");
+
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_match_vectors() {
+        assert_message_round_trip(&handshake(), HANDSHAKE);
+
+        for (message, bytes) in messages() {
+            assert_message_round_trip(&message, bytes);
+        }
+
+        assert!(matches!(Message::recv_from(&mut &KEEP_ALIVE[..]), Ok(None)));
+    }
+
+    #[cfg(feature = "use-serde")]
+    #[test]
+    fn bencode_matches_vectors() {
+        use crate::bencoded::{Metainfo, TrackerResponce};
+
+        let metainfo = assert_bencode_round_trip::<Metainfo>(SAMPLE_TORRENT);
+        assert_eq!(metainfo.info_hash(), SAMPLE_TORRENT_INFO_HASH);
+
+        let responce = assert_bencode_round_trip::<TrackerResponce>(TRACKER_RESPONCE);
+        assert_eq!(responce.peers().count(), 2);
+        assert!(assert_bencode_round_trip::<TrackerResponce>(TRACKER_FAILURE).into_result().is_err());
+    }
+}
@@ -0,0 +1,283 @@
+//! Download of torrent data from HTTP servers (web seeds).
+//!
+//! For more info see <https://www.bittorrent.org/beps/bep_0019.html>.
+use std::{fmt, sync::Arc, time::Duration};
+
+use crate::bencoded::{BInt, Files, Info, Layout};
+use crate::messages::{BTInt, Piece, Request};
+use crate::tracker::http::{self, default_connector};
+use crate::tracker::{HttpError, TlsConnector};
+
+/// Client of web seed from `url-list` of torrent (see [`Metainfo::url_list`](`crate::bencoded::Metainfo::url_list`)).
+///
+/// Blocks are fetched with HTTP range requests by the same [`Request`]s, which are sent to peers, and
+/// returned as [`Piece`]s, so web seed can be treated as one more peer, which has every piece.
+#[derive(Clone)]
+pub struct WebSeed {
+    url: String,
+    /// URLs of files in order of `info` files.
+    file_urls: Vec<String>,
+    layout: Layout,
+    tls: Option<Arc<dyn TlsConnector>>,
+    timeout: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub enum WebSeedError {
+    Http(HttpError),
+    /// Requested block is out of torrent content.
+    OutOfRange,
+    /// Server returned less data, than was requested.
+    ShortResponce { expected: usize, actual: usize },
+}
+
+impl fmt::Display for WebSeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Http(err) => write!(f, "web seed request failed: {}", err),
+            Self::OutOfRange => f.write_str("requested block is out of range"),
+            Self::ShortResponce { expected, actual } => {
+                write!(f, "web seed returned {} bytes instead of {}", actual, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WebSeedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Http(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<HttpError> for WebSeedError {
+    fn from(err: HttpError) -> Self {
+        Self::Http(err)
+    }
+}
+
+impl WebSeed {
+    /// Creates client of web seed at `url` for torrent with `info`.
+    ///
+    /// For multi-file torrents `url` is treated as directory, which holds torrent directory, otherwise
+    /// as URL of the file itself, unless it ends with `/`.
+    pub fn new(url: impl Into<String>, info: &Info) -> Self {
+        let url = url.into();
+        let directory = url.ends_with('/');
+        let base = url.trim_end_matches('/');
+
+        let file_urls = match &info.files {
+            Files::Single { .. } if !directory => vec![url.clone()],
+            Files::Single { .. } => vec![utils::join(base, [info.name.as_str()])],
+            Files::Multiple { files } => files
+                .iter()
+                .map(|file| {
+                    let path = file.path.iter().map(String::as_str);
+                    utils::join(base, [info.name.as_str()].into_iter().chain(path))
+                })
+                .collect(),
+        };
+
+        Self {
+            url,
+            file_urls,
+            layout: info.layout(),
+            tls: default_connector(),
+            timeout: None,
+        }
+    }
+
+    /// Uses `connector` for `https://` web seeds instead of default one.
+    pub fn with_tls_connector(mut self, connector: impl TlsConnector + 'static) -> Self {
+        self.tls = Some(Arc::new(connector));
+        self
+    }
+
+    /// Limits duration of connect and of every single read and write.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// URL, `file` is fetched from, `None` if file doesn't exist.
+    pub fn file_url(&self, file: usize) -> Option<&str> {
+        self.file_urls.get(file).map(String::as_str)
+    }
+
+    /// Fetches block, described by `request`. Block, which spans several files, is fetched with one
+    /// request per file. Padding files are not requested, as servers don't have them.
+    pub fn fetch(&self, request: &Request) -> Result<Piece, WebSeedError> {
+        let segments = self
+            .layout
+            .file_segments(
+                request.piece_index as BInt,
+                request.offset as BInt,
+                request.data_length as BInt,
+            )
+            .ok_or(WebSeedError::OutOfRange)?;
+
+        let mut data = Vec::with_capacity(request.data_length as usize);
+
+        for segment in segments {
+            let length = segment.length as usize;
+
+            if self.layout.is_padding(segment.file) {
+                data.resize(data.len() + length, 0);
+                continue;
+            }
+
+            let range = format!("bytes={}-{}", segment.offset, segment.offset + segment.length - 1);
+            // Server may ignore range and send the whole file
+            let max_size = self.layout.file_length(segment.file).unwrap_or_default() as usize;
+            let (status, body) = http::get(
+                &self.file_urls[segment.file],
+                &[("Range", &range)],
+                self.tls.as_deref(),
+                self.timeout,
+                max_size,
+            )?;
+
+            let body = match status {
+                206 => &body[..],
+                // Server ignored range and sent the whole file
+                200 => body.get(segment.offset as usize..).unwrap_or_default(),
+                _ => return Err(HttpError::Status(status).into()),
+            };
+
+            if body.len() < length {
+                return Err(WebSeedError::ShortResponce {
+                    expected: length,
+                    actual: body.len(),
+                });
+            }
+
+            data.extend_from_slice(&body[..length]);
+        }
+
+        Ok(Piece {
+            piece_index: request.piece_index,
+            offset: request.offset,
+            data: data.into(),
+        })
+    }
+
+    /// Fetches the whole `piece`.
+    pub fn fetch_piece(&self, piece: BTInt) -> Result<Piece, WebSeedError> {
+        let length = self.layout.piece_size(piece as BInt).ok_or(WebSeedError::OutOfRange)?;
+
+        self.fetch(&Request {
+            piece_index: piece,
+            offset: 0,
+            data_length: length as BTInt,
+        })
+    }
+}
+
+impl fmt::Debug for WebSeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebSeed").field("url", &self.url).finish_non_exhaustive()
+    }
+}
+
+mod utils {
+    /// Appends percent-encoded path `parts` to `base` URL.
+    pub fn join<'a>(base: &str, parts: impl IntoIterator<Item = &'a str>) -> String {
+        let mut url = base.to_owned();
+
+        for part in parts {
+            url.push('/');
+
+            for &byte in part.as_bytes() {
+                if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+                    url.push(char::from(byte));
+                } else {
+                    url.push_str(&format!("%{:02X}", byte));
+                }
+            }
+        }
+
+        url
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bencoded::FileInfo;
+    use crate::test_utils;
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    fn info() -> Info {
+        let file = |length, path: &[&str]| FileInfo {
+            length,
+            md5sum: None,
+            path: path.iter().map(|part| part.to_string()).collect(),
+            attr: None,
+        };
+
+        let files = vec![file(5, &["a b.txt"]), file(7, &["sub", "c"])];
+
+        Info {
+            name: "dir".to_owned(),
+            ..test_utils::info(8, Files::Multiple { files })
+        }
+    }
+
+    #[test]
+    fn file_urls_follow_torrent_layout() {
+        let seed = WebSeed::new("http://example.com/seed/", &info());
+
+        assert_eq!(seed.file_url(0), Some("http://example.com/seed/dir/a%20b.txt"));
+        assert_eq!(seed.file_url(1), Some("http://example.com/seed/dir/sub/c"));
+    }
+
+    #[test]
+    fn block_spanning_files_is_fetched() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let server = thread::spawn(move || {
+            let mut requests = vec![];
+
+            for body in [&b"de"[..], &b"fgh"[..]] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = vec![];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let mut byte = [0];
+                    stream.read_exact(&mut byte).unwrap();
+                    request.push(byte[0]);
+                }
+
+                write!(stream, "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\n\r\n", body.len()).unwrap();
+                stream.write_all(body).unwrap();
+                requests.push(String::from_utf8(request).unwrap());
+            }
+
+            requests
+        });
+
+        let piece = WebSeed::new(url, &info())
+            .fetch(&Request {
+                piece_index: 0,
+                offset: 3,
+                data_length: 5,
+            })
+            .unwrap();
+        let requests = server.join().unwrap();
+
+        assert_eq!(piece.data, &b"defgh"[..]);
+        assert!(requests[0].starts_with("GET /dir/a%20b.txt HTTP/1.1\r\n"));
+        assert!(requests[0].contains("Range: bytes=3-4\r\n"));
+        assert!(requests[1].contains("Range: bytes=0-2\r\n"));
+    }
+}
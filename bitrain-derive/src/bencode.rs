@@ -0,0 +1,91 @@
+mod decode;
+mod encode;
+
+pub use decode::bdecode;
+pub use encode::bencode;
+
+static MOD_PATH: &str = "::bitrain_core::bencoded";
+
+static TO_ENTRY_TRAIT_NAME: &str = "ToEntry";
+static FROM_ENTRY_TRAIT_NAME: &str = "FromEntry";
+static PARSE_ERROR_NAME: &str = "CustomParseError";
+static ENTRY_PATH: &str = "encoding::Entry";
+static DICTIONARY_PATH: &str = "encoding::BDictionary";
+
+#[derive(Debug, darling::FromDeriveInput)]
+#[darling(attributes(bencode), supports(struct_named))]
+struct Params {
+    ident: syn::Ident,
+    generics: syn::Generics,
+    data: darling::ast::Data<darling::util::Ignored, Field>,
+    mod_path: Option<syn::Path>,
+}
+
+impl Params {
+    fn item_path(&self, item: &str) -> syn::Path {
+        let mut path = self
+            .mod_path
+            .to_owned()
+            .unwrap_or_else(|| syn::parse_str(MOD_PATH).unwrap());
+
+        path.segments
+            .extend(syn::parse_str::<syn::Path>(item).unwrap().segments);
+
+        path
+    }
+
+    fn fields(&self) -> Vec<&Field> {
+        self.data.as_ref().take_struct().unwrap().fields
+    }
+
+    /// Field, which collects unknown keys, if any.
+    fn extra_field(&self) -> darling::Result<Option<&Field>> {
+        let mut extra = self.fields().into_iter().filter(|field| field.extra.is_present());
+        let first = extra.next();
+
+        match extra.next() {
+            Some(field) => Err(
+                darling::Error::custom("Only one field can collect unknown keys.").with_span(&field.ident)
+            ),
+            None => Ok(first),
+        }
+    }
+
+    /// Fields, stored under their own keys.
+    fn keyed_fields(&self) -> Vec<&Field> {
+        self.fields()
+            .into_iter()
+            .filter(|field| !field.extra.is_present())
+            .collect()
+    }
+
+    /// Generics of `impl` block with all type parameters bound by trait at `trait_path`.
+    fn bound_generics(&self, trait_path: &syn::Path) -> syn::Generics {
+        use crate::ast::bounds::Bind;
+
+        let bound: syn::TraitBound = syn::parse_quote!(#trait_path);
+        let mut generics = self.generics.clone();
+        generics.params.bind_all(Some(bound));
+
+        generics
+    }
+}
+
+#[derive(Debug, darling::FromField)]
+#[darling(attributes(bencode))]
+struct Field {
+    ident: Option<syn::Ident>,
+    /// Dictionary key of field, if it differs from field name.
+    rename: Option<String>,
+    /// Field is `BDictionary`, holding all keys, which don't belong to other fields.
+    extra: darling::util::Flag,
+}
+
+impl Field {
+    fn key(&self) -> String {
+        match &self.rename {
+            Some(rename) => rename.to_owned(),
+            None => self.ident.as_ref().unwrap().to_string(),
+        }
+    }
+}
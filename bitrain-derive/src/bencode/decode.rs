@@ -0,0 +1,65 @@
+use darling::{FromDeriveInput, Result};
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::parse_quote;
+
+pub fn bdecode(input: syn::DeriveInput) -> Result<TokenStream> {
+    FromEntryImpl::from_input(input).map(ToTokens::into_token_stream)
+}
+
+struct FromEntryImpl {
+    impl_block: syn::ItemImpl,
+}
+
+impl FromEntryImpl {
+    fn from_input(input: syn::DeriveInput) -> Result<Self> {
+        let params = <super::Params as FromDeriveInput>::from_derive_input(&input)?;
+
+        let trait_path = params.item_path(super::FROM_ENTRY_TRAIT_NAME);
+        let entry_path = params.item_path(super::ENTRY_PATH);
+        let error_path = params.item_path(super::PARSE_ERROR_NAME);
+
+        let extra_init = params.extra_field()?.map(|field| {
+            let ident = &field.ident;
+            quote::quote!(#ident: dictionary,)
+        });
+
+        let field_inits = params.keyed_fields().into_iter().map(|field| {
+            let ident = &field.ident;
+            let key = field.key();
+
+            quote::quote!(#ident: #trait_path::take_from(&mut dictionary, #key)?,)
+        });
+
+        let ident = &params.ident;
+        let generics = params.bound_generics(&trait_path);
+        let (impl_gens, ty_gens, where_clause) = generics.split_for_impl();
+
+        //Fields are initialized in order of declaration, so leftover keys are moved into extra field last
+        let impl_block = parse_quote! {
+            #[automatically_derived]
+            impl #impl_gens #trait_path for #ident #ty_gens #where_clause {
+                fn from_entry(entry: #entry_path) -> ::std::result::Result<Self, #error_path> {
+                    #[allow(unused_mut)]
+                    let mut dictionary = match entry {
+                        #entry_path::Dictionary(dictionary) => dictionary,
+                        _ => return Err(#error_path::InvalidValue),
+                    };
+
+                    Ok(Self {
+                        #(#field_inits)*
+                        #extra_init
+                    })
+                }
+            }
+        };
+
+        Ok(Self { impl_block })
+    }
+}
+
+impl ToTokens for FromEntryImpl {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.impl_block.to_tokens(tokens)
+    }
+}
@@ -0,0 +1,61 @@
+use darling::{FromDeriveInput, Result};
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::parse_quote;
+
+pub fn bencode(input: syn::DeriveInput) -> Result<TokenStream> {
+    ToEntryImpl::from_input(input).map(ToTokens::into_token_stream)
+}
+
+struct ToEntryImpl {
+    impl_block: syn::ItemImpl,
+}
+
+impl ToEntryImpl {
+    fn from_input(input: syn::DeriveInput) -> Result<Self> {
+        let params = <super::Params as FromDeriveInput>::from_derive_input(&input)?;
+
+        let trait_path = params.item_path(super::TO_ENTRY_TRAIT_NAME);
+        let entry_path = params.item_path(super::ENTRY_PATH);
+        let dictionary_path = params.item_path(super::DICTIONARY_PATH);
+
+        let dictionary_init: syn::Expr = match params.extra_field()? {
+            Some(field) => {
+                let ident = &field.ident;
+                parse_quote!(::std::clone::Clone::clone(&self.#ident))
+            }
+            None => parse_quote!(#dictionary_path::new()),
+        };
+
+        let inserts = params.keyed_fields().into_iter().map(|field| {
+            let ident = &field.ident;
+            let key = field.key();
+
+            quote::quote!(#trait_path::insert_into(&self.#ident, &mut dictionary, #key);)
+        });
+
+        let ident = &params.ident;
+        let generics = params.bound_generics(&trait_path);
+        let (impl_gens, ty_gens, where_clause) = generics.split_for_impl();
+
+        let impl_block = parse_quote! {
+            #[automatically_derived]
+            impl #impl_gens #trait_path for #ident #ty_gens #where_clause {
+                fn to_entry(&self) -> #entry_path {
+                    let mut dictionary: #dictionary_path = #dictionary_init;
+                    #(#inserts)*
+
+                    #entry_path::Dictionary(dictionary)
+                }
+            }
+        };
+
+        Ok(Self { impl_block })
+    }
+}
+
+impl ToTokens for ToEntryImpl {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        self.impl_block.to_tokens(tokens)
+    }
+}
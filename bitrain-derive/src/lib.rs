@@ -1,7 +1,9 @@
-mod utils;
+#[cfg(any(feature = "message", feature = "bencode"))]
 mod ast;
 #[cfg(feature = "message")]
 mod messages;
+#[cfg(feature = "bencode")]
+mod bencode;
 
 use proc_macro::TokenStream;
 use syn::{parse_macro_input, DeriveInput};
@@ -37,6 +39,18 @@ pub fn send(input: TokenStream) -> TokenStream {
     expand_derive(input, messages::send)
 }
 
+#[cfg(feature = "bencode")]
+#[proc_macro_derive(Bencode, attributes(bencode))]
+pub fn bencode(input: TokenStream) -> TokenStream {
+    expand_derive(input, bencode::bencode)
+}
+
+#[cfg(feature = "bencode")]
+#[proc_macro_derive(Bdecode, attributes(bencode))]
+pub fn bdecode(input: TokenStream) -> TokenStream {
+    expand_derive(input, bencode::bdecode)
+}
+
 fn expand_derive<F: FnOnce(DeriveInput) -> darling::Result<proc_macro2::TokenStream>>(input: TokenStream, implementor: F) -> TokenStream {
     implementor(parse_macro_input!(input))
         .unwrap_or_else(darling::Error::write_errors)
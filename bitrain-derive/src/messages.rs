@@ -14,16 +14,320 @@ static MOD_PATH: &str = "::bitrain_core::messages";
 
 static ENCODE_TRAIT_NAME: &str = "Encode";
 static DECODE_TRAIT_NAME: &str = "Decode";
+static DECODE_BORROWED_TRAIT_NAME: &str = "DecodeBorrowed";
 static STANDALONE_TRAIT_NAME: &str = "Standalone";
 static RECV_TRAIT_NAME: &str = "Recv";
 static SEND_TRAIT_NAME: &str = "Send";
+static FIXED_SIZE_TRAIT_NAME: &str = "FixedSize";
+static LITTLE_ENDIAN_TRAIT_NAME: &str = "LittleEndianCodec";
 
 static CONTAINER_STRUCT_NAME: &str = "Container";
 
+static UTILS_MOD_NAME: &str = "utils";
+static ERROR_TYPE_NAME: &str = "Error";
+static RESULT_TYPE_NAME: &str = "Result";
+static LIMITS_TYPE_NAME: &str = "Limits";
+
 #[derive(Debug, darling::FromField)]
+#[darling(attributes(message))]
 struct Field {
     ident: Option<syn::Ident>,
-    ty: syn::Type
+    ty: syn::Type,
+    /// Field is not transmitted and is decoded as `Default::default()`.
+    skip: darling::util::Flag,
+    /// Field is not transmitted and is decoded as given expression (or `Default::default()`).
+    default: Option<darling::util::Override<syn::Expr>>,
+    /// Module with `size`, `encode_to` and `decode_from` functions, used instead of field type implementations.
+    with: Option<syn::Path>,
+    /// Byte order of field, defaults to byte order of container (which is network byte order by default).
+    endian: Option<Endian>,
+    /// Integer type of item count, which prefixes `Vec<T>` field, followed by items, encoded one by one.
+    count_prefix: Option<syn::Type>,
+}
+
+impl Field {
+    fn is_skipped(&self) -> bool {
+        self.skip.is_present() || self.default.is_some()
+    }
+
+    fn is_little_endian(&self) -> bool {
+        self.endian == Some(Endian::Little)
+    }
+
+    /// Statement, encoding `value` (reference to field) into `writer`.
+    fn encode_to_call(&self, value: &syn::Expr, trait_path: &syn::Path) -> syn::Stmt {
+        if let Some(count) = &self.count_prefix {
+            let utils_path = sibling_path(trait_path, UTILS_MOD_NAME);
+            return syn::parse_quote!(#utils_path::encode_counted::<#count, _>(#value, writer)?;);
+        }
+
+        match &self.with {
+            Some(with) => syn::parse_quote!(#with::encode_to(#value, writer)?;),
+            None if self.is_little_endian() => {
+                let le_path = little_endian_path(trait_path);
+                syn::parse_quote!(#le_path::encode_le_to(#value, writer)?;)
+            }
+            None => syn::parse_quote!(#trait_path::encode_to((#value).deref(), writer)?;),
+        }
+    }
+
+    /// Expression, returning encoded size of `value` (reference to field).
+    fn size_call(&self, value: &syn::Expr, trait_path: &syn::Path) -> syn::Expr {
+        if let Some(count) = &self.count_prefix {
+            let utils_path = sibling_path(trait_path, UTILS_MOD_NAME);
+            return syn::parse_quote!(#utils_path::counted_size::<#count, _>(#value));
+        }
+
+        match &self.with {
+            Some(with) => syn::parse_quote!(#with::size(#value)),
+            None => syn::parse_quote!(#trait_path::size((#value).deref())),
+        }
+    }
+
+    /// Expression, decoding field from `reader` with `method` of trait at `trait_path`.
+    fn decode_from_call(&self, trait_path: &syn::Path, method: &syn::Ident) -> syn::Expr {
+        let ty = &self.ty;
+
+        if let Some(count) = &self.count_prefix {
+            let utils_path = sibling_path(trait_path, UTILS_MOD_NAME);
+            let decode_counted = if method == "decode_borrowed" {
+                quote::format_ident!("decode_counted_borrowed")
+            } else {
+                quote::format_ident!("decode_counted")
+            };
+
+            return syn::parse_quote!(#utils_path::#decode_counted::<#count, _>(len_hint, reader));
+        }
+
+        match &self.with {
+            Some(with) => syn::parse_quote!(#with::decode_from(len_hint, reader)),
+            None if self.is_little_endian() => {
+                let le_path = little_endian_path(trait_path);
+                syn::parse_quote!(<#ty as #le_path>::decode_le_from(len_hint, reader))
+            }
+            None => syn::parse_quote!(<#ty as #trait_path>::#method(len_hint, reader)),
+        }
+    }
+
+    /// Field is declared as `Option<_>` and is decoded only if message has bytes left.
+    fn is_optional(&self) -> bool {
+        match &self.ty {
+            syn::Type::Path(ty) => ty
+                .path
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "Option"),
+            _ => false,
+        }
+    }
+
+    /// Value of skipped field after decoding.
+    fn default_value(&self) -> syn::Expr {
+        match &self.default {
+            Some(darling::util::Override::Explicit(expr)) => expr.to_owned(),
+            _ => syn::parse_quote!(::std::default::Default::default()),
+        }
+    }
+}
+
+/// Byte order of integer fields, set with `#[message(endian = "...")]` on field or whole container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, darling::FromMeta)]
+enum Endian {
+    #[darling(rename = "big")]
+    Big,
+    #[darling(rename = "little")]
+    Little,
+}
+
+/// Applies byte order of container to all fields, which don't specify their own.
+fn propagate_endian(data: &mut darling::ast::Data<Variant, Field>, endian: Option<Endian>) {
+    let fields: Vec<&mut Field> = match data {
+        darling::ast::Data::Enum(variants) => variants
+            .iter_mut()
+            .flat_map(|variant| variant.fields.fields.iter_mut())
+            .collect(),
+        darling::ast::Data::Struct(fields) => fields.fields.iter_mut().collect(),
+    };
+
+    fields
+        .into_iter()
+        .filter(|field| field.endian.is_none())
+        .for_each(|field| field.endian = endian);
+}
+
+/// Path to `LittleEndianCodec`, located in the same module as `trait_path`.
+fn little_endian_path(trait_path: &syn::Path) -> syn::Path {
+    sibling_path(trait_path, LITTLE_ENDIAN_TRAIT_NAME)
+}
+
+/// Path to item `name`, located in the same module as `trait_path`.
+fn sibling_path(trait_path: &syn::Path, name: &str) -> syn::Path {
+    let mut path = trait_path.to_owned();
+
+    if let Some(last) = path.segments.last_mut() {
+        last.ident = syn::Ident::new(name, last.ident.span());
+        last.arguments = syn::PathArguments::None;
+    }
+
+    path
+}
+
+/// Checks, that `count_prefix` is used only with fields of network byte order without custom codecs.
+fn check_count_prefixes(data: &darling::ast::Data<Variant, Field>) -> darling::Result<()> {
+    let fields: Vec<&Field> = match data {
+        darling::ast::Data::Enum(variants) => variants.iter().flat_map(|variant| variant.fields.iter()).collect(),
+        darling::ast::Data::Struct(fields) => fields.iter().collect(),
+    };
+
+    let mut errors = darling::Error::accumulator();
+
+    for field in fields.into_iter().filter(|field| field.count_prefix.is_some()) {
+        if field.with.is_some() {
+            errors.push(
+                darling::Error::custom("`count_prefix` can't be combined with custom codecs.").with_span(&field.ty)
+            );
+        } else if field.is_little_endian() {
+            errors.push(
+                darling::Error::custom("`count_prefix` supports only big endian fields.").with_span(&field.ty)
+            );
+        }
+    }
+
+    errors.finish()
+}
+
+/// Checks, that no transmitted field follows optional one, as absent values aren't transmitted at all.
+fn check_trailing_options<'a>(fields: impl IntoIterator<Item = &'a Field>) -> darling::Result<()> {
+    let mut optional_seen = false;
+
+    for field in fields.into_iter().filter(|field| !field.is_skipped()) {
+        if field.is_optional() {
+            optional_seen = true;
+        } else if optional_seen {
+            return Err(
+                darling::Error::custom("Only trailing fields can be optional.").with_span(&field.ty)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Variant of enum, encoded as tag byte followed by its fields.
+///
+/// Tag is taken from `#[message(tag = ...)]` or explicit discriminant, otherwise it follows the tag of previous
+/// variant, same as implicit discriminants do. See [`variant_tags`].
+#[derive(Debug, darling::FromVariant)]
+#[darling(attributes(message))]
+struct Variant {
+    ident: syn::Ident,
+    discriminant: Option<syn::Expr>,
+    fields: darling::ast::Fields<Field>,
+    tag: Option<u8>,
+}
+
+impl Variant {
+    /// Tag, set explicitly for this variant, if any.
+    fn explicit_tag(&self) -> darling::Result<Option<u8>> {
+        if self.tag.is_some() {
+            return Ok(self.tag);
+        }
+
+        match &self.discriminant {
+            Some(syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(lit), .. })) => lit
+                .base10_parse::<u8>()
+                .map(Some)
+                .map_err(|_| darling::Error::custom("Tag doesn't fit into `u8`.").with_span(lit)),
+            Some(discriminant) => Err(darling::Error::custom(
+                "Only integer literal discriminants can be used as tags, use `#[message(tag = ...)]`.",
+            )
+            .with_span(discriminant)),
+            None => Ok(None),
+        }
+    }
+
+    /// Names, to which fields are bound in [`Variant::pattern`] (except skipped ones) and [`Variant::constructor`].
+    fn bindings(&self) -> Vec<syn::Ident> {
+        self.fields
+            .iter()
+            .enumerate()
+            .map(|(pos, field)| match &field.ident {
+                Some(ident) => ident.to_owned(),
+                None => quote::format_ident!("__field_{}", pos),
+            })
+            .collect()
+    }
+
+    /// Pattern, binding all transmitted fields of variant.
+    fn pattern(&self) -> proc_macro2::TokenStream {
+        let bindings = self
+            .fields
+            .iter()
+            .zip(self.bindings())
+            .map(|(field, binding)| match (field.is_skipped(), &field.ident) {
+                (false, _) => quote::quote!(#binding),
+                (true, Some(ident)) => quote::quote!(#ident: _),
+                (true, None) => quote::quote!(_),
+            });
+
+        self.with_fields(bindings)
+    }
+
+    /// Expression, constructing variant from values, bound to [`Variant::bindings`].
+    fn constructor(&self) -> proc_macro2::TokenStream {
+        self.with_fields(self.bindings())
+    }
+
+    fn with_fields<T: quote::ToTokens>(&self, fields: impl IntoIterator<Item = T>) -> proc_macro2::TokenStream {
+        let ident = &self.ident;
+        let fields = fields.into_iter();
+
+        match self.fields.style {
+            darling::ast::Style::Struct => quote::quote!(Self::#ident { #(#fields),* }),
+            darling::ast::Style::Tuple => quote::quote!(Self::#ident(#(#fields),*)),
+            darling::ast::Style::Unit => quote::quote!(Self::#ident),
+        }
+    }
+
+    /// Transmitted fields with their bindings.
+    fn transmitted_fields(&self) -> Vec<(&Field, syn::Expr)> {
+        self.fields
+            .iter()
+            .zip(self.bindings())
+            .filter(|(field, _)| !field.is_skipped())
+            .map(|(field, binding)| (field, syn::parse_quote!(#binding)))
+            .collect()
+    }
+}
+
+/// Computes tags of all `variants`, checking that they fit into `u8` and are unique.
+fn variant_tags(variants: &[&Variant]) -> darling::Result<Vec<u8>> {
+    let mut errors = darling::Error::accumulator();
+    let mut tags: Vec<u8> = Vec::with_capacity(variants.len());
+
+    for variant in variants {
+        let Some(explicit_tag) = errors.handle(variant.explicit_tag()) else { continue };
+        let tag = match explicit_tag {
+            Some(tag) => tag,
+            None => match tags.last() {
+                Some(prev) => match prev.checked_add(1) {
+                    Some(tag) => tag,
+                    None => {
+                        errors.push(darling::Error::custom("Tag doesn't fit into `u8`.").with_span(&variant.ident));
+                        continue;
+                    }
+                },
+                None => 0,
+            },
+        };
+
+        if tags.contains(&tag) {
+            errors.push(darling::Error::custom(format!("Duplicate tag {}.", tag)).with_span(&variant.ident));
+        }
+        tags.push(tag);
+    }
+
+    errors.finish_with(tags)
 }
 
 fn full_item_path(custom_mod_path: &Option<syn::Path>, mod_path: &str, trait_name: &str) -> syn::Path {
@@ -1,6 +1,6 @@
-use darling::{ast::Data, util::Ignored, Error, FromDeriveInput, Result, ToTokens};
+use darling::{ast::Data, Error, FromDeriveInput, Result, ToTokens};
 use proc_macro2::TokenStream;
-use quote::format_ident;
+use quote::{format_ident, quote};
 use syn::{parse_quote, DeriveInput};
 
 pub fn decode(input: DeriveInput) -> Result<TokenStream> {
@@ -10,19 +10,90 @@ pub fn decode(input: DeriveInput) -> Result<TokenStream> {
 #[derive(darling::FromDeriveInput)]
 #[darling(
     attributes(message),
-    supports(struct_named, struct_unit, struct_tuple, struct_newtype)
+    supports(struct_named, struct_unit, struct_tuple, struct_newtype, enum_any),
+    and_then = "DecodeParams::propagate_endian"
 )]
 struct DecodeParams {
     mod_path: Option<syn::Path>,
     ident: syn::Ident,
     generics: syn::Generics,
-    data: Data<Ignored, super::Field>,
+    data: Data<super::Variant, super::Field>,
+    ///Conditions on decoded `self`, value is rejected as `InvalidData` if any of them doesn't hold.
+    #[darling(multiple)]
+    check: Vec<syn::Expr>,
+    ///Handled by `Encode` derive.
+    #[darling(rename = "fixed_size")]
+    _fixed_size: darling::util::Flag,
+    /// Default byte order of fields.
+    endian: Option<super::Endian>,
 }
 
 impl DecodeParams {
-    fn full_trait_path(&self) -> syn::Path {
+    fn propagate_endian(mut self) -> Result<Self> {
+        super::propagate_endian(&mut self.data, self.endian);
+        Ok(self)
+    }
+
+    ///Lifetime of borrowed fields. If present, `DecodeBorrowed` is implemented instead of `Decode`.
+    fn borrowed_lifetime(&self) -> Result<Option<&syn::Lifetime>> {
+        let mut lifetimes = self.generics.lifetimes().map(|param| &param.lifetime);
+        let first = lifetimes.next();
+
+        match lifetimes.next() {
+            Some(lifetime) => Err(
+                Error::custom("Only one lifetime can be borrowed from decoded bytes.").with_span(lifetime)
+            ),
+            None => Ok(first),
+        }
+    }
+
+    fn decode_trait_path(&self) -> syn::Path {
         super::full_item_path(&self.mod_path, super::MOD_PATH, super::DECODE_TRAIT_NAME)
     }
+
+    ///Path to implemented trait, either `Decode` or `DecodeBorrowed<'a>`.
+    fn full_trait_path(&self) -> syn::Path {
+        match self.borrowed_lifetime() {
+            Ok(Some(lifetime)) => {
+                let path = super::full_item_path(
+                    &self.mod_path,
+                    super::MOD_PATH,
+                    super::DECODE_BORROWED_TRAIT_NAME,
+                );
+
+                parse_quote!(#path<#lifetime>)
+            }
+            _ => self.decode_trait_path(),
+        }
+    }
+
+    fn decode_method(&self) -> syn::Ident {
+        match self.borrowed_lifetime() {
+            Ok(Some(_)) => format_ident!("decode_borrowed"),
+            _ => format_ident!("decode_from"),
+        }
+    }
+
+    fn reader_type(&self) -> syn::Type {
+        match self.borrowed_lifetime() {
+            Ok(Some(lifetime)) => parse_quote!(&#lifetime [u8]),
+            _ => parse_quote!(impl ::std::io::Read),
+        }
+    }
+
+    ///Expression, returning `decoded` value (of type `Self`) as result of `decode_from`.
+    fn checked_result(&self, decoded: impl ToTokens) -> syn::Expr {
+        if self.check.is_empty() {
+            parse_quote!(Ok(Some(#decoded)))
+        } else {
+            parse_quote!({
+                let decoded = #decoded;
+                decoded.__check_decoded()?;
+
+                Ok(Some(decoded))
+            })
+        }
+    }
 }
 
 struct DecodeFromCall {
@@ -32,16 +103,27 @@ struct DecodeFromCall {
 impl DecodeFromCall {
     fn from_struct_field(
         (pos, field): (usize, &super::Field),
-        trait_path: &syn::Path,
+        params: &DecodeParams,
     ) -> Result<Self> {
-        let var_name = struct_field_name((pos, field));
+        Self::new(&struct_field_name((pos, field)), field, params)
+    }
+
+    fn new(var_name: &syn::Ident, field: &super::Field, params: &DecodeParams) -> Result<Self> {
         let field_type = &field.ty;
 
+        if field.is_skipped() {
+            let default_value = field.default_value();
+
+            return Ok(Self {
+                call: parse_quote!(let #var_name: #field_type = #default_value;),
+            });
+        }
+
+        let decode_from_call =
+            field.decode_from_call(&params.full_trait_path(), &params.decode_method());
+
         let call: syn::Stmt = parse_quote! {
-            let #var_name = if let Some(val) = <#field_type as #trait_path>::decode_from(
-                len_hint,
-                reader
-            )? {
+            let #var_name: #field_type = if let Some(val) = #decode_from_call? {
                 val
             } else {
                 return Ok(None)
@@ -72,28 +154,20 @@ impl SelfInit {
             .map(|(pos, field)| struct_field_name((pos, *field)));
 
         let init: syn::Expr = if fields.is_tuple() {
-            parse_quote!(
-                Ok(
-                    Some(
-                        Self(#(#underscored,)*)
-                    )
-                )
-            )
+            parse_quote!(Self(#(#underscored,)*))
         } else {
             let field_names = fields.iter().map(|field| field.ident.as_ref().unwrap());
 
             parse_quote!(
-                Ok(
-                    Some(
-                        Self {
-                            #(#field_names: #underscored,)*
-                        }
-                    )
-                )
+                Self {
+                    #(#field_names: #underscored,)*
+                }
             )
         };
 
-        Ok(Self { init })
+        Ok(Self {
+            init: params.checked_result(init),
+        })
     }
 }
 
@@ -108,27 +182,94 @@ struct DecodeFromDef {
 }
 
 impl DecodeFromDef {
-    fn from_struct_fields<'a>(params: &DecodeParams) -> Result<Self> {
+    fn from_params(params: &DecodeParams) -> Result<Self> {
+        if params.data.is_enum() {
+            Self::from_variants(params)
+        } else {
+            Self::from_struct_fields(params)
+        }
+    }
+
+    fn from_variants(params: &DecodeParams) -> Result<Self> {
+        let decode_path = params.decode_trait_path();
+        let variants = params.data.as_ref().take_enum().unwrap();
+        let tags = super::variant_tags(&variants)?;
+        let mut errors = Error::accumulator();
+
+        let match_arms = variants
+            .into_iter()
+            .zip(tags)
+            .map(|(variant, tag)| {
+                super::check_trailing_options(variant.fields.iter())?;
+
+                let result = params.checked_result(variant.constructor());
+
+                let inner_calls = variant
+                    .fields
+                    .iter()
+                    .zip(variant.bindings())
+                    .map(|(field, binding)| DecodeFromCall::new(&binding, field, params))
+                    .collect::<Result<Vec<_>>>()?;
+
+                Ok(quote! {
+                    #tag => {
+                        #(#inner_calls)*
+
+                        #result
+                    }
+                })
+            })
+            .filter_map(|result| errors.handle(result))
+            .collect::<Vec<_>>();
+
+        errors.finish()?;
+
+        let method = params.decode_method();
+        let reader_type = params.reader_type();
+
+        let fn_def: syn::ItemFn = parse_quote! {
+            fn #method(
+                len_hint: &mut usize,
+                reader: &mut #reader_type
+            ) -> ::std::io::Result<::std::option::Option<Self>> {
+                let tag = if let Some(val) = <u8 as #decode_path>::decode_from(len_hint, reader)? {
+                    val
+                } else {
+                    return Ok(None)
+                };
+
+                match tag {
+                    #(#match_arms)*
+                    _ => Ok(None)
+                }
+            }
+        };
+
+        Ok(Self { fn_def })
+    }
+
+    fn from_struct_fields(params: &DecodeParams) -> Result<Self> {
         let fields = params.data.as_ref().take_struct().unwrap();
+        super::check_trailing_options(fields.iter().copied())?;
         let mut errors = Error::accumulator();
 
         let inner_calls = fields
             .iter()
             .enumerate()
-            .map(|(pos, field)| {
-                DecodeFromCall::from_struct_field((pos, *field), &params.full_trait_path())
-            })
+            .map(|(pos, field)| DecodeFromCall::from_struct_field((pos, *field), params))
             .filter_map(|result| errors.handle(result))
             .collect::<Vec<_>>();
 
         errors.finish()?;
 
         let self_init = SelfInit::from_struct_fields(params)?;
+        let method = params.decode_method();
+        let reader_type = params.reader_type();
 
         let fn_def: syn::ItemFn = parse_quote! {
-            fn decode_from(
+            fn #method(
                 len_hint: &mut usize,
-                reader: &mut impl ::std::io::Read
+                reader: &mut #reader_type
             ) -> ::std::io::Result<::std::option::Option<Self>> {
                 #(#inner_calls)*
 
@@ -146,17 +287,61 @@ impl ToTokens for DecodeFromDef {
     }
 }
 
+/// Inherent method, validating decoded value against `#[message(check = ...)]` conditions.
+struct CheckDef {
+    impl_block: syn::ItemImpl,
+}
+
+impl CheckDef {
+    fn from_params(params: &DecodeParams) -> Option<Self> {
+        if params.check.is_empty() {
+            return None;
+        }
+
+        let ident = &params.ident;
+        let (impl_gens, ty_gens, where_clause) = params.generics.split_for_impl();
+
+        let checks = params.check.iter().map(|check| {
+            let msg = format!("{} check failed: {}", ident, check.to_token_stream());
+
+            quote! {
+                if !(#check) {
+                    return Err(::std::io::Error::new(::std::io::ErrorKind::InvalidData, #msg));
+                }
+            }
+        });
+
+        let impl_block = parse_quote! {
+            #[automatically_derived]
+            impl #impl_gens #ident #ty_gens #where_clause {
+                #[doc(hidden)]
+                fn __check_decoded(&self) -> ::std::io::Result<()> {
+                    #(#checks)*
+
+                    Ok(())
+                }
+            }
+        };
+
+        Some(Self { impl_block })
+    }
+}
+
 struct DecodeImpl {
     impl_block: syn::ItemImpl,
+    check_def: Option<CheckDef>,
 }
 
 impl DecodeImpl {
     fn for_struct(input: DeriveInput) -> Result<Self> {
         let mut params: DecodeParams = FromDeriveInput::from_derive_input(&input)?;
+        params.borrowed_lifetime()?;
+        super::check_count_prefixes(&params.data)?;
 
-        let decode_from_def = DecodeFromDef::from_struct_fields(&params)?;
+        let decode_from_def = DecodeFromDef::from_params(&params)?;
         let trait_path = params.full_trait_path();
         Self::adjust_generics(&mut params);
+        let check_def = CheckDef::from_params(&params);
 
         let DecodeParams {
             ident, generics, ..
@@ -171,7 +356,10 @@ impl DecodeImpl {
             }
         };
 
-        Ok(Self { impl_block })
+        Ok(Self {
+            impl_block,
+            check_def,
+        })
     }
 
     fn adjust_generics(meta: &mut DecodeParams) {
@@ -185,7 +373,11 @@ impl DecodeImpl {
 
 impl ToTokens for DecodeImpl {
     fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
-        self.impl_block.to_tokens(tokens)
+        self.impl_block.to_tokens(tokens);
+
+        if let Some(check_def) = &self.check_def {
+            check_def.impl_block.to_tokens(tokens)
+        }
     }
 }
 
@@ -1,9 +1,8 @@
 use darling::ast::{Data, Fields};
-use darling::util::Ignored;
 use darling::{Error, FromDeriveInput, Result};
 
 use proc_macro2::TokenStream;
-use quote::ToTokens;
+use quote::{quote, ToTokens};
 use syn::parse_quote;
 
 pub fn encode(container: syn::DeriveInput) -> Result<TokenStream> {
@@ -13,20 +12,37 @@ pub fn encode(container: syn::DeriveInput) -> Result<TokenStream> {
 #[derive(darling::FromDeriveInput)]
 #[darling(
     attributes(message),
-    supports(struct_named, struct_unit, struct_tuple, struct_newtype)
+    supports(struct_named, struct_unit, struct_tuple, struct_newtype, enum_any),
+    and_then = "EncodeParams::propagate_endian"
 )]
 struct EncodeParams {
     ident: syn::Ident,
     generics: syn::Generics,
-    data: Data<Ignored, super::Field>,
+    data: Data<super::Variant, super::Field>,
     mod_path: Option<syn::Path>,
+    /// Additionally implement `FixedSize`, summing sizes of all transmitted fields.
+    fixed_size: darling::util::Flag,
+    ///Handled by `Decode` derive.
+    #[darling(multiple, rename = "check")]
+    _check: Vec<syn::Expr>,
+    /// Default byte order of fields.
+    endian: Option<super::Endian>,
 }
 
 impl EncodeParams {
+    fn propagate_endian(mut self) -> Result<Self> {
+        super::propagate_endian(&mut self.data, self.endian);
+        Ok(self)
+    }
+
     fn full_trait_path(&self) -> syn::Path {
         super::full_item_path(&self.mod_path, super::MOD_PATH, super::ENCODE_TRAIT_NAME)
     }
 
+    fn fixed_size_trait_path(&self) -> syn::Path {
+        super::full_item_path(&self.mod_path, super::MOD_PATH, super::FIXED_SIZE_TRAIT_NAME)
+    }
+
     fn fields(&self) -> Option<Fields<&super::Field>> {
         self.data.as_ref().take_struct()
     }
@@ -38,17 +54,7 @@ struct EncodeToCall {
 
 impl EncodeToCall {
     fn from_field((pos, field): (usize, &super::Field), trait_path: &syn::Path) -> Result<Self> {
-        let call = if let Some(ident) = &field.ident {
-            parse_quote! {
-                #trait_path::encode_to((&self.#ident).deref(), writer)?;
-            }
-        } else {
-            let index = syn::Index::from(pos);
-
-            parse_quote! {
-                #trait_path::encode_to((&self.#index).deref(), writer)?;
-            }
-        };
+        let call = field.encode_to_call(&field_ref((pos, field)), trait_path);
 
         Ok(Self { call })
     }
@@ -65,7 +71,48 @@ struct EncodeToDef {
 }
 
 impl EncodeToDef {
-    fn from_fields<'a>(params: &EncodeParams) -> Result<Self> {
+    fn from_params(params: &EncodeParams) -> Result<Self> {
+        if params.data.is_enum() {
+            Self::from_variants(params)
+        } else {
+            Self::from_fields(params)
+        }
+    }
+
+    fn from_variants(params: &EncodeParams) -> Result<Self> {
+        let trait_path = params.full_trait_path();
+        let variants = params.data.as_ref().take_enum().unwrap();
+        let tags = super::variant_tags(&variants)?;
+
+        let match_arms = variants.into_iter().zip(tags).map(|(variant, tag)| {
+            let pattern = variant.pattern();
+            let calls = variant
+                .transmitted_fields()
+                .into_iter()
+                .map(|(field, binding)| field.encode_to_call(&binding, &trait_path));
+
+            quote! {
+                #pattern => {
+                    <u8 as #trait_path>::encode_to(&(#tag), writer)?;
+                    #(#calls)*
+                }
+            }
+        });
+
+        let fn_def = parse_quote! {
+            fn encode_to(&self, writer: &mut impl ::std::io::Write) -> ::std::io::Result<()> {
+                match self {
+                    #(#match_arms)*
+                }
+
+                Ok(())
+            }
+        };
+
+        Ok(Self { fn_def })
+    }
+
+    fn from_fields(params: &EncodeParams) -> Result<Self> {
         let mut errors = Error::accumulator();
 
         let fields = params.fields().unwrap();
@@ -74,6 +121,7 @@ impl EncodeToDef {
         let inner_calls = fields
             .into_iter()
             .enumerate()
+            .filter(|(_, field)| !field.is_skipped())
             .map(|arg| EncodeToCall::from_field(arg, &trait_path))
             .filter_map(|result| errors.handle(result))
             .collect::<Vec<_>>();
@@ -110,17 +158,7 @@ struct SizeCall {
 
 impl SizeCall {
     fn from_field((pos, field): (usize, &super::Field), trait_path: &syn::Path) -> Result<Self> {
-        let size_call = if let Some(ident) = &field.ident {
-            parse_quote!(
-                #trait_path::size((&self.#ident).deref())
-            )
-        } else {
-            let index = syn::Index::from(pos);
-
-            parse_quote!(
-                #trait_path::size((&self.#index).deref())
-            )
-        };
+        let size_call = field.size_call(&field_ref((pos, field)), trait_path);
 
         Ok(Self { size_call })
     }
@@ -137,7 +175,42 @@ struct SizeDef {
 }
 
 impl SizeDef {
-    fn from_params<'a>(params: &EncodeParams) -> Result<Self> {
+    fn from_params(params: &EncodeParams) -> Result<Self> {
+        if params.data.is_enum() {
+            Self::from_variants(params)
+        } else {
+            Self::from_fields(params)
+        }
+    }
+
+    fn from_variants(params: &EncodeParams) -> Result<Self> {
+        let trait_path = params.full_trait_path();
+        let variants = params.data.as_ref().take_enum().unwrap();
+
+        let match_arms = variants.into_iter().map(|variant| {
+            let pattern = variant.pattern();
+            let size_calls = variant
+                .transmitted_fields()
+                .into_iter()
+                .map(|(field, binding)| field.size_call(&binding, &trait_path));
+
+            quote! {
+                #pattern => ::std::mem::size_of::<u8>() #(+ #size_calls)*,
+            }
+        });
+
+        let fn_def = parse_quote! {
+            fn size(&self) -> usize {
+                match self {
+                    #(#match_arms)*
+                }
+            }
+        };
+
+        Ok(Self { fn_def })
+    }
+
+    fn from_fields(params: &EncodeParams) -> Result<Self> {
         let mut errors = Error::accumulator();
 
         let fields = params.data.as_ref().take_struct().unwrap();
@@ -146,6 +219,7 @@ impl SizeDef {
         let inner_calls = fields
             .into_iter()
             .enumerate()
+            .filter(|(_, field)| !field.is_skipped())
             .map(|arg| SizeCall::from_field(arg, &trait_path))
             .filter_map(|result| errors.handle(result))
             .collect::<Vec<_>>();
@@ -174,20 +248,75 @@ impl ToTokens for SizeDef {
     }
 }
 
+struct FixedSizeImpl {
+    impl_block: syn::Item,
+}
+
+impl FixedSizeImpl {
+    fn from_params(params: &EncodeParams) -> Result<Self> {
+        let fields = params
+            .fields()
+            .ok_or_else(|| Error::unsupported_shape("`fixed_size` is supported only for structs."))?
+            .into_iter()
+            .filter(|field| !field.is_skipped())
+            .collect::<Vec<_>>();
+
+        if let Some(field) = fields.iter().find(|field| field.with.is_some()) {
+            return Err(
+                Error::custom("`fixed_size` can't be combined with custom codecs.").with_span(&field.ty)
+            );
+        }
+
+        if let Some(field) = fields.iter().find(|field| field.count_prefix.is_some()) {
+            return Err(
+                Error::custom("`fixed_size` can't be combined with count-prefixed fields.").with_span(&field.ty)
+            );
+        }
+
+        let trait_path = params.fixed_size_trait_path();
+        let tys = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+
+        let mut generics = params.generics.clone();
+        let where_clause = generics.make_where_clause();
+        tys.iter()
+            .for_each(|ty| where_clause.predicates.push(parse_quote!(#ty: #trait_path)));
+
+        let ident = &params.ident;
+        let (impl_gens, ty_gens, where_clause) = generics.split_for_impl();
+
+        let impl_block = parse_quote! {
+            #[automatically_derived]
+            impl #impl_gens #trait_path for #ident #ty_gens #where_clause {
+                const SIZE: usize = #(<#tys as #trait_path>::SIZE +)* 0usize;
+            }
+        };
+
+        Ok(Self { impl_block })
+    }
+}
+
 struct EncodeImpl {
     impl_block: syn::Item,
+    fixed_size: Option<FixedSizeImpl>,
 }
 
 impl EncodeImpl {
     fn for_struct(input: syn::DeriveInput) -> Result<Self> {
         let mut params: EncodeParams = FromDeriveInput::from_derive_input(&input)?;
+        super::check_count_prefixes(&params.data)?;
 
-        let encode_to_def = EncodeToDef::from_fields(&params)?;
+        let encode_to_def = EncodeToDef::from_params(&params)?;
         let size_def = SizeDef::from_params(&params)?;
 
         Self::adjust_generics(&mut params);
         let trait_path = params.full_trait_path();
 
+        let fixed_size = if params.fixed_size.is_present() {
+            Some(FixedSizeImpl::from_params(&params)?)
+        } else {
+            None
+        };
+
         let EncodeParams {
             ident, generics, ..
         } = params;
@@ -202,10 +331,13 @@ impl EncodeImpl {
             }
         };
 
-        Ok(Self { impl_block })
+        Ok(Self {
+            impl_block,
+            fixed_size,
+        })
     }
 
-    fn adjust_generics(params: &mut EncodeParams) -> () {
+    fn adjust_generics(params: &mut EncodeParams) {
         use crate::ast::bounds::Bind;
 
         let bound: syn::TraitBound =
@@ -218,6 +350,21 @@ impl EncodeImpl {
 
 impl ToTokens for EncodeImpl {
     fn to_tokens(&self, tokens: &mut TokenStream) {
-        self.impl_block.to_tokens(tokens)
+        self.impl_block.to_tokens(tokens);
+
+        if let Some(fixed_size) = &self.fixed_size {
+            fixed_size.impl_block.to_tokens(tokens)
+        }
+    }
+}
+
+/// Reference to field of `self` at `pos`.
+fn field_ref((pos, field): (usize, &super::Field)) -> syn::Expr {
+    match &field.ident {
+        Some(ident) => parse_quote!(&self.#ident),
+        None => {
+            let index = syn::Index::from(pos);
+            parse_quote!(&self.#index)
+        }
     }
 }
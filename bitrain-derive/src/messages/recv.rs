@@ -35,6 +35,22 @@ impl RecvParams {
     fn recv_trait_path(&self) -> syn::Path {
         super::full_item_path(&self.mod_path, super::MOD_PATH, super::RECV_TRAIT_NAME)
     }
+
+    fn utils_mod_path(&self) -> syn::Path {
+        super::full_item_path(&self.mod_path, super::MOD_PATH, super::UTILS_MOD_NAME)
+    }
+
+    fn error_type_path(&self) -> syn::Path {
+        super::full_item_path(&self.mod_path, super::MOD_PATH, super::ERROR_TYPE_NAME)
+    }
+
+    fn result_type_path(&self) -> syn::Path {
+        super::full_item_path(&self.mod_path, super::MOD_PATH, super::RESULT_TYPE_NAME)
+    }
+
+    fn limits_type_path(&self) -> syn::Path {
+        super::full_item_path(&self.mod_path, super::MOD_PATH, super::LIMITS_TYPE_NAME)
+    }
 }
 
 #[derive(Debug, FromVariant)]
@@ -64,6 +80,7 @@ impl RecvFromMatchArm {
         variant: &RecvVariant,
         standalone_trait_path: &syn::Path,
         decode_trait_path: &syn::Path,
+        utils_mod_path: &syn::Path,
     ) -> Result<Self> {
         let match_arm: syn::Arm = match variant.fields.style {
             Style::Struct => {
@@ -83,7 +100,8 @@ impl RecvFromMatchArm {
                             &mut len_hint, 
                             reader
                         )?;
-                        #struct_ident.map(|#struct_ident| Self::#variant_ident { #struct_ident })
+                        #utils_mod_path::decoded(#struct_ident, len_hint, ::std::any::type_name::<#ty>())
+                            .map(|#struct_ident| Self::#variant_ident { #struct_ident })
                     }
                 }
             }
@@ -103,7 +121,8 @@ impl RecvFromMatchArm {
                             &mut len_hint, 
                             reader
                         )?;
-                        data.map(Self::#variant_ident)
+                        #utils_mod_path::decoded(data, len_hint, ::std::any::type_name::<#ty>())
+                            .map(Self::#variant_ident)
                     }
                 }
             }
@@ -119,7 +138,7 @@ impl RecvFromMatchArm {
                 let id = variant.id.to_owned().unwrap();
 
                 parse_quote! {
-                    #id => Some(Self::#variant_ident)
+                    #id => Ok(Self::#variant_ident)
                 }
             }
         };
@@ -142,6 +161,10 @@ impl RecvFromDef {
     fn from_params(params: &RecvParams) -> Result<Self> {
         let decode_trait_path = params.decode_trait_path();
         let standalone_trait_path = params.standalone_trait_path();
+        let utils_mod_path = params.utils_mod_path();
+        let error_type_path = params.error_type_path();
+        let result_type_path = params.result_type_path();
+        let limits_type_path = params.limits_type_path();
 
         let mut errors = Error::accumulator();
 
@@ -152,7 +175,12 @@ impl RecvFromDef {
             .unwrap()
             .into_iter()
             .map(|var| {
-                RecvFromMatchArm::from_variant(var, &standalone_trait_path, &decode_trait_path)
+                RecvFromMatchArm::from_variant(
+                    var,
+                    &standalone_trait_path,
+                    &decode_trait_path,
+                    &utils_mod_path,
+                )
             })
             .filter_map(|res| errors.handle(res))
             .collect::<Vec<_>>();
@@ -160,37 +188,24 @@ impl RecvFromDef {
         errors.finish()?;
 
         let fn_def: syn::ItemFn = parse_quote! {
-            fn recv_from(reader: &mut impl ::std::io::Read) -> ::std::io::Result<::std::option::Option<Self>> {
-                let mut len_hint = if let Some(val) = <u32 as #decode_trait_path>::decode_or_discard_from(
-                    &mut ::std::mem::size_of::<u32>(), 
-                    reader
-                )? {
-                    val as usize
-                } else {
-                    return Ok(None)
+            fn recv_limited_from(
+                reader: &mut impl ::std::io::Read,
+                limits: &#limits_type_path
+            ) -> #result_type_path<::std::option::Option<Self>> {
+                let (id, mut len_hint) = match #utils_mod_path::recv_header(reader, limits)? {
+                    ::std::option::Option::Some(header) => header,
+                    ::std::option::Option::None => return Ok(::std::option::Option::None),
                 };
 
-                if len_hint == 0 {
-                    return Ok(None)
-                }
-
-                let id = if let Some(val) = <u8 as #decode_trait_path>::decode_or_discard_from(
-                    &mut ::std::mem::size_of::<u8>(), 
-                    reader
-                )? {
-                    val
-                } else {
-                    return Ok(None)
-                };
-
-                len_hint -= 1;
-
                 let message = match id {
                     #(#match_arms,)*
-                    _ => None
+                    _ => {
+                        #utils_mod_path::discard_bytes(reader, len_hint)?;
+                        Err(#error_type_path::UnknownMessage { id })
+                    }
                 };
-                
-                Ok(message)
+
+                message.map(::std::option::Option::Some)
             }
         };
 
@@ -210,7 +225,7 @@ struct RecvImpl {
 
 impl RecvImpl {
     fn for_enum(input: &DeriveInput) -> Result<Self> {
-        let mut params = <RecvParams as FromDeriveInput>::from_derive_input(&input)?;
+        let mut params = <RecvParams as FromDeriveInput>::from_derive_input(input)?;
 
         let recv_from_def = RecvFromDef::from_params(&params)?;
         let recv_trait_path = params.recv_trait_path();
@@ -183,7 +183,7 @@ struct SendImpl {
 
 impl SendImpl {
     fn for_enum(input: &DeriveInput) -> Result<Self> {
-        let mut params = <SendParams as FromDeriveInput>::from_derive_input(&input)?;
+        let mut params = <SendParams as FromDeriveInput>::from_derive_input(input)?;
 
         let send_to_def = SendToDef::from_params(&params)?;
         let send_trait_path = params.send_trait_path();
@@ -1,23 +1,35 @@
-use darling::{FromDeriveInput, Result};
+use darling::ast::Data;
+use darling::util::Ignored;
+use darling::{Error, FromDeriveInput, FromVariant, Result};
 use proc_macro2::TokenStream;
 use quote::ToTokens;
 use syn::parse_quote;
 
 pub fn standalone(input: syn::DeriveInput) -> Result<TokenStream> {
-    StandaloneImpl::for_struct(input).map(ToTokens::into_token_stream)
+    StandaloneImpl::from_input(input).map(ToTokens::into_token_stream)
 }
 
 #[derive(FromDeriveInput)]
 #[darling(
     attributes(message, standalone),
-    supports(struct_named, struct_unit, struct_tuple, struct_newtype)
+    supports(struct_named, struct_unit, struct_tuple, struct_newtype, enum_unit)
 )]
 struct StandaloneParams {
     mod_path: Option<syn::Path>,
     #[darling(rename = "id")]
-    id: u8,
+    id: Option<u8>,
     ident: syn::Ident,
     generics: syn::Generics,
+    data: Data<StandaloneVariant, Ignored>,
+    ///Handled by `Encode` derive.
+    #[darling(rename = "fixed_size")]
+    _fixed_size: darling::util::Flag,
+    ///Handled by `Decode` derive.
+    #[darling(multiple, rename = "check")]
+    _check: Vec<syn::Expr>,
+    ///Handled by `Encode` and `Decode` derives.
+    #[darling(rename = "endian")]
+    _endian: Option<super::Endian>,
 }
 
 impl StandaloneParams {
@@ -30,14 +42,43 @@ impl StandaloneParams {
     }
 }
 
+/// Variant of fieldless enum, which maps to message id.
+///
+/// Id is taken from `#[standalone(id = ...)]` or discriminant (explicit or implicit).
+#[derive(FromVariant)]
+#[darling(attributes(standalone))]
+struct StandaloneVariant {
+    ident: syn::Ident,
+    id: Option<u8>,
+}
+
+impl StandaloneVariant {
+    fn id(&self) -> syn::Expr {
+        let ident = &self.ident;
+
+        match self.id {
+            Some(id) => parse_quote!(#id),
+            None => parse_quote!(Self::#ident as u8),
+        }
+    }
+}
+
 struct StandaloneImpl {
-    impl_block: syn::ItemImpl,
+    items: Vec<syn::Item>,
 }
 
 impl StandaloneImpl {
-    fn for_struct(input: syn::DeriveInput) -> Result<Self> {
+    fn from_input(input: syn::DeriveInput) -> Result<Self> {
         let params = <StandaloneParams as FromDeriveInput>::from_derive_input(&input)?;
 
+        if params.data.is_enum() {
+            Self::for_enum(params)
+        } else {
+            Self::for_struct(params)
+        }
+    }
+
+    fn for_struct(params: StandaloneParams) -> Result<Self> {
         let trait_path = params.full_trait_path();
 
         let StandaloneParams {
@@ -46,6 +87,7 @@ impl StandaloneImpl {
             generics,
             ..
         } = params;
+        let id = id.ok_or_else(|| Error::missing_field("id"))?;
         let (impl_gens, ty_gens, where_clause) = generics.split_for_impl();
 
         let impl_block = parse_quote! {
@@ -54,12 +96,81 @@ impl StandaloneImpl {
             }
         };
 
-        Ok(Self { impl_block })
+        Ok(Self {
+            items: vec![impl_block],
+        })
+    }
+
+    /// Generates mapping between variants of fieldless enum and message ids.
+    fn for_enum(params: StandaloneParams) -> Result<Self> {
+        if params.id.is_some() {
+            return Err(Error::unsupported_shape(
+                "Enums map each variant to its own id, use #[standalone(id = ...)] on variants.",
+            ));
+        }
+
+        let StandaloneParams {
+            ident,
+            generics,
+            data,
+            ..
+        } = params;
+        //Variants with fields are rejected by `supports(enum_unit)`
+        let variants = data.take_enum().unwrap();
+
+        let (impl_gens, ty_gens, where_clause) = generics.split_for_impl();
+
+        let variant_idents = variants.iter().map(|variant| &variant.ident).collect::<Vec<_>>();
+        let ids = variants.iter().map(StandaloneVariant::id).collect::<Vec<_>>();
+
+        let inherent_impl = parse_quote! {
+            #[automatically_derived]
+            impl #impl_gens #ident #ty_gens #where_clause {
+                /// Returns message id of `self`.
+                pub const fn id(&self) -> u8 {
+                    match self {
+                        #(Self::#variant_idents => #ids,)*
+                    }
+                }
+
+                /// Returns variant, corresponding to message `id`.
+                pub fn from_id(id: u8) -> ::std::option::Option<Self> {
+                    match id {
+                        #(id if id == #ids => ::std::option::Option::Some(Self::#variant_idents),)*
+                        _ => ::std::option::Option::None,
+                    }
+                }
+            }
+        };
+
+        let into_impl = parse_quote! {
+            #[automatically_derived]
+            impl #impl_gens ::std::convert::From<#ident #ty_gens> for u8 #where_clause {
+                fn from(value: #ident #ty_gens) -> u8 {
+                    value.id()
+                }
+            }
+        };
+
+        let try_from_impl = parse_quote! {
+            #[automatically_derived]
+            impl #impl_gens ::std::convert::TryFrom<u8> for #ident #ty_gens #where_clause {
+                type Error = u8;
+
+                fn try_from(id: u8) -> ::std::result::Result<Self, u8> {
+                    Self::from_id(id).ok_or(id)
+                }
+            }
+        };
+
+        Ok(Self {
+            items: vec![inherent_impl, into_impl, try_from_impl],
+        })
     }
 }
 
 impl ToTokens for StandaloneImpl {
     fn to_tokens(&self, tokens: &mut TokenStream) {
-        self.impl_block.to_tokens(tokens)
+        self.items.iter().for_each(|item| item.to_tokens(tokens))
     }
 }